@@ -45,30 +45,30 @@ fn main() {
 
     let select_start_time = Instant::now();
     for word in words.iter() {
-        ring.calc_candidates(word).nth(0).unwrap();
+        ring.calc_candidates(word).next().unwrap();
     }
     let select_end_time = Instant::now();
 
     let mut counts: HashMap<&str, _> = HashMap::from_iter(ring.nodes().iter().map(|k| (k.key, 0)));
     for word in words.iter() {
-        let selected = ring.calc_candidates(word).nth(0).unwrap();
+        let selected = ring.calc_candidates(word).next().unwrap();
         *counts.get_mut(selected.key).unwrap() += 1;
     }
 
-    println!("");
+    println!();
     println!("SELECTED COUNT PER NODE:");
     for (node, count) in counts {
         println!("- {}: \t{}", node, count);
     }
-    println!("");
+    println!();
 
     let build_elapsed = build_end_time - build_start_time;
     let build_elapsed_micros = build_elapsed.as_secs() * 1_000_000 +
-                               (build_elapsed.subsec_nanos() / 1000) as u64;
+                               build_elapsed.subsec_micros() as u64;
 
     let select_elapsed = select_end_time - select_start_time;
     let select_elapsed_micros = select_elapsed.as_secs() * 1_000_000 +
-                                (select_elapsed.subsec_nanos() / 1000) as u64;
+                                select_elapsed.subsec_micros() as u64;
     println!("ELAPSED: {} ms (for building ring), {} ms (for selecting nodes)",
              build_elapsed_micros / 1000,
              select_elapsed_micros / 1000);
@@ -0,0 +1,267 @@
+use std::collections::VecDeque;
+use std::fmt::{self, Write};
+use std::hash::Hash;
+
+use {RingHash, StaticHashRing};
+
+// The maximum number of changes kept for `RingStatus::recent_changes`.
+const MAX_RECENT_CHANGES: usize = 16;
+
+// The bounded history of the virtual node removals of a ring.
+#[derive(Debug, Default, Clone)]
+pub struct ChangeHistory(VecDeque<(u64, usize, u64)>);
+impl ChangeHistory {
+    pub fn record(&mut self, vnode_hash: u64, node: usize, generation: u64) {
+        if self.0.len() == MAX_RECENT_CHANGES {
+            self.0.pop_front();
+        }
+        self.0.push_back((vnode_hash, node, generation));
+    }
+
+    // Shifts the node indices for inserting (or removing) a node at `index`.
+    //
+    // The changes of a removed node are dropped.
+    pub fn shift(&mut self, index: usize, inserted: bool) {
+        if !inserted {
+            self.0.retain(|&(_, node, _)| node != index);
+        }
+        for change in &mut self.0 {
+            if change.1 >= index {
+                if inserted {
+                    change.1 += 1;
+                } else {
+                    change.1 -= 1;
+                }
+            }
+        }
+    }
+}
+
+/// The status of a ring, which is intended to be exposed by an admin endpoint.
+///
+/// This is created by calling `StaticHashRing::status` method,
+/// and can be serialized by `to_json` method.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RingStatus {
+    /// The format tag of the ring (see `StaticHashRing::format_tag`).
+    pub format: String,
+
+    /// The generation of the ring.
+    pub generation: u64,
+
+    /// The fingerprint of the ring (see `StaticHashRing::fingerprint`).
+    pub fingerprint: u64,
+
+    /// The count of the virtual nodes in the ring.
+    pub vnodes: usize,
+
+    /// The status of each node (ordered as `StaticHashRing::nodes`).
+    pub nodes: Vec<NodeStatus>,
+
+    /// The recent virtual node removals (from oldest to newest).
+    pub recent_changes: Vec<ChangeStatus>,
+}
+impl RingStatus {
+    /// Serializes this status as a JSON object.
+    ///
+    /// Hash codes (i.e., `fingerprint` and `vnode_hash`) are encoded as hexadecimal strings,
+    /// because they may not be representable by JSON numbers precisely.
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        self.write_json(&mut out).expect("Never fails");
+        out
+    }
+
+    fn write_json(&self, out: &mut String) -> fmt::Result {
+        write!(out, "{{\"format\":")?;
+        write_json_str(out, &self.format)?;
+        write!(out,
+               ",\"generation\":{},\"fingerprint\":\"{:016x}\",\"vnodes\":{},\"nodes\":[",
+               self.generation,
+               self.fingerprint,
+               self.vnodes)?;
+        for (i, node) in self.nodes.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            write!(out, "{{\"key\":")?;
+            write_json_str(out, &node.key)?;
+            write!(out,
+                   ",\"quantity\":{},\"vnodes\":{},\"replica_only\":{},\"ownership\":{}}}",
+                   node.quantity,
+                   node.vnodes,
+                   node.replica_only,
+                   node.ownership)?;
+        }
+        write!(out, "],\"recent_changes\":[")?;
+        for (i, change) in self.recent_changes.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            write!(out,
+                   "{{\"generation\":{},\"vnode_hash\":\"{:016x}\",\"node_key\":",
+                   change.generation,
+                   change.vnode_hash)?;
+            write_json_str(out, &change.node_key)?;
+            out.push('}');
+        }
+        write!(out, "]}}")
+    }
+}
+
+/// The status of a node in a ring.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeStatus {
+    /// The key of the node (formatted by `Display`).
+    pub key: String,
+
+    /// The quantity of virtual nodes initially assigned for the node.
+    pub quantity: usize,
+
+    /// The count of the virtual nodes of the node which remain in the ring.
+    pub vnodes: usize,
+
+    /// Whether the node is replica-only (see `StaticHashRing::set_replica_only`).
+    pub replica_only: bool,
+
+    /// The fraction of the hash space owned by the node as primary.
+    pub ownership: f64,
+}
+
+/// A virtual node removal made by `StaticHashRing::take` and its variants.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ChangeStatus {
+    /// The generation of the ring just after the removal.
+    pub generation: u64,
+
+    /// The hash code of the removed virtual node.
+    pub vnode_hash: u64,
+
+    /// The key of the node which owned the removed virtual node (formatted by `Display`).
+    pub node_key: String,
+}
+
+fn write_json_str(out: &mut String, s: &str) -> fmt::Result {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32)?,
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    Ok(())
+}
+
+impl<K, V, H> StaticHashRing<K, V, H>
+    where K: Hash + fmt::Display,
+          H: RingHash
+{
+    /// Returns the status of this ring.
+    ///
+    /// This is a framework-agnostic building block of ring-introspection endpoints
+    /// (e.g., an HTTP handler can respond with `ring.status().to_json()`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let mut ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(2)));
+    /// ring.take(&"foo");
+    ///
+    /// let status = ring.status();
+    /// assert_eq!(status.generation, 1);
+    /// assert_eq!(status.vnodes, 5);
+    /// assert_eq!(status.nodes.len(), 3);
+    /// assert_eq!(status.recent_changes.len(), 1);
+    /// assert!(status.to_json().starts_with(r#"{"format":"ring-v1-siphash13","generation":1,"#));
+    /// ```
+    pub fn status(&self) -> RingStatus {
+        let mut vnodes = vec![0; self.nodes.len()];
+        for vn in &self.ring {
+            vnodes[vn.index] += 1;
+        }
+        let mut owned = vec![0; self.nodes.len()];
+        for r in self.primary_ranges() {
+            owned[r.node] += r.len();
+        }
+        let nodes = self.nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| {
+                NodeStatus {
+                    key: node.key.to_string(),
+                    quantity: node.quantity,
+                    vnodes: vnodes[i],
+                    replica_only: self.states[i].replica_only,
+                    ownership: owned[i] as f64 / 2f64.powi(64),
+                }
+            })
+            .collect();
+        let recent_changes = self.history
+            .0
+            .iter()
+            .map(|&(vnode_hash, node, generation)| {
+                ChangeStatus {
+                    generation,
+                    vnode_hash,
+                    node_key: self.nodes[node].key.to_string(),
+                }
+            })
+            .collect();
+        RingStatus {
+            format: self.format_tag(),
+            generation: self.generation,
+            fingerprint: self.fingerprint(),
+            vnodes: self.ring.len(),
+            nodes,
+            recent_changes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {DefaultHash, Node, StaticHashRing};
+
+    #[test]
+    fn status_works() {
+        let nodes = vec![Node::new("a\"b").quantity(2), Node::new("c").quantity(0)];
+        let mut ring = StaticHashRing::new(DefaultHash, nodes.into_iter());
+        for _ in 0..3 {
+            ring.take(&"foo");
+        }
+        let status = ring.status();
+        assert_eq!(status.generation, 2);
+        assert_eq!(status.vnodes, 0);
+        assert_eq!(status.recent_changes.len(), 2);
+        assert_eq!(status.recent_changes[1].generation, 2);
+        assert_eq!(status.to_json(),
+                   format!(concat!(r#"{{"format":"ring-v1-siphash13","generation":2,"#,
+                                   r#""fingerprint":"{:016x}","vnodes":0,"nodes":["#,
+                                   r#"{{"key":"a\"b","quantity":2,"vnodes":0,"replica_only":false,"ownership":0}},"#,
+                                   r#"{{"key":"c","quantity":0,"vnodes":0,"replica_only":false,"ownership":0}}],"#,
+                                   r#""recent_changes":[{{"generation":1,"vnode_hash":"{:016x}","node_key":"a\"b"}},"#,
+                                   r#"{{"generation":2,"vnode_hash":"{:016x}","node_key":"a\"b"}}]}}"#),
+                           status.fingerprint,
+                           status.recent_changes[0].vnode_hash,
+                           status.recent_changes[1].vnode_hash));
+    }
+
+    #[test]
+    fn recent_changes_are_bounded() {
+        let mut ring = StaticHashRing::new(DefaultHash, (0..2).map(|i| Node::new(i).quantity(20)));
+        for i in 0..40 {
+            ring.take(&i);
+        }
+        let status = ring.status();
+        assert_eq!(status.recent_changes.len(), super::MAX_RECENT_CHANGES);
+        assert_eq!(status.recent_changes.last().map(|c| c.generation), Some(40));
+    }
+}
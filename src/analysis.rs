@@ -0,0 +1,588 @@
+//! Functions for analyzing hash rings.
+use std::hash::Hash;
+
+use range::HashRange;
+use {Node, RingHash, StaticHashRing};
+
+/// Returns the fraction of `sample_keys` whose top-`k` candidate lists differ between `old` and `new`.
+///
+/// Two lists are regarded as different if they differ in either the members or the order.
+/// So, with `k = 1` this measures the movement of primary nodes, and
+/// with `k` equal to the replication factor this measures the stability of replica sets.
+///
+/// If `sample_keys` is empty, this function returns `0.0`.
+///
+/// # Examples
+///
+/// ```
+/// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+/// use consistent_hash::analysis::verify_minimal_disruption;
+///
+/// let old = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(50)));
+/// let new = StaticHashRing::new(DefaultHash, (0..5).map(|i| Node::new(i).quantity(50)));
+///
+/// let keys = (0..1000).collect::<Vec<_>>();
+/// let primary = verify_minimal_disruption(&old, &new, &keys, 1);
+/// let replicas = verify_minimal_disruption(&old, &new, &keys, 3);
+/// assert!(0.1 < primary && primary < 0.3);
+/// assert!(primary < replicas);
+/// ```
+pub fn verify_minimal_disruption<'a, T, K, V1, V2, H1, H2, I>(old: &StaticHashRing<K, V1, H1>,
+                                                             new: &StaticHashRing<K, V2, H2>,
+                                                             sample_keys: I,
+                                                             k: usize)
+                                                             -> f64
+    where T: 'a + Hash,
+          K: Eq,
+          H1: RingHash,
+          H2: RingHash,
+          I: IntoIterator<Item = &'a T>
+{
+    let mut total = 0;
+    let mut changed = 0;
+    for key in sample_keys {
+        total += 1;
+        let old_candidates = old.calc_candidates(key).take(k).map(|n| &n.key);
+        let new_candidates = new.calc_candidates(key).take(k).map(|n| &n.key);
+        if !old_candidates.eq(new_candidates) {
+            changed += 1;
+        }
+    }
+    if total == 0 {
+        0.0
+    } else {
+        changed as f64 / total as f64
+    }
+}
+
+/// The vnode quantity adjustments proposed by `advise_rebalance`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RebalanceAdvice<K> {
+    /// The proposed quantity of each node (ordered as `StaticHashRing::nodes`).
+    pub quantities: Vec<(K, usize)>,
+
+    /// The estimated fraction of the hash space which would move if the proposal were applied.
+    pub predicted_churn: f64,
+}
+
+/// Suggests vnode quantity adjustments which make the observed loads converge toward `weight`.
+///
+/// `loads` are the observed load counters of the nodes of `ring`
+/// (ordered as `ring.nodes()`, e.g., the result of `LoadTracker::load_snapshot`),
+/// and `weight` returns the desired relative share of each node.
+///
+/// The quantity of each node is scaled by the ratio of its desired share to its observed share,
+/// but changed by at most `max_step` per call, so repeated calls converge gradually.
+/// Nodes which have positive weights keep at least one virtual node.
+/// If no loads are observed, the current quantities are proposed.
+///
+/// The churn is estimated by assuming that the ownership of each node is proportional to its quantity.
+///
+/// # Panics
+///
+/// Panics if the length of `loads` differs from the number of the nodes of `ring`.
+///
+/// # Examples
+///
+/// ```
+/// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+/// use consistent_hash::analysis::advise_rebalance;
+///
+/// let ring = StaticHashRing::new(DefaultHash, (0..2).map(|i| Node::new(i).quantity(10)));
+///
+/// let advice = advise_rebalance(&ring, &[300, 100], |_| 1.0, 5);
+/// assert_eq!(advice.quantities, [(0, 7), (1, 15)]);
+/// assert!(0.1 < advice.predicted_churn && advice.predicted_churn < 0.2);
+/// ```
+pub fn advise_rebalance<K, V, H, F>(ring: &StaticHashRing<K, V, H>,
+                                    loads: &[usize],
+                                    weight: F,
+                                    max_step: usize)
+                                    -> RebalanceAdvice<K>
+    where K: Clone,
+          F: Fn(&Node<K, V>) -> f64
+{
+    let nodes = ring.nodes();
+    assert_eq!(loads.len(), nodes.len());
+
+    let weights = nodes.iter()
+        .map(|n| {
+            let w = weight(n);
+            if w > 0.0 { w } else { 0.0 }
+        })
+        .collect::<Vec<_>>();
+    let total_weight = weights.iter().sum::<f64>();
+    let total_load = loads.iter().sum::<usize>();
+
+    let mut quantities = Vec::with_capacity(nodes.len());
+    for ((node, &load), &w) in nodes.iter().zip(loads).zip(&weights) {
+        let q = node.quantity;
+        let ideal = if total_load == 0 || total_weight == 0.0 {
+            q as f64
+        } else if load == 0 {
+            if w > 0.0 { f64::INFINITY } else { 0.0 }
+        } else {
+            let desired = w / total_weight;
+            let observed = load as f64 / total_load as f64;
+            q as f64 * desired / observed
+        };
+        let lower = q.saturating_sub(max_step);
+        let upper = q.saturating_add(max_step);
+        let mut new_q = if ideal >= upper as f64 {
+            upper
+        } else {
+            (ideal.round() as usize).max(lower)
+        };
+        if w > 0.0 {
+            new_q = new_q.max(1);
+        }
+        quantities.push((node.key.clone(), new_q));
+    }
+
+    let old_total = nodes.iter().map(|n| n.quantity).sum::<usize>();
+    let new_total = quantities.iter().map(|&(_, q)| q).sum::<usize>();
+    let predicted_churn = if old_total == 0 || new_total == 0 {
+        if old_total == new_total { 0.0 } else { 1.0 }
+    } else {
+        nodes.iter()
+            .zip(&quantities)
+            .map(|(n, &(_, q))| {
+                let old_share = n.quantity as f64 / old_total as f64;
+                let new_share = q as f64 / new_total as f64;
+                (new_share - old_share).max(0.0)
+            })
+            .sum()
+    };
+    RebalanceAdvice {
+        quantities,
+        predicted_churn,
+    }
+}
+
+/// The distribution statistics of a ring returned by `stats`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RingStats<K> {
+    /// The fraction of the hash space owned (as primary) by each node (ordered as `StaticHashRing::nodes`).
+    pub ownership: Vec<(K, f64)>,
+
+    /// The standard deviation of the ownership fractions.
+    pub std_dev: f64,
+
+    /// The smallest ownership fraction (`0.0` if the ring has no nodes).
+    pub min: f64,
+
+    /// The largest ownership fraction (`0.0` if the ring has no nodes).
+    pub max: f64,
+
+    /// The largest range of hash codes between two adjacent virtual nodes
+    /// (i.e., the largest range owned by a single virtual node).
+    ///
+    /// If the ring has no virtual nodes, this is `None`.
+    pub largest_gap: Option<HashRange>,
+}
+impl<K> RingStats<K> {
+    /// Returns the ratio of the largest ownership fraction to the smallest one.
+    ///
+    /// If the smallest fraction is zero, this returns `f64::INFINITY` (or `NaN` if both are zero).
+    pub fn spread(&self) -> f64 {
+        self.max / self.min
+    }
+}
+
+/// Computes the distribution statistics of `ring` analytically from its virtual node layout.
+///
+/// Unlike benchmarks which hash sample items, the result is exact.
+/// Replica-only nodes are taken into account (i.e., they own nothing unless every node is replica-only).
+///
+/// # Examples
+///
+/// ```
+/// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+/// use consistent_hash::analysis::stats;
+///
+/// let ring = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(100)));
+///
+/// let stats = stats(&ring);
+/// assert_eq!(stats.ownership.len(), 4);
+/// assert!((stats.ownership.iter().map(|&(_, f)| f).sum::<f64>() - 1.0).abs() < 1e-9);
+/// assert!(0.2 < stats.min && stats.max < 0.3);
+/// assert!(stats.std_dev < 0.05);
+/// assert!(stats.largest_gap.unwrap().len() < 1 << 60);
+/// ```
+pub fn stats<K, V, H>(ring: &StaticHashRing<K, V, H>) -> RingStats<K>
+    where K: Clone
+{
+    let mut owned = vec![0; ring.nodes.len()];
+    for r in ring.primary_ranges() {
+        owned[r.node] += r.len();
+    }
+    let ownership = ring.nodes
+        .iter()
+        .zip(owned)
+        .map(|(n, owned)| (n.key.clone(), owned as f64 / 2f64.powi(64)))
+        .collect::<Vec<_>>();
+
+    let fractions = ownership.iter().map(|&(_, f)| f).collect::<Vec<_>>();
+    let (std_dev, min, max) = if fractions.is_empty() {
+        (0.0, 0.0, 0.0)
+    } else {
+        let mean = fractions.iter().sum::<f64>() / fractions.len() as f64;
+        let variance = fractions.iter().map(|f| (f - mean).powi(2)).sum::<f64>() / fractions.len() as f64;
+        (variance.sqrt(),
+         fractions.iter().cloned().fold(f64::INFINITY, f64::min),
+         fractions.iter().cloned().fold(f64::NEG_INFINITY, f64::max))
+    };
+
+    let largest_gap = ring.ring
+        .iter()
+        .enumerate()
+        .map(|(i, vn)| {
+            let prev = ring.ring[(i + ring.ring.len() - 1) % ring.ring.len()].hash;
+            HashRange::new(prev.wrapping_add(1), vn.hash)
+        })
+        .max_by_key(|r| r.len());
+    RingStats {
+        ownership,
+        std_dev,
+        min,
+        max,
+        largest_gap,
+    }
+}
+
+/// The kind of a `VnodeChange`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VnodeChangeKind {
+    /// The virtual node is added.
+    Add,
+
+    /// The virtual node is removed.
+    Remove,
+}
+
+/// A change of a virtual node proposed by `solve_ownership`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VnodeChange<K> {
+    /// Whether the virtual node is added or removed.
+    pub kind: VnodeChangeKind,
+
+    /// The key of the node which owns the virtual node.
+    pub key: K,
+
+    /// The sequence number of the virtual node.
+    pub seq: usize,
+
+    /// The hash code of the virtual node.
+    pub hash: u64,
+}
+
+/// The virtual node changes proposed by `solve_ownership`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OwnershipPlan<K> {
+    /// The proposed changes (in the order of application).
+    pub changes: Vec<VnodeChange<K>>,
+
+    /// The quantity of each node after the changes (ordered as `StaticHashRing::nodes`).
+    ///
+    /// Virtual nodes are always added or removed at the end of the sequence numbers,
+    /// so the changes can be applied by setting these quantities (e.g., by `RingBuilder::set_quantity`).
+    pub quantities: Vec<(K, usize)>,
+
+    /// The predicted ownership fraction of each node after the changes (ordered as `StaticHashRing::nodes`).
+    pub ownership: Vec<(K, f64)>,
+
+    /// The predicted residual error, i.e., the sum of the absolute differences
+    /// between the predicted ownership fractions and the targets.
+    pub residual_error: f64,
+}
+
+/// Computes virtual node additions and removals which make the ownership of `ring` approach `target`.
+///
+/// `target` returns the desired relative share of each node (non-positive shares are regarded as zero),
+/// and at most `max_changes` changes are proposed.
+/// The changes are chosen greedily: each step applies the single addition or removal
+/// which reduces the squared error of the ownership fractions the most,
+/// and the solver stops early if no change reduces the error
+/// (except for the changes which do not increase the error but unblock the next changes).
+/// Unlike `advise_rebalance`, the ownership is computed exactly from the virtual node layout
+/// (replica-only flags are ignored).
+///
+/// # Examples
+///
+/// ```
+/// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+/// use consistent_hash::analysis::{solve_ownership, stats};
+///
+/// let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(20)));
+/// let target = |n: &Node<i32, ()>| if n.key == 0 { 2.0 } else { 1.0 };
+///
+/// let plan = solve_ownership(&ring, target, 30);
+/// assert!(plan.changes.len() <= 30);
+/// assert!(plan.residual_error < 0.1);
+///
+/// let mut builder = ring.clone_topology();
+/// for &(key, quantity) in &plan.quantities {
+///     builder.set_quantity(&key, quantity);
+/// }
+/// let ownership = stats(&builder.build()).ownership;
+/// assert!(ownership.iter().zip(&plan.ownership).all(|(a, b)| (a.1 - b.1).abs() < 1e-9));
+/// ```
+pub fn solve_ownership<K, V, H, F>(ring: &StaticHashRing<K, V, H>,
+                                   target: F,
+                                   max_changes: usize)
+                                   -> OwnershipPlan<K>
+    where K: Hash + Clone,
+          H: RingHash,
+          F: Fn(&Node<K, V>) -> f64
+{
+    let nodes = &ring.nodes;
+    let mut targets = nodes.iter()
+        .map(|n| {
+            let t = target(n);
+            if t > 0.0 { t } else { 0.0 }
+        })
+        .collect::<Vec<_>>();
+    let total_target = targets.iter().sum::<f64>();
+    if total_target > 0.0 {
+        for t in &mut targets {
+            *t /= total_target;
+        }
+    }
+
+    // `(hash, node index, seq)` in ring order (node indices are ordered as the keys).
+    let mut points = ring.ring.iter().map(|vn| (vn.hash, vn.index, vn.seq)).collect::<Vec<_>>();
+    let mut quantities = nodes.iter().map(|n| n.quantity).collect::<Vec<_>>();
+    let mut owned = vec![0.0; nodes.len()];
+    for i in 0..points.len() {
+        owned[points[i].1] += arc(&points, i);
+    }
+
+    // The change of the squared error when `len` moves from `from` to `to`.
+    let delta = |owned: &[f64], to: usize, from: Option<usize>, len: f64| {
+        if from == Some(to) {
+            return 0.0;
+        }
+        let sq = |i: usize, d: f64| (owned[i] + d - targets[i]).powi(2) - (owned[i] - targets[i]).powi(2);
+        sq(to, len) + from.map_or(0.0, |f| sq(f, -len))
+    };
+
+    let mut changes = Vec::new();
+    while changes.len() < max_changes {
+        let mut lasts = vec![None; nodes.len()];
+        for (i, p) in points.iter().enumerate() {
+            if lasts[p.1].is_none_or(|j: usize| points[j].2 < p.2) {
+                lasts[p.1] = Some(i);
+            }
+        }
+
+        // `(delta, is_addition, node index, position, len, counterpart)`
+        let mut best: Option<(f64, bool, usize, usize, f64, Option<usize>)> = None;
+
+        // A change which does not increase the error (e.g., the adjacent range is owned by the same node),
+        // but unblocks the next change of an under- or over-provisioned node.
+        let mut neutral = None;
+        for node in 0..nodes.len() {
+            let hash = ring.hash.hash_vnode(&nodes[node].key, quantities[node]);
+            let p = points.binary_search(&(hash, node, quantities[node])).unwrap_or_else(|p| p);
+            let (len, from) = if points.is_empty() {
+                (1.0, None)
+            } else {
+                let prev = points[(p + points.len() - 1) % points.len()].0;
+                (as_fraction(hash.wrapping_sub(prev)), Some(points[p % points.len()].1))
+            };
+            let d = delta(&owned, node, from, len);
+            if best.is_none_or(|b| d < b.0) {
+                best = Some((d, true, node, p, len, from));
+            }
+            let deviation = owned[node] - targets[node];
+            if d <= 1e-15 && deviation < 0.0 && neutral.is_none_or(|n: (f64, _, _, _, _, _)| deviation < n.0) {
+                neutral = Some((deviation, true, node, p, len, from));
+            }
+
+            if let Some(p) = lasts[node] {
+                let len = arc(&points, p);
+                let to = if points.len() > 1 { Some(points[(p + 1) % points.len()].1) } else { None };
+                let d = match to {
+                    Some(to) => delta(&owned, to, Some(node), len),
+                    None => (owned[node] - len - targets[node]).powi(2) - (owned[node] - targets[node]).powi(2),
+                };
+                if best.is_none_or(|b| d < b.0) {
+                    best = Some((d, false, node, p, len, to));
+                }
+                if d <= 1e-15 && deviation > 0.0 && neutral.is_none_or(|n| -deviation < n.0) {
+                    neutral = Some((-deviation, false, node, p, len, to));
+                }
+            }
+        }
+
+        let (_, is_addition, node, p, len, counterpart) = match (best, neutral) {
+            (Some(b), _) if b.0 < -1e-15 => b,
+            (_, Some(n)) => n,
+            _ => break,
+        };
+        let key = nodes[node].key.clone();
+        if is_addition {
+            let seq = quantities[node];
+            let hash = ring.hash.hash_vnode(&key, seq);
+            points.insert(p, (hash, node, seq));
+            quantities[node] += 1;
+            owned[node] += len;
+            if let Some(from) = counterpart {
+                owned[from] -= len;
+            }
+            changes.push(VnodeChange {
+                kind: VnodeChangeKind::Add,
+                key,
+                seq,
+                hash,
+            });
+        } else {
+            let (hash, _, seq) = points.remove(p);
+            quantities[node] = seq;
+            owned[node] -= len;
+            if let Some(to) = counterpart {
+                owned[to] += len;
+            }
+            changes.push(VnodeChange {
+                kind: VnodeChangeKind::Remove,
+                key,
+                seq,
+                hash,
+            });
+        }
+    }
+
+    let residual_error = owned.iter().zip(&targets).map(|(o, t)| (o - t).abs()).sum();
+    OwnershipPlan {
+        changes,
+        quantities: nodes.iter().zip(quantities).map(|(n, q)| (n.key.clone(), q)).collect(),
+        ownership: nodes.iter().zip(owned).map(|(n, o)| (n.key.clone(), o)).collect(),
+        residual_error,
+    }
+}
+
+// Returns the fraction of the hash space owned by `points[i]`.
+fn arc(points: &[(u64, usize, usize)], i: usize) -> f64 {
+    if points.len() == 1 {
+        1.0
+    } else {
+        let prev = points[(i + points.len() - 1) % points.len()].0;
+        as_fraction(points[i].0.wrapping_sub(prev))
+    }
+}
+
+fn as_fraction(len: u64) -> f64 {
+    len as f64 / 2f64.powi(64)
+}
+
+#[cfg(test)]
+mod tests {
+    use {DefaultHash, Node, RingHash, StaticHashRing};
+    use super::*;
+
+    #[test]
+    fn verify_minimal_disruption_works() {
+        let nodes = || (0..3).map(|i| Node::new(i).quantity(10));
+        let old = StaticHashRing::new(DefaultHash, nodes());
+        let new = StaticHashRing::new(DefaultHash, nodes().map(|n| n.value("ignored")));
+
+        let keys = (0..100).collect::<Vec<_>>();
+        assert_eq!(verify_minimal_disruption(&old, &new, &keys, 3), 0.0);
+        assert_eq!(verify_minimal_disruption(&old, &new, &[] as &[usize], 3), 0.0);
+
+        let new = StaticHashRing::new(DefaultHash, nodes().take(2));
+        let primary = verify_minimal_disruption(&old, &new, &keys, 1);
+        assert!(0.0 < primary && primary < 1.0);
+        assert_eq!(verify_minimal_disruption(&old, &new, &keys, 3), 1.0);
+    }
+
+    #[test]
+    fn advise_rebalance_works() {
+        let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+
+        // Balanced.
+        let advice = advise_rebalance(&ring, &[100, 100, 100], |_| 1.0, 5);
+        assert_eq!(advice.quantities, [(0, 10), (1, 10), (2, 10)]);
+        assert_eq!(advice.predicted_churn, 0.0);
+
+        // No loads are observed.
+        let advice = advise_rebalance(&ring, &[0, 0, 0], |_| 1.0, 5);
+        assert_eq!(advice.quantities, [(0, 10), (1, 10), (2, 10)]);
+
+        // Idle and zero-weight nodes.
+        let advice = advise_rebalance(&ring, &[100, 0, 100], |n| if n.key == 2 { 0.0 } else { 1.0 }, 3);
+        assert_eq!(advice.quantities, [(0, 10), (1, 13), (2, 7)]);
+        assert!(advice.predicted_churn > 0.0);
+
+        // Large steps are bounded, but nodes keep at least one virtual node.
+        let advice = advise_rebalance(&ring, &[1000, 10, 10], |_| 1.0, 100);
+        assert_eq!(advice.quantities[0], (0, 3));
+        assert_eq!(advice.quantities[1], (1, 110));
+        let advice = advise_rebalance(&ring, &[1000, 10, 10], |n| if n.key == 0 { 0.01 } else { 1.0 }, 100);
+        assert_eq!(advice.quantities[0], (0, 1));
+    }
+
+    #[test]
+    fn solve_ownership_works() {
+        let ring = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(10)));
+        let target = |n: &Node<i32, ()>| if n.key == 3 { 0.0 } else { 1.0 + n.key as f64 };
+        let initial = solve_ownership(&ring, target, 0);
+        assert!(initial.changes.is_empty());
+        assert_eq!(initial.quantities, [(0, 10), (1, 10), (2, 10), (3, 10)]);
+
+        let plan = solve_ownership(&ring, target, 80);
+        assert!(!plan.changes.is_empty() && plan.changes.len() <= 80);
+        assert!(plan.residual_error < initial.residual_error / 2.0);
+        for c in &plan.changes {
+            assert_eq!(c.hash, DefaultHash.hash_vnode(&c.key, c.seq));
+        }
+        let removed = plan.changes.iter().filter(|c| c.kind == VnodeChangeKind::Remove).count();
+        let added = plan.changes.len() - removed;
+        assert_eq!(plan.quantities.iter().map(|&(_, q)| q).sum::<usize>() + removed, 40 + added);
+
+        // The prediction is exact.
+        let mut builder = ring.clone_topology();
+        for &(key, quantity) in &plan.quantities {
+            builder.set_quantity(&key, quantity);
+        }
+        let actual = stats(&builder.build()).ownership;
+        for (a, p) in actual.iter().zip(&plan.ownership) {
+            assert!((a.1 - p.1).abs() < 1e-9, "{:?} {:?}", actual, plan.ownership);
+        }
+
+        let empty = StaticHashRing::new(DefaultHash, (0..2).map(|i| Node::new(i).quantity(0)));
+        let plan = solve_ownership(&empty, |_| 1.0, 10);
+        assert!(plan.residual_error < 1.0);
+        assert!((plan.ownership.iter().map(|o| o.1).sum::<f64>() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn stats_works() {
+        let mut ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+        let s = stats(&ring);
+        assert_eq!(s.ownership.iter().map(|&(k, _)| k).collect::<Vec<_>>(), [0, 1, 2]);
+        assert!((s.ownership.iter().map(|&(_, f)| f).sum::<f64>() - 1.0).abs() < 1e-9);
+        assert!(0.0 < s.min && s.min <= s.max && s.spread() >= 1.0);
+
+        // Every vnode range is at most the largest gap, and the gaps cover the whole space.
+        let hashes = ring.vnodes().map(|(h, _, _)| h).collect::<Vec<_>>();
+        let gap = s.largest_gap.unwrap();
+        assert!(hashes.windows(2).all(|w| (w[1] - w[0]) as u128 <= gap.len()));
+        assert!(hashes.contains(&gap.end));
+
+        ring.set_replica_only(&1, true);
+        let s = stats(&ring);
+        assert_eq!((s.ownership[1].1, s.min), (0.0, 0.0));
+        assert!(s.spread().is_infinite());
+
+        let single = StaticHashRing::new(DefaultHash, (0..1).map(|i| Node::new(i).quantity(1)));
+        let s = stats(&single);
+        assert_eq!((s.ownership[0].1, s.std_dev), (1.0, 0.0));
+        assert!(s.largest_gap.unwrap().is_full());
+
+        let empty = StaticHashRing::new(DefaultHash, (0..0).map(Node::new));
+        let s = stats(&empty);
+        assert!(s.ownership.is_empty());
+        assert_eq!((s.std_dev, s.min, s.max, s.largest_gap), (0.0, 0.0, 0.0, None));
+    }
+}
@@ -0,0 +1,217 @@
+use std::hash::Hash;
+
+use {Node, RingHash};
+
+/// A hash ring based on AnchorHash.
+///
+/// The ring has a fixed capacity of buckets (the "anchor"), and each node occupies a bucket.
+/// An item is assigned in O(1) expected time (for moderate ratios of the capacity to the node count),
+/// and the ring only stores a few integers per bucket.
+/// Adding or removing a node only moves the items which are assigned to (or from) the node,
+/// and removing a node and then adding one restores the previous assignment.
+/// Like `JumpHashRing`, the quantities of nodes are ignored.
+///
+/// See [AnchorHash: A Scalable Consistent Hash](https://arxiv.org/abs/1812.09674).
+///
+/// # Examples
+///
+/// ```
+/// use consistent_hash::{AnchorRing, Node, DefaultHash};
+///
+/// let mut ring = AnchorRing::new(DefaultHash, 10);
+/// for key in &["foo", "bar", "baz"] {
+///     assert!(ring.try_insert_node(Node::new(*key)).is_ok());
+/// }
+/// let before = ring.calc_candidate(&"item").map(|n| n.key).unwrap();
+///
+/// // Removing another node does not move the item.
+/// let other = ["foo", "bar", "baz"].iter().cloned().find(|k| *k != before).unwrap();
+/// ring.remove_node(&other);
+/// assert_eq!(ring.calc_candidate(&"item").map(|n| n.key), Some(before));
+/// ```
+#[derive(Debug, Clone)]
+pub struct AnchorRing<K, V, H> {
+    hash: H,
+    nodes: Vec<Option<Node<K, V>>>,
+
+    // The arrays of the paper: `anchors[b]` is the size of the working set just after the removal of `b`
+    // (`0` for working buckets), `working` and `locations` map positions and buckets onto each other,
+    // `successors` gives the replacement of a removed bucket, and `removed` is the stack of removed buckets.
+    anchors: Vec<usize>,
+    working: Vec<usize>,
+    locations: Vec<usize>,
+    successors: Vec<usize>,
+    removed: Vec<usize>,
+    len: usize,
+}
+impl<K, V, H> AnchorRing<K, V, H>
+    where K: Eq,
+          H: RingHash
+{
+    /// Makes a new `AnchorRing` instance which can contain up to `capacity` nodes.
+    pub fn new(hash: H, capacity: usize) -> Self {
+        AnchorRing {
+            hash,
+            nodes: (0..capacity).map(|_| None).collect(),
+            anchors: (0..capacity).collect(),
+            working: (0..capacity).collect(),
+            locations: (0..capacity).collect(),
+            successors: (0..capacity).collect(),
+            removed: (0..capacity).rev().collect(),
+            len: 0,
+        }
+    }
+
+    /// Inserts `node` into this ring, and returns the bucket assigned to it.
+    ///
+    /// The bucket is the most recently freed one, so an item assigned to a removed node comes back
+    /// to the node inserted next.
+    /// If a node which has the same key already exists, or this ring is full,
+    /// this returns `Err(node)` without changing this ring.
+    pub fn try_insert_node(&mut self, node: Node<K, V>) -> Result<usize, Node<K, V>> {
+        if self.bucket_of(&node.key).is_some() {
+            return Err(node);
+        }
+        let b = match self.removed.pop() {
+            None => return Err(node),
+            Some(b) => b,
+        };
+        self.anchors[b] = 0;
+        self.locations[self.working[self.len]] = self.len;
+        self.working[self.locations[b]] = b;
+        self.successors[b] = b;
+        self.len += 1;
+        self.nodes[b] = Some(node);
+        Ok(b)
+    }
+
+    /// Removes the node identified by `key` from this ring, and returns it.
+    pub fn remove_node(&mut self, key: &K) -> Option<Node<K, V>> {
+        let b = self.bucket_of(key)?;
+        self.removed.push(b);
+        self.len -= 1;
+        self.anchors[b] = self.len;
+        let last = self.working[self.len];
+        self.working[self.locations[b]] = last;
+        self.successors[b] = last;
+        self.locations[last] = self.locations[b];
+        self.nodes[b].take()
+    }
+
+    /// Returns the bucket of the node to which `item` is assigned.
+    ///
+    /// If this ring is empty, this returns `None`.
+    pub fn calc_candidate_bucket<T: Hash>(&self, item: &T) -> Option<usize> {
+        self.calc_candidate_bucket_by_hash(self.hash.hash_item(item))
+    }
+
+    /// Returns the bucket of the node to which the item whose hash code is `item_hash` is assigned.
+    pub fn calc_candidate_bucket_by_hash(&self, item_hash: u64) -> Option<usize> {
+        if self.len == 0 {
+            return None;
+        }
+        let mut b = (item_hash % self.nodes.len() as u64) as usize;
+        while self.anchors[b] > 0 {
+            let mut h = (self.hash.hash_item(&(item_hash, b as u64)) % self.anchors[b] as u64) as usize;
+            while self.anchors[h] >= self.anchors[b] {
+                h = self.successors[h];
+            }
+            b = h;
+        }
+        Some(b)
+    }
+
+    /// Returns the node to which `item` is assigned.
+    ///
+    /// If this ring is empty, this returns `None`.
+    pub fn calc_candidate<T: Hash>(&self, item: &T) -> Option<&Node<K, V>> {
+        self.calc_candidate_bucket(item).and_then(|b| self.nodes[b].as_ref())
+    }
+
+    /// Returns the bucket of the node identified by `key`.
+    pub fn bucket_of(&self, key: &K) -> Option<usize> {
+        self.nodes.iter().position(|n| n.as_ref().is_some_and(|n| n.key == *key))
+    }
+}
+impl<K, V, H> AnchorRing<K, V, H> {
+    /// Returns the node which occupies the bucket `bucket`.
+    pub fn node_at(&self, bucket: usize) -> Option<&Node<K, V>> {
+        self.nodes.get(bucket).and_then(|n| n.as_ref())
+    }
+
+    /// Returns the nodes of this ring (in bucket order).
+    pub fn nodes(&self) -> impl Iterator<Item = &Node<K, V>> {
+        self.nodes.iter().flatten()
+    }
+
+    /// Returns the maximum count of the nodes in this ring.
+    pub fn capacity(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns the count of the nodes in this ring.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if this ring has no nodes, otherwise `false`.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {DefaultHash, Node};
+    use super::*;
+
+    #[test]
+    fn anchor_ring_works() {
+        let mut ring = AnchorRing::new(DefaultHash, 16);
+        assert_eq!(ring.calc_candidate(&"foo"), None);
+        for i in 0..8 {
+            assert!(ring.try_insert_node(Node::new(i)).is_ok());
+        }
+        assert!(ring.try_insert_node(Node::new(3)).is_err());
+        let owners = |ring: &AnchorRing<_, _, _>| {
+            (0..4000).map(|i| ring.calc_candidate(&i).unwrap().key).collect::<Vec<_>>()
+        };
+        let before = owners(&ring);
+
+        let mut counts = [0; 8];
+        for &k in &before {
+            counts[k] += 1;
+        }
+        assert!(counts.iter().all(|&c| 350 < c && c < 650), "{:?}", counts);
+
+        // Only the items of the removed nodes move.
+        ring.remove_node(&2);
+        ring.remove_node(&5);
+        let removed = owners(&ring);
+        assert!(before.iter().zip(&removed).all(|(b, r)| b == r || *b == 2 || *b == 5));
+        assert!(!removed.contains(&2) && !removed.contains(&5));
+
+        // The most recently freed bucket is reused, so the assignment is restored.
+        let b5 = ring.try_insert_node(Node::new(5)).unwrap();
+        let b2 = ring.try_insert_node(Node::new(2)).unwrap();
+        assert_eq!(owners(&ring), before);
+        assert_eq!(ring.bucket_of(&5), Some(b5));
+        assert_eq!(ring.node_at(b2).map(|n| n.key), Some(2));
+
+        // Only the items which move to the new node move.
+        let added = ring.try_insert_node(Node::new(8)).unwrap();
+        let after = owners(&ring);
+        assert!(before.iter().zip(&after).all(|(b, a)| b == a || *a == 8));
+        assert_eq!(ring.node_at(added).map(|n| n.key), Some(8));
+
+        while ring.len() < ring.capacity() {
+            ring.try_insert_node(Node::new(ring.len() + 100)).unwrap();
+        }
+        assert!(ring.try_insert_node(Node::new(1000)).is_err());
+        for i in 0..9 {
+            ring.remove_node(&i);
+        }
+        assert_eq!(ring.nodes().count(), 7);
+        assert!(owners(&ring).iter().all(|&k| k >= 100));
+    }
+}
@@ -0,0 +1,102 @@
+use std::hash::Hash;
+
+use {Node, RingHash, StaticHashRing};
+
+impl<K, V, H> StaticHashRing<K, V, H>
+    where H: RingHash
+{
+    /// Assigns each of `items` to its highest priority node (i.e., `calc_node`).
+    ///
+    /// This is intended for batch jobs which assign a large number of items.
+    /// The ring states are inspected once, and if no node is replica-only, excluded, disabled,
+    /// or scheduled for removal, each item is assigned by a single lookup
+    /// without setting up a candidate walk.
+    ///
+    /// Items which cannot be assigned (e.g., this ring is empty) are skipped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+    /// let items = vec!["foo", "bar", "baz"];
+    /// for (item, node) in ring.assign_all(&items) {
+    ///     assert_eq!(Some(node), ring.calc_node(item));
+    /// }
+    /// ```
+    pub fn assign_all<'a, 'i, T, I>(&'a self, items: I) -> Assignments<'a, K, V, H, I::IntoIter>
+        where T: Hash + 'i,
+              I: IntoIterator<Item = &'i T>
+    {
+        Assignments {
+            ring: self,
+            items: items.into_iter(),
+            direct: self.has_plain_states(),
+        }
+    }
+}
+
+/// An iterator over the pairs of items and the nodes assigned to them.
+///
+/// This is created by calling `StaticHashRing::assign_all` method.
+#[derive(Debug)]
+pub struct Assignments<'a, K: 'a, V: 'a, H: 'a, I> {
+    ring: &'a StaticHashRing<K, V, H>,
+    items: I,
+    direct: bool,
+}
+impl<'a, 'i, K: 'a, V: 'a, H: 'a, T: 'i, I> Iterator for Assignments<'a, K, V, H, I>
+    where H: RingHash,
+          T: Hash,
+          I: Iterator<Item = &'i T>
+{
+    type Item = (&'i T, &'a Node<K, V>);
+    fn next(&mut self) -> Option<Self::Item> {
+        let ring = self.ring;
+        if ring.ring.is_empty() {
+            return None;
+        }
+        for item in &mut self.items {
+            let item_hash = ring.hash.hash_item(item);
+            let node = if self.direct {
+                let vnode = &ring.ring[ring.locate(item_hash) % ring.ring.len()];
+                Some(&ring.nodes[vnode.index])
+            } else {
+                ring.calc_node_by_hash(item_hash)
+            };
+            if let Some(node) = node {
+                return Some((item, node));
+            }
+        }
+        None
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let upper = if self.ring.ring.is_empty() { Some(0) } else { self.items.size_hint().1 };
+        (0, upper)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use {DefaultHash, Node, StaticHashRing};
+
+    #[test]
+    fn assign_all_works() {
+        let mut ring = StaticHashRing::new(DefaultHash, (0..5).map(|i| Node::new(i).quantity(20)));
+        let items = (0..500).collect::<Vec<_>>();
+        for _ in 0..2 {
+            let assigned = ring.assign_all(&items).collect::<Vec<_>>();
+            assert_eq!(assigned.len(), items.len());
+            for (item, node) in assigned {
+                assert_eq!(Some(node), ring.calc_node(item));
+            }
+            ring.set_replica_only(&1, true);
+            ring.exclude_for(&2, Duration::from_secs(60));
+        }
+
+        let empty = StaticHashRing::new(DefaultHash, (0..0).map(Node::new));
+        assert_eq!(empty.assign_all(&items).count(), 0);
+    }
+}
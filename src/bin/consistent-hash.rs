@@ -0,0 +1,155 @@
+//! A command-line tool for inspecting rings.
+//!
+//! Node lists are read from ring configs (see `StaticHashRing::from_config`) if their names end with `.toml`,
+//! and from plain-text files otherwise.
+//! Each line of a plain-text file is `KEY [QUANTITY]`. Empty lines and lines starting with `#` are ignored,
+//! and nodes without quantities have the virtual node count given by `--vnodes`.
+//!
+//! ```text
+//! $ consistent-hash stats nodes.txt
+//! $ consistent-hash lookup nodes.txt foo --replicas 3
+//! $ consistent-hash diff old-nodes.txt new-nodes.txt
+//! ```
+extern crate clap;
+extern crate consistent_hash;
+
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::process;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use consistent_hash::{ConfigHash, Node, StaticHashRing};
+use consistent_hash::analysis::stats;
+
+type Ring = StaticHashRing<String, Option<String>, ConfigHash>;
+
+fn main() {
+    let nodes_arg = |name| Arg::with_name(name).index(1).required(true);
+    let vnodes_arg = || {
+        Arg::with_name("VNODES")
+            .long("vnodes")
+            .takes_value(true)
+            .default_value("160")
+            .help("Virtual node count of the nodes without quantities")
+    };
+    let matches = App::new("consistent-hash")
+        .about("Inspects consistent hash rings built from node lists")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(SubCommand::with_name("layout")
+            .about("Prints the virtual nodes in hash order")
+            .arg(nodes_arg("NODES_FILE"))
+            .arg(vnodes_arg()))
+        .subcommand(SubCommand::with_name("stats")
+            .about("Prints the distribution statistics")
+            .arg(nodes_arg("NODES_FILE"))
+            .arg(vnodes_arg()))
+        .subcommand(SubCommand::with_name("lookup")
+            .about("Prints the candidate nodes of a key")
+            .arg(nodes_arg("NODES_FILE"))
+            .arg(Arg::with_name("KEY").index(2).required(true))
+            .arg(Arg::with_name("REPLICAS")
+                .long("replicas")
+                .takes_value(true)
+                .default_value("1"))
+            .arg(vnodes_arg()))
+        .subcommand(SubCommand::with_name("diff")
+            .about("Prints the ranges whose primary nodes differ between two node lists")
+            .arg(nodes_arg("OLD_NODES_FILE"))
+            .arg(Arg::with_name("NEW_NODES_FILE").index(2).required(true))
+            .arg(vnodes_arg()))
+        .get_matches();
+
+    match matches.subcommand() {
+        ("layout", Some(m)) => {
+            let ring = load_ring(m, "NODES_FILE");
+            for (hash, node, seq) in ring.vnodes() {
+                println!("{:016x} {} {}", hash, node.key, seq);
+            }
+        }
+        ("stats", Some(m)) => {
+            let ring = load_ring(m, "NODES_FILE");
+            let stats = stats(&ring);
+            println!("NODE COUNT: {}", ring.nodes().len());
+            println!("VIRTUAL NODE COUNT: {}", ring.len());
+            println!();
+            println!("OWNERSHIP PER NODE:");
+            for (node, (key, fraction)) in ring.nodes().iter().zip(&stats.ownership) {
+                println!("  {}: {:.4}% ({} vnodes)", key, fraction * 100.0, node.quantity);
+            }
+            println!();
+            println!("STD DEV: {:.4}%", stats.std_dev * 100.0);
+            println!("MIN: {:.4}%", stats.min * 100.0);
+            println!("MAX: {:.4}%", stats.max * 100.0);
+            println!("SPREAD: {:.4}", stats.spread());
+            if let Some(gap) = stats.largest_gap {
+                println!("LARGEST GAP: {:016x}..={:016x} ({:.4}%)",
+                         gap.start,
+                         gap.end,
+                         gap.len() as f64 / 2f64.powi(64) * 100.0);
+            }
+        }
+        ("lookup", Some(m)) => {
+            let ring = load_ring(m, "NODES_FILE");
+            let key = m.value_of("KEY").unwrap();
+            let replicas = parse_arg(m, "REPLICAS");
+            for node in ring.calc_candidates(&key).take(replicas) {
+                println!("{}", node.key);
+            }
+        }
+        ("diff", Some(m)) => {
+            let old = load_ring(m, "OLD_NODES_FILE");
+            let new = load_ring(m, "NEW_NODES_FILE");
+            let diff = old.diff(&new);
+            let name = |n: Option<&Node<String, Option<String>>>| n.map_or("-", |n| n.key.as_str()).to_owned();
+            for m in &diff.moves {
+                println!("{:016x}..={:016x} {} -> {}",
+                         m.range.start,
+                         m.range.end,
+                         name(m.before),
+                         name(m.after));
+            }
+            println!();
+            println!("MOVED RANGES: {}", diff.moves.len());
+            println!("MOVED FRACTION: {:.4}%", diff.moved_fraction() * 100.0);
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn load_ring(matches: &ArgMatches, name: &str) -> Ring {
+    let path = matches.value_of(name).unwrap();
+    let vnodes = parse_arg(matches, "VNODES");
+    if path.ends_with(".toml") {
+        let config = fs::read_to_string(path).unwrap_or_else(|e| exit(&format!("Cannot read {}: {}", path, e)));
+        return StaticHashRing::from_config(&config).unwrap_or_else(|e| exit(&format!("{}: {}", path, e)));
+    }
+    let file = File::open(path).unwrap_or_else(|e| exit(&format!("Cannot open {}: {}", path, e)));
+    let mut nodes = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.unwrap_or_else(|e| exit(&format!("Cannot read {}: {}", path, e)));
+        let mut fields = line.split_whitespace();
+        let key = match fields.next() {
+            None => continue,
+            Some(key) if key.starts_with('#') => continue,
+            Some(key) => key.to_owned(),
+        };
+        let quantity = match (fields.next(), fields.next()) {
+            (None, _) => vnodes,
+            (Some(q), None) => {
+                q.parse().unwrap_or_else(|_| exit(&format!("{}:{}: Wrong quantity: {:?}", path, i + 1, q)))
+            }
+            (Some(_), Some(_)) => exit(&format!("{}:{}: Too many fields", path, i + 1)),
+        };
+        nodes.push(Node::new(key).value(None).quantity(quantity));
+    }
+    StaticHashRing::new(ConfigHash::Default, nodes.into_iter())
+}
+
+fn parse_arg(matches: &ArgMatches, name: &str) -> usize {
+    let value = matches.value_of(name).unwrap();
+    value.parse().unwrap_or_else(|_| exit(&format!("Wrong integer for {}: {:?}", name, value)))
+}
+
+fn exit(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1)
+}
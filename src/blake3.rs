@@ -0,0 +1,349 @@
+use std::hash::{Hash, Hasher};
+
+use {DefaultHash, RingHash};
+
+const IV: [u32; 8] = [0x6A09_E667, 0xBB67_AE85, 0x3C6E_F372, 0xA54F_F53A, 0x510E_527F, 0x9B05_688C,
+                      0x1F83_D9AB, 0x5BE0_CD19];
+const MSG_PERMUTATION: [usize; 16] = [2, 6, 3, 10, 7, 0, 4, 13, 1, 11, 12, 5, 9, 14, 15, 8];
+
+const BLOCK_LEN: usize = 64;
+const CHUNK_LEN: usize = 1024;
+
+const CHUNK_START: u32 = 1;
+const CHUNK_END: u32 = 2;
+const PARENT: u32 = 4;
+const ROOT: u32 = 8;
+const KEYED_HASH: u32 = 16;
+
+/// A `RingHash` implementation which uses `BLAKE3` (the first 64 bits of the digest).
+///
+/// This is a cryptographic hash function, so it is suitable for the rings whose items are
+/// controlled by untrusted parties.
+///
+/// This is available if the `blake3` feature is enabled.
+#[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub struct Blake3Hash;
+impl RingHash for Blake3Hash {
+    fn hash_item<T: Hash>(&self, item: &T) -> u64 {
+        let mut hasher = Blake3Hasher::new(IV, 0);
+        item.hash(&mut hasher);
+        hasher.finish()
+    }
+    fn algorithm_id(&self) -> &str {
+        "blake3"
+    }
+}
+impl Blake3Hash {
+    /// Makes a `BLAKE3` hasher which uses the keyed hash mode with the secret `key`.
+    ///
+    /// Rings built with different keys have independent layouts,
+    /// and the layouts cannot be predicted without the keys.
+    pub fn with_key(key: [u8; 32]) -> KeyedBlake3Hash {
+        KeyedBlake3Hash {
+            key: first_8_words(block_words(&key)),
+            algorithm_id: format!("blake3-keyed-{:016x}", DefaultHash.hash_item(&key)),
+        }
+    }
+}
+
+/// A `RingHash` implementation which uses the keyed hash mode of `BLAKE3`.
+///
+/// This is created by calling `Blake3Hash::with_key` function.
+///
+/// This is available if the `blake3` feature is enabled.
+#[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub struct KeyedBlake3Hash {
+    key: [u32; 8],
+    algorithm_id: String,
+}
+impl RingHash for KeyedBlake3Hash {
+    fn hash_item<T: Hash>(&self, item: &T) -> u64 {
+        let mut hasher = Blake3Hasher::new(self.key, KEYED_HASH);
+        item.hash(&mut hasher);
+        hasher.finish()
+    }
+    fn algorithm_id(&self) -> &str {
+        &self.algorithm_id
+    }
+}
+
+// The streaming state of `BLAKE3`.
+//
+// The written bytes are digested block by block, and only the chaining values of the completed subtrees
+// are kept (so items shorter than a chunk never allocate).
+struct Blake3Hasher {
+    key: [u32; 8],
+    mode: u32,
+    chunk: ChunkState,
+
+    // The chaining values of the completed subtrees (the largest one first).
+    stack: Vec<[u32; 8]>,
+}
+impl Blake3Hasher {
+    // `key` is `IV` and `mode` is `0` for the hash mode, and they are the key words and `KEYED_HASH`
+    // for the keyed hash mode.
+    fn new(key: [u32; 8], mode: u32) -> Self {
+        Blake3Hasher {
+            key,
+            mode,
+            chunk: ChunkState::new(key, 0, mode),
+            stack: Vec::new(),
+        }
+    }
+
+    // Returns the first 64 bytes of the digest (as little-endian words).
+    fn root(&self) -> [u32; 16] {
+        let mut output = self.chunk.output();
+        for left in self.stack.iter().rev() {
+            output = parent_output(left, &output.chaining_value(), &self.key, self.mode);
+        }
+        output.root()
+    }
+}
+impl Hasher for Blake3Hasher {
+    fn write(&mut self, mut bytes: &[u8]) {
+        while !bytes.is_empty() {
+            if self.chunk.len() == CHUNK_LEN {
+                let mut cv = self.chunk.output().chaining_value();
+                let mut total = self.chunk.counter + 1;
+                while total & 1 == 0 {
+                    cv = parent_output(&self.stack.pop().expect("Never fails"), &cv, &self.key, self.mode)
+                        .chaining_value();
+                    total >>= 1;
+                }
+                self.stack.push(cv);
+                self.chunk = ChunkState::new(self.key, self.chunk.counter + 1, self.mode);
+            }
+            let n = (CHUNK_LEN - self.chunk.len()).min(bytes.len());
+            self.chunk.update(&bytes[..n]);
+            bytes = &bytes[n..];
+        }
+    }
+    fn finish(&self) -> u64 {
+        let words = self.root();
+        u64::from(words[0]) | (u64::from(words[1]) << 32)
+    }
+}
+
+// The state of the chunk which is being digested.
+//
+// The last block is kept in `block` until it is known not to be the last one of the chunk.
+struct ChunkState {
+    cv: [u32; 8],
+    counter: u64,
+    block: [u8; BLOCK_LEN],
+    block_len: usize,
+    blocks_compressed: usize,
+    mode: u32,
+}
+impl ChunkState {
+    fn new(key: [u32; 8], counter: u64, mode: u32) -> Self {
+        ChunkState {
+            cv: key,
+            counter,
+            block: [0; BLOCK_LEN],
+            block_len: 0,
+            blocks_compressed: 0,
+            mode,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.blocks_compressed * BLOCK_LEN + self.block_len
+    }
+
+    fn flags(&self) -> u32 {
+        if self.blocks_compressed == 0 { self.mode | CHUNK_START } else { self.mode }
+    }
+
+    fn update(&mut self, mut bytes: &[u8]) {
+        while !bytes.is_empty() {
+            if self.block_len == BLOCK_LEN {
+                let block = block_words(&self.block);
+                let flags = self.flags();
+                self.cv = first_8_words(compress(&self.cv, &block, self.counter, BLOCK_LEN as u32, flags));
+                self.blocks_compressed += 1;
+                self.block_len = 0;
+            }
+            let n = (BLOCK_LEN - self.block_len).min(bytes.len());
+            self.block[self.block_len..self.block_len + n].copy_from_slice(&bytes[..n]);
+            self.block_len += n;
+            bytes = &bytes[n..];
+        }
+    }
+
+    fn output(&self) -> Output {
+        Output {
+            cv: self.cv,
+            block: block_words(&self.block[..self.block_len]),
+            counter: self.counter,
+            block_len: self.block_len as u32,
+            flags: self.flags() | CHUNK_END,
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn g(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize, mx: u32, my: u32) {
+    state[a] = state[a].wrapping_add(state[b]).wrapping_add(mx);
+    state[d] = (state[d] ^ state[a]).rotate_right(16);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_right(12);
+    state[a] = state[a].wrapping_add(state[b]).wrapping_add(my);
+    state[d] = (state[d] ^ state[a]).rotate_right(8);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_right(7);
+}
+
+fn round(state: &mut [u32; 16], m: &[u32; 16]) {
+    g(state, 0, 4, 8, 12, m[0], m[1]);
+    g(state, 1, 5, 9, 13, m[2], m[3]);
+    g(state, 2, 6, 10, 14, m[4], m[5]);
+    g(state, 3, 7, 11, 15, m[6], m[7]);
+    g(state, 0, 5, 10, 15, m[8], m[9]);
+    g(state, 1, 6, 11, 12, m[10], m[11]);
+    g(state, 2, 7, 8, 13, m[12], m[13]);
+    g(state, 3, 4, 9, 14, m[14], m[15]);
+}
+
+fn compress(cv: &[u32; 8], block: &[u32; 16], counter: u64, block_len: u32, flags: u32) -> [u32; 16] {
+    let mut state = [0; 16];
+    state[..8].copy_from_slice(cv);
+    state[8..12].copy_from_slice(&IV[..4]);
+    state[12] = counter as u32;
+    state[13] = (counter >> 32) as u32;
+    state[14] = block_len;
+    state[15] = flags;
+
+    let mut m = *block;
+    for _ in 0..7 {
+        round(&mut state, &m);
+        let mut permuted = [0; 16];
+        for (p, &i) in permuted.iter_mut().zip(MSG_PERMUTATION.iter()) {
+            *p = m[i];
+        }
+        m = permuted;
+    }
+    for i in 0..8 {
+        state[i] ^= state[i + 8];
+        state[i + 8] ^= cv[i];
+    }
+    state
+}
+
+fn first_8_words(words: [u32; 16]) -> [u32; 8] {
+    let mut cv = [0; 8];
+    cv.copy_from_slice(&words[..8]);
+    cv
+}
+
+fn block_words(block: &[u8]) -> [u32; 16] {
+    let mut bytes = [0; BLOCK_LEN];
+    bytes[..block.len()].copy_from_slice(block);
+    let mut words = [0; 16];
+    for (w, b) in words.iter_mut().zip(bytes.chunks(4)) {
+        *w = u32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+    }
+    words
+}
+
+// The inputs of a compression whose result is not yet known to be the root.
+struct Output {
+    cv: [u32; 8],
+    block: [u32; 16],
+    counter: u64,
+    block_len: u32,
+    flags: u32,
+}
+impl Output {
+    fn chaining_value(&self) -> [u32; 8] {
+        first_8_words(compress(&self.cv, &self.block, self.counter, self.block_len, self.flags))
+    }
+
+    fn root(&self) -> [u32; 16] {
+        compress(&self.cv, &self.block, 0, self.block_len, self.flags | ROOT)
+    }
+}
+
+fn parent_output(left: &[u32; 8], right: &[u32; 8], key: &[u32; 8], mode: u32) -> Output {
+    let mut block = [0; 16];
+    block[..8].copy_from_slice(left);
+    block[8..].copy_from_slice(right);
+    Output {
+        cv: *key,
+        block,
+        counter: 0,
+        block_len: BLOCK_LEN as u32,
+        flags: mode | PARENT,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {Node, RingHash, StaticHashRing};
+    use super::*;
+
+    // Returns the first 64 bytes of the digest of `input` (as little-endian words).
+    fn blake3(input: &[u8], key: &[u32; 8], mode: u32) -> [u32; 16] {
+        let mut hasher = Blake3Hasher::new(*key, mode);
+        hasher.write(input);
+        hasher.root()
+    }
+
+    fn hex(words: [u32; 16]) -> String {
+        words[..8].iter().flat_map(|w| w.to_le_bytes().to_vec()).map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn blake3_works() {
+        assert_eq!(hex(blake3(b"", &IV, 0)),
+                   "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262");
+        assert_eq!(hex(blake3(b"abc", &IV, 0)),
+                   "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85");
+
+        assert_eq!(Blake3Hash.hash_item(&"foo"), Blake3Hash.hash_item(&"foo"));
+        assert_eq!(Blake3Hash.hash_bytes(b"abc"), 0x3351_4638_acb3_3764);
+        let ring = StaticHashRing::new(Blake3Hash, (0..3).map(|i| Node::new(i).quantity(10)));
+        assert_eq!(ring.format_tag(), "ring-v1-blake3");
+        assert_eq!(ring.calc_candidates(&"foo").count(), 3);
+    }
+
+    #[test]
+    fn blake3_streaming_works() {
+        let input = (0..8193).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        assert_eq!(hex(blake3(&input[..1024], &IV, 0)),
+                   "42214739f095a406f3fc83deb889744ac00df831c10daa55189b5d121c855af7");
+        assert_eq!(hex(blake3(&input[..1025], &IV, 0)),
+                   "d00278ae47eb27b34faecf67b4fe263f82d5412916c1ffd97c8cb7fb814b8444");
+        assert_eq!(hex(blake3(&input[..3073], &IV, 0)),
+                   "7124b49501012f81cc7f11ca069ec9226cecb8a2c850cfe644e327d22d3e1cd3");
+        assert_eq!(hex(blake3(&input[..4096], &IV, 0)),
+                   "015094013f57a5277b59d8475c0501042c0b642e531b0a1c8f58d2163229e969");
+        assert_eq!(hex(blake3(&input, &IV, 0)),
+                   "bab6c09cb8ce8cf459261398d2e7aef35700bf488116ceb94a36d0f5f1b7bc3b");
+
+        for &len in &[0, 63, 64, 65, 1024, 1025, 2048, 5121] {
+            for &split in &[1, 7, 64, 100, 1024] {
+                let mut hasher = Blake3Hasher::new(IV, 0);
+                for piece in input[..len].chunks(split) {
+                    hasher.write(piece);
+                }
+                assert_eq!(hasher.root(), blake3(&input[..len], &IV, 0));
+            }
+        }
+    }
+
+    #[test]
+    fn keyed_blake3_works() {
+        // The key of the official test vectors.
+        let hash = Blake3Hash::with_key(*b"whats the Elvish word for friend");
+        assert_eq!(hex(blake3(b"", &hash.key, KEYED_HASH)),
+                   "92b2b75604ed3c761f9d6f62392c8a9227ad0ea3f09573e783f1498a4ed60d26");
+
+        assert_eq!(hash.hash_item(&"foo"), hash.hash_item(&"foo"));
+        assert_ne!(hash.hash_item(&"foo"), Blake3Hash.hash_item(&"foo"));
+        assert_ne!(hash.hash_item(&"foo"), Blake3Hash::with_key([0; 32]).hash_item(&"foo"));
+        let ring = StaticHashRing::new(hash, (0..3).map(|i| Node::new(i).quantity(10)));
+        assert!(ring.format_tag().starts_with("ring-v1-blake3-keyed-"));
+    }
+}
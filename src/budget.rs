@@ -0,0 +1,105 @@
+use std::cmp;
+use std::hash::Hash;
+
+use {CandidateVnodes, Node, RingHash, StaticHashRing};
+
+impl<K, V, H> StaticHashRing<K, V, H>
+    where H: RingHash
+{
+    /// Returns the candidate nodes for `item`, visiting at most `max_vnodes` virtual nodes.
+    ///
+    /// This protects latency-sensitive paths from pathological rings
+    /// (e.g., where many consecutive virtual nodes belong to excluded nodes).
+    /// The resulting sequence is a prefix of `calc_candidates(item)`
+    /// (except that replica-only nodes found within the budget are yielded at the end
+    /// if no nodes which can be primary are found), and `BudgetedCandidates::is_truncated`
+    /// tells whether the walk was cut off by the budget.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+    ///
+    /// let mut candidates = ring.calc_candidates_with_budget(&"foo", 1);
+    /// assert_eq!(candidates.next(), ring.calc_candidates(&"foo").next());
+    /// assert_eq!(candidates.next(), None);
+    /// assert!(candidates.is_truncated());
+    ///
+    /// let mut candidates = ring.calc_candidates_with_budget(&"foo", 30);
+    /// assert_eq!(candidates.by_ref().count(), 3);
+    /// assert!(!candidates.is_truncated());
+    /// ```
+    pub fn calc_candidates_with_budget<T: Hash + ?Sized>(&self,
+                                                         item: &T,
+                                                         max_vnodes: usize)
+                                                         -> BudgetedCandidates<'_, K, V> {
+        let mut vnodes = self.candidate_vnodes(self.locate(self.hash.hash_item(&item)));
+        vnodes.max_count = cmp::min(max_vnodes, self.ring.len());
+        BudgetedCandidates(vnodes)
+    }
+}
+
+/// An iterator which represents a sequence of the candidate nodes for an item
+/// found within a budget of visited virtual nodes.
+///
+/// This is created by calling `StaticHashRing::calc_candidates_with_budget` method.
+pub struct BudgetedCandidates<'a, K: 'a, V: 'a>(CandidateVnodes<'a, K, V>);
+impl<'a, K: 'a, V: 'a> BudgetedCandidates<'a, K, V> {
+    /// Returns `true` if the walk has been cut off by the budget
+    /// (i.e., some candidates may be missing), otherwise `false`.
+    ///
+    /// The result is final once this iterator has returned `None`.
+    pub fn is_truncated(&self) -> bool {
+        let vnodes = &self.0;
+        vnodes.count == vnodes.max_count && vnodes.count < vnodes.ring.len() &&
+        vnodes.seens.len() + vnodes.unseen_excluded < vnodes.nodes
+    }
+
+    /// Returns the count of the virtual nodes visited so far.
+    pub fn visited_vnodes(&self) -> usize {
+        self.0.count
+    }
+}
+impl<'a, K: 'a, V: 'a> Iterator for BudgetedCandidates<'a, K, V> {
+    type Item = &'a Node<K, V>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|i| self.0.owner(i))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use {DefaultHash, Node, StaticHashRing};
+
+    #[test]
+    fn budgeted_candidates_works() {
+        let mut ring = StaticHashRing::new(DefaultHash, (0..5).map(|i| Node::new(i).quantity(10)));
+        for i in 0..4 {
+            ring.exclude_for(&i, Duration::from_secs(10));
+        }
+        for item in 0..100 {
+            let all = ring.calc_candidates(&item).collect::<Vec<_>>();
+            assert_eq!(all.len(), 1);
+            let walk = ring.walk_vnodes(&item).position(|(_, n)| n.key == 4).unwrap();
+
+            let mut candidates = ring.calc_candidates_with_budget(&item, walk);
+            assert_eq!(candidates.next(), None);
+            assert!(candidates.is_truncated());
+            assert_eq!(candidates.visited_vnodes(), walk);
+
+            let mut candidates = ring.calc_candidates_with_budget(&item, walk + 1);
+            assert_eq!(candidates.by_ref().collect::<Vec<_>>(), all);
+
+            // The remaining nodes are all excluded.
+            assert!(!candidates.is_truncated());
+        }
+
+        let mut candidates = ring.calc_candidates_with_budget(&0, usize::MAX);
+        assert_eq!(candidates.by_ref().count(), 1);
+        assert!(!candidates.is_truncated());
+        assert!(candidates.visited_vnodes() <= ring.len());
+    }
+}
@@ -0,0 +1,105 @@
+use std::hash::Hash;
+use std::mem;
+
+use {RingHash, StaticHashRing};
+
+/// A reusable buffer for looking up candidates without allocations.
+///
+/// Lookups for small rings never allocate, whereas lookups which visit many nodes of a large ring
+/// allocate a bitset for deduplicating the nodes.
+/// A buffer keeps the bitset (and the resulting indices) across lookups,
+/// so repeated lookups (e.g., one per request) only allocate on the first call.
+///
+/// `Default::default()` is the same as `CandidateBuffer::new()`.
+///
+/// # Examples
+///
+/// ```
+/// use consistent_hash::{Node, StaticHashRing, DefaultHash, CandidateBuffer};
+///
+/// let ring = StaticHashRing::new(DefaultHash, (0..1000).map(|i| Node::new(i).quantity(4)));
+/// let mut buffer = CandidateBuffer::new();
+/// for item in 0..100 {
+///     let expected = ring.calc_candidate_indices(&item).take(3).collect::<Vec<_>>();
+///     assert_eq!(ring.calc_candidate_indices_into(&item, 3, &mut buffer), &expected[..]);
+/// }
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct CandidateBuffer {
+    indices: Vec<usize>,
+    seens: Vec<u64>,
+}
+impl CandidateBuffer {
+    /// Makes a new empty `CandidateBuffer` instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the indices stored by the last lookup.
+    pub fn indices(&self) -> &[usize] {
+        &self.indices
+    }
+}
+
+impl<K, V, H> StaticHashRing<K, V, H>
+    where H: RingHash
+{
+    /// Stores the indices of the first `n` candidate nodes for `item` into `buffer`, and returns them.
+    ///
+    /// The indices are the same as the first `n` elements of `calc_candidate_indices`,
+    /// but the memory of `buffer` is reused instead of being allocated for each lookup.
+    /// Note that deferring replica-only nodes may still allocate.
+    pub fn calc_candidate_indices_into<'b, T: Hash + ?Sized>(&self,
+                                                             item: &T,
+                                                             n: usize,
+                                                             buffer: &'b mut CandidateBuffer)
+                                                             -> &'b [usize] {
+        let item_hash = self.hash.hash_item(&item);
+        let started = self.observer.start();
+        let start = self.locate(item_hash);
+        if let Some(started) = started {
+            self.notify_lookup(item_hash, self.candidate_vnodes(start).next(), started);
+        }
+
+        let mut vnodes = self.candidate_vnodes(start).with_seen_buffer(mem::take(&mut buffer.seens));
+        buffer.indices.clear();
+        while buffer.indices.len() < n {
+            match vnodes.next() {
+                None => break,
+                Some(i) => buffer.indices.push(self.ring[i].index),
+            }
+        }
+        buffer.seens = vnodes.into_seen_buffer();
+        &buffer.indices
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {DefaultHash, Node, StaticHashRing};
+    use super::*;
+
+    #[test]
+    fn candidate_buffer_works() {
+        let mut buffer = CandidateBuffer::new();
+        for &nodes in &[3, 10, 300, 1000] {
+            let ring = StaticHashRing::new(DefaultHash, (0..nodes).map(|i| Node::new(i).quantity(3)));
+            for item in 0..20 {
+                let expected = ring.calc_candidate_indices(&item).collect::<Vec<_>>();
+                assert_eq!(expected.len(), nodes);
+                assert_eq!(ring.calc_candidate_indices_into(&item, usize::MAX, &mut buffer), &expected[..]);
+                assert_eq!(ring.calc_candidate_indices_into(&item, 2, &mut buffer), &expected[..2]);
+                assert_eq!(buffer.indices(), &expected[..2]);
+            }
+        }
+
+        // The memory is reused once allocated.
+        let ring = StaticHashRing::new(DefaultHash, (0..1000).map(|i| Node::new(i).quantity(3)));
+        ring.calc_candidate_indices_into(&"foo", 1000, &mut buffer);
+        let capacities = (buffer.indices.capacity(), buffer.seens.capacity());
+        for item in 0..10 {
+            ring.calc_candidate_indices_into(&item, 1000, &mut buffer);
+        }
+        assert_eq!((buffer.indices.capacity(), buffer.seens.capacity()), capacities);
+    }
+}
@@ -0,0 +1,489 @@
+use std::error::Error;
+use std::fmt;
+use std::hash::Hash;
+use std::mem;
+
+use namespace::Namespaced;
+use {Node, RingHash, StaticHashRing};
+
+#[derive(Debug, Clone)]
+struct Entry<K, V> {
+    node: Node<K, V>,
+
+    // The vnode hashes whose sequence numbers are less than the length of this cache (in any order).
+    cache: Option<Vec<u64>>,
+
+    // If `true`, `cache` holds the hashes of all of the vnodes, and they are kept even if the hash is replaced.
+    frozen: bool,
+}
+impl<K, V> Entry<K, V> {
+    fn new(node: Node<K, V>) -> Self {
+        Entry {
+            node,
+            cache: None,
+            frozen: false,
+        }
+    }
+
+    // Discards the cache unless this entry is frozen.
+    fn rehash(self) -> Self {
+        if self.frozen {
+            self
+        } else {
+            Entry::new(self.node)
+        }
+    }
+}
+
+/// The error which is returned when a `RingBuilder` has an invalid configuration.
+///
+/// See `RingBuilder::try_build`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum BuildError<K> {
+    /// The builder has no nodes.
+    NoNodes,
+
+    /// The sum of the quantities of the nodes is zero.
+    ZeroQuantity,
+
+    /// A node which has the key was added more than once by `RingBuilder::add_node`.
+    DuplicateKey(K),
+}
+impl<K: fmt::Debug> fmt::Display for BuildError<K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BuildError::NoNodes => write!(f, "The ring has no nodes"),
+            BuildError::ZeroQuantity => write!(f, "The total quantity of the nodes is zero"),
+            BuildError::DuplicateKey(ref key) => write!(f, "Duplicate node key: {:?}", key),
+        }
+    }
+}
+impl<K: fmt::Debug> Error for BuildError<K> {}
+
+/// A builder of `StaticHashRing` which keeps the computed hash codes of virtual nodes.
+///
+/// This is created by calling `StaticHashRing::builder`, `StaticHashRing::clone_topology`, or `RingBuilder::new`.
+/// When a ring is built, the cached hash codes are reused as long as the hash is not replaced,
+/// so building variants of an existing ring (e.g., "same nodes, different quantities")
+/// only computes the hash codes of the new virtual nodes.
+#[derive(Debug, Clone)]
+pub struct RingBuilder<K, V, H> {
+    hash: H,
+
+    // Sorted by the keys of the nodes.
+    entries: Vec<Entry<K, V>>,
+
+    // The first duplicate key added by `add_node` (if any).
+    duplicate: Option<K>,
+}
+impl<K, V, H> RingBuilder<K, V, H>
+    where K: Hash + Eq + Ord,
+          H: RingHash
+{
+    /// Makes a new `RingBuilder` instance which has no nodes.
+    pub fn new(hash: H) -> Self {
+        RingBuilder {
+            hash,
+            entries: Vec::new(),
+            duplicate: None,
+        }
+    }
+
+    /// Adds `node` to this builder, and returns the builder.
+    ///
+    /// Unlike `insert_node`, adding a node whose key already exists is regarded as a configuration error,
+    /// which is reported by `try_build` (the first added node is kept).
+    pub fn add_node(mut self, node: Node<K, V>) -> Self {
+        match self.position(&node.key) {
+            Ok(_) => {
+                if self.duplicate.is_none() {
+                    self.duplicate = Some(node.key);
+                }
+            }
+            Err(i) => self.entries.insert(i, Entry::new(node)),
+        }
+        self
+    }
+
+    /// Inserts `node` into this builder.
+    ///
+    /// If this builder already contains a node which has the same key,
+    /// the node is replaced by `node` and the old one is returned
+    /// (if the node is frozen, its quantity is kept).
+    pub fn insert_node(&mut self, node: Node<K, V>) -> Option<Node<K, V>> {
+        match self.position(&node.key) {
+            Ok(i) => {
+                let entry = &mut self.entries[i];
+                let old = mem::replace(&mut entry.node, node);
+                if entry.frozen {
+                    entry.node.quantity = old.quantity;
+                }
+                Some(old)
+            }
+            Err(i) => {
+                self.entries.insert(i, Entry::new(node));
+                None
+            }
+        }
+    }
+
+    /// Removes the node identified by `key` from this builder, and returns it.
+    pub fn remove_node(&mut self, key: &K) -> Option<Node<K, V>> {
+        self.position(key).ok().map(|i| self.entries.remove(i).node)
+    }
+
+    /// Sets the quantity of the node identified by `key` to `quantity`.
+    ///
+    /// Returns `false` if this builder does not contain the node or the node is frozen.
+    pub fn set_quantity(&mut self, key: &K, quantity: usize) -> bool {
+        match self.position(key) {
+            Ok(i) if !self.entries[i].frozen => {
+                self.entries[i].node.quantity = quantity;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Freezes the placement of the node identified by `key`.
+    ///
+    /// The current virtual node positions of a frozen node are pinned in the rings built by this builder,
+    /// even if its quantity is changed by `set_quantity` (which is ignored) or the hash is replaced.
+    /// So, a node undergoing data verification keeps exactly its key set (as far as the neighbours do not change)
+    /// while the rest of the ring is retuned.
+    ///
+    /// Returns `false` if this builder does not contain the node.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+    ///
+    /// let mut builder = ring.clone_topology();
+    /// assert!(builder.freeze(&0));
+    /// assert!(!builder.set_quantity(&0, 20));
+    /// let salted = builder.namespace("v2").build();
+    ///
+    /// assert!(salted.vnodes().filter(|v| v.1.key == 0).map(|v| v.0)
+    ///     .eq(ring.vnodes().filter(|v| v.1.key == 0).map(|v| v.0)));
+    /// ```
+    pub fn freeze(&mut self, key: &K) -> bool {
+        let i = match self.position(key) {
+            Err(_) => return false,
+            Ok(i) => i,
+        };
+        let entry = &mut self.entries[i];
+        if !entry.frozen {
+            let quantity = entry.node.quantity;
+            let cached = match entry.cache.take() {
+                Some(hashes) if hashes.len() <= quantity => hashes,
+                _ => Vec::new(),
+            };
+            let hash = &self.hash;
+            let key = &entry.node.key;
+            let hashes = (cached.len()..quantity).map(|seq| hash.hash_vnode(key, seq));
+            entry.cache = Some(cached.iter().cloned().chain(hashes).collect());
+            entry.frozen = true;
+        }
+        true
+    }
+
+    /// Unfreezes the node identified by `key` (see `freeze`).
+    ///
+    /// The pinned positions are kept as a cache until the hash is replaced.
+    ///
+    /// Returns `false` if this builder does not contain the node or the node is not frozen.
+    pub fn unfreeze(&mut self, key: &K) -> bool {
+        match self.position(key) {
+            Ok(i) if self.entries[i].frozen => {
+                self.entries[i].frozen = false;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if the node identified by `key` is frozen, otherwise `false`.
+    pub fn is_frozen(&self, key: &K) -> bool {
+        self.position(key).is_ok_and(|i| self.entries[i].frozen)
+    }
+
+    /// Replaces the hash of this builder with `hash` (e.g., a hash with another salt).
+    ///
+    /// Since the hash codes of virtual nodes depend on the hash, the cached codes are discarded
+    /// (except for frozen nodes).
+    pub fn hash<H2: RingHash>(self, hash: H2) -> RingBuilder<K, V, H2> {
+        RingBuilder {
+            hash,
+            entries: self.entries.into_iter().map(Entry::rehash).collect(),
+            duplicate: self.duplicate,
+        }
+    }
+
+    /// Salts the hash of this builder with `namespace` (see `Namespaced`).
+    ///
+    /// As with `hash`, the cached codes are discarded (except for frozen nodes).
+    pub fn namespace(self, namespace: &str) -> RingBuilder<K, V, Namespaced<H>> {
+        RingBuilder {
+            hash: Namespaced::new(self.hash, namespace),
+            entries: self.entries.into_iter().map(Entry::rehash).collect(),
+            duplicate: self.duplicate,
+        }
+    }
+
+    /// Returns an iterator over the nodes of this builder (sorted in key order).
+    pub fn nodes(&self) -> impl Iterator<Item = &Node<K, V>> {
+        self.entries.iter().map(|e| &e.node)
+    }
+
+    /// Builds a `StaticHashRing` instance after validating the configuration of this builder.
+    ///
+    /// This fails if a duplicate key was added by `add_node`, if there are no nodes,
+    /// or if the total quantity of the nodes is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash, BuildError};
+    ///
+    /// let ring = StaticHashRing::builder(DefaultHash)
+    ///     .add_node(Node::new("foo").quantity(5))
+    ///     .add_node(Node::new("bar").quantity(5))
+    ///     .try_build()
+    ///     .unwrap();
+    /// assert_eq!(ring.len(), 10);
+    ///
+    /// let result = StaticHashRing::builder(DefaultHash)
+    ///     .add_node(Node::new("foo").quantity(5))
+    ///     .add_node(Node::new("foo").quantity(3))
+    ///     .try_build();
+    /// assert_eq!(result.err(), Some(BuildError::DuplicateKey("foo")));
+    /// ```
+    pub fn try_build(self) -> Result<StaticHashRing<K, V, H>, BuildError<K>> {
+        if let Some(key) = self.duplicate {
+            return Err(BuildError::DuplicateKey(key));
+        }
+        if self.entries.is_empty() {
+            return Err(BuildError::NoNodes);
+        }
+        if self.entries.iter().all(|e| e.node.quantity == 0) {
+            return Err(BuildError::ZeroQuantity);
+        }
+        Ok(self.build())
+    }
+
+    /// Builds a `StaticHashRing` instance.
+    ///
+    /// Unlike `try_build`, this never fails (duplicate keys added by `add_node` are ignored).
+    pub fn build(self) -> StaticHashRing<K, V, H> {
+        let (nodes, cached): (Vec<_>, Vec<_>) = self.entries.into_iter().map(|e| (e.node, e.cache)).unzip();
+        StaticHashRing::from_sorted_nodes(self.hash, nodes, &cached)
+    }
+
+    fn position(&self, key: &K) -> Result<usize, usize> {
+        self.entries.binary_search_by(|e| e.node.key.cmp(key))
+    }
+}
+
+impl<K, V, H> StaticHashRing<K, V, H>
+    where K: Hash + Eq + Ord + Clone,
+          V: Clone,
+          H: RingHash + Clone
+{
+    /// Returns a builder which has the same nodes and hash as this ring.
+    ///
+    /// The hash codes of the virtual nodes of this ring are kept in the builder
+    /// (except for the nodes whose virtual nodes have been removed by `take` and the like),
+    /// and the node states (e.g., replica-only flags) are not copied.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(100)));
+    ///
+    /// let mut builder = ring.clone_topology();
+    /// builder.set_quantity(&0, 200);
+    /// let variant = builder.build();
+    /// assert_eq!(variant.len(), 400);
+    /// ```
+    pub fn clone_topology(&self) -> RingBuilder<K, V, H> {
+        let mut caches = vec![Vec::new(); self.nodes.len()];
+        for vn in &self.ring {
+            caches[vn.index].push(vn.hash);
+        }
+        let entries = self.nodes
+            .iter()
+            .zip(caches)
+            .map(|(n, hashes)| {
+                Entry {
+                    node: n.clone(),
+                    cache: if hashes.len() == n.quantity { Some(hashes) } else { None },
+                    frozen: false,
+                }
+            })
+            .collect();
+        RingBuilder {
+            hash: self.hash.clone(),
+            entries,
+            duplicate: None,
+        }
+    }
+}
+impl<K, V, H> StaticHashRing<K, V, H>
+    where K: Hash + Eq + Ord,
+          H: RingHash
+{
+    /// Returns a new builder which has no nodes (i.e., `RingBuilder::new(hash)`).
+    pub fn builder(hash: H) -> RingBuilder<K, V, H> {
+        RingBuilder::new(hash)
+    }
+
+    /// Makes a new `StaticHashRing` instance, rejecting configurations which would make an empty ring.
+    ///
+    /// Unlike `new`, this fails if `nodes` is empty, if the total quantity of the nodes is zero
+    /// (i.e., the ring would have no virtual nodes), or if multiple nodes have the same key.
+    /// The checks are the same as those of `RingBuilder::try_build`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash, BuildError};
+    ///
+    /// let ring = StaticHashRing::try_new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10))).unwrap();
+    /// assert_eq!(ring.len(), 30);
+    ///
+    /// let result = StaticHashRing::try_new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(0)));
+    /// assert_eq!(result.err(), Some(BuildError::ZeroQuantity));
+    /// ```
+    pub fn try_new<I>(hash: H, nodes: I) -> Result<Self, BuildError<K>>
+        where I: Iterator<Item = Node<K, V>>
+    {
+        nodes.fold(Self::builder(hash), |builder, node| builder.add_node(node)).try_build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::hash::Hash;
+    use std::rc::Rc;
+    use {DefaultHash, Node, RingHash, StaticHashRing};
+    use super::*;
+
+    // Counts the calls of `hash_vnode`.
+    #[derive(Debug, Clone, Default)]
+    struct CountingHash(Rc<Cell<usize>>, u64);
+    impl RingHash for CountingHash {
+        fn hash_item<T: Hash>(&self, item: &T) -> u64 {
+            DefaultHash.hash_item(&(self.1, item))
+        }
+        fn hash_vnode<K: Hash>(&self, node_key: &K, vnode_seq: usize) -> u64 {
+            self.0.set(self.0.get() + 1);
+            self.hash_item(&(node_key, vnode_seq))
+        }
+    }
+
+    fn same_placement<H0: RingHash, H1: RingHash>(a: &StaticHashRing<i32, (), H0>,
+                                                  b: &StaticHashRing<i32, (), H1>)
+                                                  -> bool {
+        (0..100).all(|i| a.calc_candidates(&i).eq(b.calc_candidates(&i)))
+    }
+
+    #[test]
+    fn clone_topology_works() {
+        let hash = CountingHash::default();
+        let calls = hash.0.clone();
+        let mut ring = StaticHashRing::new(hash.clone(), (0..3).map(|i| Node::new(i).quantity(10)));
+        assert_eq!(calls.get(), 30);
+
+        let mut builder = ring.clone_topology();
+        builder.set_quantity(&0, 15);
+        builder.set_quantity(&1, 5);
+        builder.insert_node(Node::new(3).quantity(10));
+        assert_eq!(builder.remove_node(&2), Some(Node::new(2).quantity(10)));
+        let variant = builder.build();
+        assert_eq!(calls.get(), 30 + 5 + 5 + 10);
+        let nodes = vec![Node::new(0).quantity(15), Node::new(1).quantity(5), Node::new(3).quantity(10)];
+        assert!(same_placement(&variant, &StaticHashRing::new(hash.clone(), nodes.into_iter())));
+
+        // Nodes whose virtual nodes were taken are recomputed.
+        ring.take(&"foo");
+        calls.set(0);
+        let rebuilt = ring.clone_topology().build();
+        assert_eq!(calls.get(), 10);
+        assert_eq!(rebuilt.len(), 30);
+
+        // Replacing the hash discards the caches.
+        calls.set(0);
+        let salted = ring.clone_topology().hash(CountingHash(calls.clone(), 1)).build();
+        assert_eq!(calls.get(), 30);
+        let expected = StaticHashRing::new(CountingHash(calls.clone(), 1), (0..3).map(|i| Node::new(i).quantity(10)));
+        assert!(same_placement(&salted, &expected));
+    }
+
+    #[test]
+    fn freeze_works() {
+        let hash = CountingHash::default();
+        let calls = hash.0.clone();
+        let ring = StaticHashRing::new(hash.clone(), (0..3).map(|i| Node::new(i).quantity(10)));
+        let vnodes_of = |r: &StaticHashRing<i32, (), CountingHash>, key| {
+            r.vnodes().filter(|v| v.1.key == key).map(|v| v.0).collect::<Vec<_>>()
+        };
+
+        let mut builder = ring.clone_topology();
+        assert!(builder.freeze(&1));
+        assert!(builder.is_frozen(&1) && !builder.is_frozen(&0));
+        assert!(!builder.freeze(&5));
+        assert!(!builder.set_quantity(&1, 30));
+        assert!(builder.set_quantity(&0, 30));
+        builder.insert_node(Node::new(1).quantity(1));
+        calls.set(0);
+        let retuned = builder.clone().hash(CountingHash(calls.clone(), 1)).build();
+        assert_eq!(calls.get(), 30 + 10);
+        assert_eq!(vnodes_of(&retuned, 1), vnodes_of(&ring, 1));
+        assert_ne!(vnodes_of(&retuned, 2), vnodes_of(&ring, 2));
+
+        // Freezing a node without caches computes its hashes.
+        let mut builder = RingBuilder::new(hash.clone());
+        builder.insert_node(Node::new(0).quantity(10));
+        assert!(builder.freeze(&0));
+        assert!(builder.unfreeze(&0));
+        assert!(!builder.unfreeze(&0));
+        assert!(builder.set_quantity(&0, 5));
+        assert_eq!(builder.build().len(), 5);
+    }
+
+    #[test]
+    fn try_build_works() {
+        let builder = (0..3).fold(StaticHashRing::builder(DefaultHash),
+                                  |b, i| b.add_node(Node::new(i).quantity(10)));
+        let ring = builder.try_build().unwrap();
+        assert!(same_placement(&ring,
+                               &StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)))));
+
+        let result = StaticHashRing::<i32, (), _>::builder(DefaultHash).try_build();
+        assert_eq!(result.err(), Some(BuildError::NoNodes));
+
+        let result = StaticHashRing::builder(DefaultHash).add_node(Node::new(0).quantity(0)).try_build();
+        assert_eq!(result.err(), Some(BuildError::ZeroQuantity));
+
+        let builder = StaticHashRing::builder(DefaultHash)
+            .add_node(Node::new(0).quantity(1))
+            .add_node(Node::new(1).quantity(1))
+            .add_node(Node::new(1).quantity(2))
+            .add_node(Node::new(0).quantity(2));
+        assert_eq!(builder.clone().try_build().err(), Some(BuildError::DuplicateKey(1)));
+        assert_eq!(builder.build().len(), 2);
+
+        // `try_new` makes the same checks.
+        assert!(StaticHashRing::try_new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10))).unwrap() == ring);
+        assert_eq!(StaticHashRing::try_new(DefaultHash, (0..0).map(Node::new)).err(), Some(BuildError::NoNodes));
+        let nodes = vec![Node::new(0).quantity(1), Node::new(0).quantity(2)];
+        assert_eq!(StaticHashRing::try_new(DefaultHash, nodes.into_iter()).err(),
+                   Some(BuildError::DuplicateKey(0)));
+    }
+}
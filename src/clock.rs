@@ -0,0 +1,75 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// A source of the current time.
+///
+/// Time-based features of this crate (e.g., `StaticHashRing::exclude_for` and `ScheduledRing::ring_now`)
+/// read the time via this trait, so they can be tested deterministically by using `MockClock`.
+pub trait Clock: fmt::Debug {
+    /// Returns the current time.
+    fn now(&self) -> SystemTime;
+}
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now(&self) -> SystemTime {
+        (**self).now()
+    }
+}
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> SystemTime {
+        (**self).now()
+    }
+}
+
+/// The `Clock` implementation which returns the system time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct SystemClock;
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A `Clock` implementation whose time is advanced manually.
+///
+/// Clones of a mock clock share the same time,
+/// so a clone can be given to a ring while the original one controls the time.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::{Duration, UNIX_EPOCH};
+/// use consistent_hash::{Clock, MockClock};
+///
+/// let clock = MockClock::new(UNIX_EPOCH);
+/// let shared = clock.clone();
+///
+/// clock.advance(Duration::from_secs(10));
+/// assert_eq!(shared.now(), UNIX_EPOCH + Duration::from_secs(10));
+///
+/// clock.set(UNIX_EPOCH);
+/// assert_eq!(shared.now(), UNIX_EPOCH);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MockClock(Arc<Mutex<SystemTime>>);
+impl MockClock {
+    /// Makes a new `MockClock` instance whose time is `now`.
+    pub fn new(now: SystemTime) -> Self {
+        MockClock(Arc::new(Mutex::new(now)))
+    }
+
+    /// Sets the time of this clock to `now`.
+    pub fn set(&self, now: SystemTime) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) = now;
+    }
+
+    /// Advances the time of this clock by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) += duration;
+    }
+}
+impl Clock for MockClock {
+    fn now(&self) -> SystemTime {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
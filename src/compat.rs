@@ -0,0 +1,172 @@
+//! Interoperability checks against other consistent hashing implementations.
+//!
+//! # Examples
+//!
+//! ```
+//! use consistent_hash::{Node, StaticHashRing, DefaultHash};
+//! use consistent_hash::compat;
+//!
+//! let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+//!
+//! // Reference vectors exported from another implementation (here, the ring itself).
+//! let vectors = (0..100).map(|i| (i, ring.calc_candidates(&i).next().unwrap().key)).collect::<Vec<_>>();
+//! assert_eq!(compat::self_test(&ring, vectors.iter().cloned()), Ok(100));
+//!
+//! let divergence = compat::self_test(&ring, vec![(0, 10)]).unwrap_err();
+//! assert_eq!(divergence.index, 0);
+//! assert_eq!(divergence.expected, 10);
+//! println!("{}", divergence);
+//! ```
+use std::error::Error;
+use std::fmt;
+use std::hash::Hash;
+
+use {RingHash, StaticHashRing};
+
+/// The first divergence between a ring and reference vectors.
+///
+/// This is returned by `self_test` function.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Divergence<K> {
+    /// The position of the diverging vector.
+    pub index: usize,
+
+    /// The format tag of the ring (see `StaticHashRing::format_tag`).
+    pub format_tag: String,
+
+    /// The hash code of the item.
+    pub item_hash: u64,
+
+    /// The key of the expected node.
+    pub expected: K,
+
+    /// The key of the node actually chosen by the ring.
+    ///
+    /// If the ring is empty, this is `None`.
+    pub actual: Option<K>,
+
+    /// The hash code of the virtual node from which the lookup started
+    /// (i.e., the first virtual node at or after `item_hash`).
+    pub start_vnode_hash: Option<u64>,
+
+    /// The hash code of the virtual node of the actual node which owns the item.
+    pub actual_vnode_hash: Option<u64>,
+
+    /// The sequence number and the hash code of the virtual node of the expected node
+    /// which is the nearest to `item_hash` in ring order.
+    ///
+    /// If the expected node is not contained in the ring or has no virtual nodes, this is `None`.
+    pub expected_vnode: Option<(usize, u64)>,
+}
+impl<K: fmt::Debug> fmt::Display for Divergence<K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Divergence at vector #{} (format={:?}):", self.index, self.format_tag)?;
+        writeln!(f, "  item_hash:         {:016x}", self.item_hash)?;
+        writeln!(f, "  expected node:     {:?}", self.expected)?;
+        writeln!(f, "  actual node:       {:?}", self.actual)?;
+        writeln!(f, "  start vnode hash:  {}", Hex(self.start_vnode_hash))?;
+        writeln!(f, "  actual vnode hash: {}", Hex(self.actual_vnode_hash))?;
+        match self.expected_vnode {
+            None => write!(f, "  expected vnode:    none"),
+            Some((seq, hash)) => {
+                write!(f,
+                       "  expected vnode:    seq={}, hash={:016x}, distance={:016x}",
+                       seq,
+                       hash,
+                       hash.wrapping_sub(self.item_hash))
+            }
+        }
+    }
+}
+impl<K: fmt::Debug> Error for Divergence<K> {}
+
+struct Hex(Option<u64>);
+impl fmt::Display for Hex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            None => write!(f, "none"),
+            Some(h) => write!(f, "{:016x}", h),
+        }
+    }
+}
+
+/// Validates that `ring` assigns each item of `vectors` to the expected node (as primary).
+///
+/// `vectors` is a sequence of `(item, expected node key)` pairs,
+/// which are typically exported from a reference implementation.
+///
+/// If all of the vectors match, this returns the count of the vectors.
+/// Otherwise, this returns the details of the first divergence.
+pub fn self_test<K, V, H, T, I>(ring: &StaticHashRing<K, V, H>,
+                                 vectors: I)
+                                 -> Result<usize, Divergence<K>>
+    where K: Hash + Eq + Clone,
+          H: RingHash,
+          T: Hash,
+          I: IntoIterator<Item = (T, K)>
+{
+    let mut count = 0;
+    for (index, (item, expected)) in vectors.into_iter().enumerate() {
+        let item_hash = ring.hash.hash_item(&item);
+        let start = ring.locate(item_hash);
+        let owner = ring.candidate_vnodes(start).next().map(|i| &ring.ring[i]);
+        if owner.map(|vn| &ring.nodes[vn.index].key) == Some(&expected) {
+            count += 1;
+            continue;
+        }
+
+        let expected_vnode = ring.nodes
+            .iter()
+            .find(|n| n.key == expected)
+            .and_then(|n| {
+                (0..n.quantity)
+                    .map(|seq| (seq, ring.hash.hash_vnode(&n.key, seq)))
+                    .min_by_key(|&(_, hash)| hash.wrapping_sub(item_hash))
+            });
+        return Err(Divergence {
+            index,
+            format_tag: ring.format_tag(),
+            item_hash,
+            expected,
+            actual: owner.map(|vn| ring.nodes[vn.index].key.clone()),
+            start_vnode_hash: ring.ring.get(start).or_else(|| ring.ring.first()).map(|vn| vn.hash),
+            actual_vnode_hash: owner.map(|vn| vn.hash),
+            expected_vnode,
+        });
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use {DefaultHash, Node, RingHash, StaticHashRing};
+    use super::*;
+
+    #[test]
+    fn self_test_works() {
+        let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+        let vectors = (0..100)
+            .map(|i| (i, ring.calc_candidates(&i).next().unwrap().key))
+            .collect::<Vec<_>>();
+        assert_eq!(self_test(&ring, vectors.clone()), Ok(100));
+
+        let mut diverged = vectors.clone();
+        let expected = (diverged[50].1 + 1) % 3;
+        diverged[50].1 = expected;
+        let d = self_test(&ring, diverged).unwrap_err();
+        assert_eq!(d.index, 50);
+        assert_eq!(d.item_hash, DefaultHash.hash_item(&50));
+        assert_eq!(d.actual, Some(vectors[50].1));
+        assert_eq!(d.expected, expected);
+        let (seq, hash) = d.expected_vnode.unwrap();
+        assert_eq!(hash, DefaultHash.hash_vnode(&expected, seq));
+        assert!(d.to_string().contains("vector #50"));
+
+        let d = self_test(&ring, vec![(0, 5)]).unwrap_err();
+        assert_eq!(d.expected_vnode, None);
+
+        let empty = StaticHashRing::new(DefaultHash, (0..0).map(|i| Node::new(i).quantity(10)));
+        let d = self_test(&empty, vec![(0, 0)]).unwrap_err();
+        assert_eq!((d.actual, d.start_vnode_hash, d.actual_vnode_hash), (None, None, None));
+    }
+}
@@ -0,0 +1,367 @@
+use std::error::Error;
+use std::fmt;
+use std::hash::Hash;
+
+use weight::allocate;
+use {DefaultHash, KetamaHash, Node, RingHash, StaticHashRing};
+#[cfg(feature = "blake3")]
+use Blake3Hash;
+#[cfg(feature = "fnv")]
+use Fnv;
+#[cfg(feature = "xxhash")]
+use XxHash;
+
+/// The default quantity of the nodes in a config which specify neither `quantity` nor `weight`.
+pub const DEFAULT_CONFIG_VNODES: usize = 160;
+
+/// The hashing algorithm chosen by a config (see `StaticHashRing::from_config`).
+///
+/// The algorithms which require optional features are only available if the features are enabled.
+///
+/// This is available if the `config` feature is enabled.
+#[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash, Default)]
+pub enum ConfigHash {
+    /// `DefaultHash` (`hasher = "default"`).
+    #[default]
+    Default,
+
+    /// `KetamaHash` (`hasher = "ketama"`).
+    Ketama,
+
+    /// `Fnv` (`hasher = "fnv"`).
+    #[cfg(feature = "fnv")]
+    Fnv,
+
+    /// `XxHash` (`hasher = "xxhash"`).
+    #[cfg(feature = "xxhash")]
+    XxHash,
+
+    /// `Blake3Hash` (`hasher = "blake3"`).
+    #[cfg(feature = "blake3")]
+    Blake3,
+}
+impl ConfigHash {
+    /// Returns the algorithm whose name in configs is `name`.
+    ///
+    /// If the name is unknown, or the algorithm requires a disabled feature, this returns `None`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(ConfigHash::Default),
+            "ketama" => Some(ConfigHash::Ketama),
+            #[cfg(feature = "fnv")]
+            "fnv" => Some(ConfigHash::Fnv),
+            #[cfg(feature = "xxhash")]
+            "xxhash" => Some(ConfigHash::XxHash),
+            #[cfg(feature = "blake3")]
+            "blake3" => Some(ConfigHash::Blake3),
+            _ => None,
+        }
+    }
+}
+impl RingHash for ConfigHash {
+    fn hash_item<T: Hash>(&self, item: &T) -> u64 {
+        match *self {
+            ConfigHash::Default => DefaultHash.hash_item(item),
+            ConfigHash::Ketama => KetamaHash.hash_item(item),
+            #[cfg(feature = "fnv")]
+            ConfigHash::Fnv => Fnv.hash_item(item),
+            #[cfg(feature = "xxhash")]
+            ConfigHash::XxHash => XxHash.hash_item(item),
+            #[cfg(feature = "blake3")]
+            ConfigHash::Blake3 => Blake3Hash.hash_item(item),
+        }
+    }
+    fn hash_vnode<K: Hash>(&self, node_key: &K, vnode_seq: usize) -> u64 {
+        match *self {
+            ConfigHash::Default => DefaultHash.hash_vnode(node_key, vnode_seq),
+            ConfigHash::Ketama => KetamaHash.hash_vnode(node_key, vnode_seq),
+            #[cfg(feature = "fnv")]
+            ConfigHash::Fnv => Fnv.hash_vnode(node_key, vnode_seq),
+            #[cfg(feature = "xxhash")]
+            ConfigHash::XxHash => XxHash.hash_vnode(node_key, vnode_seq),
+            #[cfg(feature = "blake3")]
+            ConfigHash::Blake3 => Blake3Hash.hash_vnode(node_key, vnode_seq),
+        }
+    }
+    fn hash_bytes(&self, bytes: &[u8]) -> u64 {
+        match *self {
+            ConfigHash::Default => DefaultHash.hash_bytes(bytes),
+            ConfigHash::Ketama => KetamaHash.hash_bytes(bytes),
+            #[cfg(feature = "fnv")]
+            ConfigHash::Fnv => Fnv.hash_bytes(bytes),
+            #[cfg(feature = "xxhash")]
+            ConfigHash::XxHash => XxHash.hash_bytes(bytes),
+            #[cfg(feature = "blake3")]
+            ConfigHash::Blake3 => Blake3Hash.hash_bytes(bytes),
+        }
+    }
+    fn algorithm_id(&self) -> &str {
+        match *self {
+            ConfigHash::Default => DefaultHash.algorithm_id(),
+            ConfigHash::Ketama => KetamaHash.algorithm_id(),
+            #[cfg(feature = "fnv")]
+            ConfigHash::Fnv => Fnv.algorithm_id(),
+            #[cfg(feature = "xxhash")]
+            ConfigHash::XxHash => XxHash.algorithm_id(),
+            #[cfg(feature = "blake3")]
+            ConfigHash::Blake3 => Blake3Hash.algorithm_id(),
+        }
+    }
+}
+
+/// The error which is returned when a config is invalid.
+///
+/// See `StaticHashRing::from_config`.
+///
+/// This is available if the `config` feature is enabled.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ConfigError {
+    /// The line number (1-origin) at which the error was detected.
+    pub line: usize,
+
+    /// The description of the error.
+    pub message: String,
+}
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid ring config (line {}): {}", self.line, self.message)
+    }
+}
+impl Error for ConfigError {}
+
+#[derive(Debug, Default)]
+struct NodeConfig {
+    line: usize,
+    key: Option<String>,
+    quantity: Option<usize>,
+    weight: Option<f64>,
+    zone: Option<String>,
+}
+
+impl StaticHashRing<String, Option<String>, ConfigHash> {
+    /// Makes a new `StaticHashRing` instance from the declarative config `config`.
+    ///
+    /// The config is written in a subset of TOML (key/value pairs, `[[nodes]]` tables,
+    /// basic and literal strings, numbers, and comments) as follows:
+    ///
+    /// - `hasher`: the hashing algorithm (see `ConfigHash`; the default is `"default"`)
+    /// - `vnodes`: the quantity of the nodes without `quantity` (the default is `DEFAULT_CONFIG_VNODES`)
+    /// - `total_vnodes`: the total quantity distributed by weights (the default is `vnodes` times the node count)
+    /// - `[[nodes]]`: a node which has `key` (required), and `quantity` or `weight`, and optionally `zone`
+    ///
+    /// If any node has a weight, every node must have one,
+    /// and the quantities are derived from the weights as `with_weights` does.
+    /// The values of the nodes are their zones.
+    ///
+    /// This is available if the `config` feature is enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::StaticHashRing;
+    ///
+    /// let config = r#"
+    /// hasher = "ketama"
+    /// total_vnodes = 300
+    ///
+    /// [[nodes]]
+    /// key = "foo"
+    /// weight = 2
+    /// zone = "us-east-1a"
+    ///
+    /// [[nodes]]
+    /// key = "bar"
+    /// weight = 1.0 # half the capacity
+    /// "#;
+    /// let ring = StaticHashRing::from_config(config).unwrap();
+    ///
+    /// let nodes = ring.nodes();
+    /// assert_eq!(nodes.iter().map(|n| n.key.as_str()).collect::<Vec<_>>(), ["bar", "foo"]);
+    /// assert_eq!(nodes.iter().map(|n| n.quantity).collect::<Vec<_>>(), [100, 200]);
+    /// assert_eq!(nodes[1].value, Some("us-east-1a".to_owned()));
+    ///
+    /// let err = StaticHashRing::from_config("hasher = \"md5\"").err().unwrap();
+    /// assert_eq!(err.line, 1);
+    /// ```
+    pub fn from_config(config: &str) -> Result<Self, ConfigError> {
+        let mut hash = ConfigHash::default();
+        let mut vnodes = DEFAULT_CONFIG_VNODES;
+        let mut total_vnodes = None;
+        let mut nodes: Vec<NodeConfig> = Vec::new();
+        for (i, line) in config.lines().enumerate() {
+            let line_no = i + 1;
+            let error = |message: String| ConfigError { line: line_no, message };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with('[') {
+                if strip_comment(line) != "[[nodes]]" {
+                    return Err(error(format!("Unknown table: {}", line)));
+                }
+                nodes.push(NodeConfig { line: line_no, ..NodeConfig::default() });
+                continue;
+            }
+
+            let (name, value) = line.split_once('=').ok_or_else(|| error(format!("Expected `key = value`: {}", line)))?;
+            let (name, value) = (name.trim(), parse_value(value.trim()).map_err(&error)?);
+            match (nodes.last_mut(), name) {
+                (None, "hasher") => {
+                    let name = value.into_string().map_err(&error)?;
+                    hash = ConfigHash::from_name(&name)
+                        .ok_or_else(|| error(format!("Unknown or disabled hasher: {:?}", name)))?;
+                }
+                (None, "vnodes") => vnodes = value.to_usize().map_err(&error)?,
+                (None, "total_vnodes") => total_vnodes = Some(value.to_usize().map_err(&error)?),
+                (Some(node), "key") => node.key = Some(value.into_string().map_err(&error)?),
+                (Some(node), "quantity") => node.quantity = Some(value.to_usize().map_err(&error)?),
+                (Some(node), "weight") => node.weight = Some(value.to_weight().map_err(&error)?),
+                (Some(node), "zone") => node.zone = Some(value.into_string().map_err(&error)?),
+                _ => return Err(error(format!("Unknown key: {:?}", name))),
+            }
+        }
+
+        let weighted = nodes.iter().any(|n| n.weight.is_some());
+        let mut keys = Vec::with_capacity(nodes.len());
+        for node in &nodes {
+            let error = |message: &str| ConfigError { line: node.line, message: message.to_owned() };
+            let key = node.key.as_ref().ok_or_else(|| error("The node has no key"))?;
+            if keys.contains(&key) {
+                return Err(error(&format!("Duplicate node key: {:?}", key)));
+            }
+            if weighted && (node.weight.is_none() || node.quantity.is_some()) {
+                return Err(error("Quantities and weights cannot be mixed"));
+            }
+            keys.push(key);
+        }
+        let quantities = if weighted {
+            let weights = nodes.iter().map(|n| n.weight.unwrap_or(0.0)).collect::<Vec<_>>();
+            allocate(&weights, total_vnodes.unwrap_or(vnodes * nodes.len()))
+        } else {
+            nodes.iter().map(|n| n.quantity.unwrap_or(vnodes)).collect()
+        };
+
+        let nodes = nodes.into_iter()
+            .zip(quantities)
+            .map(|(n, quantity)| Node::new(n.key.expect("Never fails")).value(n.zone).quantity(quantity));
+        Ok(StaticHashRing::new(hash, nodes))
+    }
+}
+
+#[derive(Debug)]
+enum Value {
+    String(String),
+    Number(String),
+}
+impl Value {
+    fn into_string(self) -> Result<String, String> {
+        match self {
+            Value::String(s) => Ok(s),
+            Value::Number(n) => Err(format!("Expected a string: {}", n)),
+        }
+    }
+
+    fn to_usize(&self) -> Result<usize, String> {
+        match *self {
+            Value::Number(ref n) => n.replace('_', "").parse().map_err(|_| format!("Expected an integer: {}", n)),
+            Value::String(ref s) => Err(format!("Expected an integer: {:?}", s)),
+        }
+    }
+
+    fn to_weight(&self) -> Result<f64, String> {
+        let weight = match *self {
+            Value::Number(ref n) => n.replace('_', "").parse::<f64>().ok().filter(|w| w.is_finite() && *w >= 0.0),
+            Value::String(_) => None,
+        };
+        weight.ok_or_else(|| format!("Expected a non-negative number: {:?}", self))
+    }
+}
+
+// Parses a value which may be followed by a comment.
+fn parse_value(s: &str) -> Result<Value, String> {
+    let (value, rest) = match s.chars().next() {
+        Some('"') => {
+            let mut value = String::new();
+            let mut chars = s.char_indices().skip(1);
+            loop {
+                match chars.next() {
+                    None => return Err(format!("Unterminated string: {}", s)),
+                    Some((i, '"')) => break (Value::String(value), &s[i + 1..]),
+                    Some((_, '\\')) => {
+                        value.push(match chars.next() {
+                            Some((_, '"')) => '"',
+                            Some((_, '\\')) => '\\',
+                            Some((_, 'n')) => '\n',
+                            Some((_, 'r')) => '\r',
+                            Some((_, 't')) => '\t',
+                            _ => return Err(format!("Unsupported escape sequence: {}", s)),
+                        })
+                    }
+                    Some((_, c)) => value.push(c),
+                }
+            }
+        }
+        Some('\'') => {
+            let end = s[1..].find('\'').ok_or_else(|| format!("Unterminated string: {}", s))? + 1;
+            (Value::String(s[1..end].to_owned()), &s[end + 1..])
+        }
+        _ => {
+            let value = strip_comment(s);
+            if value.is_empty() {
+                return Err("Missing value".to_owned());
+            }
+            (Value::Number(value.to_owned()), "")
+        }
+    };
+    if !strip_comment(rest).is_empty() {
+        return Err(format!("Unexpected trailing characters: {}", rest.trim()));
+    }
+    Ok(value)
+}
+
+fn strip_comment(s: &str) -> &str {
+    s.split('#').next().unwrap_or("").trim()
+}
+
+#[cfg(test)]
+mod tests {
+    use {DefaultHash, Node, RingHash, StaticHashRing};
+
+    #[test]
+    fn from_config_works() {
+        let config = r#"
+            # Quantities are given explicitly (or by `vnodes`).
+            vnodes = 20
+
+            [[nodes]]
+            key = "foo"
+            quantity = 1_0
+            zone = 'a#1'
+
+            [[nodes]]
+            key = "b\"ar"  # comment
+        "#;
+        let ring = StaticHashRing::from_config(config).unwrap();
+        let expected = StaticHashRing::new(DefaultHash,
+                                           vec![Node::new("b\"ar".to_owned()).quantity(20),
+                                                Node::new("foo".to_owned()).quantity(10)]
+                                               .into_iter());
+        assert_eq!(ring.vnodes().map(|v| v.0).collect::<Vec<_>>(),
+                   expected.vnodes().map(|v| v.0).collect::<Vec<_>>());
+        assert_eq!(ring.nodes()[1].value, Some("a#1".to_owned()));
+        assert_eq!(ring.nodes()[0].value, None);
+        assert_eq!(ring.hash.algorithm_id(), DefaultHash.algorithm_id());
+
+        let error = |config: &str| StaticHashRing::from_config(config).err().map(|e| e.line);
+        assert_eq!(error("[[nodes]]\nkey = \"a\"\n[[nodes]]\nkey = \"a\""), Some(3));
+        assert_eq!(error("[[nodes]]\nkey = \"a\"\nweight = 1\n[[nodes]]\nkey = \"b\""), Some(4));
+        assert_eq!(error("[[nodes]]\nkey = \"a\"\nweight = -1"), Some(3));
+        assert_eq!(error("\n[[nodes]]\nquantity = 3"), Some(2));
+        assert_eq!(error("vnodes = \"3\""), Some(1));
+        assert_eq!(error("vnodes = 3 3"), Some(1));
+        assert_eq!(error("[nodes]"), Some(1));
+        assert_eq!(error("zone = \"a\""), Some(1));
+        assert_eq!(error("hasher = \"default"), Some(1));
+        assert!(StaticHashRing::from_config("").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,206 @@
+use std::hash::Hash;
+
+use RingHash;
+
+/// Which virtual nodes can own an item whose hash code equals their hash codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Bound {
+    /// An item is owned by the first virtual node whose hash code is equal to or beyond the item's one.
+    ///
+    /// This is the default convention of this crate.
+    Inclusive,
+
+    /// An item is owned by the first virtual node whose hash code is strictly beyond the item's one.
+    Exclusive,
+}
+
+/// The direction in which rings are walked from the hash code of an item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// Rings are walked in ascending order of hash codes (i.e., `hash ≥ item` for `Bound::Inclusive`).
+    ///
+    /// This is the default convention of this crate.
+    Clockwise,
+
+    /// Rings are walked in descending order of hash codes (i.e., `hash ≤ item` for `Bound::Inclusive`).
+    Counterclockwise,
+}
+
+/// A `RingHash` adapter which changes the lookup convention of rings.
+///
+/// By default, an item is owned by the first virtual node whose hash code is equal to or greater than
+/// the item's one (wrapping around to the smallest one), and candidates follow in ascending order.
+/// Matching an existing system sometimes requires another convention,
+/// which is achieved by wrapping the hash of the ring with this adapter.
+///
+/// The adapter maps the hash codes of `inner` onto ring positions,
+/// so all of the hash codes observed through rings (e.g., those of virtual nodes and owned ranges)
+/// are positions rather than the raw hash codes of `inner`.
+/// `Direction::Counterclockwise` maps a hash code `h` to `!h`,
+/// and `Bound::Exclusive` additionally shifts the positions of items by one.
+/// The default convention maps hash codes as they are.
+///
+/// # Examples
+///
+/// ```
+/// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+/// use consistent_hash::{Bound, Direction, RingConvention};
+///
+/// let hash = RingConvention::new(DefaultHash).bound(Bound::Exclusive).direction(Direction::Counterclockwise);
+/// let ring = StaticHashRing::new(hash, (0..3).map(|i| Node::new(i).quantity(10)));
+/// assert_eq!(ring.format_tag(), "ring-v1-siphash13-ccw-exclusive");
+/// assert_eq!(ring.calc_candidates(&"foo").count(), 3);
+/// ```
+#[derive(Debug, Clone)]
+pub struct RingConvention<H> {
+    inner: H,
+    bound: Bound,
+    direction: Direction,
+    algorithm_id: Option<String>,
+}
+impl<H: RingHash> RingConvention<H> {
+    /// Makes a new `RingConvention` instance which has the default convention.
+    pub fn new(inner: H) -> Self {
+        RingConvention {
+            inner,
+            bound: Bound::Inclusive,
+            direction: Direction::Clockwise,
+            algorithm_id: None,
+        }
+    }
+
+    /// Sets the bound convention to `bound`.
+    pub fn bound(mut self, bound: Bound) -> Self {
+        self.bound = bound;
+        self.update_algorithm_id();
+        self
+    }
+
+    /// Sets the direction convention to `direction`.
+    pub fn direction(mut self, direction: Direction) -> Self {
+        self.direction = direction;
+        self.update_algorithm_id();
+        self
+    }
+
+    /// Returns the bound convention.
+    pub fn get_bound(&self) -> Bound {
+        self.bound
+    }
+
+    /// Returns the direction convention.
+    pub fn get_direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// Returns a reference to the inner hash.
+    pub fn inner(&self) -> &H {
+        &self.inner
+    }
+
+    fn to_position(&self, hash: u64) -> u64 {
+        match self.direction {
+            Direction::Clockwise => hash,
+            Direction::Counterclockwise => !hash,
+        }
+    }
+
+    fn update_algorithm_id(&mut self) {
+        let direction = match self.direction {
+            Direction::Clockwise => "",
+            Direction::Counterclockwise => "-ccw",
+        };
+        let bound = match self.bound {
+            Bound::Inclusive => "",
+            Bound::Exclusive => "-exclusive",
+        };
+        self.algorithm_id = if direction.is_empty() && bound.is_empty() {
+            None
+        } else {
+            Some(format!("{}{}{}", self.inner.algorithm_id(), direction, bound))
+        };
+    }
+}
+impl<H: RingHash> RingHash for RingConvention<H> {
+    fn hash_item<T: Hash>(&self, item: &T) -> u64 {
+        let position = self.to_position(self.inner.hash_item(item));
+        match self.bound {
+            Bound::Inclusive => position,
+            Bound::Exclusive => position.wrapping_add(1),
+        }
+    }
+    fn hash_vnode<K: Hash>(&self, node_key: &K, vnode_seq: usize) -> u64 {
+        self.to_position(self.inner.hash_vnode(node_key, vnode_seq))
+    }
+    fn algorithm_id(&self) -> &str {
+        match self.algorithm_id {
+            None => self.inner.algorithm_id(),
+            Some(ref id) => id,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hash::{Hash, Hasher};
+    use {Node, RingHash, StaticHashRing};
+    use super::*;
+
+    // Uses `u64` items and node keys as the hash codes themselves.
+    struct RawHash;
+    struct RawHasher(u64);
+    impl Hasher for RawHasher {
+        fn finish(&self) -> u64 {
+            self.0
+        }
+        fn write(&mut self, bytes: &[u8]) {
+            for &b in bytes {
+                self.0 = (self.0 << 8) | u64::from(b);
+            }
+        }
+        fn write_u64(&mut self, n: u64) {
+            self.0 = n;
+        }
+    }
+    impl RingHash for RawHash {
+        fn hash_item<T: Hash>(&self, item: &T) -> u64 {
+            let mut hasher = RawHasher(0);
+            item.hash(&mut hasher);
+            hasher.finish()
+        }
+        fn hash_vnode<K: Hash>(&self, node_key: &K, _vnode_seq: usize) -> u64 {
+            self.hash_item(node_key) * 10
+        }
+    }
+
+    fn owners(bound: Bound, direction: Direction, items: &[u64]) -> Vec<u64> {
+        let hash = RingConvention::new(RawHash).bound(bound).direction(direction);
+        let ring = StaticHashRing::new(hash, (1..4).map(Node::new));
+        items.iter().map(|i| ring.calc_candidates(i).next().unwrap().key).collect()
+    }
+
+    #[test]
+    fn ring_convention_works() {
+        // The virtual nodes are at 10, 20 and 30.
+        let items = [5, 10, 15, 20, 25, 30, 35];
+        assert_eq!(owners(Bound::Inclusive, Direction::Clockwise, &items),
+                   [1, 1, 2, 2, 3, 3, 1]);
+        assert_eq!(owners(Bound::Exclusive, Direction::Clockwise, &items),
+                   [1, 2, 2, 3, 3, 1, 1]);
+        assert_eq!(owners(Bound::Inclusive, Direction::Counterclockwise, &items),
+                   [3, 1, 1, 2, 2, 3, 3]);
+        assert_eq!(owners(Bound::Exclusive, Direction::Counterclockwise, &items),
+                   [3, 3, 1, 1, 2, 2, 3]);
+        assert_eq!(owners(Bound::Exclusive, Direction::Counterclockwise, &[0, u64::MAX]), [3, 3]);
+
+        let hash = RingConvention::new(RawHash).direction(Direction::Counterclockwise);
+        let ring = StaticHashRing::new(hash, (1..4).map(Node::new));
+        assert_eq!(ring.calc_candidates(&25).map(|n| n.key).collect::<Vec<_>>(), [2, 1, 3]);
+
+        assert_eq!(RingConvention::new(RawHash).algorithm_id(), "custom");
+        assert_eq!(RingConvention::new(RawHash).bound(Bound::Exclusive).algorithm_id(),
+                   "custom-exclusive");
+        assert_eq!(RingConvention::new(RawHash).bound(Bound::Exclusive).bound(Bound::Inclusive).algorithm_id(),
+                   "custom");
+    }
+}
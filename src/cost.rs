@@ -0,0 +1,228 @@
+use std::cmp;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use {Candidates, Node, RingHash, StaticHashRing};
+
+/// The number of the buckets of `LookupCostSnapshot::visited_buckets`.
+pub const LOOKUP_COST_BUCKETS: usize = 32;
+
+/// Statistics of the costs of candidate walks, which can be updated concurrently.
+///
+/// The costs are only recorded by the walks created by `StaticHashRing::calc_candidates_instrumented`,
+/// so the other lookups never pay for the statistics.
+/// Watching the statistics can detect that the shape of a ring or exclusion patterns
+/// degrade lookups (e.g., many consecutive virtual nodes are skipped) before it shows in latencies.
+///
+/// # Examples
+///
+/// ```
+/// use consistent_hash::{Node, StaticHashRing, DefaultHash, LookupCostStats};
+///
+/// let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+/// let stats = LookupCostStats::new();
+///
+/// for i in 0..100 {
+///     ring.calc_candidates_instrumented(&i, &stats).next();
+/// }
+/// let snapshot = stats.snapshot();
+/// assert_eq!(snapshot.walks, 100);
+/// assert_eq!(snapshot.max_visited, 1);
+/// assert_eq!(snapshot.mean_search_depth(), 5.0);
+/// ```
+#[derive(Debug)]
+pub struct LookupCostStats {
+    walks: AtomicU64,
+    visited: AtomicU64,
+    max_visited: AtomicU64,
+    search_depth: AtomicU64,
+    buckets: [AtomicU64; LOOKUP_COST_BUCKETS],
+}
+impl LookupCostStats {
+    /// Makes a new `LookupCostStats` instance.
+    pub fn new() -> Self {
+        LookupCostStats {
+            walks: AtomicU64::new(0),
+            visited: AtomicU64::new(0),
+            max_visited: AtomicU64::new(0),
+            search_depth: AtomicU64::new(0),
+            buckets: Default::default(),
+        }
+    }
+
+    /// Returns a snapshot of the statistics.
+    ///
+    /// The counters are read individually,
+    /// so the result is not necessarily an atomic snapshot under concurrent updates.
+    pub fn snapshot(&self) -> LookupCostSnapshot {
+        LookupCostSnapshot {
+            walks: self.walks.load(Ordering::Relaxed),
+            visited: self.visited.load(Ordering::Relaxed),
+            max_visited: self.max_visited.load(Ordering::Relaxed),
+            search_depth: self.search_depth.load(Ordering::Relaxed),
+            visited_buckets: self.buckets.iter().map(|b| b.load(Ordering::Relaxed)).collect(),
+        }
+    }
+
+    /// Resets the statistics.
+    pub fn reset(&self) {
+        self.walks.store(0, Ordering::Relaxed);
+        self.visited.store(0, Ordering::Relaxed);
+        self.max_visited.store(0, Ordering::Relaxed);
+        self.search_depth.store(0, Ordering::Relaxed);
+        for b in &self.buckets {
+            b.store(0, Ordering::Relaxed);
+        }
+    }
+
+    fn record(&self, visited: u64, search_depth: u64) {
+        self.walks.fetch_add(1, Ordering::Relaxed);
+        self.visited.fetch_add(visited, Ordering::Relaxed);
+        self.max_visited.fetch_max(visited, Ordering::Relaxed);
+        self.search_depth.fetch_add(search_depth, Ordering::Relaxed);
+        self.buckets[bucket_of(visited)].fetch_add(1, Ordering::Relaxed);
+    }
+}
+impl Default for LookupCostStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Returns the index of the bucket which `visited` belongs to.
+//
+// The bucket `0` holds `0..=1`, and the bucket `i` holds `(2^(i-1))+1..=2^i` (the last one is unbounded).
+fn bucket_of(visited: u64) -> usize {
+    if visited <= 1 {
+        0
+    } else {
+        cmp::min(64 - (visited - 1).leading_zeros() as usize, LOOKUP_COST_BUCKETS - 1)
+    }
+}
+
+/// A snapshot of `LookupCostStats`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LookupCostSnapshot {
+    /// The count of the recorded walks.
+    pub walks: u64,
+
+    /// The total count of the virtual nodes visited by the walks.
+    pub visited: u64,
+
+    /// The maximum count of the virtual nodes visited by a walk.
+    pub max_visited: u64,
+
+    /// The total count of the probes of the binary searches which located the starting points of the walks.
+    pub search_depth: u64,
+
+    /// The distribution of the counts of the virtual nodes visited by the walks.
+    ///
+    /// `visited_buckets[0]` is the count of the walks which visited at most one virtual node,
+    /// and `visited_buckets[i]` is the count of the walks which visited `(2^(i-1), 2^i]` virtual nodes
+    /// (the last bucket also holds the longer walks).
+    pub visited_buckets: Vec<u64>,
+}
+impl LookupCostSnapshot {
+    /// Returns the mean count of the virtual nodes visited by a walk (`0.0` if no walks are recorded).
+    pub fn mean_visited(&self) -> f64 {
+        if self.walks == 0 { 0.0 } else { self.visited as f64 / self.walks as f64 }
+    }
+
+    /// Returns the mean count of the binary search probes of a walk (`0.0` if no walks are recorded).
+    pub fn mean_search_depth(&self) -> f64 {
+        if self.walks == 0 { 0.0 } else { self.search_depth as f64 / self.walks as f64 }
+    }
+}
+
+impl<K, V, H> StaticHashRing<K, V, H>
+    where H: RingHash
+{
+    /// Returns the candidate nodes for `item` in the same way as `calc_candidates`,
+    /// recording the cost of the walk into `stats` when the iterator is dropped.
+    pub fn calc_candidates_instrumented<'a, T: Hash>(&'a self,
+                                                    item: &T,
+                                                    stats: &'a LookupCostStats)
+                                                    -> InstrumentedCandidates<'a, K, V> {
+        // The number of the probes of a binary search over the ring.
+        let search_depth = usize::BITS - self.ring.len().leading_zeros();
+        InstrumentedCandidates {
+            candidates: self.calc_candidates(item),
+            stats,
+            search_depth: search_depth as u64,
+        }
+    }
+}
+
+/// An iterator which represents a sequence of the candidate nodes for an item, recording the cost of the walk.
+///
+/// This is created by calling `StaticHashRing::calc_candidates_instrumented` method.
+pub struct InstrumentedCandidates<'a, K: 'a, V: 'a> {
+    candidates: Candidates<'a, K, V>,
+    stats: &'a LookupCostStats,
+    search_depth: u64,
+}
+impl<'a, K: 'a, V: 'a> InstrumentedCandidates<'a, K, V> {
+    /// Returns the count of the virtual nodes visited so far.
+    pub fn visited_vnodes(&self) -> usize {
+        self.candidates.0.count
+    }
+}
+impl<'a, K: 'a, V: 'a> Iterator for InstrumentedCandidates<'a, K, V> {
+    type Item = &'a Node<K, V>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.candidates.next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.candidates.size_hint()
+    }
+}
+impl<'a, K: 'a, V: 'a> Drop for InstrumentedCandidates<'a, K, V> {
+    fn drop(&mut self) {
+        self.stats.record(self.visited_vnodes() as u64, self.search_depth);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use {DefaultHash, Node, StaticHashRing};
+    use super::*;
+
+    #[test]
+    fn lookup_cost_stats_works() {
+        assert_eq!(bucket_of(0), 0);
+        assert_eq!(bucket_of(1), 0);
+        assert_eq!(bucket_of(2), 1);
+        assert_eq!(bucket_of(3), 2);
+        assert_eq!(bucket_of(4), 2);
+        assert_eq!(bucket_of(5), 3);
+        assert_eq!(bucket_of(u64::MAX), LOOKUP_COST_BUCKETS - 1);
+
+        let mut ring = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(10)));
+        let stats = LookupCostStats::new();
+        let mut expected = 0;
+        for i in 0..100 {
+            let mut candidates = ring.calc_candidates_instrumented(&i, &stats);
+            assert_eq!(candidates.by_ref().count(), 4);
+            expected += candidates.visited_vnodes() as u64;
+        }
+        let snapshot = stats.snapshot();
+        assert_eq!((snapshot.walks, snapshot.visited), (100, expected));
+        assert_eq!(snapshot.visited_buckets.iter().sum::<u64>(), 100);
+        assert_eq!(snapshot.mean_search_depth(), 6.0);
+        assert!(snapshot.max_visited >= 4);
+
+        // Excluded nodes make walks longer.
+        stats.reset();
+        assert_eq!(stats.snapshot().mean_visited(), 0.0);
+        for i in 0..3 {
+            ring.exclude_for(&i, Duration::from_secs(10));
+        }
+        for i in 0..100 {
+            ring.calc_candidates_instrumented(&i, &stats).next();
+        }
+        let snapshot = stats.snapshot();
+        assert!(snapshot.mean_visited() > 1.0);
+        assert_eq!(snapshot.visited_buckets[0], 100 - snapshot.visited_buckets[1..].iter().sum::<u64>());
+    }
+}
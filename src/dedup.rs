@@ -0,0 +1,226 @@
+use std::collections::HashSet;
+use std::hash::Hash;
+
+use {Candidates, Node, RingHash, StaticHashRing, VnodeWalk};
+
+/// The uniqueness semantics of candidate sequences.
+///
+/// See `StaticHashRing::calc_candidates_dedup`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Dedup<F> {
+    /// Each node appears at most once (i.e., the same as `calc_candidates`).
+    Node,
+
+    /// Each value projected by the function appears at most once.
+    ///
+    /// Only the first node (in ring order) of each projected value is yielded.
+    Value(F),
+
+    /// No deduplication (i.e., the raw walk over the virtual nodes).
+    ///
+    /// A node appears as many times as the count of its virtual nodes.
+    None,
+}
+impl<V> Dedup<fn(&V)> {
+    /// Returns `Dedup::Node`.
+    ///
+    /// Unlike the variant itself, this does not require annotating the type of the (unused) projection.
+    pub fn node() -> Self {
+        Dedup::Node
+    }
+
+    /// Returns `Dedup::None`.
+    ///
+    /// Unlike the variant itself, this does not require annotating the type of the (unused) projection.
+    pub fn none() -> Self {
+        Dedup::None
+    }
+}
+
+impl<K, V, H> StaticHashRing<K, V, H>
+    where H: RingHash
+{
+    /// Returns the candidate nodes for `item` with the given uniqueness semantics.
+    ///
+    /// Except for `Dedup::Node`, replica-only nodes are not treated specially
+    /// (i.e., the nodes are yielded in pure ring order).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash, Dedup};
+    ///
+    /// // The values of nodes are their hosts.
+    /// let nodes = (0..6).map(|i| Node::new(i).value(i / 2).quantity(3));
+    /// let ring = StaticHashRing::new(DefaultHash, nodes);
+    ///
+    /// assert_eq!(ring.calc_candidates_dedup(&"foo", Dedup::node()).count(), 6);
+    /// assert_eq!(ring.calc_candidates_dedup(&"foo", Dedup::Value(|host: &i32| *host)).count(), 3);
+    /// assert_eq!(ring.calc_candidates_dedup(&"foo", Dedup::none()).count(), 18);
+    /// ```
+    pub fn calc_candidates_dedup<T, U, F>(&self, item: &T, dedup: Dedup<F>) -> DedupCandidates<'_, K, V, U, F>
+        where T: Hash,
+              U: PartialEq,
+              F: Fn(&V) -> U
+    {
+        let start = self.locate(self.hash.hash_item(item));
+        let inner = match dedup {
+            Dedup::Node => Inner::Node(Candidates(self.candidate_vnodes(start))),
+            Dedup::Value(f) => Inner::Walk(VnodeWalk::new(&self.ring, &self.nodes, start), Some((f, Vec::new()))),
+            Dedup::None => Inner::Walk(VnodeWalk::new(&self.ring, &self.nodes, start), None),
+        };
+        DedupCandidates(inner)
+    }
+
+    /// Returns the candidate nodes for `item`, yielding at most one node for each group.
+    ///
+    /// `group_of` returns the group of a node (e.g., its datacenter),
+    /// and only the first candidate (in the order of `calc_candidates`) of each group is yielded.
+    /// The groups of the nodes are enumerated when this is called,
+    /// so the walk stops as soon as every group has been yielded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// // Each datacenter has two nodes.
+    /// let ring = StaticHashRing::new(DefaultHash, (0..6).map(|i| Node::new(i).quantity(3)));
+    /// let candidates = ring.calc_candidates_unique_by(&"foo", |n| n.key / 2).collect::<Vec<_>>();
+    /// assert_eq!(candidates.len(), 3);
+    ///
+    /// let mut dcs = candidates.iter().map(|n| n.key / 2).collect::<Vec<_>>();
+    /// dcs.sort();
+    /// assert_eq!(dcs, [0, 1, 2]);
+    /// ```
+    pub fn calc_candidates_unique_by<T, G, F>(&self, item: &T, group_of: F) -> UniqueCandidates<'_, K, V, G, F>
+        where T: Hash,
+              G: Hash + Eq,
+              F: Fn(&Node<K, V>) -> G
+    {
+        let groups = self.nodes
+            .iter()
+            .zip(&self.vnode_counts)
+            .filter(|&(_, &c)| c > 0)
+            .map(|(n, _)| group_of(n))
+            .collect::<HashSet<_>>()
+            .len();
+        UniqueCandidates {
+            candidates: self.calc_candidates(item),
+            group_of,
+            seens: HashSet::new(),
+            groups,
+        }
+    }
+}
+
+/// An iterator which represents a sequence of the candidate nodes which belong to distinct groups.
+///
+/// This is created by calling `StaticHashRing::calc_candidates_unique_by` method.
+pub struct UniqueCandidates<'a, K: 'a, V: 'a, G, F> {
+    candidates: Candidates<'a, K, V>,
+    group_of: F,
+    seens: HashSet<G>,
+    groups: usize,
+}
+impl<'a, K: 'a, V: 'a, G, F> Iterator for UniqueCandidates<'a, K, V, G, F>
+    where G: Hash + Eq,
+          F: Fn(&Node<K, V>) -> G
+{
+    type Item = &'a Node<K, V>;
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.seens.len() < self.groups {
+            let node = self.candidates.next()?;
+            if self.seens.insert((self.group_of)(node)) {
+                return Some(node);
+            }
+        }
+        None
+    }
+}
+
+enum Inner<'a, K: 'a, V: 'a, U, F> {
+    Node(Candidates<'a, K, V>),
+    Walk(VnodeWalk<'a, K, V>, Option<(F, Vec<U>)>),
+}
+
+/// An iterator which represents a sequence of the candidate nodes deduplicated by `Dedup`.
+///
+/// This is created by calling `StaticHashRing::calc_candidates_dedup` method.
+pub struct DedupCandidates<'a, K: 'a, V: 'a, U, F>(Inner<'a, K, V, U, F>);
+impl<'a, K: 'a, V: 'a, U, F> Iterator for DedupCandidates<'a, K, V, U, F>
+    where U: PartialEq,
+          F: Fn(&V) -> U
+{
+    type Item = &'a Node<K, V>;
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.0 {
+            Inner::Node(ref mut candidates) => candidates.next(),
+            Inner::Walk(ref mut walk, None) => walk.next().map(|(_, n)| n),
+            Inner::Walk(ref mut walk, Some((ref f, ref mut seens))) => {
+                for (_, node) in walk {
+                    let value = f(&node.value);
+                    if !seens.contains(&value) {
+                        seens.push(value);
+                        return Some(node);
+                    }
+                }
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {DefaultHash, Node, StaticHashRing};
+    use super::*;
+
+    #[test]
+    fn calc_candidates_dedup_works() {
+        let nodes = (0..6).map(|i| Node::new(i).value(i % 3).quantity(4));
+        let mut ring = StaticHashRing::new(DefaultHash, nodes);
+        ring.set_replica_only(&0, true);
+        for item in 0..100 {
+            let by_node = ring.calc_candidates_dedup(&item, Dedup::node()).collect::<Vec<_>>();
+            assert_eq!(by_node, ring.calc_candidates(&item).collect::<Vec<_>>());
+
+            let raw = ring.calc_candidates_dedup(&item, Dedup::none()).collect::<Vec<_>>();
+            assert_eq!(raw.len(), 24);
+
+            let by_value = ring.calc_candidates_dedup(&item, Dedup::Value(|v: &i32| *v))
+                .collect::<Vec<_>>();
+            let mut expected: Vec<&Node<i32, i32>> = Vec::new();
+            for n in raw {
+                if expected.iter().all(|e| e.value != n.value) {
+                    expected.push(n);
+                }
+            }
+            assert_eq!(by_value, expected);
+        }
+
+        let empty = StaticHashRing::new(DefaultHash, (0..0).map(Node::new));
+        assert_eq!(empty.calc_candidates_dedup(&"foo", Dedup::none()).count(), 0);
+    }
+
+    #[test]
+    fn calc_candidates_unique_by_works() {
+        let mut ring = StaticHashRing::new(DefaultHash, (0..9).map(|i| Node::new(i).quantity(4)));
+        ring.set_replica_only(&0, true);
+        for item in 0..100 {
+            let unique = ring.calc_candidates_unique_by(&item, |n| n.key % 3).collect::<Vec<_>>();
+            let mut expected: Vec<&Node<i32, ()>> = Vec::new();
+            for n in ring.calc_candidates(&item) {
+                if expected.iter().all(|e| e.key % 3 != n.key % 3) {
+                    expected.push(n);
+                }
+            }
+            assert_eq!(unique, expected);
+            assert_eq!(unique.len(), 3);
+        }
+        assert_eq!(ring.calc_candidates_unique_by(&"foo", |_| ()).count(), 1);
+
+        let empty = StaticHashRing::new(DefaultHash, (0..0).map(Node::new));
+        assert_eq!(empty.calc_candidates_unique_by(&"foo", |n: &Node<i32, ()>| n.key).count(), 0);
+    }
+}
@@ -0,0 +1,263 @@
+use std::hash::Hash;
+
+use range::HashRange;
+use ownership::OwnedRange;
+use {Node, RingHash, StaticHashRing};
+
+/// A range of hash codes whose primary node differs between two rings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RangeMove<'a, 'b, K: 'a + 'b, V: 'a + 'b> {
+    /// The range of hash codes (never wraps around).
+    pub range: HashRange,
+
+    /// The node which owns the range in the old ring (`None` if the ring is empty).
+    pub before: Option<&'a Node<K, V>>,
+
+    /// The node which owns the range in the new ring (`None` if the ring is empty).
+    pub after: Option<&'b Node<K, V>>,
+}
+
+/// The movement of the hash space between two rings.
+///
+/// This is created by calling `StaticHashRing::diff` method.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RingDiff<'a, 'b, K: 'a + 'b, V: 'a + 'b> {
+    /// The ranges whose primary nodes differ, ordered by their hash codes.
+    ///
+    /// Adjacent ranges which have the same pair of the old and new owners are merged.
+    pub moves: Vec<RangeMove<'a, 'b, K, V>>,
+}
+impl<'a, 'b, K: 'a + 'b, V: 'a + 'b> RingDiff<'a, 'b, K, V> {
+    /// Returns `true` if no ranges move, otherwise `false`.
+    pub fn is_empty(&self) -> bool {
+        self.moves.is_empty()
+    }
+
+    /// Returns the fraction of the hash space which moves (`0.0..=1.0`).
+    pub fn moved_fraction(&self) -> f64 {
+        self.moves.iter().map(|m| m.range.len() as f64).sum::<f64>() / 2f64.powi(64)
+    }
+}
+
+impl<K, V, H> StaticHashRing<K, V, H>
+    where K: Eq
+{
+    /// Computes the movement of the primary ownership from this ring to `other`.
+    ///
+    /// The result can be used as a data-migration plan for a topology change
+    /// (i.e., the data in each range should be moved from `before` to `after`).
+    /// Nodes are compared by their keys, and replica-only nodes are taken into account.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let old = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(50)));
+    /// let new = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(50)));
+    ///
+    /// let diff = old.diff(&new);
+    /// assert!(diff.moves.iter().all(|m| m.after.map(|n| n.key) == Some(3)));
+    /// assert!(0.15 < diff.moved_fraction() && diff.moved_fraction() < 0.35);
+    /// assert!(old.diff(&old).is_empty());
+    /// ```
+    pub fn diff<'o, H2>(&self, other: &'o StaticHashRing<K, V, H2>) -> RingDiff<'_, 'o, K, V> {
+        let same = |a: usize, b: usize| self.nodes[a].key == other.nodes[b].key;
+        let moves = moved_ranges(&self.primary_ranges(), &other.primary_ranges(), same)
+            .into_iter()
+            .map(|(range, before, after)| {
+                RangeMove {
+                    range,
+                    before: before.map(|i| &self.nodes[i]),
+                    after: after.map(|i| &other.nodes[i]),
+                }
+            })
+            .collect();
+        RingDiff { moves }
+    }
+}
+
+// Returns the ranges whose primary nodes differ between `befores` and `afters` (compared by `same`),
+// paired with the indices of the old and new owners.
+//
+// Adjacent ranges which have the same pair of the old and new owners are merged.
+pub(crate) fn moved_ranges<F>(befores: &[OwnedRange],
+                              afters: &[OwnedRange],
+                              same: F)
+                              -> Vec<(HashRange, Option<usize>, Option<usize>)>
+    where F: Fn(usize, usize) -> bool
+{
+    let mut moves: Vec<(HashRange, Option<usize>, Option<usize>)> = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    let mut start = 0u64;
+    loop {
+        let before = befores.get(i);
+        let after = afters.get(j);
+        let end = match (before, after) {
+            (Some(b), Some(a)) => b.end.min(a.end),
+            (Some(b), None) => b.end,
+            (None, Some(a)) => a.end,
+            (None, None) => break,
+        };
+        let before = before.map(|r| r.node);
+        let after = after.map(|r| r.node);
+        let unchanged = match (before, after) {
+            (Some(b), Some(a)) => same(b, a),
+            (b, a) => b.is_none() && a.is_none(),
+        };
+        if !unchanged {
+            let merged = moves.last_mut().is_some_and(|m| {
+                if m.0.end.wrapping_add(1) == start && m.1 == before && m.2 == after {
+                    m.0.end = end;
+                    true
+                } else {
+                    false
+                }
+            });
+            if !merged {
+                moves.push((HashRange::new(start, end), before, after));
+            }
+        }
+        if end == u64::MAX {
+            break;
+        }
+        start = end + 1;
+        if befores.get(i).is_some_and(|r| r.end == end) {
+            i += 1;
+        }
+        if afters.get(j).is_some_and(|r| r.end == end) {
+            j += 1;
+        }
+    }
+    moves
+}
+
+impl<K, V, H> StaticHashRing<K, V, H>
+    where K: Eq,
+          H: RingHash
+{
+    /// Returns the keys whose primary nodes differ between this ring and `new`,
+    /// paired with the old and new owners.
+    ///
+    /// Each key is hashed only once (by the hasher of this ring),
+    /// so `new` is expected to use the same hashing algorithm as this ring.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let old = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(50)));
+    /// let new = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(50)));
+    ///
+    /// for (_key, from, to) in old.keys_to_move(&new, 0..100) {
+    ///     assert_ne!(from.map(|n| n.key), to.map(|n| n.key));
+    ///     assert_eq!(to.map(|n| n.key), Some(3));
+    /// }
+    /// ```
+    pub fn keys_to_move<'o, T, I>(&self,
+                                  new: &'o StaticHashRing<K, V, H>,
+                                  keys: I)
+                                  -> KeyMoves<'_, 'o, K, V, H, I::IntoIter>
+        where T: Hash,
+              I: IntoIterator<Item = T>
+    {
+        KeyMoves {
+            old: self,
+            new,
+            keys: keys.into_iter(),
+        }
+    }
+}
+
+/// An iterator over the keys whose primary nodes differ between two rings.
+///
+/// Each element is a tuple of a key, its owner in the old ring, and its owner in the new ring
+/// (the owners are `None` if the rings are empty).
+///
+/// This is created by calling `StaticHashRing::keys_to_move` method.
+pub struct KeyMoves<'a, 'b, K: 'a + 'b, V: 'a + 'b, H: 'a + 'b, I> {
+    old: &'a StaticHashRing<K, V, H>,
+    new: &'b StaticHashRing<K, V, H>,
+    keys: I,
+}
+impl<'a, 'b, K: 'a + 'b, V: 'a + 'b, H: 'a + 'b, I> Iterator for KeyMoves<'a, 'b, K, V, H, I>
+    where K: Eq,
+          H: RingHash,
+          I: Iterator,
+          I::Item: Hash
+{
+    type Item = (I::Item, Option<&'a Node<K, V>>, Option<&'b Node<K, V>>);
+    fn next(&mut self) -> Option<Self::Item> {
+        for key in &mut self.keys {
+            let hash = self.old.hash.hash_item(&key);
+            let from = self.old.calc_node_by_hash(hash);
+            let to = self.new.calc_node_by_hash(hash);
+            if !same_key(from, to) {
+                return Some((key, from, to));
+            }
+        }
+        None
+    }
+}
+
+fn same_key<K: Eq, V>(a: Option<&Node<K, V>>, b: Option<&Node<K, V>>) -> bool {
+    a.map(|n| &n.key) == b.map(|n| &n.key)
+}
+
+#[cfg(test)]
+mod tests {
+    use {DefaultHash, Node, RingHash, StaticHashRing};
+
+    #[test]
+    fn diff_works() {
+        let old = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(20)));
+        let mut new = StaticHashRing::new(DefaultHash, (1..5).map(|i| Node::new(i).quantity(20)));
+        new.set_replica_only(&1, true);
+
+        let diff = old.diff(&new);
+        for w in diff.moves.windows(2) {
+            assert!(w[0].range.end < w[1].range.start);
+        }
+        for i in 0..1000 {
+            let h = DefaultHash.hash_item(&i);
+            let before = old.calc_candidates(&i).next().map(|n| n.key);
+            let after = new.calc_candidates(&i).next().map(|n| n.key);
+            let moved = diff.moves.iter().find(|m| m.range.contains(h));
+            match moved {
+                None => assert_eq!(before, after),
+                Some(m) => {
+                    assert_eq!(m.before.map(|n| n.key), before);
+                    assert_eq!(m.after.map(|n| n.key), after);
+                    assert_ne!(before, after);
+                }
+            }
+        }
+
+        let empty = StaticHashRing::new(DefaultHash, (0..0).map(Node::new));
+        let diff = empty.diff(&old);
+        assert!((diff.moved_fraction() - 1.0).abs() < 1e-9);
+        assert!(diff.moves.iter().all(|m| m.before.is_none()));
+        assert!(empty.diff(&empty).is_empty());
+    }
+
+    #[test]
+    fn keys_to_move_works() {
+        let old = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(20)));
+        let new = StaticHashRing::new(DefaultHash, (1..5).map(|i| Node::new(i).quantity(20)));
+
+        let moves = old.keys_to_move(&new, 0..1000).collect::<Vec<_>>();
+        let expected = (0..1000)
+            .filter(|i| old.calc_node(i).map(|n| n.key) != new.calc_node(i).map(|n| n.key))
+            .collect::<Vec<_>>();
+        assert_eq!(moves.iter().map(|m| m.0).collect::<Vec<_>>(), expected);
+        for (key, from, to) in moves {
+            assert_eq!(from, old.calc_node(&key));
+            assert_eq!(to, new.calc_node(&key));
+        }
+        assert_eq!(old.keys_to_move(&old, 0..1000).count(), 0);
+
+        let empty = StaticHashRing::new(DefaultHash, (0..0).map(Node::new));
+        assert_eq!(empty.keys_to_move(&old, 0..10).count(), 10);
+    }
+}
@@ -0,0 +1,317 @@
+use std::fmt;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use {Node, RingHash, StaticHashRing};
+
+/// A pair of rings which computes placements under two hashing algorithms and reports divergences.
+///
+/// This is used during a migration from a legacy hashing scheme
+/// to measure how much of real traffic would be placed differently before cutover.
+/// Requests are routed by the primary ring, and the shadow ring is only consulted for comparison.
+///
+/// A migration can also be switched over at a chosen generation (see `schedule_switch`):
+/// `calc_node` serves from the primary ring until the generation advanced by `advance_to` reaches it,
+/// and from the shadow ring afterwards, while the divergences keep being measured.
+///
+/// # Examples
+///
+/// ```
+/// use consistent_hash::{Node, StaticHashRing, DefaultHash, DualHashRing, KetamaHash};
+///
+/// let nodes = (0..3).map(|i| Node::new(i).quantity(10));
+/// let legacy = StaticHashRing::new(KetamaHash, nodes.clone());
+/// let ring = StaticHashRing::new(DefaultHash, nodes);
+/// let dual = DualHashRing::new(legacy, ring);
+///
+/// for item in 0..100 {
+///     let owners = dual.owners(&item);
+///     assert_eq!(owners.primary, dual.primary_ring().calc_node(&item));
+/// }
+/// assert_eq!(dual.lookups(), 100);
+/// assert!(dual.divergences() > 0);
+/// ```
+pub struct DualHashRing<K, V, H1, H2> {
+    primary: StaticHashRing<K, V, H1>,
+    shadow: StaticHashRing<K, V, H2>,
+    lookups: AtomicUsize,
+    divergences: AtomicUsize,
+    log: Option<Box<dyn DivergenceLog<K> + Send + Sync>>,
+    generation: AtomicU64,
+
+    // `u64::MAX` means that no switch is scheduled.
+    switch_generation: AtomicU64,
+}
+impl<K, V, H1, H2> DualHashRing<K, V, H1, H2> {
+    /// Makes a new `DualHashRing` instance which routes by `primary` and compares with `shadow`.
+    pub fn new(primary: StaticHashRing<K, V, H1>, shadow: StaticHashRing<K, V, H2>) -> Self {
+        DualHashRing {
+            primary,
+            shadow,
+            lookups: AtomicUsize::new(0),
+            divergences: AtomicUsize::new(0),
+            log: None,
+            generation: AtomicU64::new(0),
+            switch_generation: AtomicU64::new(u64::MAX),
+        }
+    }
+
+    /// Returns the reference to the primary ring.
+    pub fn primary_ring(&self) -> &StaticHashRing<K, V, H1> {
+        &self.primary
+    }
+
+    /// Returns the reference to the shadow ring.
+    pub fn shadow_ring(&self) -> &StaticHashRing<K, V, H2> {
+        &self.shadow
+    }
+
+    /// Sets the function which is called for each divergent lookup (e.g., for logging the items).
+    ///
+    /// If `log` is `None`, divergences are only counted.
+    pub fn set_divergence_log(&mut self, log: Option<Box<dyn DivergenceLog<K> + Send + Sync>>) {
+        self.log = log;
+    }
+
+    /// Returns the count of the lookups made by `owners`.
+    pub fn lookups(&self) -> usize {
+        self.lookups.load(Ordering::Relaxed)
+    }
+
+    /// Returns the count of the lookups whose owners differ between the two rings.
+    pub fn divergences(&self) -> usize {
+        self.divergences.load(Ordering::Relaxed)
+    }
+
+    /// Returns the fraction of the divergent lookups (`0.0` if no lookups have been made).
+    pub fn divergence_rate(&self) -> f64 {
+        match self.lookups() {
+            0 => 0.0,
+            lookups => self.divergences() as f64 / lookups as f64,
+        }
+    }
+
+    /// Resets the counts of the lookups and the divergences.
+    pub fn reset_counts(&self) {
+        self.lookups.store(0, Ordering::Relaxed);
+        self.divergences.store(0, Ordering::Relaxed);
+    }
+
+    /// Schedules the switch to the shadow ring at `generation`.
+    ///
+    /// If the current generation already reached `generation`, the switch takes effect immediately.
+    pub fn schedule_switch(&self, generation: u64) {
+        self.switch_generation.store(generation, Ordering::SeqCst);
+    }
+
+    /// Cancels the scheduled switch (i.e., serves from the primary ring again).
+    pub fn cancel_switch(&self) {
+        self.switch_generation.store(u64::MAX, Ordering::SeqCst);
+    }
+
+    /// Returns the generation at which the switch is scheduled.
+    pub fn switch_generation(&self) -> Option<u64> {
+        match self.switch_generation.load(Ordering::SeqCst) {
+            u64::MAX => None,
+            g => Some(g),
+        }
+    }
+
+    /// Advances the current generation to `generation` (e.g., the configuration generation of a cluster).
+    ///
+    /// The generation never goes backwards, so older generations are ignored.
+    pub fn advance_to(&self, generation: u64) {
+        self.generation.fetch_max(generation, Ordering::SeqCst);
+    }
+
+    /// Returns the current generation.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    /// Returns `true` if lookups are served from the shadow ring, otherwise `false`.
+    pub fn is_switched(&self) -> bool {
+        self.generation() >= self.switch_generation.load(Ordering::SeqCst)
+    }
+
+    /// Finishes the migration and returns the shadow ring.
+    pub fn finish(self) -> StaticHashRing<K, V, H2> {
+        self.shadow
+    }
+}
+impl<K, V, H1, H2> DualHashRing<K, V, H1, H2>
+    where K: Eq,
+          H1: RingHash,
+          H2: RingHash
+{
+    /// Returns the owners (i.e., primary nodes) of `item` in the two rings.
+    ///
+    /// The lookup is counted, and if the owners differ, it is also counted as a divergence
+    /// and passed to the function registered by `set_divergence_log`.
+    pub fn owners<T: Hash>(&self, item: &T) -> DualOwners<'_, K, V> {
+        let item_hash = self.primary.hash.hash_item(item);
+        let owners = DualOwners {
+            primary: self.primary.calc_node_by_hash(item_hash),
+            shadow: self.shadow.calc_node(item),
+        };
+        self.lookups.fetch_add(1, Ordering::Relaxed);
+        if owners.is_divergent() {
+            self.divergences.fetch_add(1, Ordering::Relaxed);
+            if let Some(ref log) = self.log {
+                log.log(&DualDivergence {
+                    item_hash,
+                    primary: owners.primary.map(|n| &n.key),
+                    shadow: owners.shadow.map(|n| &n.key),
+                });
+            }
+        }
+        owners
+    }
+
+    /// Returns the node which serves `item`.
+    ///
+    /// This is the owner in the primary ring before the scheduled switch, and the one in the shadow ring after it.
+    /// In either case, the lookup is compared as `owners` does,
+    /// so `divergence_rate` continuously reports the fraction of the lookups which move by the switch.
+    pub fn calc_node<T: Hash>(&self, item: &T) -> Option<&Node<K, V>> {
+        let switched = self.is_switched();
+        let owners = self.owners(item);
+        if switched {
+            owners.shadow
+        } else {
+            owners.primary
+        }
+    }
+}
+impl<K: fmt::Debug, V: fmt::Debug, H1: fmt::Debug, H2: fmt::Debug> fmt::Debug for DualHashRing<K, V, H1, H2> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DualHashRing")
+            .field("primary", &self.primary)
+            .field("shadow", &self.shadow)
+            .field("lookups", &self.lookups)
+            .field("divergences", &self.divergences)
+            .field("log", &self.log.as_ref().map(|_| ".."))
+            .field("generation", &self.generation)
+            .field("switch_generation", &self.switch_generation)
+            .finish()
+    }
+}
+
+/// The owners of an item in the primary and shadow rings.
+///
+/// This is returned by `DualHashRing::owners` method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DualOwners<'a, K: 'a, V: 'a> {
+    /// The owner in the primary ring (`None` if the ring is empty).
+    pub primary: Option<&'a Node<K, V>>,
+
+    /// The owner in the shadow ring (`None` if the ring is empty).
+    pub shadow: Option<&'a Node<K, V>>,
+}
+impl<'a, K: 'a, V: 'a> DualOwners<'a, K, V>
+    where K: Eq
+{
+    /// Returns `true` if the owner of the item differs between the two rings.
+    pub fn is_divergent(&self) -> bool {
+        self.primary.map(|n| &n.key) != self.shadow.map(|n| &n.key)
+    }
+}
+
+/// A divergent lookup of `DualHashRing`.
+///
+/// This is passed to the function registered by `DualHashRing::set_divergence_log`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DualDivergence<'a, K: 'a> {
+    /// The hash code of the item computed by the hasher of the primary ring.
+    pub item_hash: u64,
+
+    /// The key of the owner in the primary ring.
+    pub primary: Option<&'a K>,
+
+    /// The key of the owner in the shadow ring.
+    pub shadow: Option<&'a K>,
+}
+
+/// This trait allows receiving the divergent lookups of `DualHashRing`.
+///
+/// This is implemented for any `Fn(&DualDivergence<K>)` closures.
+pub trait DivergenceLog<K> {
+    /// Logs `divergence`.
+    fn log(&self, divergence: &DualDivergence<K>);
+}
+impl<K, F> DivergenceLog<K> for F
+    where F: Fn(&DualDivergence<K>)
+{
+    fn log(&self, divergence: &DualDivergence<K>) {
+        self(divergence)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use {DefaultHash, KetamaHash, Node, RingHash, StaticHashRing};
+    use super::*;
+
+    #[test]
+    fn dual_hash_ring_works() {
+        let nodes = (0..4).map(|i| Node::new(i).quantity(20));
+        let legacy = StaticHashRing::new(KetamaHash, nodes.clone());
+        let mut dual = DualHashRing::new(legacy, StaticHashRing::new(DefaultHash, nodes));
+
+        let logged = Arc::new(Mutex::new(Vec::new()));
+        let sink = logged.clone();
+        dual.set_divergence_log(Some(Box::new(move |d: &DualDivergence<i32>| {
+            sink.lock().unwrap().push((d.item_hash, *d.primary.unwrap(), *d.shadow.unwrap()));
+        })));
+
+        let mut expected = Vec::new();
+        for item in 0..1000 {
+            let owners = dual.owners(&item);
+            assert_eq!(owners.primary, dual.primary_ring().calc_node(&item));
+            assert_eq!(owners.shadow, dual.shadow_ring().calc_node(&item));
+            if owners.is_divergent() {
+                expected.push((KetamaHash.hash_item(&item), owners.primary.unwrap().key, owners.shadow.unwrap().key));
+            }
+        }
+        assert_eq!(dual.lookups(), 1000);
+        assert_eq!(dual.divergences(), expected.len());
+        assert_eq!(*logged.lock().unwrap(), expected);
+        assert!(0.5 < dual.divergence_rate() && dual.divergence_rate() < 1.0);
+
+        dual.reset_counts();
+        assert_eq!((dual.lookups(), dual.divergences(), dual.divergence_rate()), (0, 0, 0.0));
+
+        let same = DualHashRing::new(StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(20))),
+                                     StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(20))));
+        assert!((0..100).all(|i| !same.owners(&i).is_divergent()));
+        assert_eq!(same.divergences(), 0);
+    }
+
+    #[test]
+    fn switch_works() {
+        let nodes = (0..4).map(|i| Node::new(i).quantity(20));
+        let legacy = StaticHashRing::new(KetamaHash, nodes.clone());
+        let dual = DualHashRing::new(legacy, StaticHashRing::new(DefaultHash, nodes));
+        assert_eq!(dual.switch_generation(), None);
+
+        dual.schedule_switch(10);
+        dual.advance_to(9);
+        assert!(!dual.is_switched());
+        assert!((0..100).all(|i| dual.calc_node(&i) == dual.primary_ring().calc_node(&i)));
+        assert!(dual.divergence_rate() > 0.0);
+
+        dual.advance_to(10);
+        dual.advance_to(3);
+        assert_eq!(dual.generation(), 10);
+        assert!(dual.is_switched());
+        assert!((0..100).all(|i| dual.calc_node(&i) == dual.shadow_ring().calc_node(&i)));
+        assert_eq!(dual.lookups(), 200);
+
+        dual.cancel_switch();
+        assert!(!dual.is_switched());
+        dual.schedule_switch(5);
+        assert!(dual.is_switched());
+        assert_eq!(dual.finish().nodes().len(), 4);
+    }
+}
@@ -0,0 +1,129 @@
+use std::hash::{Hash, Hasher};
+
+use RingHash;
+
+/// An object-safe counterpart of `RingHash`.
+///
+/// Every `RingHash` implements this trait, and `Box<dyn DynRingHash>` (and the like) implements `RingHash`,
+/// so a `StaticHashRing` can be built with a hasher chosen at runtime (e.g., from a config file)
+/// without an enum dispatch wrapper.
+/// A boxed hasher places items and virtual nodes exactly as the hasher itself does.
+///
+/// Items and node keys are passed as functions which feed them to a hasher (e.g., `|h| item.hash(h)`).
+///
+/// # Examples
+///
+/// ```
+/// use consistent_hash::{Node, StaticHashRing, DefaultHash, DynRingHash, KetamaHash};
+///
+/// fn hasher(name: &str) -> Box<dyn DynRingHash + Send + Sync> {
+///     match name {
+///         "ketama" => Box::new(KetamaHash),
+///         _ => Box::new(DefaultHash),
+///     }
+/// }
+///
+/// let nodes = (0..3).map(|i| Node::new(i).quantity(10));
+/// let boxed = StaticHashRing::new(hasher("ketama"), nodes.clone());
+/// let ring = StaticHashRing::new(KetamaHash, nodes);
+/// assert_eq!(boxed.calc_node(&"foo"), ring.calc_node(&"foo"));
+/// assert_eq!(boxed.format_tag(), ring.format_tag());
+/// ```
+pub trait DynRingHash {
+    /// Calculates the hash code of the item which is fed to a hasher by `item`.
+    ///
+    /// See `RingHash::hash_item`.
+    fn dyn_hash_item(&self, item: &dyn Fn(&mut dyn Hasher)) -> u64;
+
+    /// Calculates the hash code of the virtual node whose key is fed to a hasher by `node_key`.
+    ///
+    /// See `RingHash::hash_vnode`.
+    fn dyn_hash_vnode(&self, node_key: &dyn Fn(&mut dyn Hasher), vnode_seq: usize) -> u64;
+
+    /// Calculates the hash code of the raw bytes `bytes`.
+    ///
+    /// See `RingHash::hash_bytes`.
+    fn dyn_hash_bytes(&self, bytes: &[u8]) -> u64;
+
+    /// Returns the identifier of the hashing algorithm.
+    ///
+    /// See `RingHash::algorithm_id`.
+    fn dyn_algorithm_id(&self) -> &str;
+}
+impl<H: RingHash> DynRingHash for H {
+    fn dyn_hash_item(&self, item: &dyn Fn(&mut dyn Hasher)) -> u64 {
+        self.hash_item(&Fed(item))
+    }
+    fn dyn_hash_vnode(&self, node_key: &dyn Fn(&mut dyn Hasher), vnode_seq: usize) -> u64 {
+        self.hash_vnode(&Fed(node_key), vnode_seq)
+    }
+    fn dyn_hash_bytes(&self, bytes: &[u8]) -> u64 {
+        self.hash_bytes(bytes)
+    }
+    fn dyn_algorithm_id(&self) -> &str {
+        self.algorithm_id()
+    }
+}
+
+impl<D: DynRingHash + ?Sized> RingHash for Box<D> {
+    fn hash_item<T: Hash>(&self, item: &T) -> u64 {
+        (**self).dyn_hash_item(&|mut h| item.hash(&mut h))
+    }
+    fn hash_vnode<K: Hash>(&self, node_key: &K, vnode_seq: usize) -> u64 {
+        (**self).dyn_hash_vnode(&|mut h| node_key.hash(&mut h), vnode_seq)
+    }
+    fn hash_bytes(&self, bytes: &[u8]) -> u64 {
+        (**self).dyn_hash_bytes(bytes)
+    }
+    fn algorithm_id(&self) -> &str {
+        (**self).dyn_algorithm_id()
+    }
+}
+
+// An item which is fed to a hasher by the wrapped function.
+//
+// The hasher is passed as a trait object, so each call (e.g., `write_u8`) reaches the hasher as it is.
+struct Fed<'a>(&'a dyn Fn(&mut dyn Hasher));
+impl<'a> Hash for Fed<'a> {
+    fn hash<S: Hasher>(&self, state: &mut S) {
+        (self.0)(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {DefaultHash, KetamaHash, Node, RingHash, StaticHashRing};
+    use super::*;
+
+    fn assert_same_placements<H: RingHash + Clone + Send + Sync + 'static>(hash: H) {
+        let nodes = (0..5).map(|i| Node::new(format!("node-{}", i)).quantity(20));
+        let ring = StaticHashRing::new(hash.clone(), nodes.clone());
+        let boxed = StaticHashRing::new(Box::new(hash) as Box<dyn DynRingHash + Send + Sync>, nodes);
+        assert_eq!(boxed.vnodes().map(|v| v.0).collect::<Vec<_>>(),
+                   ring.vnodes().map(|v| v.0).collect::<Vec<_>>());
+        for item in 0..100 {
+            assert!(boxed.calc_candidates(&item).eq(ring.calc_candidates(&item)));
+            let item = format!("item-{}", item);
+            assert!(boxed.calc_candidates(&item).eq(ring.calc_candidates(&item)));
+        }
+        assert!(boxed.calc_candidates_bytes(b"foo").eq(ring.calc_candidates_bytes(b"foo")));
+        assert_eq!(boxed.format_tag(), ring.format_tag());
+    }
+
+    #[test]
+    fn boxed_ring_hash_works() {
+        assert_same_placements(DefaultHash);
+        assert_same_placements(DefaultHash::with_keys(1, 2));
+        assert_same_placements(KetamaHash);
+        #[cfg(feature = "fnv")]
+        assert_same_placements(::Fnv);
+        #[cfg(feature = "xxhash")]
+        assert_same_placements(::XxHash);
+        #[cfg(feature = "blake3")]
+        assert_same_placements(::Blake3Hash);
+
+        let boxed: Box<dyn DynRingHash> = Box::new(DefaultHash);
+        assert_eq!(boxed.hash_item(&"foo"), DefaultHash.hash_item(&"foo"));
+        assert_eq!(boxed.algorithm_id(), "siphash13");
+    }
+}
@@ -0,0 +1,445 @@
+use std::cmp::Ordering;
+use std::hash::Hash;
+use std::mem;
+
+use {Node, RingHash, SeenSet};
+
+#[derive(Debug, Clone, Copy)]
+struct Vnode {
+    hash: u64,
+    slot: usize,
+    seq: usize,
+}
+
+/// A virtual node based hash ring which supports adding and removing nodes at runtime.
+///
+/// Unlike `StaticHashRing`, nodes can be inserted and removed after construction.
+/// Each modification only adds or removes the virtual nodes of the affected node
+/// (the remaining virtual nodes are kept as they are),
+/// and the placements are the same as a `StaticHashRing` built from the same nodes.
+///
+/// # Examples
+///
+/// ```
+/// use consistent_hash::{Node, DynamicHashRing, DefaultHash};
+///
+/// let mut ring = DynamicHashRing::new(DefaultHash);
+/// ring.insert_node(Node::new("foo").quantity(5));
+/// ring.insert_node(Node::new("bar").quantity(5));
+/// ring.insert_node(Node::new("baz").quantity(1));
+/// assert_eq!(ring.len(), 11);
+/// assert_eq!(ring.calc_candidates(&"aa").map(|n| &n.key).collect::<Vec<_>>(),
+///            [&"bar", &"foo", &"baz"]);
+///
+/// ring.remove_node(&"bar");
+/// assert_eq!(ring.len(), 6);
+/// assert_eq!(ring.calc_candidates(&"aa").map(|n| &n.key).collect::<Vec<_>>(),
+///            [&"foo", &"baz"]);
+/// ```
+#[derive(Debug)]
+pub struct DynamicHashRing<K, V, H> {
+    hash: H,
+    slots: Vec<Option<Node<K, V>>>,
+    free_slots: Vec<usize>,
+
+    // The count of the active virtual nodes of each slot (see `ramp` method).
+    actives: Vec<usize>,
+
+    // The slots of the nodes sorted by their keys.
+    sorted_slots: Vec<usize>,
+
+    ring: Vec<Vnode>,
+}
+impl<K, V, H> DynamicHashRing<K, V, H>
+    where K: Hash + Eq + Ord,
+          H: RingHash
+{
+    /// Makes a new `DynamicHashRing` instance which has no nodes.
+    pub fn new(hash: H) -> Self {
+        DynamicHashRing {
+            hash,
+            slots: Vec::new(),
+            free_slots: Vec::new(),
+            actives: Vec::new(),
+            sorted_slots: Vec::new(),
+            ring: Vec::new(),
+        }
+    }
+
+    /// Makes a new `DynamicHashRing` instance which has `nodes`.
+    ///
+    /// If multiple nodes which have the same key are contained in `nodes`,
+    /// all of those nodes but first one are ignored (as with `StaticHashRing::new`).
+    pub fn with_nodes<I>(hash: H, nodes: I) -> Self
+        where I: Iterator<Item = Node<K, V>>
+    {
+        let mut this = Self::new(hash);
+        for node in nodes {
+            if this.get_node(&node.key).is_none() {
+                this.insert_node(node);
+            }
+        }
+        this
+    }
+
+    /// Inserts `node` into this ring.
+    ///
+    /// If this ring already contains a node which has the same key,
+    /// the node is replaced with `node` and returned.
+    pub fn insert_node(&mut self, node: Node<K, V>) -> Option<Node<K, V>> {
+        let old = self.remove_node(&node.key);
+
+        let slot = if let Some(slot) = self.free_slots.pop() {
+            slot
+        } else {
+            self.slots.push(None);
+            self.actives.push(0);
+            self.slots.len() - 1
+        };
+        let position = self.sorted_slots
+            .binary_search_by(|&s| self.node(s).key.cmp(&node.key))
+            .unwrap_err();
+        self.sorted_slots.insert(position, slot);
+        let quantity = node.quantity;
+        self.slots[slot] = Some(node);
+        self.actives[slot] = 0;
+        self.add_vnodes(slot, quantity);
+
+        old
+    }
+
+    /// Changes the count of the active virtual nodes of the node which has the key `key`
+    /// to `fraction` of its quantity (rounded to the nearest integer).
+    ///
+    /// Only the difference from the current count is added to (or removed from) the ring,
+    /// so a new node can be ramped up gradually (e.g., `0.0`, `0.25`, ..., `1.0`)
+    /// instead of absorbing its full share instantly.
+    /// `fraction` is clamped to the range `0.0..=1.0`.
+    ///
+    /// Returns the new count of the active virtual nodes,
+    /// or `None` if no such node exists.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, DynamicHashRing, DefaultHash};
+    ///
+    /// let mut ring = DynamicHashRing::with_nodes(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+    /// ring.insert_node(Node::new(3).quantity(10));
+    /// assert_eq!(ring.ramp(&3, 0.0), Some(0));
+    /// assert_eq!(ring.len(), 30);
+    ///
+    /// assert_eq!(ring.ramp(&3, 0.5), Some(5));
+    /// assert_eq!(ring.len(), 35);
+    /// assert_eq!(ring.ramp(&3, 1.0), Some(10));
+    /// assert_eq!(ring.len(), 40);
+    /// ```
+    pub fn ramp(&mut self, key: &K, fraction: f64) -> Option<usize> {
+        let position = self.sorted_slots
+            .binary_search_by(|&s| self.node(s).key.cmp(key))
+            .ok()?;
+        let slot = self.sorted_slots[position];
+        let fraction = if fraction > 0.0 { fraction.min(1.0) } else { 0.0 };
+        let active = (self.node(slot).quantity as f64 * fraction).round() as usize;
+        if active < self.actives[slot] {
+            self.ring.retain(|vn| vn.slot != slot || vn.seq < active);
+            self.actives[slot] = active;
+        } else {
+            self.add_vnodes(slot, active);
+        }
+        Some(active)
+    }
+
+    // Adds the virtual nodes of `slot` so that it has `active` virtual nodes.
+    fn add_vnodes(&mut self, slot: usize, active: usize) {
+        let mut vnodes = {
+            let key = &self.node(slot).key;
+            (self.actives[slot]..active)
+                .map(|seq| {
+                    Vnode {
+                        hash: self.hash.hash_vnode(key, seq),
+                        slot,
+                        seq,
+                    }
+                })
+                .collect::<Vec<_>>()
+        };
+        vnodes.sort_by_key(|vn| vn.hash);
+        self.actives[slot] = active;
+
+        // Merges the new virtual nodes into the ring.
+        let old_ring = mem::take(&mut self.ring);
+        let mut ring = Vec::with_capacity(old_ring.len() + vnodes.len());
+        let mut vnodes = vnodes.into_iter().peekable();
+        for vnode in old_ring {
+            while let Some(new) = vnodes.next_if(|new| self.compare(new, &vnode) == Ordering::Less) {
+                ring.push(new);
+            }
+            ring.push(vnode);
+        }
+        ring.extend(vnodes);
+        self.ring = ring;
+    }
+
+    /// Removes the node which has the key `key` from this ring, and returns it.
+    ///
+    /// If no such node exists, this returns `None`.
+    pub fn remove_node(&mut self, key: &K) -> Option<Node<K, V>> {
+        let position = self.sorted_slots
+            .binary_search_by(|&s| self.node(s).key.cmp(key))
+            .ok()?;
+        let slot = self.sorted_slots.remove(position);
+        self.ring.retain(|vn| vn.slot != slot);
+        self.actives[slot] = 0;
+        self.free_slots.push(slot);
+        self.slots[slot].take()
+    }
+
+    /// Returns the reference to the node which has the key `key`.
+    pub fn get_node(&self, key: &K) -> Option<&Node<K, V>> {
+        self.sorted_slots
+            .binary_search_by(|&s| self.node(s).key.cmp(key))
+            .ok()
+            .map(|i| self.node(self.sorted_slots[i]))
+    }
+
+    /// Returns the candidate nodes for `item`.
+    ///
+    /// The higher priority node is located in front of the returned candidate sequence.
+    pub fn calc_candidates<T: Hash + ?Sized>(&self, item: &T) -> DynamicCandidates<'_, K, V, H> {
+        let item_hash = self.hash.hash_item(&item);
+        let start = self.ring.binary_search_by_key(&(item_hash, 0), |vn| (vn.hash, 1)).unwrap_err();
+        DynamicCandidates {
+            ring: self,
+            start,
+            count: 0,
+            seens: SeenSet::new(self.slots.len()),
+        }
+    }
+
+    fn compare(&self, a: &Vnode, b: &Vnode) -> Ordering {
+        (a.hash, &self.node(a.slot).key).cmp(&(b.hash, &self.node(b.slot).key))
+    }
+}
+impl<K, V, H> DynamicHashRing<K, V, H> {
+    /// Returns the count of the virtual nodes in this ring.
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+
+    /// Returns `true` if this ring has no virtual nodes.
+    pub fn is_empty(&self) -> bool {
+        self.ring.is_empty()
+    }
+
+    /// Returns the count of the real nodes in this ring.
+    pub fn node_count(&self) -> usize {
+        self.sorted_slots.len()
+    }
+
+    /// Returns an iterator over the real nodes in this ring (sorted by their keys).
+    pub fn nodes(&self) -> impl Iterator<Item = &Node<K, V>> {
+        self.sorted_slots.iter().map(move |&s| self.node(s))
+    }
+
+    /// Rewrites the internal tables of this ring, and returns the count of the reclaimed bytes.
+    ///
+    /// Removing nodes leaves empty slots which are reused by later insertions,
+    /// and the tables never shrink by themselves.
+    /// This drops the empty slots, renumbers the remaining slots in the order of the node keys,
+    /// and shrinks the capacities of the tables, keeping long-lived rings lean after heavy churn.
+    /// The placements are not changed.
+    ///
+    /// Only the memory held by the tables of this ring is counted
+    /// (e.g., heap data owned by the keys of nodes is not).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, DynamicHashRing, DefaultHash};
+    ///
+    /// let mut ring = DynamicHashRing::with_nodes(DefaultHash, (0..100).map(|i| Node::new(i).quantity(10)));
+    /// for i in 0..90 {
+    ///     ring.remove_node(&i);
+    /// }
+    /// let candidates = ring.calc_candidates(&"foo").map(|n| n.key).collect::<Vec<_>>();
+    ///
+    /// assert!(ring.compact() > 0);
+    /// assert_eq!(ring.compact(), 0);
+    /// assert_eq!(ring.calc_candidates(&"foo").map(|n| n.key).collect::<Vec<_>>(), candidates);
+    /// ```
+    pub fn compact(&mut self) -> usize {
+        let before = self.allocated_bytes();
+
+        let mut renumbered = vec![usize::MAX; self.slots.len()];
+        let mut slots = Vec::with_capacity(self.sorted_slots.len());
+        let mut actives = Vec::with_capacity(self.sorted_slots.len());
+        for &old in &self.sorted_slots {
+            renumbered[old] = slots.len();
+            slots.push(self.slots[old].take());
+            actives.push(self.actives[old]);
+        }
+        for vn in &mut self.ring {
+            vn.slot = renumbered[vn.slot];
+        }
+        self.sorted_slots = (0..slots.len()).collect();
+        self.slots = slots;
+        self.actives = actives;
+        self.free_slots = Vec::new();
+        self.ring.shrink_to_fit();
+
+        before - self.allocated_bytes()
+    }
+
+    fn allocated_bytes(&self) -> usize {
+        self.slots.capacity() * mem::size_of::<Option<Node<K, V>>>() +
+        (self.free_slots.capacity() + self.actives.capacity() + self.sorted_slots.capacity()) *
+        mem::size_of::<usize>() + self.ring.capacity() * mem::size_of::<Vnode>()
+    }
+
+    fn node(&self, slot: usize) -> &Node<K, V> {
+        self.slots[slot].as_ref().expect("Never fails")
+    }
+}
+
+/// An iterator which represents a sequence of the candidate nodes for an item.
+///
+/// This is created by calling `DynamicHashRing::calc_candidates` method.
+pub struct DynamicCandidates<'a, K: 'a, V: 'a, H: 'a> {
+    ring: &'a DynamicHashRing<K, V, H>,
+    start: usize,
+    count: usize,
+    seens: SeenSet,
+}
+impl<'a, K: 'a, V: 'a, H: 'a> Iterator for DynamicCandidates<'a, K, V, H> {
+    type Item = &'a Node<K, V>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let ring = &self.ring.ring;
+        while self.seens.len() < self.ring.sorted_slots.len() && self.count < ring.len() {
+            let vnode = ring[(self.start + self.count) % ring.len()];
+            self.count += 1;
+            if !self.seens.contains(vnode.slot) {
+                self.seens.insert(vnode.slot);
+                return Some(self.ring.node(vnode.slot));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {DefaultHash, Node, StaticHashRing};
+    use super::*;
+
+    #[test]
+    fn dynamic_ring_works() {
+        let mut ring = DynamicHashRing::new(DefaultHash);
+        for i in 0..10 {
+            assert!(ring.insert_node(Node::new(i).quantity(i % 4)).is_none());
+        }
+        assert_eq!(ring.node_count(), 10);
+        assert_eq!(ring.len(), (0..10).map(|i| i % 4).sum::<usize>());
+        assert_eq!(ring.remove_node(&3).map(|n| n.quantity), Some(3));
+        assert!(ring.remove_node(&3).is_none());
+        assert_eq!(ring.insert_node(Node::new(5).quantity(10)).map(|n| n.quantity), Some(1));
+        assert!(ring.insert_node(Node::new(3).quantity(2)).is_none());
+        assert_eq!(ring.get_node(&5).map(|n| n.quantity), Some(10));
+        assert_eq!(ring.nodes().map(|n| n.key).collect::<Vec<_>>(), (0..10).collect::<Vec<_>>());
+
+        let nodes = (0..10).map(|i| {
+            let quantity = match i {
+                3 => 2,
+                5 => 10,
+                _ => i % 4,
+            };
+            Node::new(i).quantity(quantity)
+        });
+        let expected = StaticHashRing::new(DefaultHash, nodes);
+        assert_eq!(ring.len(), expected.len());
+        for item in 0..100 {
+            let actual = ring.calc_candidates(&item).map(|n| n.key).collect::<Vec<_>>();
+            let expected = expected.calc_candidates(&item).map(|n| n.key).collect::<Vec<_>>();
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn calc_candidates_with_many_nodes_works() {
+        // More nodes than the inline words of the seen set can index.
+        let nodes = || (0..300).map(|i| Node::new(i).quantity(3));
+        let mut ring = DynamicHashRing::with_nodes(DefaultHash, nodes());
+        ring.remove_node(&7);
+        ring.insert_node(Node::new(7).quantity(3));
+        let expected = StaticHashRing::new(DefaultHash, nodes());
+        for item in &["foo", "bar", "baz"] {
+            let actual = ring.calc_candidates(*item).map(|n| n.key).collect::<Vec<_>>();
+            let expected = expected.calc_candidates(*item).map(|n| n.key).collect::<Vec<_>>();
+            assert_eq!(actual.len(), 300);
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn ramp_works() {
+        let mut ring = DynamicHashRing::with_nodes(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+        assert_eq!(ring.ramp(&5, 0.5), None);
+        assert_eq!(ring.ramp(&0, 0.26), Some(3));
+        assert_eq!(ring.ramp(&0, -1.0), Some(0));
+        assert_eq!(ring.ramp(&0, 0.7), Some(7));
+        assert_eq!(ring.ramp(&0, 2.0), Some(10));
+        assert_eq!(ring.ramp(&0, 0.5), Some(5));
+        assert_eq!(ring.len(), 25);
+
+        // A ramped ring places items as a static ring which has the first vnodes of the ramped node.
+        let expected = StaticHashRing::new(DefaultHash,
+                                           (0..3).map(|i| Node::new(i).quantity(if i == 0 { 5 } else { 10 })));
+        for item in 0..100 {
+            let actual = ring.calc_candidates(&item).map(|n| n.key).collect::<Vec<_>>();
+            let expected = expected.calc_candidates(&item).map(|n| n.key).collect::<Vec<_>>();
+            assert_eq!(actual, expected);
+        }
+
+        // Re-inserting a node restores its full quantity.
+        ring.insert_node(Node::new(0).quantity(10));
+        assert_eq!(ring.len(), 30);
+    }
+
+    #[test]
+    fn compact_works() {
+        let mut ring = DynamicHashRing::with_nodes(DefaultHash, (0..20).map(|i| Node::new(i).quantity(5)));
+        for i in (0..20).filter(|i| i % 3 != 0) {
+            ring.remove_node(&i);
+        }
+        let layout = |ring: &DynamicHashRing<_, _, _>| {
+            (0..100).map(|i| ring.calc_candidates(&i).map(|n| n.key).collect::<Vec<_>>()).collect::<Vec<_>>()
+        };
+        let before = layout(&ring);
+        assert!(ring.compact() > 0);
+        assert_eq!((ring.slots.len(), ring.free_slots.len(), ring.ring.capacity()), (7, 0, 35));
+        assert_eq!(layout(&ring), before);
+
+        // The compacted ring keeps working as usual.
+        ring.insert_node(Node::new(1).quantity(5));
+        assert_eq!(ring.ramp(&0, 0.4), Some(2));
+        let expected = StaticHashRing::new(DefaultHash,
+                                           (0..20)
+                                               .filter(|i| i % 3 == 0 || *i == 1)
+                                               .map(|i| Node::new(i).quantity(if i == 0 { 2 } else { 5 })));
+        for item in 0..100 {
+            let actual = ring.calc_candidates(&item).map(|n| n.key).collect::<Vec<_>>();
+            let expected = expected.calc_candidates(&item).map(|n| n.key).collect::<Vec<_>>();
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn with_nodes_works() {
+        let nodes = vec![Node::new("foo").quantity(2), Node::new("foo").quantity(3)];
+        let ring = DynamicHashRing::with_nodes(DefaultHash, nodes.into_iter());
+        assert_eq!(ring.len(), 2);
+
+        let empty = DynamicHashRing::<&str, (), _>::new(DefaultHash);
+        assert_eq!(empty.calc_candidates(&"foo").count(), 0);
+    }
+}
@@ -0,0 +1,515 @@
+use std::hash::Hash;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use {Candidates, Clock, Node, RingHash, StaticHashRing};
+
+/// The behavior of lookups when every node is excluded.
+///
+/// See `StaticHashRing::calc_candidates_with_fallback`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ExclusionFallback {
+    /// Yields no candidates (i.e., the same as `calc_candidates`).
+    #[default]
+    Empty,
+
+    /// Yields the candidates as if no nodes were excluded (or disabled).
+    IgnoreExclusions,
+
+    /// Yields only the highest priority node among the excluded ones.
+    Degraded,
+}
+
+/// An iterator which represents a sequence of the candidate nodes for an item,
+/// which may fall back to excluded nodes.
+///
+/// This is created by calling `StaticHashRing::calc_candidates_with_fallback` method.
+pub struct FallbackCandidates<'a, K: 'a, V: 'a> {
+    candidates: Candidates<'a, K, V>,
+    remaining: usize,
+    degraded: bool,
+}
+impl<'a, K: 'a, V: 'a> FallbackCandidates<'a, K, V> {
+    /// Returns `true` if the candidates are excluded nodes (i.e., the fallback took effect), otherwise `false`.
+    pub fn is_degraded(&self) -> bool {
+        self.degraded
+    }
+}
+impl<'a, K: 'a, V: 'a> Iterator for FallbackCandidates<'a, K, V> {
+    type Item = &'a Node<K, V>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        self.candidates.next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.candidates.len().min(self.remaining);
+        (n, Some(n))
+    }
+}
+impl<'a, K: 'a, V: 'a> ExactSizeIterator for FallbackCandidates<'a, K, V> {}
+
+impl<K, V, H> StaticHashRing<K, V, H>
+    where K: Hash + Eq + Ord,
+          H: RingHash
+{
+    /// Excludes the node `key` from candidate selection for `duration`.
+    ///
+    /// An excluded node is skipped by candidate sequences (and by `take` and the like)
+    /// until the deadline passes.
+    /// The deadline is checked lazily against the clock of this ring (see `set_clock`),
+    /// so there is no need to re-include the node explicitly (i.e., circuit-breaker semantics).
+    ///
+    /// If the node is already excluded, the deadline is overwritten.
+    /// Returns `false` if this ring does not contain the node.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let nodes = vec![Node::new("foo").quantity(5), Node::new("bar").quantity(5)];
+    /// let mut ring = StaticHashRing::new(DefaultHash, nodes.into_iter());
+    /// assert_eq!(ring.calc_candidates(&"aa").map(|n| n.key).collect::<Vec<_>>(),
+    ///            ["bar", "foo"]);
+    ///
+    /// ring.exclude_for(&"bar", Duration::from_secs(10));
+    /// assert!(ring.is_excluded(&"bar"));
+    /// assert_eq!(ring.calc_candidates(&"aa").map(|n| n.key).collect::<Vec<_>>(),
+    ///            ["foo"]);
+    /// ```
+    pub fn exclude_for(&mut self, key: &K, duration: Duration) -> bool {
+        let deadline = self.clock.now() + duration;
+        self.exclude_until(key, deadline)
+    }
+
+    /// Excludes the node `key` from candidate selection until `deadline`.
+    ///
+    /// See `exclude_for` for the details.
+    pub fn exclude_until(&mut self, key: &K, deadline: SystemTime) -> bool {
+        if let Some(i) = self.node_index(key) {
+            if self.states[i].excluded_until.is_none() {
+                self.excluded_nodes += 1;
+            }
+            self.states[i].excluded_until = Some(deadline);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Cancels the exclusion of the node `key`.
+    ///
+    /// Returns `false` if the node was not excluded (or this ring does not contain the node).
+    pub fn cancel_exclusion(&mut self, key: &K) -> bool {
+        if let Some(i) = self.node_index(key) {
+            if self.states[i].excluded_until.take().is_some() {
+                self.excluded_nodes -= 1;
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Returns `true` if the node `key` is currently excluded, otherwise `false`.
+    pub fn is_excluded(&self, key: &K) -> bool {
+        let now = self.clock.now();
+        self.node_index(key)
+            .and_then(|i| self.states[i].excluded_until)
+            .is_some_and(|deadline| now < deadline)
+    }
+
+    /// Forgets the exclusions whose deadlines have passed, and returns the count of them.
+    ///
+    /// Expired exclusions are ignored even if they are not cleared,
+    /// but clearing them makes subsequent lookups cheaper.
+    pub fn clear_expired_exclusions(&mut self) -> usize {
+        let now = self.clock.now();
+        let mut cleared = 0;
+        for state in &mut self.states {
+            if state.excluded_until.is_some_and(|deadline| deadline <= now) {
+                state.excluded_until = None;
+                cleared += 1;
+            }
+        }
+        self.excluded_nodes -= cleared;
+        cleared
+    }
+
+    /// Disables the node `key` (e.g., for draining it).
+    ///
+    /// A disabled node is skipped by candidate sequences (and by `take` and the like)
+    /// in the same way as an excluded node, so its items fall through to the next candidates.
+    /// Unlike `take`, the virtual nodes of the node are kept in place,
+    /// so `enable_node` restores the previous placement instantly.
+    /// Unlike exclusions, a disabled node stays disabled until it is enabled explicitly.
+    /// Range and ownership queries (e.g., `owned_ranges` and `ownership_by`) follow the lookups,
+    /// so a disabled node owns no ranges.
+    ///
+    /// Returns `false` if this ring does not contain the node.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let nodes = vec![Node::new("foo").quantity(5), Node::new("bar").quantity(5)];
+    /// let mut ring = StaticHashRing::new(DefaultHash, nodes.into_iter());
+    ///
+    /// ring.disable_node(&"bar");
+    /// assert!(ring.is_disabled(&"bar"));
+    /// assert_eq!(ring.calc_candidates(&"aa").map(|n| n.key).collect::<Vec<_>>(), ["foo"]);
+    /// assert_eq!(ring.len(), 10);
+    ///
+    /// ring.enable_node(&"bar");
+    /// assert_eq!(ring.calc_candidates(&"aa").map(|n| n.key).collect::<Vec<_>>(), ["bar", "foo"]);
+    /// ```
+    pub fn disable_node(&mut self, key: &K) -> bool {
+        if let Some(i) = self.node_index(key) {
+            if !self.states[i].disabled {
+                self.states[i].disabled = true;
+                self.disabled_nodes += 1;
+            }
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Enables the node `key` which was disabled by `disable_node`.
+    ///
+    /// Returns `false` if the node was not disabled (or this ring does not contain the node).
+    pub fn enable_node(&mut self, key: &K) -> bool {
+        if let Some(i) = self.node_index(key) {
+            if self.states[i].disabled {
+                self.states[i].disabled = false;
+                self.disabled_nodes -= 1;
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Returns `true` if the node `key` is disabled, otherwise `false`.
+    pub fn is_disabled(&self, key: &K) -> bool {
+        self.node_index(key).is_some_and(|i| self.states[i].disabled)
+    }
+
+    /// Schedules the removal of the node `key` at `deadline`.
+    ///
+    /// Until the deadline, the node keeps serving as a replica but is never chosen as primary
+    /// (i.e., it is regarded as a replica-only node, see `set_replica_only`).
+    /// After the deadline, the node is skipped by candidate sequences (and by `take` and the like)
+    /// in the same way as an excluded node.
+    /// Range and ownership queries (e.g., `owned_ranges` and `ownership_by`) follow the lookups,
+    /// so the node owns no ranges once its removal is scheduled.
+    /// The deadline is checked lazily against the clock of this ring (see `set_clock`),
+    /// so the node is dropped automatically.
+    ///
+    /// If the removal of the node is already scheduled, the deadline is overwritten.
+    /// Returns `false` if this ring does not contain the node.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::{Duration, UNIX_EPOCH};
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash, MockClock};
+    ///
+    /// let clock = MockClock::new(UNIX_EPOCH);
+    /// let nodes = vec![Node::new("foo").quantity(5), Node::new("bar").quantity(5)];
+    /// let mut ring = StaticHashRing::new(DefaultHash, nodes.into_iter());
+    /// ring.set_clock(clock.clone());
+    /// assert_eq!(ring.calc_candidates(&"aa").map(|n| n.key).collect::<Vec<_>>(), ["bar", "foo"]);
+    ///
+    /// ring.schedule_removal(&"bar", UNIX_EPOCH + Duration::from_secs(60));
+    /// assert_eq!(ring.calc_candidates(&"aa").map(|n| n.key).collect::<Vec<_>>(), ["foo", "bar"]);
+    ///
+    /// clock.advance(Duration::from_secs(60));
+    /// assert_eq!(ring.calc_candidates(&"aa").map(|n| n.key).collect::<Vec<_>>(), ["foo"]);
+    /// ```
+    pub fn schedule_removal(&mut self, key: &K, deadline: SystemTime) -> bool {
+        if let Some(i) = self.node_index(key) {
+            if self.states[i].removal_at.is_none() {
+                self.removal_nodes += 1;
+            }
+            self.states[i].removal_at = Some(deadline);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Cancels the scheduled removal of the node `key`.
+    ///
+    /// Returns `false` if the removal was not scheduled (or this ring does not contain the node).
+    pub fn cancel_removal(&mut self, key: &K) -> bool {
+        if let Some(i) = self.node_index(key) {
+            if self.states[i].removal_at.take().is_some() {
+                self.removal_nodes -= 1;
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Returns the deadline of the scheduled removal of the node `key`.
+    pub fn removal_deadline(&self, key: &K) -> Option<SystemTime> {
+        self.node_index(key).and_then(|i| self.states[i].removal_at)
+    }
+
+    /// Returns the candidate nodes for `item`, falling back as `fallback` specifies
+    /// if every node is excluded (including disabled nodes and the nodes whose removal deadlines have passed).
+    ///
+    /// If some nodes are not excluded, this yields the same candidates as `calc_candidates`.
+    /// Whether the fallback took effect can be checked by `FallbackCandidates::is_degraded`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash, ExclusionFallback};
+    ///
+    /// let nodes = vec![Node::new("foo").quantity(5), Node::new("bar").quantity(5)];
+    /// let mut ring = StaticHashRing::new(DefaultHash, nodes.into_iter());
+    /// ring.disable_node(&"foo");
+    /// ring.disable_node(&"bar");
+    ///
+    /// let candidates = ring.calc_candidates_with_fallback(&"aa", ExclusionFallback::Empty);
+    /// assert!(!candidates.is_degraded());
+    /// assert_eq!(candidates.count(), 0);
+    ///
+    /// let candidates = ring.calc_candidates_with_fallback(&"aa", ExclusionFallback::IgnoreExclusions);
+    /// assert!(candidates.is_degraded());
+    /// assert_eq!(candidates.map(|n| n.key).collect::<Vec<_>>(), ["bar", "foo"]);
+    ///
+    /// let candidates = ring.calc_candidates_with_fallback(&"aa", ExclusionFallback::Degraded);
+    /// assert_eq!(candidates.map(|n| n.key).collect::<Vec<_>>(), ["bar"]);
+    /// ```
+    pub fn calc_candidates_with_fallback<T: Hash + ?Sized>(&self,
+                                                           item: &T,
+                                                           fallback: ExclusionFallback)
+                                                           -> FallbackCandidates<'_, K, V> {
+        let item_hash = self.hash.hash_item(&item);
+        let started = self.observer.start();
+        let start = self.locate(item_hash);
+        let vnodes = self.candidate_vnodes(start);
+        let candidates = if vnodes.len() > 0 || fallback == ExclusionFallback::Empty {
+            FallbackCandidates {
+                candidates: Candidates(vnodes),
+                remaining: usize::MAX,
+                degraded: false,
+            }
+        } else {
+            FallbackCandidates {
+                candidates: Candidates(self.candidate_vnodes(start).include_excluded()),
+                remaining: if fallback == ExclusionFallback::Degraded { 1 } else { usize::MAX },
+                degraded: self.live_nodes > 0,
+            }
+        };
+        if let Some(started) = started {
+            self.notify_lookup(item_hash, candidates.candidates.0.clone().next(), started);
+        }
+        candidates
+    }
+
+    /// Replaces the clock of this ring with `clock`.
+    ///
+    /// The default clock is `SystemClock`.
+    pub fn set_clock<C>(&mut self, clock: C)
+        where C: Clock + Send + Sync + 'static
+    {
+        self.clock = Arc::new(clock);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+    use {DefaultHash, MockClock, Node, StaticHashRing};
+    use super::*;
+
+    #[test]
+    fn calc_candidates_with_fallback_works() {
+        let clock = MockClock::new(UNIX_EPOCH);
+        let mut ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+        ring.set_clock(clock.clone());
+        let all = ring.calc_candidates(&"foo").map(|n| n.key).collect::<Vec<_>>();
+        let keys = |ring: &StaticHashRing<_, _, _>, fallback| {
+            let candidates = ring.calc_candidates_with_fallback(&"foo", fallback);
+            (candidates.is_degraded(), candidates.map(|n| n.key).collect::<Vec<_>>())
+        };
+
+        // Some nodes remain.
+        ring.exclude_for(&all[0], Duration::from_secs(10));
+        ring.disable_node(&all[1]);
+        for &fallback in &[ExclusionFallback::Empty, ExclusionFallback::IgnoreExclusions, ExclusionFallback::Degraded] {
+            assert_eq!(keys(&ring, fallback), (false, vec![all[2]]));
+        }
+
+        // Every node is excluded (in different ways).
+        ring.schedule_removal(&all[2], UNIX_EPOCH);
+        assert_eq!(ring.calc_candidates(&"foo").count(), 0);
+        assert_eq!(keys(&ring, ExclusionFallback::default()), (false, vec![]));
+        assert_eq!(keys(&ring, ExclusionFallback::IgnoreExclusions),
+                   (true, vec![all[0], all[1], all[2]]));
+        assert_eq!(keys(&ring, ExclusionFallback::Degraded), (true, vec![all[0]]));
+        assert_eq!(ring.calc_candidates_with_fallback(&"foo", ExclusionFallback::Degraded).len(), 1);
+
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(keys(&ring, ExclusionFallback::Degraded), (false, vec![all[0]]));
+
+        let empty = StaticHashRing::new(DefaultHash, (0..0).map(Node::new));
+        assert_eq!(keys(&empty, ExclusionFallback::IgnoreExclusions), (false, vec![]));
+    }
+
+    #[cfg(feature = "observer")]
+    #[test]
+    fn calc_candidates_with_fallback_notifies_observer() {
+        use std::sync::Arc;
+        use SelectionCounters;
+
+        let clock = MockClock::new(UNIX_EPOCH);
+        let mut ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+        ring.set_clock(clock.clone());
+        let counters = Arc::new(SelectionCounters::new());
+        ring.set_observer(Some(counters.clone()));
+        let all = ring.calc_candidates(&"foo").map(|n| ring.node_id(&n.key).unwrap()).collect::<Vec<_>>();
+        counters.reset();
+
+        ring.calc_candidates_with_fallback(&"foo", ExclusionFallback::Empty);
+        assert_eq!(counters.lookups(all[0]), 1);
+
+        ring.exclude_for(&0, Duration::from_secs(10));
+        ring.exclude_for(&1, Duration::from_secs(10));
+        ring.exclude_for(&2, Duration::from_secs(10));
+        ring.calc_candidates_with_fallback(&"foo", ExclusionFallback::Degraded);
+        ring.calc_candidates_with_fallback(&"foo", ExclusionFallback::Empty);
+        assert_eq!(counters.lookups(all[0]), 2);
+        assert_eq!(counters.lookups(all[1]) + counters.lookups(all[2]), 0);
+    }
+
+    #[test]
+    fn exclusion_works() {
+        let clock = MockClock::new(UNIX_EPOCH);
+        let mut ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+        ring.set_clock(clock.clone());
+        let candidates = |ring: &StaticHashRing<_, _, _>| {
+            ring.calc_candidates(&"foo").map(|n| n.key).collect::<Vec<_>>()
+        };
+        let all = candidates(&ring);
+
+        assert!(ring.exclude_for(&all[0], Duration::from_secs(10)));
+        assert!(ring.exclude_until(&all[2], UNIX_EPOCH + Duration::from_secs(5)));
+        assert!(!ring.exclude_for(&3, Duration::from_secs(10)));
+        assert_eq!(candidates(&ring), [all[1]]);
+        assert_eq!(ring.calc_candidates_n(&"foo", 3).len(), 1);
+        assert_eq!(ring.calc_candidates_n(&"foo", 3).count(), 1);
+        assert_eq!(ring.take(&"foo").map(|t| t.node.key), Some(all[1]));
+
+        clock.advance(Duration::from_secs(5));
+        assert!(!ring.is_excluded(&all[2]));
+        let mut expired = candidates(&ring);
+        expired.sort();
+        let mut expected = vec![all[1], all[2]];
+        expected.sort();
+        assert_eq!(expired, expected);
+        assert_eq!(ring.clear_expired_exclusions(), 1);
+
+        assert!(ring.cancel_exclusion(&all[0]));
+        assert!(!ring.cancel_exclusion(&all[0]));
+        assert_eq!(ring.calc_candidates(&"foo").count(), 3);
+    }
+
+    #[test]
+    fn disable_node_works() {
+        let mut ring = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(10)));
+        let candidates = |ring: &StaticHashRing<_, _, _>, item| {
+            ring.calc_candidates(&item).map(|n| n.key).collect::<Vec<_>>()
+        };
+        let before = (0..100).map(|i| candidates(&ring, i)).collect::<Vec<_>>();
+
+        assert!(ring.disable_node(&1));
+        assert!(ring.disable_node(&1));
+        assert!(!ring.disable_node(&4));
+        assert!(ring.is_disabled(&1) && !ring.is_excluded(&1));
+        for (i, all) in before.iter().enumerate() {
+            assert_eq!(ring.calc_candidates(&i).len(), 3);
+            assert_eq!(candidates(&ring, i), all.iter().cloned().filter(|&k| k != 1).collect::<Vec<_>>());
+        }
+
+        assert!(ring.enable_node(&1));
+        assert!(!ring.enable_node(&1));
+        assert!(!ring.is_disabled(&1));
+        for (i, all) in before.iter().enumerate() {
+            assert_eq!(&candidates(&ring, i), all);
+        }
+
+        ring.disable_node(&1);
+        assert_ne!(ring.take(&"foo").map(|t| t.node.key), Some(1));
+        assert_eq!(ring.len(), 39);
+    }
+
+    #[test]
+    fn schedule_removal_works() {
+        let clock = MockClock::new(UNIX_EPOCH);
+        let mut ring = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(10)));
+        ring.set_clock(clock.clone());
+        let candidates = |ring: &StaticHashRing<_, _, _>, item| {
+            ring.calc_candidates(&item).map(|n| n.key).collect::<Vec<_>>()
+        };
+        let before = (0..100).map(|i| candidates(&ring, i)).collect::<Vec<_>>();
+
+        let deadline = UNIX_EPOCH + Duration::from_secs(10);
+        assert!(ring.schedule_removal(&2, deadline));
+        assert!(!ring.schedule_removal(&4, deadline));
+        assert_eq!(ring.removal_deadline(&2), Some(deadline));
+        for (i, all) in before.iter().enumerate() {
+            let mut expected = all.iter().cloned().filter(|&k| k != 2).collect::<Vec<_>>();
+            let position = all.iter().position(|&k| k == 2).unwrap().max(1);
+            expected.insert(position, 2);
+            assert_eq!(candidates(&ring, i), expected);
+        }
+
+        clock.advance(Duration::from_secs(10));
+        for (i, all) in before.iter().enumerate() {
+            assert_eq!(ring.calc_candidates(&i).len(), 3);
+            assert_eq!(candidates(&ring, i), all.iter().cloned().filter(|&k| k != 2).collect::<Vec<_>>());
+        }
+
+        assert!(ring.cancel_removal(&2));
+        assert!(!ring.cancel_removal(&2));
+        assert_eq!(ring.removal_deadline(&2), None);
+        for (i, all) in before.iter().enumerate() {
+            assert_eq!(&candidates(&ring, i), all);
+        }
+    }
+
+    #[test]
+    fn disabled_and_removed_nodes_own_no_ranges() {
+        let clock = MockClock::new(UNIX_EPOCH);
+        let mut ring = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(10)));
+        ring.set_clock(clock.clone());
+        ring.disable_node(&1);
+        ring.schedule_removal(&2, UNIX_EPOCH + Duration::from_secs(10));
+        for _ in 0..2 {
+            let ownership = ring.ownership_by(|n| n.key);
+            assert_eq!((ownership[&1], ownership[&2]), (0.0, 0.0));
+            assert!((ownership.values().sum::<f64>() - 1.0).abs() < 1e-9);
+            for key in 1..3 {
+                assert_eq!(ring.owned_ranges(&key).count(), 0);
+                assert!(ring.sample_keys_for(&key, 10, &mut || 1).is_empty());
+            }
+
+            let mut seed = 1u64;
+            let hashes = ring.sample_keys_for(&3, 100, &mut || {
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                seed
+            });
+            assert_eq!(hashes.len(), 100);
+            assert!(hashes.iter().all(|&h| ring.calc_node_by_hash(h).map(|n| n.key) == Some(3)));
+            clock.advance(Duration::from_secs(10));
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use std::hash::Hash;
+
+use {Node, RingHash, StaticHashRing};
+
+/// This trait allows packaging a replica fan-out behavior as a reusable policy.
+///
+/// A policy consumes a candidate sequence (e.g., `Candidates`) and selects the nodes to contact.
+///
+/// See `StaticHashRing::fan_out`.
+pub trait FanOut<'a, K: 'a, V: 'a> {
+    /// The selection made by this policy.
+    type Output;
+
+    /// Selects the nodes to contact from `candidates` (ordered by priority).
+    fn fan_out<I>(&self, candidates: I) -> Self::Output where I: Iterator<Item = &'a Node<K, V>>;
+}
+
+/// A policy which selects the first candidate satisfying the health predicate.
+#[derive(Debug, Clone, Copy)]
+pub struct FirstHealthy<F>(pub F);
+impl<'a, K: 'a, V: 'a, F> FanOut<'a, K, V> for FirstHealthy<F>
+    where F: Fn(&Node<K, V>) -> bool
+{
+    type Output = Option<&'a Node<K, V>>;
+    fn fan_out<I>(&self, mut candidates: I) -> Self::Output
+        where I: Iterator<Item = &'a Node<K, V>>
+    {
+        candidates.find(|n| (self.0)(n))
+    }
+}
+
+/// A policy which selects all of the first `k` candidates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TopK(pub usize);
+impl<'a, K: 'a, V: 'a> FanOut<'a, K, V> for TopK {
+    type Output = Vec<&'a Node<K, V>>;
+    fn fan_out<I>(&self, candidates: I) -> Self::Output
+        where I: Iterator<Item = &'a Node<K, V>>
+    {
+        candidates.take(self.0).collect()
+    }
+}
+
+/// A policy which selects the first `replicas` candidates,
+/// of which a majority must acknowledge an operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Quorum(pub usize);
+impl<'a, K: 'a, V: 'a> FanOut<'a, K, V> for Quorum {
+    type Output = QuorumSet<'a, K, V>;
+    fn fan_out<I>(&self, candidates: I) -> Self::Output
+        where I: Iterator<Item = &'a Node<K, V>>
+    {
+        QuorumSet {
+            nodes: candidates.take(self.0).collect(),
+            required: self.0 / 2 + 1,
+        }
+    }
+}
+
+/// The nodes selected by `Quorum`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuorumSet<'a, K: 'a, V: 'a> {
+    /// The nodes to contact (ordered by priority).
+    pub nodes: Vec<&'a Node<K, V>>,
+
+    /// The count of the acknowledgements required (i.e., the majority of the replicas).
+    pub required: usize,
+}
+impl<'a, K: 'a, V: 'a> QuorumSet<'a, K, V> {
+    /// Returns `true` if enough nodes are selected to reach the quorum.
+    pub fn is_attainable(&self) -> bool {
+        self.nodes.len() >= self.required
+    }
+}
+
+/// A policy which selects the first two candidates:
+/// the primary, and the secondary to which a hedge request is sent.
+///
+/// If there is only one candidate, the secondary is `None`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct HedgedPair;
+impl<'a, K: 'a, V: 'a> FanOut<'a, K, V> for HedgedPair {
+    type Output = Option<(&'a Node<K, V>, Option<&'a Node<K, V>>)>;
+    fn fan_out<I>(&self, mut candidates: I) -> Self::Output
+        where I: Iterator<Item = &'a Node<K, V>>
+    {
+        candidates.next().map(|primary| (primary, candidates.next()))
+    }
+}
+
+impl<K, V, H> StaticHashRing<K, V, H>
+    where H: RingHash
+{
+    /// Applies the fan-out `policy` to the candidates for `item`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash, FirstHealthy, Quorum, TopK};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..5).map(|i| Node::new(i).quantity(10)));
+    /// let candidates = ring.calc_candidates(&"foo").collect::<Vec<_>>();
+    ///
+    /// assert_eq!(ring.fan_out(&"foo", &TopK(2)), &candidates[..2]);
+    ///
+    /// let healthy = ring.fan_out(&"foo", &FirstHealthy(|n: &Node<i32, ()>| n.key != candidates[0].key));
+    /// assert_eq!(healthy, Some(candidates[1]));
+    ///
+    /// let quorum = ring.fan_out(&"foo", &Quorum(3));
+    /// assert_eq!(quorum.nodes, &candidates[..3]);
+    /// assert_eq!(quorum.required, 2);
+    /// ```
+    pub fn fan_out<'a, T, P>(&'a self, item: &T, policy: &P) -> P::Output
+        where T: Hash,
+              P: FanOut<'a, K, V>
+    {
+        policy.fan_out(self.calc_candidates(item))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {DefaultHash, Node, StaticHashRing};
+    use super::*;
+
+    #[test]
+    fn fan_out_works() {
+        let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+        for item in 0..100 {
+            let candidates = ring.calc_candidates(&item).collect::<Vec<_>>();
+
+            assert_eq!(ring.fan_out(&item, &TopK(5)), candidates);
+            assert_eq!(ring.fan_out(&item, &FirstHealthy(|_: &Node<i32, ()>| false)), None);
+            assert_eq!(ring.fan_out(&item, &HedgedPair),
+                       Some((candidates[0], Some(candidates[1]))));
+
+            let quorum = ring.fan_out(&item, &Quorum(5));
+            assert_eq!(quorum.nodes, candidates);
+            assert_eq!(quorum.required, 3);
+            assert!(quorum.is_attainable());
+            assert!(!Quorum(7).fan_out(ring.calc_candidates(&item)).is_attainable());
+        }
+
+        let single = StaticHashRing::new(DefaultHash, (0..1).map(|i| Node::new(i).quantity(10)));
+        assert_eq!(single.fan_out(&"foo", &HedgedPair).map(|(_, s)| s), Some(None));
+        let empty = StaticHashRing::new(DefaultHash, (0..0).map(Node::new));
+        assert_eq!(empty.fan_out(&"foo", &HedgedPair), None);
+    }
+}
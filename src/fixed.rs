@@ -0,0 +1,219 @@
+use std::hash::Hash;
+
+use {Node, RingHash};
+
+/// A hash ring whose capacity is fixed at compile time, and which never allocates heap memory.
+///
+/// At most `MAX_VNODES` virtual nodes (and therefore at most `MAX_VNODES` real nodes) can be contained.
+/// Items are placed in the same way as `StaticHashRing` (except that replica-only nodes are not supported),
+/// so this is suitable for embedded environments whose nodes are few and known in advance.
+///
+/// # Examples
+///
+/// ```
+/// use consistent_hash::{Node, FixedRing, DefaultHash};
+///
+/// let mut ring: FixedRing<_, _, _, 8> = FixedRing::new(DefaultHash);
+/// assert!(ring.try_insert_node(Node::new("foo").quantity(4)).is_ok());
+/// assert!(ring.try_insert_node(Node::new("bar").quantity(4)).is_ok());
+///
+/// // The ring is full.
+/// assert!(ring.try_insert_node(Node::new("baz")).is_err());
+///
+/// let candidates = ring.calc_candidates(&"item").map(|n| n.key).collect::<Vec<_>>();
+/// assert_eq!(candidates.len(), 2);
+/// assert_eq!(ring.calc_candidate(&"item").map(|n| n.key), Some(candidates[0]));
+/// ```
+#[derive(Debug, Clone)]
+pub struct FixedRing<K, V, H, const MAX_VNODES: usize> {
+    hash: H,
+    nodes: [Option<Node<K, V>>; MAX_VNODES],
+    node_count: usize,
+
+    // The pairs of the hash codes and the node indices, sorted by the hash codes (and the node keys).
+    vnodes: [(u64, usize); MAX_VNODES],
+    vnode_count: usize,
+}
+impl<K, V, H, const MAX_VNODES: usize> FixedRing<K, V, H, MAX_VNODES>
+    where K: Hash + Ord,
+          H: RingHash
+{
+    /// Makes a new empty `FixedRing` instance.
+    pub fn new(hash: H) -> Self {
+        FixedRing {
+            hash,
+            nodes: ::std::array::from_fn(|_| None),
+            node_count: 0,
+            vnodes: [(0, 0); MAX_VNODES],
+            vnode_count: 0,
+        }
+    }
+
+    /// Inserts `node` into this ring.
+    ///
+    /// If a node which has the same key already exists,
+    /// or the count of the virtual nodes would exceed `MAX_VNODES`,
+    /// this returns `Err(node)` without changing this ring.
+    pub fn try_insert_node(&mut self, node: Node<K, V>) -> Result<(), Node<K, V>> {
+        if self.vnode_count + node.quantity > MAX_VNODES || self.node(&node.key).is_some() {
+            return Err(node);
+        }
+        let index = self.node_count;
+        self.nodes[index] = Some(node);
+        self.node_count += 1;
+        for seq in 0..self.nodes[index].as_ref().expect("Never fails").quantity {
+            let key = self.key_at(index);
+            let hash = self.hash.hash_vnode(key, seq);
+            let position = self.vnodes[..self.vnode_count]
+                .iter()
+                .position(|&(h, i)| (h, self.key_at(i)) > (hash, key))
+                .unwrap_or(self.vnode_count);
+            self.vnodes.copy_within(position..self.vnode_count, position + 1);
+            self.vnodes[position] = (hash, index);
+            self.vnode_count += 1;
+        }
+        Ok(())
+    }
+
+    /// Removes the node identified by `key` from this ring.
+    ///
+    /// The indices of the nodes which follow the removed one are shifted.
+    pub fn remove_node(&mut self, key: &K) -> Option<Node<K, V>> {
+        let index = self.nodes().position(|n| n.key == *key)?;
+        let mut kept = 0;
+        for i in 0..self.vnode_count {
+            let (h, j) = self.vnodes[i];
+            if j != index {
+                self.vnodes[kept] = (h, if j > index { j - 1 } else { j });
+                kept += 1;
+            }
+        }
+        self.vnode_count = kept;
+
+        let node = self.nodes[index].take();
+        self.nodes[index..self.node_count].rotate_left(1);
+        self.node_count -= 1;
+        node
+    }
+
+    /// Returns the node identified by `key`.
+    pub fn node(&self, key: &K) -> Option<&Node<K, V>> {
+        self.nodes().find(|n| n.key == *key)
+    }
+
+    /// Returns the highest priority node for `item`.
+    ///
+    /// If this ring is empty, this returns `None`.
+    pub fn calc_candidate<T: Hash>(&self, item: &T) -> Option<&Node<K, V>> {
+        self.calc_candidates(item).next()
+    }
+
+    /// Returns the candidate nodes for `item`.
+    ///
+    /// The higher priority node is placed in front of the resulting sequence.
+    pub fn calc_candidates<T: Hash>(&self, item: &T) -> FixedCandidates<'_, K, V, H, MAX_VNODES> {
+        let item_hash = self.hash.hash_item(item);
+        let start = self.vnodes[..self.vnode_count]
+            .iter()
+            .position(|&(h, _)| h >= item_hash)
+            .unwrap_or(0);
+        FixedCandidates {
+            ring: self,
+            start,
+            offset: 0,
+        }
+    }
+
+    fn key_at(&self, index: usize) -> &K {
+        &self.nodes[index].as_ref().expect("Never fails").key
+    }
+}
+impl<K, V, H, const MAX_VNODES: usize> FixedRing<K, V, H, MAX_VNODES> {
+    /// Returns the nodes contained in this ring (in insertion order).
+    pub fn nodes(&self) -> impl Iterator<Item = &Node<K, V>> {
+        self.nodes[..self.node_count].iter().map(|n| n.as_ref().expect("Never fails"))
+    }
+
+    /// Returns the count of the real nodes contained in this ring.
+    pub fn len(&self) -> usize {
+        self.node_count
+    }
+
+    /// Returns `true` if this ring contains no nodes.
+    pub fn is_empty(&self) -> bool {
+        self.node_count == 0
+    }
+
+    /// Returns the count of the virtual nodes contained in this ring.
+    pub fn vnode_count(&self) -> usize {
+        self.vnode_count
+    }
+
+    /// Returns the maximum count of the virtual nodes (i.e., `MAX_VNODES`).
+    pub fn capacity(&self) -> usize {
+        MAX_VNODES
+    }
+}
+
+/// An iterator which represents a sequence of the candidate nodes for an item.
+///
+/// This is created by calling `FixedRing::calc_candidates` method.
+#[derive(Debug)]
+pub struct FixedCandidates<'a, K: 'a, V: 'a, H: 'a, const MAX_VNODES: usize> {
+    ring: &'a FixedRing<K, V, H, MAX_VNODES>,
+    start: usize,
+    offset: usize,
+}
+impl<'a, K: 'a, V: 'a, H: 'a, const MAX_VNODES: usize> Iterator for FixedCandidates<'a, K, V, H, MAX_VNODES> {
+    type Item = &'a Node<K, V>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let vnodes = &self.ring.vnodes[..self.ring.vnode_count];
+        let start = self.start;
+        let at = |offset: usize| vnodes[(start + offset) % vnodes.len()].1;
+        while self.offset < vnodes.len() {
+            let index = at(self.offset);
+            let seen = (0..self.offset).any(|o| at(o) == index);
+            self.offset += 1;
+            if !seen {
+                return self.ring.nodes[index].as_ref();
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {DefaultHash, Node, StaticHashRing};
+    use super::*;
+
+    #[test]
+    fn fixed_ring_works() {
+        let mut ring: FixedRing<_, _, _, 64> = FixedRing::new(DefaultHash);
+        for i in 0..5 {
+            assert!(ring.try_insert_node(Node::new(i).quantity(12)).is_ok());
+        }
+        assert!(ring.try_insert_node(Node::new(5).quantity(5)).is_err());
+        assert!(ring.try_insert_node(Node::new(0).quantity(1)).is_err());
+        assert_eq!(ring.len(), 5);
+        assert_eq!(ring.vnode_count(), 60);
+
+        // The placement is the same as `StaticHashRing`.
+        let expected = StaticHashRing::new(DefaultHash, (0..5).map(|i| Node::new(i).quantity(12)));
+        for item in 0..100 {
+            assert!(ring.calc_candidates(&item).map(|n| n.key).eq(expected.calc_candidates(&item).map(|n| n.key)));
+        }
+
+        assert_eq!(ring.remove_node(&2).map(|n| n.key), Some(2));
+        assert_eq!(ring.remove_node(&2), None);
+        assert_eq!(ring.vnode_count(), 48);
+        let expected = StaticHashRing::new(DefaultHash, [0, 1, 3, 4].iter().map(|&i| Node::new(i).quantity(12)));
+        for item in 0..100 {
+            assert!(ring.calc_candidates(&item).map(|n| n.key).eq(expected.calc_candidates(&item).map(|n| n.key)));
+        }
+
+        let empty: FixedRing<i32, (), _, 4> = FixedRing::new(DefaultHash);
+        assert!(empty.is_empty());
+        assert_eq!(empty.calc_candidate(&"foo"), None);
+    }
+}
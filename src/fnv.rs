@@ -0,0 +1,116 @@
+use std::hash::{Hash, Hasher};
+
+use {DefaultHash, RingHash};
+
+const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const PRIME: u64 = 0x0100_0000_01b3;
+
+/// A `RingHash` implementation which uses `FNV-1a` (64-bit).
+///
+/// This is much faster than `DefaultHash` for short items, but it is not resistant to hash flooding.
+///
+/// This is available if the `fnv` feature is enabled.
+#[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub struct Fnv;
+impl RingHash for Fnv {
+    fn hash_item<T: Hash>(&self, item: &T) -> u64 {
+        let mut hasher = FnvHasher(OFFSET_BASIS);
+        item.hash(&mut hasher);
+        hasher.finish()
+    }
+    fn algorithm_id(&self) -> &str {
+        "fnv1a64"
+    }
+}
+impl Fnv {
+    /// Makes a `FNV-1a` hasher which uses `seed`.
+    ///
+    /// The seed is digested before every item, so rings built with different seeds have independent layouts.
+    pub fn with_seed(seed: u64) -> SeededFnv {
+        let mut hasher = FnvHasher(OFFSET_BASIS);
+        hasher.write(&seed.to_le_bytes());
+        SeededFnv {
+            basis: hasher.finish(),
+            algorithm_id: format!("fnv1a64-seeded-{:016x}", DefaultHash.hash_item(&seed)),
+        }
+    }
+}
+
+/// A `RingHash` implementation which uses `FNV-1a` (64-bit) with a seed.
+///
+/// This is created by calling `Fnv::with_seed` function.
+///
+/// Note that a secret seed does not make `FNV-1a` resistant to hash flooding
+/// (use `DefaultHash::with_keys` for that purpose).
+///
+/// This is available if the `fnv` feature is enabled.
+#[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub struct SeededFnv {
+    basis: u64,
+    algorithm_id: String,
+}
+impl RingHash for SeededFnv {
+    fn hash_item<T: Hash>(&self, item: &T) -> u64 {
+        let mut hasher = FnvHasher(self.basis);
+        item.hash(&mut hasher);
+        hasher.finish()
+    }
+    fn algorithm_id(&self) -> &str {
+        &self.algorithm_id
+    }
+}
+
+struct FnvHasher(u64);
+impl Hasher for FnvHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= u64::from(b);
+            self.0 = self.0.wrapping_mul(PRIME);
+        }
+    }
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hash::Hasher;
+    use {Node, RingHash, StaticHashRing};
+    use super::*;
+
+    #[test]
+    fn fnv_works() {
+        let digest = |bytes: &[u8]| {
+            let mut hasher = FnvHasher(OFFSET_BASIS);
+            hasher.write(bytes);
+            hasher.finish()
+        };
+        assert_eq!(digest(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(digest(b"a"), 0xaf63_dc4c_8601_ec8c);
+
+        assert_eq!(Fnv.hash_item(&"foo"), Fnv.hash_item(&"foo"));
+        assert_eq!(Fnv.hash_bytes(b"a"), 0xaf63_dc4c_8601_ec8c);
+        let ring = StaticHashRing::new(Fnv, (0..3).map(|i| Node::new(i).quantity(10)));
+        assert_eq!(ring.format_tag(), "ring-v1-fnv1a64");
+        assert_eq!(ring.calc_candidates(&"foo").count(), 3);
+    }
+
+    #[test]
+    fn seeded_fnv_works() {
+        let digest = |seed: u64, bytes: &[u8]| {
+            let mut hasher = FnvHasher(OFFSET_BASIS);
+            hasher.write(&seed.to_le_bytes());
+            hasher.write(bytes);
+            hasher.finish()
+        };
+        let mut hasher = FnvHasher(Fnv::with_seed(7).basis);
+        hasher.write(b"a");
+        assert_eq!(hasher.finish(), digest(7, b"a"));
+
+        assert_eq!(Fnv::with_seed(1).hash_item(&"foo"), Fnv::with_seed(1).hash_item(&"foo"));
+        assert_ne!(Fnv::with_seed(1).hash_item(&"foo"), Fnv::with_seed(2).hash_item(&"foo"));
+        let ring = StaticHashRing::new(Fnv::with_seed(1), (0..3).map(|i| Node::new(i).quantity(10)));
+        assert!(ring.format_tag().starts_with("ring-v1-fnv1a64-seeded-"));
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::btree_map;
+use std::collections::BTreeMap;
+
+use {Node, StaticHashRing};
+
+/// A view of the nodes of a ring grouped by a key (e.g., zone or tag).
+///
+/// Groups are built once on the stable node indices (see `StaticHashRing::node_index`),
+/// so they can be queried repeatedly without regrouping.
+///
+/// This is created by calling `StaticHashRing::nodes_by` method.
+#[derive(Debug, Clone)]
+pub struct NodeGroups<'a, K: 'a, V: 'a, G> {
+    nodes: &'a [Node<K, V>],
+    groups: BTreeMap<G, Vec<usize>>,
+}
+impl<'a, K: 'a, V: 'a, G: Ord> NodeGroups<'a, K, V, G> {
+    /// Returns the indices of the nodes which belong to `group` (in ascending order).
+    ///
+    /// If there are no such nodes, this returns an empty slice.
+    pub fn indices(&self, group: &G) -> &[usize] {
+        self.groups.get(group).map_or(&[], |v| &v[..])
+    }
+
+    /// Returns an iterator over the nodes which belong to `group`.
+    pub fn get(&self, group: &G) -> impl Iterator<Item = &'a Node<K, V>> + '_ {
+        let nodes = self.nodes;
+        self.indices(group).iter().map(move |&i| &nodes[i])
+    }
+
+    /// Returns an iterator over the groups and the indices of their nodes (in group order).
+    pub fn iter(&self) -> btree_map::Iter<'_, G, Vec<usize>> {
+        self.groups.iter()
+    }
+
+    /// Returns the count of the groups.
+    pub fn len(&self) -> usize {
+        self.groups.len()
+    }
+
+    /// Returns `true` if there are no groups (i.e., the ring has no nodes), otherwise `false`.
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+}
+
+impl<K, V, H> StaticHashRing<K, V, H> {
+    /// Groups the nodes of this ring by `group_of`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// // The values of nodes are their zones.
+    /// let nodes = (0..5).map(|i| Node::new(i).value(if i < 3 { "a" } else { "b" }));
+    /// let ring = StaticHashRing::new(DefaultHash, nodes);
+    ///
+    /// let zones = ring.nodes_by(|n| n.value);
+    /// assert_eq!(zones.len(), 2);
+    /// assert_eq!(zones.indices(&"a"), [0, 1, 2]);
+    /// assert_eq!(zones.get(&"b").map(|n| n.key).collect::<Vec<_>>(), [3, 4]);
+    /// assert!(zones.indices(&"c").is_empty());
+    /// ```
+    pub fn nodes_by<G, F>(&self, group_of: F) -> NodeGroups<'_, K, V, G>
+        where G: Ord,
+              F: Fn(&Node<K, V>) -> G
+    {
+        let mut groups = BTreeMap::new();
+        for (i, node) in self.nodes.iter().enumerate() {
+            groups.entry(group_of(node)).or_insert_with(Vec::new).push(i);
+        }
+        NodeGroups {
+            nodes: &self.nodes,
+            groups,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {DefaultHash, Node, StaticHashRing};
+
+    #[test]
+    fn nodes_by_works() {
+        let ring = StaticHashRing::new(DefaultHash, (0..10).map(|i| Node::new(i).value(i % 3)));
+        let groups = ring.nodes_by(|n| n.value);
+        assert_eq!(groups.iter().map(|(&g, v)| (g, v.len())).collect::<Vec<_>>(),
+                   [(0, 4), (1, 3), (2, 3)]);
+        for (g, indices) in groups.iter() {
+            assert!(indices.iter().all(|&i| ring.nodes()[i].value == *g));
+            assert!(groups.get(g).all(|n| n.value == *g));
+        }
+
+        let empty = StaticHashRing::new(DefaultHash, (0..0).map(Node::new));
+        assert!(empty.nodes_by(|n| n.key).is_empty());
+    }
+}
@@ -0,0 +1,122 @@
+use std::hash::Hash;
+
+use RingHash;
+
+/// A `RingHash` adapter which truncates hash codes to 32 bits.
+///
+/// The ring of this hash behaves as a 32-bit circle (items whose hash codes exceed the last virtual node
+/// wrap around to the first one), which is compatible with the implementations in other languages
+/// that use `u32` hash codes (e.g., twemproxy and older ketama clients).
+/// The virtual nodes are hashed by `vnode_hash`, and the items are hashed by `item_hash`,
+/// since such implementations often use different algorithms for them.
+///
+/// Their item hashes usually digest raw bytes, so items should be looked up by
+/// `StaticHashRing::calc_candidates_bytes` (`Hash` implementations of `str` and the like append extra bytes).
+///
+/// Note that the fractions of the hash space (e.g., `StaticHashRing::ownership`) are still
+/// relative to the 64-bit space.
+///
+/// # Examples
+///
+/// ```
+/// use consistent_hash::{Hash32, KetamaHash, Node, RingHash, StaticHashRing, KETAMA_POINTS_PER_SERVER};
+///
+/// // The `ketama` distribution of twemproxy with `hash: md5`.
+/// let hash = Hash32::with_item_hash(KetamaHash, KetamaHash);
+/// assert!(hash.hash_bytes(b"foo") <= u64::from(u32::MAX));
+///
+/// let nodes = vec!["10.0.0.1:11211", "10.0.0.2:11211", "10.0.0.3:11211"]
+///     .into_iter()
+///     .map(|s| Node::new(s).quantity(KETAMA_POINTS_PER_SERVER));
+/// let ring = StaticHashRing::new(hash, nodes);
+/// assert_eq!(ring.calc_candidates_bytes(b"foo").next().map(|n| n.key), Some("10.0.0.3:11211"));
+/// ```
+#[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub struct Hash32<H, I = H> {
+    vnode_hash: H,
+    item_hash: I,
+    algorithm_id: String,
+}
+impl<H> Hash32<H>
+    where H: RingHash + Clone
+{
+    /// Makes a new `Hash32` instance which uses `hash` for both virtual nodes and items.
+    pub fn new(hash: H) -> Self {
+        Self::with_item_hash(hash.clone(), hash)
+    }
+}
+impl<H, I> Hash32<H, I>
+    where H: RingHash,
+          I: RingHash
+{
+    /// Makes a new `Hash32` instance which uses `vnode_hash` for virtual nodes and `item_hash` for items.
+    pub fn with_item_hash(vnode_hash: H, item_hash: I) -> Self {
+        let algorithm_id = if vnode_hash.algorithm_id() == item_hash.algorithm_id() {
+            format!("{}-32", vnode_hash.algorithm_id())
+        } else {
+            format!("{}+{}-32", vnode_hash.algorithm_id(), item_hash.algorithm_id())
+        };
+        Hash32 {
+            vnode_hash,
+            item_hash,
+            algorithm_id,
+        }
+    }
+}
+impl<H, I> RingHash for Hash32<H, I>
+    where H: RingHash,
+          I: RingHash
+{
+    fn hash_item<T: Hash>(&self, item: &T) -> u64 {
+        truncate(self.item_hash.hash_item(item))
+    }
+    fn hash_vnode<K: Hash>(&self, node_key: &K, vnode_seq: usize) -> u64 {
+        truncate(self.vnode_hash.hash_vnode(node_key, vnode_seq))
+    }
+    fn hash_bytes(&self, bytes: &[u8]) -> u64 {
+        truncate(self.item_hash.hash_bytes(bytes))
+    }
+    fn algorithm_id(&self) -> &str {
+        &self.algorithm_id
+    }
+}
+
+fn truncate(hash: u64) -> u64 {
+    u64::from(hash as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use {DefaultHash, KetamaHash, Node, RingHash, StaticHashRing};
+    use super::*;
+
+    #[test]
+    fn hash32_works() {
+        let hash = Hash32::new(DefaultHash);
+        assert_eq!(hash.hash_item(&"foo"), DefaultHash.hash_item(&"foo") & 0xFFFF_FFFF);
+        assert_eq!(hash.hash_vnode(&"foo", 3), DefaultHash.hash_vnode(&"foo", 3) & 0xFFFF_FFFF);
+        assert_eq!(hash.algorithm_id(), "siphash13-32");
+        assert_eq!(Hash32::with_item_hash(KetamaHash, DefaultHash).algorithm_id(), "ketama+siphash13-32");
+
+        // A 32-bit circle: the items beyond the last virtual node wrap around to the first one.
+        let ring = StaticHashRing::new(hash.clone(), (0..4).map(|i| Node::new(i).quantity(10)));
+        let mut points = ring.vnodes().map(|(h, n, _)| (h, n.key)).collect::<Vec<_>>();
+        points.sort();
+        assert!(points.iter().all(|&(h, _)| h <= u64::from(u32::MAX)));
+        for item in 0..1000 {
+            let h = hash.hash_item(&item) as u32;
+            let expected = points.iter().find(|p| p.0 >= u64::from(h)).unwrap_or(&points[0]).1;
+            assert_eq!(ring.calc_node(&item).map(|n| n.key), Some(expected));
+        }
+    }
+
+    #[cfg(feature = "fnv")]
+    #[test]
+    fn hash32_matches_twemproxy_fnv1a_64() {
+        use Fnv;
+
+        // twemproxy's `fnv1a_64` is calculated with 32-bit arithmetic.
+        assert_eq!(Hash32::new(Fnv).hash_bytes(b"foo"), 0xfed9_d577);
+        assert_eq!(Hash32::new(Fnv).hash_bytes(b""), 0x8422_2325);
+    }
+}
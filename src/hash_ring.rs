@@ -0,0 +1,186 @@
+use std::hash::Hash;
+use std::slice;
+
+use {Candidates, MaglevCandidates, MaglevRing, MultiProbeCandidates, MultiProbeRing, Node, RawBytes,
+     RendezvousCandidates, RendezvousRing, RingHash, StaticHashRing};
+
+/// This trait provides the lookup operations which are common to the ring types of this crate.
+///
+/// Code which accepts `R: HashRing<K, V>` works with any of `StaticHashRing`, `RendezvousRing`,
+/// `MaglevRing`, and `MultiProbeRing`, so the algorithm can be swapped without changing it.
+/// See `DynHashRing` for trait objects.
+///
+/// # Examples
+///
+/// ```
+/// use consistent_hash::{DefaultHash, HashRing, MaglevRing, Node, StaticHashRing};
+///
+/// fn route<R: HashRing<&'static str, ()>>(ring: &R, item: &str) -> &'static str {
+///     ring.calc_node(&item).map(|n| n.key).expect("no nodes")
+/// }
+///
+/// let nodes = vec![Node::new("foo"), Node::new("bar"), Node::new("baz")];
+/// let ring = StaticHashRing::new(DefaultHash, nodes.clone().into_iter());
+/// let maglev = MaglevRing::new(DefaultHash, nodes.into_iter());
+/// assert!(["foo", "bar", "baz"].contains(&route(&ring, "item")));
+/// assert!(["foo", "bar", "baz"].contains(&route(&maglev, "item")));
+/// ```
+pub trait HashRing<K, V> {
+    /// The iterator of the candidate nodes of an item.
+    type Candidates<'a>: Iterator<Item = &'a Node<K, V>> where Self: 'a, K: 'a, V: 'a;
+
+    /// The iterator of the nodes of a ring.
+    type Nodes<'a>: Iterator<Item = &'a Node<K, V>> where Self: 'a, K: 'a, V: 'a;
+
+    /// Returns the candidate nodes for `item`.
+    ///
+    /// The higher priority node is located in front of the returned candidate sequence.
+    fn calc_candidates<T: Hash>(&self, item: &T) -> Self::Candidates<'_>;
+
+    /// Returns the node which has the highest priority for `item`.
+    ///
+    /// If the ring is empty, this returns `None`.
+    fn calc_node<T: Hash>(&self, item: &T) -> Option<&Node<K, V>> {
+        self.calc_candidates(item).next()
+    }
+
+    /// Returns the nodes of the ring.
+    fn nodes(&self) -> Self::Nodes<'_>;
+
+    /// Returns the count of the nodes of the ring.
+    fn node_count(&self) -> usize {
+        self.nodes().count()
+    }
+}
+
+/// The object-safe subset of `HashRing`.
+///
+/// Items are given as byte strings (hashed as `RingHash::hash_bytes` does),
+/// since `Hash` items cannot be passed to trait objects.
+/// This is implemented for any `HashRing` implementations.
+///
+/// # Examples
+///
+/// ```
+/// use consistent_hash::{DefaultHash, DynHashRing, Node, RendezvousRing, StaticHashRing};
+///
+/// let nodes = vec![Node::new("foo"), Node::new("bar"), Node::new("baz")];
+/// let rings: Vec<Box<dyn DynHashRing<&str, ()>>> = vec![
+///     Box::new(StaticHashRing::new(DefaultHash, nodes.clone().into_iter())),
+///     Box::new(RendezvousRing::new(DefaultHash, nodes.into_iter())),
+/// ];
+/// for ring in &rings {
+///     assert_eq!(ring.node_count(), 3);
+///     assert_eq!(ring.calc_candidates_bytes(b"item").count(), 3);
+/// }
+/// ```
+pub trait DynHashRing<K, V> {
+    /// Returns the candidate nodes for the item whose bytes are `item`.
+    fn calc_candidates_bytes<'a>(&'a self, item: &[u8]) -> Box<dyn Iterator<Item = &'a Node<K, V>> + 'a>;
+
+    /// Returns the node which has the highest priority for the item whose bytes are `item`.
+    fn calc_node_bytes(&self, item: &[u8]) -> Option<&Node<K, V>>;
+
+    /// Returns the nodes of the ring.
+    fn nodes<'a>(&'a self) -> Box<dyn Iterator<Item = &'a Node<K, V>> + 'a>;
+
+    /// Returns the count of the nodes of the ring.
+    fn node_count(&self) -> usize;
+}
+impl<K, V, R> DynHashRing<K, V> for R
+    where R: HashRing<K, V>
+{
+    fn calc_candidates_bytes<'a>(&'a self, item: &[u8]) -> Box<dyn Iterator<Item = &'a Node<K, V>> + 'a> {
+        Box::new(HashRing::calc_candidates(self, &RawBytes(item)))
+    }
+    fn calc_node_bytes(&self, item: &[u8]) -> Option<&Node<K, V>> {
+        HashRing::calc_node(self, &RawBytes(item))
+    }
+    fn nodes<'a>(&'a self) -> Box<dyn Iterator<Item = &'a Node<K, V>> + 'a> {
+        Box::new(HashRing::nodes(self))
+    }
+    fn node_count(&self) -> usize {
+        HashRing::node_count(self)
+    }
+}
+
+impl<K, V, H> HashRing<K, V> for StaticHashRing<K, V, H>
+    where H: RingHash
+{
+    type Candidates<'a> = Candidates<'a, K, V> where Self: 'a, K: 'a, V: 'a;
+    type Nodes<'a> = slice::Iter<'a, Node<K, V>> where Self: 'a, K: 'a, V: 'a;
+    fn calc_candidates<T: Hash>(&self, item: &T) -> Self::Candidates<'_> {
+        StaticHashRing::calc_candidates(self, item)
+    }
+    fn calc_node<T: Hash>(&self, item: &T) -> Option<&Node<K, V>> {
+        StaticHashRing::calc_node(self, item)
+    }
+    fn nodes(&self) -> Self::Nodes<'_> {
+        StaticHashRing::nodes(self).iter()
+    }
+    fn node_count(&self) -> usize {
+        StaticHashRing::nodes(self).len()
+    }
+}
+
+macro_rules! impl_hash_ring {
+    ($ring:ident, $candidates:ident) => {
+        impl<K, V, H> HashRing<K, V> for $ring<K, V, H>
+            where K: Hash + Eq + Ord,
+                  H: RingHash
+        {
+            type Candidates<'a> = $candidates<'a, K, V> where Self: 'a, K: 'a, V: 'a;
+            type Nodes<'a> = slice::Iter<'a, Node<K, V>> where Self: 'a, K: 'a, V: 'a;
+            fn calc_candidates<T: Hash>(&self, item: &T) -> Self::Candidates<'_> {
+                $ring::calc_candidates(self, item)
+            }
+            fn calc_node<T: Hash>(&self, item: &T) -> Option<&Node<K, V>> {
+                $ring::calc_candidate(self, item)
+            }
+            fn nodes(&self) -> Self::Nodes<'_> {
+                $ring::nodes(self).iter()
+            }
+            fn node_count(&self) -> usize {
+                $ring::nodes(self).len()
+            }
+        }
+    };
+}
+impl_hash_ring!(RendezvousRing, RendezvousCandidates);
+impl_hash_ring!(MaglevRing, MaglevCandidates);
+impl_hash_ring!(MultiProbeRing, MultiProbeCandidates);
+
+#[cfg(test)]
+mod tests {
+    use {DefaultHash, MaglevRing, MultiProbeRing, Node, RendezvousRing, RingHash, StaticHashRing};
+    use super::*;
+
+    fn check<R: HashRing<u32, ()>>(ring: &R) {
+        assert_eq!(ring.node_count(), 4);
+        assert_eq!(HashRing::nodes(ring).map(|n| n.key).collect::<Vec<_>>(), [0, 1, 2, 3]);
+        for item in 0..50 {
+            let mut candidates = HashRing::calc_candidates(ring, &item).map(|n| n.key).collect::<Vec<_>>();
+            assert_eq!(HashRing::calc_node(ring, &item).map(|n| n.key), candidates.first().cloned());
+            candidates.sort();
+            assert_eq!(candidates, [0, 1, 2, 3]);
+        }
+
+        let ring: &dyn DynHashRing<u32, ()> = ring;
+        assert_eq!(ring.node_count(), 4);
+        assert_eq!(ring.calc_node_bytes(b"foo"), ring.calc_candidates_bytes(b"foo").next());
+    }
+
+    #[test]
+    fn hash_ring_works() {
+        let nodes = (0..4).map(|i| Node::new(i).quantity(10)).collect::<Vec<_>>();
+        let ring = StaticHashRing::new(DefaultHash, nodes.clone().into_iter());
+        check(&ring);
+        check(&RendezvousRing::new(DefaultHash, nodes.clone().into_iter()));
+        check(&MaglevRing::new(DefaultHash, nodes.clone().into_iter()));
+        check(&MultiProbeRing::new(DefaultHash, nodes.into_iter()));
+
+        // Byte items are placed as `RingHash::hash_bytes` does.
+        let item_hash = DefaultHash.hash_bytes(b"foo");
+        assert_eq!(DynHashRing::calc_node_bytes(&ring, b"foo"), ring.calc_node_by_hash(item_hash));
+    }
+}
@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::{Duration, SystemTime};
+
+use weight::allocate;
+use {Clock, Node, RingHash, StaticHashRing};
+
+/// A filter which suppresses small or frequent changes of the quantities of nodes.
+///
+/// A proposed change of a quantity is accepted only if it is at least `min_change` (relative to the current quantity),
+/// and the quantity of the node has not been changed within `cooldown`.
+/// Otherwise the current quantity is kept, and the change is counted as suppressed.
+///
+/// This is intended for rings whose quantities are periodically derived from live metrics
+/// (see `StaticHashRing::reweight_by`), so fluctuations of the metrics do not cause continuous key movement.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::{Duration, UNIX_EPOCH};
+/// use consistent_hash::{MockClock, QuantityHysteresis};
+///
+/// let clock = MockClock::new(UNIX_EPOCH);
+/// let mut hysteresis = QuantityHysteresis::new(0.1, Duration::from_secs(60), &clock);
+///
+/// assert_eq!(hysteresis.stabilize(&"foo", 100, 105), 100); // too small
+/// assert_eq!(hysteresis.stabilize(&"foo", 100, 120), 120);
+/// assert_eq!(hysteresis.stabilize(&"foo", 120, 200), 120); // cooling down
+///
+/// clock.advance(Duration::from_secs(60));
+/// assert_eq!(hysteresis.stabilize(&"foo", 120, 200), 200);
+/// assert_eq!(hysteresis.suppressed_changes(), 2);
+/// ```
+#[derive(Debug, Clone)]
+pub struct QuantityHysteresis<K, C> {
+    min_change: f64,
+    cooldown: Duration,
+    clock: C,
+    last_changes: HashMap<K, SystemTime>,
+    suppressed: usize,
+}
+impl<K, C> QuantityHysteresis<K, C>
+    where K: Hash + Eq + Clone,
+          C: Clock
+{
+    /// Makes a new `QuantityHysteresis` instance.
+    ///
+    /// `min_change` is the minimum relative change (e.g., `0.1` for 10%) of an acceptable change.
+    ///
+    /// # Panics
+    ///
+    /// If `min_change` is negative (or not a number), this function will panic.
+    pub fn new(min_change: f64, cooldown: Duration, clock: C) -> Self {
+        assert!(min_change >= 0.0, "min_change must be non-negative: {}", min_change);
+        QuantityHysteresis {
+            min_change,
+            cooldown,
+            clock,
+            last_changes: HashMap::new(),
+            suppressed: 0,
+        }
+    }
+
+    /// Returns the quantity of the node `key` to be applied, given its `current` and `proposed` quantities.
+    ///
+    /// Changes from or to zero quantities (e.g., joining or draining nodes) are never regarded as small,
+    /// but they are subject to the cooldown.
+    pub fn stabilize(&mut self, key: &K, current: usize, proposed: usize) -> usize {
+        if current == proposed {
+            return current;
+        }
+        let now = self.clock.now();
+        let delta = (proposed as f64 - current as f64).abs();
+        let small = current > 0 && proposed > 0 && delta < self.min_change * current as f64;
+        let cooling = self.last_changes
+            .get(key)
+            .is_some_and(|&at| now.duration_since(at).map_or(true, |elapsed| elapsed < self.cooldown));
+        if small || cooling {
+            self.suppressed += 1;
+            current
+        } else {
+            self.last_changes.insert(key.clone(), now);
+            proposed
+        }
+    }
+
+    /// Returns the count of the changes suppressed so far.
+    pub fn suppressed_changes(&self) -> usize {
+        self.suppressed
+    }
+}
+
+impl<K, V, H> StaticHashRing<K, V, H>
+    where K: Hash + Eq + Ord + Clone,
+          V: Clone,
+          H: RingHash + Clone
+{
+    /// Recalculates the quantities of the nodes from the weights given by `weight_of` (as `with_weights_by` does),
+    /// and returns a rebuilt ring if any quantity is changed after filtering by `hysteresis`.
+    ///
+    /// If every change is suppressed, this returns `None` (i.e., this ring should be kept as is).
+    /// Note that the total count of the virtual nodes of the rebuilt ring may differ from `total_vnodes`
+    /// if some changes are suppressed.
+    ///
+    /// The node states (e.g., replica-only states and exclusions), the read policy, and the clock
+    /// are carried over to the rebuilt ring, but the virtual nodes removed by `take` (and the like) are not.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::{Duration, UNIX_EPOCH};
+    /// use consistent_hash::{MockClock, Node, QuantityHysteresis, StaticHashRing, DefaultHash};
+    ///
+    /// let clock = MockClock::new(UNIX_EPOCH);
+    /// let mut hysteresis = QuantityHysteresis::new(0.1, Duration::from_secs(60), &clock);
+    /// let ring = StaticHashRing::new(DefaultHash, (0..2).map(|i| Node::new(i).quantity(50)));
+    ///
+    /// // The weights fluctuate slightly.
+    /// assert!(ring.reweight_by(100, |n| if n.key == 0 { 1.05 } else { 0.95 }, &mut hysteresis).is_none());
+    ///
+    /// let ring = ring.reweight_by(100, |n| if n.key == 0 { 3.0 } else { 1.0 }, &mut hysteresis).unwrap();
+    /// assert_eq!(ring.nodes().iter().map(|n| n.quantity).collect::<Vec<_>>(), [75, 25]);
+    /// ```
+    pub fn reweight_by<F, C>(&self,
+                             total_vnodes: usize,
+                             weight_of: F,
+                             hysteresis: &mut QuantityHysteresis<K, C>)
+                             -> Option<Self>
+        where F: Fn(&Node<K, V>) -> f64,
+              C: Clock
+    {
+        let weights = self.nodes.iter().map(weight_of).collect::<Vec<_>>();
+        let mut nodes = self.nodes.clone();
+        let mut changed = false;
+        for (node, proposed) in nodes.iter_mut().zip(allocate(&weights, total_vnodes)) {
+            let quantity = hysteresis.stabilize(&node.key, node.quantity, proposed);
+            changed |= quantity != node.quantity;
+            node.quantity = quantity;
+        }
+        if !changed {
+            return None;
+        }
+
+        // Reuses the hash codes of the existing virtual nodes.
+        let mut cached = self.nodes.iter().map(|n| vec![0; n.quantity]).collect::<Vec<_>>();
+        for vn in self.ring.iter().chain(&self.taken) {
+            cached[vn.index][vn.seq] = vn.hash;
+        }
+        let cached = cached.into_iter()
+            .zip(&nodes)
+            .map(|(mut hashes, node)| {
+                hashes.truncate(node.quantity);
+                Some(hashes)
+            })
+            .collect::<Vec<_>>();
+
+        let mut ring = Self::from_sorted_nodes(self.hash.clone(), nodes, &cached);
+        ring.states = self.states.clone();
+        ring.excluded_nodes = self.excluded_nodes;
+        ring.disabled_nodes = self.disabled_nodes;
+        ring.removal_nodes = self.removal_nodes;
+        ring.read_policy = self.read_policy;
+        ring.clock = self.clock.clone();
+        Some(ring)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+    use {DefaultHash, MockClock, Node, StaticHashRing};
+    use super::*;
+
+    #[test]
+    fn reweight_by_works() {
+        let clock = MockClock::new(UNIX_EPOCH);
+        let mut hysteresis = QuantityHysteresis::new(0.2, Duration::from_secs(10), &clock);
+        let mut ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(40)));
+        ring.set_replica_only(&2, true);
+
+        // Node 0 grows enough, while the others change only a little.
+        let weights = [1.5, 1.1, 1.0];
+        let rebuilt = ring.reweight_by(120, |n| weights[n.key], &mut hysteresis).unwrap();
+        let quantities = rebuilt.nodes().iter().map(|n| n.quantity).collect::<Vec<_>>();
+        assert_eq!(quantities, [50, 40, 40]);
+        assert_eq!(hysteresis.suppressed_changes(), 2);
+        assert!(rebuilt.states[2].replica_only);
+        rebuilt.debug_assert_valid();
+
+        // The existing virtual nodes are kept.
+        let hashes = |ring: &StaticHashRing<usize, (), DefaultHash>, key| {
+            let mut hashes = ring.vnodes().filter(|v| v.1.key == key).map(|v| v.0).collect::<Vec<_>>();
+            hashes.sort();
+            hashes
+        };
+        assert_eq!(hashes(&ring, 1), hashes(&rebuilt, 1));
+        assert!(hashes(&ring, 0).iter().all(|h| hashes(&rebuilt, 0).contains(h)));
+
+        // Node 0 is cooling down.
+        assert!(rebuilt.reweight_by(120, |n| [1.0, 1.0, 1.0][n.key], &mut hysteresis).is_none());
+        clock.advance(Duration::from_secs(10));
+        let rebuilt = rebuilt.reweight_by(120, |n| [1.0, 1.0, 1.0][n.key], &mut hysteresis).unwrap();
+        assert_eq!(rebuilt.len(), 120);
+    }
+}
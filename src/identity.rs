@@ -0,0 +1,158 @@
+use std::error::Error;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use siphasher::sip::SipHasher13;
+
+use {HandoverToken, OwnershipProof, RingHash, RingSpec, StaticHashRing};
+
+/// This trait represents artifacts which belong to a logical ring (e.g., rings, specs, proofs, and tokens).
+///
+/// Artifacts are stamped with the identifier of the ring which issued them,
+/// so tools can detect artifacts mixed up across clusters before interpreting them.
+pub trait RingIdentity {
+    /// Returns the identifier of the logical ring (see `StaticHashRing::ring_id`).
+    fn ring_id(&self) -> u64;
+
+    /// Checks whether `other` came from the same logical ring as this.
+    fn check_same_ring<T: RingIdentity + ?Sized>(&self, other: &T) -> Result<(), RingIdMismatch> {
+        let (expected, actual) = (self.ring_id(), other.ring_id());
+        if expected == actual {
+            Ok(())
+        } else {
+            Err(RingIdMismatch { expected, actual })
+        }
+    }
+}
+impl<K, V, H: RingHash> RingIdentity for StaticHashRing<K, V, H> {
+    fn ring_id(&self) -> u64 {
+        StaticHashRing::ring_id(self)
+    }
+}
+impl<K, V> RingIdentity for RingSpec<K, V> {
+    fn ring_id(&self) -> u64 {
+        self.ring_id
+    }
+}
+impl<K> RingIdentity for OwnershipProof<K> {
+    fn ring_id(&self) -> u64 {
+        self.ring_id
+    }
+}
+impl<K> RingIdentity for HandoverToken<K> {
+    fn ring_id(&self) -> u64 {
+        self.ring_id
+    }
+}
+
+/// The error which is returned when a ring identifier differs from the expected one.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RingIdMismatch {
+    /// The identifier of the local ring (or artifact).
+    pub expected: u64,
+
+    /// The identifier which was given.
+    pub actual: u64,
+}
+impl fmt::Display for RingIdMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "Ring identity mismatch: expected={:016x}, actual={:016x}",
+               self.expected,
+               self.actual)
+    }
+}
+impl Error for RingIdMismatch {}
+
+impl<K, V, H> StaticHashRing<K, V, H> {
+    /// Returns the identifier of the logical ring which this ring belongs to.
+    ///
+    /// Unlike `fingerprint`, the identifier persists across topology changes and restarts.
+    /// If no identifier is set explicitly (see `set_ring_id`),
+    /// it is derived from the format tag (which includes the namespace, if any),
+    /// so rings of different clusters sharing a hasher and a namespace should be given explicit identifiers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash, Namespaced};
+    ///
+    /// let a = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+    /// let b = StaticHashRing::new(DefaultHash, (0..5).map(|i| Node::new(i).quantity(10)));
+    /// let c = StaticHashRing::new(Namespaced::new(DefaultHash, "users"), (0..3).map(|i| Node::new(i).quantity(10)));
+    /// assert_eq!(a.ring_id(), b.ring_id());
+    /// assert_ne!(a.ring_id(), c.ring_id());
+    ///
+    /// let mut d = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+    /// d.set_ring_id(0x1234);
+    /// assert_eq!(d.ring_id(), 0x1234);
+    /// assert_ne!(a.ring_id(), d.ring_id());
+    /// ```
+    pub fn ring_id(&self) -> u64
+        where H: RingHash
+    {
+        self.ring_id.unwrap_or_else(|| {
+            let mut hasher = SipHasher13::new();
+            ("ring-id", self.format_tag()).hash(&mut hasher);
+            hasher.finish()
+        })
+    }
+
+    /// Sets the identifier of the logical ring which this ring belongs to (e.g., a UUID folded into `u64`).
+    pub fn set_ring_id(&mut self, ring_id: u64) {
+        self.ring_id = Some(ring_id);
+    }
+
+    /// Checks whether `ring_id` is equal to the identifier of this ring.
+    pub fn check_ring_id(&self, ring_id: u64) -> Result<(), RingIdMismatch>
+        where H: RingHash
+    {
+        let expected = self.ring_id();
+        if expected == ring_id {
+            Ok(())
+        } else {
+            Err(RingIdMismatch {
+                expected,
+                actual: ring_id,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {DefaultHash, HandoverMismatch, Node, ProofMismatch, StaticHashRing};
+    use super::*;
+
+    #[test]
+    fn ring_identity_works() {
+        let nodes = || (0..4).map(|i| Node::new(i).quantity(10));
+        let mut ring = StaticHashRing::new(DefaultHash, nodes());
+        let mut other = StaticHashRing::new(DefaultHash, nodes());
+        assert_eq!(ring.check_same_ring(&other), Ok(()));
+        ring.set_ring_id(1);
+        other.set_ring_id(2);
+        assert_eq!(ring.check_same_ring(&other), Err(RingIdMismatch { expected: 1, actual: 2 }));
+        assert_eq!(other.check_ring_id(2), Ok(()));
+
+        let spec = ring.spec();
+        assert_eq!(spec.check_same_ring(&ring), Ok(()));
+        let restored = StaticHashRing::from_spec(DefaultHash, spec).unwrap();
+        assert_eq!(restored.ring_id(), 1);
+
+        // Artifacts of the same placement but of another cluster are rejected.
+        let proof = ring.ownership_proof(&"foo").unwrap();
+        assert_eq!(proof.check_same_ring(&ring), Ok(()));
+        assert_eq!(other.verify_ownership_proof(&proof), Err(ProofMismatch::RingId { ring_id: 2 }));
+        assert_eq!(restored.verify_ownership_proof(&proof), Ok(()));
+
+        let old = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+        let diff = old.diff(&ring);
+        let m = &diff.moves[0];
+        let token = ring.handover(&m.range, m.before.unwrap().key, m.after.unwrap().key).unwrap();
+        assert_eq!(other.validate_handover(&token), Err(HandoverMismatch::RingId { ring_id: 2 }));
+        assert_eq!(restored.validate_handover(&token), Ok(()));
+        let mut broken = token.clone();
+        broken.ring_id = 2;
+        assert_eq!(other.validate_handover(&broken), Err(HandoverMismatch::Checksum));
+    }
+}
@@ -0,0 +1,70 @@
+use std::hash::Hash;
+use std::time::Duration;
+
+use rng::mix;
+use {RingHash, StaticHashRing};
+
+impl<K, V, H> StaticHashRing<K, V, H>
+    where H: RingHash
+{
+    /// Returns a deterministic jitter in the range `[0, max)` for `item`.
+    ///
+    /// The jitter is derived from the hash code of `item` used by this ring,
+    /// so the same item always gets the same jitter (e.g., across processes),
+    /// while the jitters of the items owned by the same node are decorrelated.
+    /// This is useful for spreading cache expirations and retries.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+    /// let max = Duration::from_secs(60);
+    /// let jitter = ring.stable_jitter(&"foo", max);
+    /// assert!(jitter < max);
+    /// assert_eq!(jitter, ring.stable_jitter(&"foo", max));
+    /// ```
+    pub fn stable_jitter<T: Hash + ?Sized>(&self, item: &T, max: Duration) -> Duration {
+        self.stable_jitter_by_hash(self.hash.hash_item(&item), max)
+    }
+
+    /// Returns a deterministic jitter in the range `[0, max)` for the item whose hash code is `item_hash`.
+    ///
+    /// See `stable_jitter`.
+    pub fn stable_jitter_by_hash(&self, item_hash: u64, max: Duration) -> Duration {
+        // Items owned by the same node have close hash codes, so the codes are scrambled before use.
+        // `floor(max_nanos * r / 2^64)`, computed without overflow.
+        let r = mix(item_hash) as u128;
+        let max_nanos = max.as_nanos();
+        let nanos = (max_nanos >> 64) * r + (((max_nanos as u64) as u128 * r) >> 64);
+        Duration::new((nanos / 1_000_000_000) as u64, (nanos % 1_000_000_000) as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use {DefaultHash, Node, RingHash, StaticHashRing};
+
+    #[test]
+    fn stable_jitter_works() {
+        let ring = StaticHashRing::new(DefaultHash, (0..2).map(|i| Node::new(i).quantity(1)));
+        let max = Duration::from_millis(1000);
+        assert_eq!(ring.stable_jitter(&"foo", Duration::from_secs(0)), Duration::from_secs(0));
+
+        // Adjacent hash codes (i.e., owned by the same node) get spread jitters.
+        let mut buckets = [0; 10];
+        for i in 0..10000u64 {
+            let jitter = ring.stable_jitter_by_hash(i, max);
+            assert!(jitter < max);
+            buckets[(jitter.as_millis() / 100) as usize] += 1;
+        }
+        assert!(buckets.iter().all(|&b| 900 < b && b < 1100), "{:?}", buckets);
+
+        let h = DefaultHash.hash_item(&"foo");
+        assert_eq!(ring.stable_jitter(&"foo", max), ring.stable_jitter_by_hash(h, max));
+        assert!(ring.stable_jitter(&"foo", Duration::new(u64::MAX, 999_999_999)) > Duration::from_secs(0));
+    }
+}
@@ -0,0 +1,63 @@
+use std::fmt;
+use std::sync::Mutex;
+
+/// A record of a virtual node removal made by `StaticHashRing::take` and its variants.
+///
+/// Records are passed to the journal registered by `StaticHashRing::set_journal`,
+/// and they can be re-applied to a freshly built ring by `StaticHashRing::replay_take`
+/// (e.g., to recover claims after a process restart).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TakeRecord<'a, K: 'a> {
+    /// The hash code of the removed virtual node.
+    pub vnode_hash: u64,
+
+    /// The key of the node which owned the removed virtual node.
+    pub node_key: &'a K,
+
+    /// The generation of the ring just after the removal.
+    pub generation: u64,
+}
+
+/// This trait allows receiving the records of `take` operations.
+///
+/// This is implemented for any `FnMut(&TakeRecord<K>)` closures.
+pub trait TakeJournal<K> {
+    /// Records `record`.
+    fn record(&mut self, record: &TakeRecord<K>);
+}
+impl<K, F> TakeJournal<K> for F
+    where F: FnMut(&TakeRecord<K>)
+{
+    fn record(&mut self, record: &TakeRecord<K>) {
+        self(record)
+    }
+}
+
+// The journal is only accessed via `&mut self`, so the mutex is never locked
+// (it just makes the slot `Sync` without requiring journals to be `Sync`).
+pub struct JournalSlot<K>(Mutex<Option<Box<dyn TakeJournal<K> + Send>>>);
+impl<K> JournalSlot<K> {
+    pub fn new() -> Self {
+        JournalSlot(Mutex::new(None))
+    }
+    pub fn set(&mut self, journal: Option<Box<dyn TakeJournal<K> + Send>>) {
+        *self.slot() = journal;
+    }
+    pub fn record(&mut self, record: &TakeRecord<K>) {
+        if let Some(ref mut journal) = *self.slot() {
+            journal.record(record);
+        }
+    }
+    fn slot(&mut self) -> &mut Option<Box<dyn TakeJournal<K> + Send>> {
+        self.0.get_mut().unwrap_or_else(|e| e.into_inner())
+    }
+}
+impl<K> fmt::Debug for JournalSlot<K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0.lock().map(|j| j.is_some()).unwrap_or_else(|e| e.into_inner().is_some()) {
+            write!(f, "JournalSlot(Some(..))")
+        } else {
+            write!(f, "JournalSlot(None)")
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use std::hash::Hash;
+
+use {Node, RingHash};
+
+/// Returns the bucket (in the range `0..buckets`) of `key` by using jump consistent hash.
+///
+/// When `buckets` grows from `n` to `n + 1`, only about `1 / (n + 1)` keys move,
+/// and all of them move to the new bucket `n`.
+///
+/// See [A Fast, Minimal Memory, Consistent Hash Algorithm](https://arxiv.org/abs/1406.2294).
+///
+/// # Panics
+///
+/// Panics if `buckets` is `0`.
+///
+/// # Examples
+///
+/// ```
+/// use consistent_hash::jump_hash;
+///
+/// assert_eq!(jump_hash(12345, 1), 0);
+/// assert!(jump_hash(12345, 10) < 10);
+/// ```
+pub fn jump_hash(mut key: u64, buckets: usize) -> usize {
+    assert!(buckets > 0);
+    let mut b = 0;
+    let mut j = 0;
+    while j < buckets as u64 {
+        b = j;
+        key = key.wrapping_mul(2_862_933_555_777_941_757).wrapping_add(1);
+        j = ((b + 1) as f64 * ((1u64 << 31) as f64 / ((key >> 33) + 1) as f64)) as u64;
+    }
+    b as usize
+}
+
+/// A hash ring based on jump consistent hash.
+///
+/// Nodes are identified by their dense indices (i.e., the positions in `JumpHashRing::nodes`),
+/// and can only be added or removed at the end.
+/// Unlike `StaticHashRing`, this needs no virtual node table,
+/// so the quantities of nodes are ignored (i.e., every node has the same weight).
+///
+/// # Examples
+///
+/// ```
+/// use consistent_hash::{Node, JumpHashRing, DefaultHash};
+///
+/// let mut ring = JumpHashRing::new(DefaultHash);
+/// assert_eq!(ring.calc_candidate(&"foo"), None);
+///
+/// ring.push_node(Node::new("foo"));
+/// ring.push_node(Node::new("bar"));
+/// let index = ring.calc_candidate_index(&"foo").unwrap();
+/// assert_eq!(ring.calc_candidate(&"foo"), Some(&ring.nodes()[index]));
+/// ```
+#[derive(Debug, Clone)]
+pub struct JumpHashRing<K, V, H> {
+    hash: H,
+    nodes: Vec<Node<K, V>>,
+}
+impl<K, V, H> JumpHashRing<K, V, H>
+    where H: RingHash
+{
+    /// Makes a new `JumpHashRing` instance which has no nodes.
+    pub fn new(hash: H) -> Self {
+        JumpHashRing {
+            hash,
+            nodes: Vec::new(),
+        }
+    }
+
+    /// Makes a new `JumpHashRing` instance which has `nodes` (in iteration order).
+    pub fn with_nodes<I>(hash: H, nodes: I) -> Self
+        where I: Iterator<Item = Node<K, V>>
+    {
+        JumpHashRing {
+            hash,
+            nodes: nodes.collect(),
+        }
+    }
+
+    /// Adds `node` at the end of this ring.
+    pub fn push_node(&mut self, node: Node<K, V>) {
+        self.nodes.push(node);
+    }
+
+    /// Removes the last node of this ring, and returns it.
+    pub fn pop_node(&mut self) -> Option<Node<K, V>> {
+        self.nodes.pop()
+    }
+
+    /// Returns the index of the node to which `item` is assigned.
+    ///
+    /// If this ring is empty, this returns `None`.
+    pub fn calc_candidate_index<T: Hash>(&self, item: &T) -> Option<usize> {
+        if self.nodes.is_empty() {
+            None
+        } else {
+            Some(jump_hash(self.hash.hash_item(item), self.nodes.len()))
+        }
+    }
+
+    /// Returns the node to which `item` is assigned.
+    ///
+    /// If this ring is empty, this returns `None`.
+    pub fn calc_candidate<T: Hash>(&self, item: &T) -> Option<&Node<K, V>> {
+        self.calc_candidate_index(item).map(|i| &self.nodes[i])
+    }
+
+    /// Returns the nodes of this ring.
+    pub fn nodes(&self) -> &[Node<K, V>] {
+        &self.nodes
+    }
+
+    /// Returns the count of the nodes in this ring.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns `true` if this ring has no nodes, otherwise `false`.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use DefaultHash;
+    use super::*;
+
+    #[test]
+    fn jump_hash_works() {
+        for key in 0..1000 {
+            assert_eq!(jump_hash(key, 1), 0);
+            for n in 1..20 {
+                // Keys either stay or move to the new bucket.
+                let b = jump_hash(key, n + 1);
+                assert!(b == jump_hash(key, n) || b == n);
+            }
+        }
+
+        let mut counts = [0; 8];
+        for key in 0..8000u64 {
+            counts[jump_hash(DefaultHash.hash_item(&key), 8)] += 1;
+        }
+        assert!(counts.iter().all(|&c| 900 < c && c < 1100), "{:?}", counts);
+    }
+
+    #[test]
+    fn jump_hash_ring_works() {
+        let mut ring = JumpHashRing::with_nodes(DefaultHash, (0..5).map(Node::new));
+        assert_eq!(ring.len(), 5);
+        let before = (0..100).map(|i| ring.calc_candidate(&i).unwrap().key).collect::<Vec<_>>();
+
+        ring.push_node(Node::new(5));
+        let after = (0..100).map(|i| ring.calc_candidate(&i).unwrap().key).collect::<Vec<_>>();
+        assert!(before.iter().zip(&after).all(|(b, a)| b == a || *a == 5));
+        assert!(after.contains(&5));
+
+        assert_eq!(ring.pop_node(), Some(Node::new(5)));
+        let popped = (0..100).map(|i| ring.calc_candidate(&i).unwrap().key).collect::<Vec<_>>();
+        assert_eq!(popped, before);
+
+        let empty = JumpHashRing::with_nodes(DefaultHash, (0..0).map(Node::new));
+        assert!(empty.is_empty());
+        assert_eq!(empty.calc_candidate_index(&"foo"), None);
+    }
+}
@@ -0,0 +1,201 @@
+use std::hash::{Hash, Hasher};
+
+use RingHash;
+
+/// The count of the points (i.e., virtual nodes) per server in libketama (for equally weighted servers).
+pub const KETAMA_POINTS_PER_SERVER: usize = 160;
+
+const POINTS_PER_DIGEST: usize = 4;
+
+/// A `RingHash` implementation which is compatible with libketama.
+///
+/// The virtual node `seq` of a server is the `seq % 4`-th 32-bit word of `MD5("{key}-{seq / 4}")`,
+/// and the hash code of an item is the first 32-bit word of `MD5(item)`.
+/// So, a `StaticHashRing` whose node keys are `"host:port"` strings
+/// and whose quantities are `KETAMA_POINTS_PER_SERVER` maps items to the same servers as
+/// the memcached clients based on libketama (e.g., nginx and spymemcached).
+///
+/// Keys and items are expected to be strings (`str` or `String`).
+/// Other types are digested as the bytes fed by their `Hash` implementations.
+///
+/// # Examples
+///
+/// ```
+/// use consistent_hash::{Node, StaticHashRing, KetamaHash, KETAMA_POINTS_PER_SERVER};
+///
+/// let servers = vec!["10.0.0.1:11211", "10.0.0.2:11211", "10.0.0.3:11211"];
+/// let nodes = servers.into_iter().map(|s| Node::new(s).quantity(KETAMA_POINTS_PER_SERVER));
+/// let ring = StaticHashRing::new(KetamaHash, nodes);
+/// assert_eq!(ring.calc_candidates(&"foo").next().map(|n| n.key), Some("10.0.0.3:11211"));
+/// ```
+#[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub struct KetamaHash;
+impl KetamaHash {
+    /// Returns the count of the points of a server in libketama's weighted mode.
+    ///
+    /// `weight` is the weight of the server, `total_weight` is the sum of the weights of all servers,
+    /// and `servers` is the count of the servers.
+    pub fn weighted_quantity(weight: u64, total_weight: u64, servers: usize) -> usize {
+        if total_weight == 0 {
+            return 0;
+        }
+        let pct = weight as f32 / total_weight as f32;
+        (pct * 40.0 * servers as f32).floor() as usize * POINTS_PER_DIGEST
+    }
+}
+impl RingHash for KetamaHash {
+    fn hash_item<T: Hash>(&self, item: &T) -> u64 {
+        let mut bytes = ByteCollector::default();
+        item.hash(&mut bytes);
+        u64::from(digest_word(&md5(&bytes.into_bytes()), 0))
+    }
+    fn hash_vnode<K: Hash>(&self, node_key: &K, vnode_seq: usize) -> u64 {
+        let mut bytes = ByteCollector::default();
+        node_key.hash(&mut bytes);
+        let mut bytes = bytes.into_bytes();
+        bytes.extend_from_slice(format!("-{}", vnode_seq / POINTS_PER_DIGEST).as_bytes());
+        u64::from(digest_word(&md5(&bytes), vnode_seq % POINTS_PER_DIGEST))
+    }
+    fn algorithm_id(&self) -> &str {
+        "ketama"
+    }
+}
+
+// Collects the bytes fed by `Hash` implementations.
+//
+// The terminator which `str` appends (i.e., a trailing `0xFF` byte) is excluded.
+#[derive(Default)]
+struct ByteCollector {
+    bytes: Vec<u8>,
+    terminated: bool,
+}
+impl ByteCollector {
+    fn into_bytes(mut self) -> Vec<u8> {
+        if self.terminated {
+            self.bytes.pop();
+        }
+        self.bytes
+    }
+}
+impl Hasher for ByteCollector {
+    fn write(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+        self.terminated = false;
+    }
+    fn write_u8(&mut self, i: u8) {
+        self.bytes.push(i);
+        self.terminated = i == 0xFF;
+    }
+    fn finish(&self) -> u64 {
+        unreachable!()
+    }
+}
+
+fn digest_word(digest: &[u8; 16], index: usize) -> u32 {
+    let b = &digest[index * 4..];
+    u32::from_le_bytes([b[0], b[1], b[2], b[3]])
+}
+
+const SHIFTS: [u32; 64] = [7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14, 20, 5, 9,
+                           14, 20, 5, 9, 14, 20, 5, 9, 14, 20, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16,
+                           23, 4, 11, 16, 23, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21];
+
+const SINES: [u32; 64] = [
+    0xd76a_a478, 0xe8c7_b756, 0x2420_70db, 0xc1bd_ceee,
+    0xf57c_0faf, 0x4787_c62a, 0xa830_4613, 0xfd46_9501,
+    0x6980_98d8, 0x8b44_f7af, 0xffff_5bb1, 0x895c_d7be,
+    0x6b90_1122, 0xfd98_7193, 0xa679_438e, 0x49b4_0821,
+    0xf61e_2562, 0xc040_b340, 0x265e_5a51, 0xe9b6_c7aa,
+    0xd62f_105d, 0x0244_1453, 0xd8a1_e681, 0xe7d3_fbc8,
+    0x21e1_cde6, 0xc337_07d6, 0xf4d5_0d87, 0x455a_14ed,
+    0xa9e3_e905, 0xfcef_a3f8, 0x676f_02d9, 0x8d2a_4c8a,
+    0xfffa_3942, 0x8771_f681, 0x6d9d_6122, 0xfde5_380c,
+    0xa4be_ea44, 0x4bde_cfa9, 0xf6bb_4b60, 0xbebf_bc70,
+    0x289b_7ec6, 0xeaa1_27fa, 0xd4ef_3085, 0x0488_1d05,
+    0xd9d4_d039, 0xe6db_99e5, 0x1fa2_7cf8, 0xc4ac_5665,
+    0xf429_2244, 0x432a_ff97, 0xab94_23a7, 0xfc93_a039,
+    0x655b_59c3, 0x8f0c_cc92, 0xffef_f47d, 0x8584_5dd1,
+    0x6fa8_7e4f, 0xfe2c_e6e0, 0xa301_4314, 0x4e08_11a1,
+    0xf753_7e82, 0xbd3a_f235, 0x2ad7_d2bb, 0xeb86_d391,
+];
+
+fn md5(input: &[u8]) -> [u8; 16] {
+    let mut message = input.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((input.len() as u64).wrapping_mul(8)).to_le_bytes());
+
+    let mut state: [u32; 4] = [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476];
+    for block in message.chunks(64) {
+        let mut m = [0u32; 16];
+        for (w, b) in m.iter_mut().zip(block.chunks(4)) {
+            *w = u32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+        }
+        let [mut a, mut b, mut c, mut d] = state;
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let f = f.wrapping_add(a).wrapping_add(SINES[i]).wrapping_add(m[g]);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(f.rotate_left(SHIFTS[i]));
+        }
+        state[0] = state[0].wrapping_add(a);
+        state[1] = state[1].wrapping_add(b);
+        state[2] = state[2].wrapping_add(c);
+        state[3] = state[3].wrapping_add(d);
+    }
+
+    let mut digest = [0; 16];
+    for (chunk, s) in digest.chunks_mut(4).zip(state.iter()) {
+        chunk.copy_from_slice(&s.to_le_bytes());
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use {Node, RingHash, StaticHashRing};
+    use super::*;
+
+    fn hex(digest: [u8; 16]) -> String {
+        digest.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn md5_works() {
+        assert_eq!(hex(md5(b"")), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(hex(md5(b"abc")), "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(hex(md5(&[b'a'; 100])), "36a92cc94a9e0fa21f625f8bfb007adf");
+    }
+
+    #[test]
+    fn ketama_hash_works() {
+        assert_eq!(KetamaHash.hash_item(&"foo"), 3_675_831_724);
+        let points = (0..4).map(|i| KetamaHash.hash_vnode(&"127.0.0.1:11211", i)).collect::<Vec<_>>();
+        assert_eq!(points, [2_589_391_586, 1_482_608_462, 2_562_656_683, 1_506_298_073]);
+        assert_eq!(KetamaHash.hash_item(&"foo".to_owned()), KetamaHash.hash_item(&"foo"));
+        assert_eq!(KetamaHash.hash_bytes(b"foo"), 3_675_831_724);
+
+        let servers = vec!["10.0.0.1:11211", "10.0.0.2:11211", "10.0.0.3:11211"];
+        let nodes = servers.into_iter().map(|s| Node::new(s).quantity(KETAMA_POINTS_PER_SERVER));
+        let ring = StaticHashRing::new(KetamaHash, nodes);
+        let expected = [("foo", 3), ("bar", 1), ("baz", 3), ("qux", 2), ("hello", 1), ("world", 3),
+                        ("memcached", 3), ("key-42", 2)];
+        for &(key, server) in &expected {
+            let node = ring.calc_candidates(&key).next().unwrap();
+            assert_eq!(node.key, format!("10.0.0.{}:11211", server));
+        }
+
+        assert_eq!(KetamaHash::weighted_quantity(1, 3, 3), 160);
+        assert_eq!(KetamaHash::weighted_quantity(2, 4, 3), 240);
+        assert_eq!(KetamaHash::weighted_quantity(1, 0, 3), 0);
+    }
+}
@@ -0,0 +1,129 @@
+// A cache-friendly index for locating virtual nodes by hash codes.
+//
+// The hash codes of the virtual nodes are stored separately from the other fields
+// (so a search touches only the hash codes), and a top-level radix table narrows the search range
+// by the upper bits of the hash code. The rest is a branchless binary search.
+
+// The maximum radix bits (i.e., the table has at most `2^MAX_RADIX_BITS + 1` entries).
+const MAX_RADIX_BITS: u32 = 16;
+
+#[derive(Debug, Clone, Default)]
+pub struct LookupIndex {
+    hashes: Vec<u64>,
+    bits: u32,
+
+    // `buckets[p]` is the count of the hash codes whose upper `bits` bits are less than `p`.
+    buckets: Vec<u32>,
+}
+impl LookupIndex {
+    // Makes a new index of `hashes`, which must be sorted.
+    pub fn new(hashes: Vec<u64>) -> Self {
+        // Aims at a few hash codes per bucket.
+        let bits = match hashes.len() {
+            0..=3 => 0,
+            n => (63 - n.leading_zeros() - 2).min(MAX_RADIX_BITS),
+        };
+        let mut buckets = Vec::with_capacity((1 << bits) + 1);
+        let mut i = 0;
+        for p in 0..(1u64 << bits) + 1 {
+            while i < hashes.len() && prefix(hashes[i], bits) < p {
+                i += 1;
+            }
+            buckets.push(i as u32);
+        }
+        LookupIndex { hashes, bits, buckets }
+    }
+
+    // Returns the index of the first hash code which is greater than or equal to `hash`
+    // (or `self.hashes.len()` if there is no such one).
+    pub fn locate(&self, hash: u64) -> usize {
+        let p = prefix(hash, self.bits) as usize;
+        let start = self.buckets[p] as usize;
+        let end = self.buckets[p + 1] as usize;
+        start + lower_bound(&self.hashes[start..end], hash)
+    }
+
+    pub fn hashes(&self) -> &[u64] {
+        &self.hashes
+    }
+
+    pub fn insert(&mut self, index: usize, hash: u64) {
+        self.hashes.insert(index, hash);
+        let p = prefix(hash, self.bits) as usize;
+        for b in &mut self.buckets[p + 1..] {
+            *b += 1;
+        }
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        let hash = self.hashes.remove(index);
+        let p = prefix(hash, self.bits) as usize;
+        for b in &mut self.buckets[p + 1..] {
+            *b -= 1;
+        }
+    }
+}
+
+fn prefix(hash: u64, bits: u32) -> u64 {
+    hash.checked_shr(64 - bits).unwrap_or(0)
+}
+
+// Returns the index of the first element which is greater than or equal to `x`.
+//
+// The loop body has no data dependent branches (the selection is compiled into a conditional move).
+fn lower_bound(sorted: &[u64], x: u64) -> usize {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let mut base = 0;
+    let mut size = sorted.len();
+    while size > 1 {
+        let half = size / 2;
+        let mid = base + half;
+        base = if sorted[mid] < x { mid } else { base };
+        size -= half;
+    }
+    base + (sorted[base] < x) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup_index_works() {
+        let mut hashes = (0..1000u64).map(|i| i.wrapping_mul(0x9e37_79b9_7f4a_7c15)).collect::<Vec<_>>();
+        hashes.extend_from_slice(&[0, 0, 5, 5, 5, u64::MAX, u64::MAX]);
+        hashes.sort();
+
+        let expected = |hashes: &[u64], h: u64| hashes.partition_point(|&x| x < h);
+        let probes = hashes.iter()
+            .flat_map(|&h| vec![h, h.wrapping_sub(1), h.wrapping_add(1)])
+            .chain(vec![0, 1, u64::MAX])
+            .collect::<Vec<_>>();
+
+        let mut index = LookupIndex::new(hashes.clone());
+        for &h in &probes {
+            assert_eq!(index.locate(h), expected(&hashes, h));
+        }
+
+        // Incremental updates.
+        for i in (0..hashes.len()).rev().step_by(3) {
+            hashes.remove(i);
+            index.remove(i);
+        }
+        hashes.insert(0, 0);
+        index.insert(0, 0);
+        for &h in &probes {
+            assert_eq!(index.locate(h), expected(&hashes, h));
+        }
+
+        for n in 0..6 {
+            let small = (0..n).map(|i| i * 10).collect::<Vec<_>>();
+            let index = LookupIndex::new(small.clone());
+            for h in 0..60 {
+                assert_eq!(index.locate(h), expected(&small, h));
+            }
+        }
+    }
+}
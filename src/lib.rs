@@ -1,7 +1,12 @@
 //! An implementation of Consistent hashing algorithm.
 //!
-//! Currently this crate only provides `StaticHashRing` which
-//! represents statically built, virtual node based hash rings.
+//! This crate provides virtual node based hash rings:
+//! `StaticHashRing` represents statically built rings,
+//! and `DynamicHashRing` represents rings whose nodes can be added and removed at runtime.
+//! In addition, `JumpHashRing` implements jump consistent hash for nodes identified by dense indices,
+//! `RendezvousRing` implements rendezvous (highest random weight) hashing,
+//! `MaglevRing` implements Maglev hashing,
+//! and `MultiProbeRing` implements multi-probe consistent hashing.
 //!
 //! # Examples
 //!
@@ -26,11 +31,160 @@
 //! ```
 #![warn(missing_docs)]
 extern crate siphasher;
-extern crate splay_tree;
 
-use std::hash::{Hash, Hasher};
+use std::borrow::Borrow;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::hint;
+use std::iter::FusedIterator;
+use std::mem;
+use std::slice;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
 use siphasher::sip::SipHasher13;
-use splay_tree::SplaySet;
+
+#[cfg(feature = "http-admin")]
+pub use admin::{ChangeStatus, NodeStatus, RingStatus};
+pub use anchor::AnchorRing;
+pub use assign::Assignments;
+#[cfg(feature = "blake3")]
+pub use blake3::{Blake3Hash, KeyedBlake3Hash};
+pub use buffer::CandidateBuffer;
+pub use budget::BudgetedCandidates;
+pub use builder::{BuildError, RingBuilder};
+pub use clock::{Clock, MockClock, SystemClock};
+#[cfg(feature = "config")]
+pub use config::{ConfigError, ConfigHash, DEFAULT_CONFIG_VNODES};
+pub use convention::{Bound, Direction, RingConvention};
+pub use cost::{InstrumentedCandidates, LookupCostSnapshot, LookupCostStats, LOOKUP_COST_BUCKETS};
+pub use dedup::{Dedup, DedupCandidates, UniqueCandidates};
+pub use diff::{KeyMoves, RangeMove, RingDiff};
+pub use dual::{DivergenceLog, DualDivergence, DualHashRing, DualOwners};
+pub use dyn_hash::DynRingHash;
+pub use dynamic::{DynamicCandidates, DynamicHashRing};
+pub use exclusion::{ExclusionFallback, FallbackCandidates};
+pub use fanout::{FanOut, FirstHealthy, HedgedPair, Quorum, QuorumSet, TopK};
+pub use fixed::{FixedCandidates, FixedRing};
+#[cfg(feature = "fnv")]
+pub use fnv::{Fnv, SeededFnv};
+pub use groups::NodeGroups;
+pub use hash32::Hash32;
+pub use hash_ring::{DynHashRing, HashRing};
+pub use hysteresis::QuantityHysteresis;
+pub use journal::{TakeJournal, TakeRecord};
+pub use jump::{jump_hash, JumpHashRing};
+pub use ketama::{KetamaHash, KETAMA_POINTS_PER_SERVER};
+pub use load::{BoundedCandidates, LoadGuard, LoadTracker};
+pub use limit::{NodeTokenBuckets, TokenBucket};
+pub use lookup::{CandidatesSnapshot, LookupResult};
+pub use maglev::{MaglevCandidates, MaglevRing, DEFAULT_MAGLEV_TABLE_SIZE};
+pub use map::{ConsistentHashMap, Entries};
+pub use multi_get::{MultiGetBatch, MultiGetPlan, PlannedKey};
+pub use multi_probe::{MultiProbeCandidates, MultiProbeRing, DEFAULT_PROBES};
+pub use namespace::Namespaced;
+pub use nested::NestedCandidates;
+pub use node_id::{CandidateIds, NodeId};
+#[cfg(feature = "observer")]
+pub use observer::{LookupEvent, RingObserver, SelectionCounters};
+pub use ownership::{RangeTransfer, TopologyImpact};
+pub use plan::{MembershipChange, PlanReport, PlanStep};
+pub use precomputed::{PrecomputedCandidates, PrecomputedRing};
+pub use proof::{HandoverMismatch, HandoverToken, OwnershipProof, ProofMismatch};
+pub use read::{LatencyTracker, LocalFirstCandidates, ReadCandidates, ReadPolicy};
+pub use identity::{RingIdMismatch, RingIdentity};
+pub use rendezvous::{RendezvousCandidates, RendezvousRing};
+pub use residency::{Misplacement, NodeMisplacements, ResidencyReport};
+pub use retry::RetrySequence;
+pub use router::{ConsistentRouter, DEFAULT_ROUTER_VNODES};
+pub use routing::{RoutingEntry, RoutingTable};
+pub use rng::SplitMix64;
+pub use schedule::ScheduledRing;
+pub use shared::{SetValueError, SharedRing, SharedRingReader};
+pub use slot::{SlotRing, DEFAULT_SLOTS};
+pub use snapshot::{SnapshotError, SNAPSHOT_VERSION};
+pub use spec::RingSpec;
+pub use spread::{SpreadCandidates, SpreadTake};
+pub use tie_break::TieBrokenCandidates;
+pub use topology::{Relocation, TopologyOp};
+pub use transition::{MigratingRing, MigrationCandidates, TransitionOwners, TransitionRing};
+pub use weight::{NodeWeight, MAX_BALANCED_QUANTITY};
+#[cfg(feature = "xxhash")]
+pub use xxhash::{SeededXxHash, XxHash};
+
+#[cfg(feature = "http-admin")]
+mod admin;
+pub mod analysis;
+mod anchor;
+mod assign;
+#[cfg(feature = "blake3")]
+mod blake3;
+mod buffer;
+mod budget;
+mod builder;
+mod clock;
+pub mod compat;
+#[cfg(feature = "config")]
+mod config;
+mod convention;
+mod cost;
+mod dedup;
+mod diff;
+mod dual;
+mod dyn_hash;
+mod dynamic;
+mod exclusion;
+mod fanout;
+mod fixed;
+#[cfg(feature = "fnv")]
+mod fnv;
+mod groups;
+mod hash32;
+mod hash_ring;
+mod hysteresis;
+mod identity;
+mod jitter;
+mod journal;
+mod jump;
+mod ketama;
+mod layout;
+mod load;
+mod limit;
+mod lookup;
+mod maglev;
+mod map;
+mod multi_get;
+mod multi_probe;
+mod namespace;
+mod nested;
+mod node_id;
+mod observer;
+mod ownership;
+mod plan;
+mod precomputed;
+mod proof;
+pub mod range;
+mod read;
+mod rendezvous;
+mod residency;
+mod retry;
+mod router;
+mod routing;
+mod rng;
+mod schedule;
+mod shared;
+mod slot;
+mod snapshot;
+mod spec;
+mod spread;
+mod tie_break;
+mod topology;
+mod transition;
+mod validate;
+mod weight;
+#[cfg(feature = "xxhash")]
+mod xxhash;
 
 /// A node in a hash ring.
 ///
@@ -68,7 +222,7 @@ impl<K> Node<K, ()> {
     /// The value of it is `()` and the quantity is set to `1`.
     pub fn new(key: K) -> Self {
         Node {
-            key: key,
+            key,
             value: (),
             quantity: 1,
         }
@@ -81,7 +235,7 @@ impl<K, V> Node<K, V> {
     pub fn value<U>(self, value: U) -> Node<K, U> {
         Node {
             key: self.key,
-            value: value,
+            value,
             quantity: self.quantity,
         }
     }
@@ -93,10 +247,20 @@ impl<K, V> Node<K, V> {
     }
 }
 
-#[derive(Debug)]
-struct VirtualNode<'a, K: 'a, V: 'a> {
+// The mutable state of a real node in a ring.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct NodeState {
+    replica_only: bool,
+    excluded_until: Option<SystemTime>,
+    disabled: bool,
+    removal_at: Option<SystemTime>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct VirtualNode {
     hash: u64,
-    node: &'a Node<K, V>,
+    index: usize,
+    seq: usize,
 }
 
 /// This trait allows calculating hash codes for virtual nodes and items.
@@ -110,6 +274,43 @@ pub trait RingHash {
     fn hash_vnode<K: Hash>(&self, node_key: &K, vnode_seq: usize) -> u64 {
         self.hash_item(&(node_key, vnode_seq))
     }
+
+    /// Calculates the hash code of the raw bytes `bytes`.
+    ///
+    /// Unlike `hash_item(&bytes)`, no length prefix is fed to the hasher,
+    /// so the result equals the digest of `bytes` computed by non-Rust implementations of the same algorithm.
+    ///
+    /// The default implementation passes an item, which writes `bytes` to the hasher as they are, to `hash_item`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{DefaultHash, RingHash};
+    ///
+    /// assert_ne!(DefaultHash.hash_bytes(b"foo"), DefaultHash.hash_item(&b"foo".to_vec()));
+    /// assert_eq!(DefaultHash.hash_bytes(b"foo"), DefaultHash.hash_bytes(b"foo"));
+    /// ```
+    fn hash_bytes(&self, bytes: &[u8]) -> u64 {
+        self.hash_item(&RawBytes(bytes))
+    }
+
+    /// Returns the identifier of the hashing algorithm.
+    ///
+    /// This is embedded in the format tag of rings (see `StaticHashRing::format_tag`),
+    /// so implementations should return different identifiers for incompatible algorithms.
+    ///
+    /// The default implementation returns `"custom"`.
+    fn algorithm_id(&self) -> &str {
+        "custom"
+    }
+}
+
+// An item which writes the wrapped bytes as they are (i.e., without a length prefix).
+struct RawBytes<'a>(&'a [u8]);
+impl<'a> Hash for RawBytes<'a> {
+    fn hash<S: Hasher>(&self, state: &mut S) {
+        state.write(self.0);
+    }
 }
 
 /// The default `RingHash` implementation.
@@ -123,11 +324,201 @@ impl RingHash for DefaultHash {
         item.hash(&mut hasher);
         hasher.finish()
     }
+    fn algorithm_id(&self) -> &str {
+        "siphash13"
+    }
+}
+impl DefaultHash {
+    /// Makes a `SipHash 1-3` hasher which uses the secret keys `k0` and `k1`.
+    ///
+    /// Rings built with different keys have independent layouts,
+    /// and the layouts cannot be predicted without the keys.
+    pub fn with_keys(k0: u64, k1: u64) -> KeyedHash {
+        KeyedHash {
+            k0,
+            k1,
+            algorithm_id: format!("siphash13-keyed-{:016x}", DefaultHash.hash_item(&(k0, k1))),
+        }
+    }
+}
+
+/// A `RingHash` implementation which uses `SipHash 1-3` with secret keys.
+///
+/// This is created by calling `DefaultHash::with_keys` function.
+///
+/// The algorithm identifier contains a fingerprint of the keys (not the keys themselves),
+/// so the format tags of rings built with different keys differ.
+///
+/// # Examples
+///
+/// ```
+/// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+///
+/// let nodes = || (0..4).map(|i| Node::new(i).quantity(10));
+/// let tenant_a = StaticHashRing::new(DefaultHash::with_keys(1, 2), nodes());
+/// let tenant_b = StaticHashRing::new(DefaultHash::with_keys(3, 4), nodes());
+///
+/// assert_ne!(tenant_a.format_tag(), tenant_b.format_tag());
+/// assert_eq!(StaticHashRing::new(DefaultHash::with_keys(1, 2), nodes()).format_tag(),
+///            tenant_a.format_tag());
+/// ```
+#[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub struct KeyedHash {
+    k0: u64,
+    k1: u64,
+    algorithm_id: String,
+}
+impl RingHash for KeyedHash {
+    fn hash_item<T: Hash>(&self, item: &T) -> u64 {
+        let mut hasher = SipHasher13::new_with_keys(self.k0, self.k1);
+        item.hash(&mut hasher);
+        hasher.finish()
+    }
+    fn algorithm_id(&self) -> &str {
+        &self.algorithm_id
+    }
+}
+
+/// A `RingHash` implementation which uses an arbitrary `BuildHasher`.
+///
+/// This allows plugging an existing hasher of an application (e.g., `fxhash::FxBuildHasher`) into rings.
+///
+/// Note that the placements of a ring depend on the hasher,
+/// so a randomly seeded hasher like `std::collections::hash_map::RandomState`
+/// produces different placements in each process (and each instance).
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::hash_map::{DefaultHasher, RandomState};
+/// use std::hash::BuildHasherDefault;
+/// use consistent_hash::{Node, StaticHashRing, BuildHasherRingHash};
+///
+/// let hash = BuildHasherRingHash::new(BuildHasherDefault::<DefaultHasher>::default()).with_algorithm_id("std-default");
+/// let ring = StaticHashRing::new(hash, (0..3).map(|i| Node::new(i).quantity(10)));
+/// assert_eq!(ring.format_tag(), "ring-v1-std-default");
+///
+/// let ring = StaticHashRing::new(BuildHasherRingHash::new(RandomState::new()),
+///                                (0..3).map(|i| Node::new(i).quantity(10)));
+/// assert_eq!(ring.format_tag(), "ring-v1-custom");
+/// ```
+#[derive(Debug, Clone)]
+pub struct BuildHasherRingHash<B> {
+    build_hasher: B,
+    algorithm_id: String,
+}
+impl<B: BuildHasher> BuildHasherRingHash<B> {
+    /// Makes a new `BuildHasherRingHash` instance.
+    ///
+    /// The algorithm identifier is `"custom"` by default.
+    pub fn new(build_hasher: B) -> Self {
+        BuildHasherRingHash {
+            build_hasher,
+            algorithm_id: "custom".to_owned(),
+        }
+    }
+
+    /// Sets the algorithm identifier (see `RingHash::algorithm_id`) of this instance.
+    pub fn with_algorithm_id(mut self, algorithm_id: &str) -> Self {
+        self.algorithm_id = algorithm_id.to_owned();
+        self
+    }
+
+    /// Returns a reference to the underlying `BuildHasher`.
+    pub fn build_hasher(&self) -> &B {
+        &self.build_hasher
+    }
+}
+impl<B: BuildHasher> RingHash for BuildHasherRingHash<B> {
+    fn hash_item<T: Hash>(&self, item: &T) -> u64 {
+        self.build_hasher.hash_one(item)
+    }
+    fn algorithm_id(&self) -> &str {
+        &self.algorithm_id
+    }
+}
+
+// Returns the score of weighted rendezvous hashing (the lowest score wins).
+//
+// `h` is the hash code of an (item, candidate) pair, and `w` is the positive weight of the candidate.
+fn rendezvous_score(h: u64, w: f64) -> f64 {
+    let u = ((h >> 11) as f64 + 0.5) / (1u64 << 53) as f64;
+    -u.ln() / w
+}
+
+/// The version of the ring building algorithm (i.e., vnode derivation and lookup rules).
+///
+/// This is changed whenever a change of this crate could alter the placements of existing rings.
+pub const RING_ALGORITHM_VERSION: &str = "ring-v1";
+
+/// The error which is returned when a format tag differs from the expected one.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FormatMismatch {
+    /// The format tag of the local ring.
+    pub expected: String,
+
+    /// The format tag which was given.
+    pub actual: String,
+}
+impl fmt::Display for FormatMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "Ring format mismatch: expected={:?}, actual={:?}",
+               self.expected,
+               self.actual)
+    }
+}
+impl Error for FormatMismatch {}
+
+/// The error which is returned when a ring has no virtual nodes.
+///
+/// See `StaticHashRing::try_calc_candidates`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EmptyRing;
+impl fmt::Display for EmptyRing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "The ring has no virtual nodes")
+    }
+}
+impl Error for EmptyRing {}
+
+/// Extracts the Redis-style hash tag from `key`.
+///
+/// If `key` contains a `{` and a `}` after it, and there is at least one byte between them,
+/// the bytes between the first `{` and the first `}` after it are the tag.
+/// Otherwise the whole `key` is returned.
+///
+/// # Examples
+///
+/// ```
+/// use consistent_hash::hash_tag;
+///
+/// assert_eq!(hash_tag(b"{user1000}.following"), b"user1000");
+/// assert_eq!(hash_tag(b"foo{}{bar}"), b"foo{}{bar}");
+/// assert_eq!(hash_tag(b"foo{{bar}}zap"), b"{bar");
+/// assert_eq!(hash_tag(b"foo"), b"foo");
+/// ```
+pub fn hash_tag(key: &[u8]) -> &[u8] {
+    if let Some(start) = key.iter().position(|&b| b == b'{') {
+        if let Some(len) = key[start + 1..].iter().position(|&b| b == b'}') {
+            if len > 0 {
+                return &key[start + 1..start + 1 + len];
+            }
+        }
+    }
+    key
 }
 
 /// A hash ring which is built statically.
 ///
-/// Once a ring instance is created, it cannot be modified afterwards.
+/// Once a ring instance is created, no nodes can be added to it afterwards.
+///
+/// An item is owned by the first virtual node whose hash code is equal to or greater than the item's one
+/// (wrapping around to the smallest one), and the rest of candidates follow in ascending order.
+/// To use another convention, wrap the hash with `RingConvention`.
+///
+/// A ring owns its nodes (virtual nodes refer to them by indices),
+/// so it can be moved or shared across threads (e.g., via `Arc`) like any other owned value.
 ///
 /// # Examples
 ///
@@ -151,12 +542,38 @@ impl RingHash for DefaultHash {
 ///            [&"foo", &"bar", &"baz"]);
 /// ```
 #[derive(Debug)]
-pub struct StaticHashRing<'a, K: 'a, V: 'a, H> {
+pub struct StaticHashRing<K, V, H> {
     hash: H,
     nodes: Vec<Node<K, V>>,
-    ring: Vec<VirtualNode<'a, K, V>>,
+    ring: Vec<VirtualNode>,
+
+    // The hash codes of `ring` laid out for fast lookups (this must be updated along with `ring`).
+    lookup_index: layout::LookupIndex,
+    states: Vec<NodeState>,
+
+    // The virtual nodes removed by `take` and the like (in the order of the removals).
+    taken: Vec<VirtualNode>,
+
+    // The counts of the remaining virtual nodes of each node.
+    vnode_counts: Vec<usize>,
+    live_nodes: usize,
+    read_policy: ReadPolicy,
+    ring_id: Option<u64>,
+    generation: u64,
+    journal: journal::JournalSlot<K>,
+    clock: Arc<dyn Clock + Send + Sync>,
+
+    // The count of the nodes which have exclusion deadlines (including expired ones).
+    excluded_nodes: usize,
+    disabled_nodes: usize,
+
+    // The count of the nodes which have removal deadlines (including expired ones).
+    removal_nodes: usize,
+    observer: observer::ObserverSlot,
+    #[cfg(feature = "http-admin")]
+    history: admin::ChangeHistory,
 }
-impl<'a, K: 'a, V: 'a, H> StaticHashRing<'a, K, V, H>
+impl<K, V, H> StaticHashRing<K, V, H>
     where K: Hash + Eq + Ord,
           H: RingHash
 {
@@ -171,188 +588,2406 @@ impl<'a, K: 'a, V: 'a, H> StaticHashRing<'a, K, V, H>
 
         // Removes duplicate nodes
         nodes.sort_by(|a, b| a.key.cmp(&b.key));
-        for i in (1..nodes.len()).rev() {
-            if nodes[i].key == nodes[i - 1].key {
-                nodes.swap_remove(i);
-            }
-        }
+        nodes.dedup_by(|a, b| a.key == b.key);
+        Self::from_sorted_nodes(hash, nodes, &[])
+    }
 
-        let mut this = StaticHashRing {
-            hash: hash,
-            nodes: nodes,
-            ring: Vec::new(),
-        };
-        this.build_ring();
+    /// Makes a new `StaticHashRing` instance whose virtual nodes have the pre-assigned hash codes (tokens).
+    ///
+    /// Each element of `nodes` is a pair of a node and its tokens,
+    /// and the tokens are used instead of the hash codes derived by `RingHash::hash_vnode`
+    /// (the quantity of the node is replaced with the count of its tokens).
+    /// This allows honoring a layout decided externally (e.g., tokens managed by operators).
+    ///
+    /// `hash` is still used for hashing items.
+    /// Note that specs (see `spec`) carry no tokens, so the rings rebuilt from them derive the hash codes.
+    ///
+    /// If multiple nodes which have the same key are contained in `nodes`,
+    /// all of those nodes but first one are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let nodes = vec![(Node::new("foo"), vec![100, 300]), (Node::new("bar"), vec![200])];
+    /// let ring = StaticHashRing::from_tokens(DefaultHash, nodes.into_iter());
+    ///
+    /// assert_eq!(ring.len(), 3);
+    /// assert_eq!(ring.nodes()[1].quantity, 2);
+    /// assert_eq!(ring.calc_node_by_hash(150).map(|n| n.key), Some("bar"));
+    /// assert_eq!(ring.calc_node_by_hash(250).map(|n| n.key), Some("foo"));
+    /// assert_eq!(ring.calc_node_by_hash(301).map(|n| n.key), Some("foo"));
+    /// ```
+    pub fn from_tokens<I>(hash: H, nodes: I) -> Self
+        where I: Iterator<Item = (Node<K, V>, Vec<u64>)>
+    {
+        let mut nodes = nodes.map(|(node, tokens)| (node.quantity(tokens.len()), tokens)).collect::<Vec<_>>();
+        nodes.sort_by(|a, b| a.0.key.cmp(&b.0.key));
+        nodes.dedup_by(|a, b| a.0.key == b.0.key);
+        let (nodes, cached) = nodes.into_iter().map(|(node, tokens)| (node, Some(tokens))).unzip::<_, _, Vec<_>, Vec<_>>();
+        Self::from_sorted_nodes(hash, nodes, &cached)
+    }
+
+    // Makes a new instance from `nodes` which are sorted by their keys and have no duplicates.
+    //
+    // See `build_ring` for `cached`.
+    fn from_sorted_nodes(hash: H, nodes: Vec<Node<K, V>>, cached: &[Option<Vec<u64>>]) -> Self {
+        let mut this = Self::unbuilt(hash, nodes);
+        this.build_ring(cached);
+        this.debug_assert_valid();
         this
     }
 
-    fn build_ring(&mut self) {
+    // Makes a new instance which has `nodes` but no virtual nodes.
+    fn unbuilt(hash: H, nodes: Vec<Node<K, V>>) -> Self {
+        StaticHashRing {
+            hash,
+            states: vec![NodeState::default(); nodes.len()],
+            nodes,
+            ring: Vec::new(),
+            lookup_index: layout::LookupIndex::default(),
+            taken: Vec::new(),
+            vnode_counts: Vec::new(),
+            live_nodes: 0,
+            read_policy: ReadPolicy::default(),
+            ring_id: None,
+            generation: 0,
+            journal: journal::JournalSlot::new(),
+            clock: Arc::new(SystemClock),
+            excluded_nodes: 0,
+            disabled_nodes: 0,
+            removal_nodes: 0,
+            observer: observer::ObserverSlot::new(),
+            #[cfg(feature = "http-admin")]
+            history: admin::ChangeHistory::default(),
+        }
+    }
+
+    // Builds the ring by reusing `cached[i]` (if any) for the vnode hashes of `self.nodes[i]`.
+    //
+    // A cache holds the hashes of the vnodes whose sequence numbers are less than its length (in any order),
+    // and it is only used if its length does not exceed the quantity of the node.
+    fn build_ring(&mut self, cached: &[Option<Vec<u64>>]) {
         assert!(self.ring.is_empty());
 
         let ring_size = self.nodes.iter().map(|n| n.quantity).sum();
 
         let mut ring = Vec::with_capacity(ring_size);
-        for node in self.nodes.iter() {
-            for i in 0..node.quantity {
-                let hash = self.hash.hash_vnode(&node.key, i);
-                let node = unsafe { &*(node as *const _) as &'a _ };
-                let vnode = VirtualNode {
-                    hash: hash,
-                    node: node,
+        for (index, node) in self.nodes.iter().enumerate() {
+            let cache = match cached.get(index) {
+                Some(Some(hashes)) if hashes.len() <= node.quantity => &hashes[..],
+                _ => &[],
+            };
+            for seq in 0..node.quantity {
+                let hash = match cache.get(seq) {
+                    Some(&hash) => hash,
+                    None => self.hash.hash_vnode(&node.key, seq),
                 };
-                ring.push(vnode);
+                ring.push(VirtualNode { hash, index, seq });
             }
         }
+        let nodes = &self.nodes;
+        ring.sort_by_key(|vn| (vn.hash, &nodes[vn.index].key));
+        self.lookup_index = layout::LookupIndex::new(ring.iter().map(|vn| vn.hash).collect());
         self.ring = ring;
-        self.ring.sort_by_key(|vn| (vn.hash, &vn.node.key));
+        self.vnode_counts = self.nodes.iter().map(|n| n.quantity).collect();
+        self.live_nodes = self.vnode_counts.iter().filter(|&&c| c > 0).count();
     }
-}
-impl<'a, K: 'a, V: 'a, H> StaticHashRing<'a, K, V, H>
-    where H: RingHash
-{
-    /// Returns the candidate nodes for `item`.
+
+    /// Removes a virtual node for `item`, choosing the owner of the node among the candidates
+    /// with the probability proportional to `weight`, and returns the reference to the node.
     ///
-    /// The higher priority node is located in front of the returned candidate sequence.
-    pub fn calc_candidates<T: Hash>(&self, item: &T) -> Candidates<K, V> {
-        let item_hash = self.hash.hash_item(item);
-        let start =
-            self.ring.binary_search_by_key(&(item_hash, 0), |vn| (vn.hash, 1)).err().unwrap();
-        Candidates::new(start, self.nodes.len(), &self.ring)
+    /// For example, `weight` can be used to make claims respect the capacities of heterogeneous nodes.
+    /// Nodes which have non-positive weights are never chosen.
+    ///
+    /// The choice is deterministic (i.e., the same item in the same ring state selects the same node).
+    /// The removed virtual node is the one which has the highest priority for `item`
+    /// among the virtual nodes of the chosen node.
+    pub fn take_weighted<T: Hash + ?Sized, F>(&mut self, item: &T, weight: F) -> Option<&Node<K, V>>
+        where F: Fn(&Node<K, V>) -> f64
+    {
+        let item_hash = self.hash.hash_item(&item);
+        let started = self.observer.start();
+        let start = self.locate(item_hash);
+
+        // Weighted rendezvous hashing among the distinct candidates.
+        let mut chosen = None;
+        let mut min_score = f64::INFINITY;
+        for i in self.candidate_vnodes(start) {
+            let node = &self.nodes[self.ring[i].index];
+            let w = weight(node);
+            if w.is_nan() || w <= 0.0 {
+                continue;
+            }
+            let score = rendezvous_score(self.hash.hash_item(&(item_hash, &node.key)), w);
+            if score < min_score {
+                min_score = score;
+                chosen = Some(i);
+            }
+        }
+        chosen.map(move |i| self.remove_vnode(i, item_hash, started).node)
     }
 
-    /// Removes the virtual node which associated to `item` and returns the reference to the node.
-    pub fn take<T: Hash>(&mut self, item: &T) -> Option<&Node<K, V>> {
-        self.take_if(item, |_| true)
+    /// Returns the index of the node which has the key `key`.
+    ///
+    /// The index is stable unless nodes are added or removed by `apply`, and it is the same as
+    /// the one used by index-yielding APIs (e.g., `calc_candidate_indices`).
+    pub fn node_index(&self, key: &K) -> Option<usize> {
+        self.nodes.binary_search_by(|n| n.key.cmp(key)).ok()
     }
 
-    /// Removes the virtual node which has the highest priority for `item`
-    /// among satisfying the predicate `f`,
-    /// and returns the reference to the node.
-    pub fn take_if<T: Hash, F>(&mut self, item: &T, f: F) -> Option<&Node<K, V>>
-        where F: Fn(&Node<K, V>) -> bool
+    /// Returns the node which has the key `key`.
+    ///
+    /// Since the nodes are sorted by their keys, this is a binary search.
+    /// As with `BTreeMap::get`, `key` may be any borrowed form of the key type.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let nodes = vec![Node::new("foo".to_owned()).value(1), Node::new("bar".to_owned()).value(2)];
+    /// let ring = StaticHashRing::new(DefaultHash, nodes.into_iter());
+    /// assert_eq!(ring.find_node("bar").map(|n| n.value), Some(2));
+    /// assert_eq!(ring.find_node("baz"), None);
+    /// ```
+    pub fn find_node<Q>(&self, key: &Q) -> Option<&Node<K, V>>
+        where K: Borrow<Q>,
+              Q: Ord + ?Sized
     {
-        let item_hash = self.hash.hash_item(item);
-        let start =
-            self.ring.binary_search_by_key(&(item_hash, 0), |vn| (vn.hash, 1)).err().unwrap();
-        let vnode_index = CandidateVnodes::new(start, self.nodes.len(), &self.ring)
-            .find(|&i| f(&self.ring[i].node));
-        if let Some(index) = vnode_index {
-            Some(self.ring.remove(index).node)
-        } else {
-            None
-        }
-    }
-}
-impl<'a, K: 'a, V: 'a, H> StaticHashRing<'a, K, V, H> {
-    /// Returns the count of the virtual nodes in this ring.
-    pub fn len(&self) -> usize {
-        self.ring.len()
+        self.nodes
+            .binary_search_by(|n| n.key.borrow().cmp(key))
+            .ok()
+            .map(|i| &self.nodes[i])
     }
 
-    /// Returns the reference to the real nodes contained in this ring.
+    /// Returns the mutable reference to the value of the node which has the key `key`.
     ///
-    /// Note that the order of the returning nodes are undefined.
-    pub fn nodes(&self) -> &[Node<K, V>] {
-        &self.nodes[..]
+    /// Since the placement does not depend on the values,
+    /// they can be updated in place (e.g., health statuses or connection handles) without rebuilding the ring.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let mut ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).value(true)));
+    /// *ring.node_value_mut(&1).unwrap() = false;
+    /// assert_eq!(ring.nodes().iter().map(|n| n.value).collect::<Vec<_>>(), [true, false, true]);
+    /// assert_eq!(ring.node_value_mut(&3), None);
+    /// ```
+    pub fn node_value_mut(&mut self, key: &K) -> Option<&mut V> {
+        self.node_index(key).map(move |i| &mut self.nodes[i].value)
     }
-}
 
-/// An iterator which represents a sequence of the candidate nodes for an item.
-///
-/// The higher priority node is placed in front of this sequence.
-///
-/// This is created by calling `StaticHashRing::calc_candidates` method.
-pub struct Candidates<'a, K: 'a, V: 'a>(CandidateVnodes<'a, K, V>);
-impl<'a, K: 'a, V: 'a> Candidates<'a, K, V> {
-    fn new(start: usize, nodes: usize, ring: &'a [VirtualNode<'a, K, V>]) -> Self {
-        Candidates(CandidateVnodes::new(start, nodes, ring))
-    }
-}
-impl<'a, K: 'a, V: 'a> Iterator for Candidates<'a, K, V> {
-    type Item = &'a Node<K, V>;
-    fn next(&mut self) -> Option<Self::Item> {
-        self.0.next().map(|i| self.0.ring[i].node)
+    /// Makes the node `key` replica-only (or a normal node if `replica_only` is `false`).
+    ///
+    /// Replica-only nodes (e.g., read replicas or canary hosts) appear in candidate sequences,
+    /// but they are never placed at the front of the sequences as long as there are other candidates.
+    /// That is, a replica-only node which would be the primary node for an item
+    /// is moved just after the first normal node.
+    ///
+    /// Returns `false` if this ring does not contain the node.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let nodes = vec![Node::new("foo").quantity(5), Node::new("bar").quantity(5)];
+    /// let mut ring = StaticHashRing::new(DefaultHash, nodes.into_iter());
+    /// assert_eq!(ring.calc_candidates(&"aa").map(|n| n.key).collect::<Vec<_>>(),
+    ///            ["bar", "foo"]);
+    ///
+    /// ring.set_replica_only(&"bar", true);
+    /// assert_eq!(ring.calc_candidates(&"aa").map(|n| n.key).collect::<Vec<_>>(),
+    ///            ["foo", "bar"]);
+    /// ```
+    pub fn set_replica_only(&mut self, key: &K, replica_only: bool) -> bool {
+        if let Some(i) = self.node_index(key) {
+            self.states[i].replica_only = replica_only;
+            true
+        } else {
+            false
+        }
     }
-}
 
-struct CandidateVnodes<'a, K: 'a, V: 'a> {
-    start: usize,
-    nodes: usize,
-    ring: &'a [VirtualNode<'a, K, V>],
-    count: usize,
-    seens: SplaySet<usize>,
-}
-impl<'a, K: 'a, V: 'a> CandidateVnodes<'a, K, V> {
-    fn new(start: usize, nodes: usize, ring: &'a [VirtualNode<'a, K, V>]) -> Self {
-        CandidateVnodes {
-            start: start,
-            nodes: nodes,
-            ring: ring,
-            count: 0,
-            seens: Default::default(),
+    /// Changes the quantity of the node `key` to `quantity`.
+    ///
+    /// Only the virtual nodes of the node are added (or removed), and they are merged into the ring
+    /// without rebuilding it, so the quantity can be changed repeatedly at low cost
+    /// (e.g., ramping up the traffic of a new node from 1% to 100% of its share).
+    /// The resulting placements are the same as those of a ring built with the new quantity.
+    ///
+    /// Virtual nodes which are removed by `take` and the like stay removed,
+    /// and the ones whose sequence numbers exceed the new quantity are discarded.
+    /// If the quantity is changed, the generation of this ring is incremented.
+    ///
+    /// Returns `false` if this ring does not contain the node.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let nodes = (0..3).map(|i| Node::new(i).quantity(100)).chain(Some(Node::new(3).quantity(1)));
+    /// let mut ring = StaticHashRing::new(DefaultHash, nodes);
+    /// for &quantity in &[10, 100] {
+    ///     assert!(ring.set_quantity(&3, quantity));
+    ///     assert_eq!(ring.len(), 300 + quantity);
+    /// }
+    /// assert!(ring == StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(100))));
+    /// assert!(!ring.set_quantity(&4, 10));
+    /// ```
+    pub fn set_quantity(&mut self, key: &K, quantity: usize) -> bool {
+        let index = match self.node_index(key) {
+            None => return false,
+            Some(index) => index,
+        };
+        let old_quantity = self.nodes[index].quantity;
+        if quantity == old_quantity {
+            return true;
         }
-    }
-}
-impl<'a, K: 'a, V: 'a> Iterator for CandidateVnodes<'a, K, V> {
-    type Item = usize;
-    fn next(&mut self) -> Option<Self::Item> {
-        while self.seens.len() < self.nodes && self.count < self.ring.len() {
-            let index = self.start;
-            if let Some(vn) = self.ring.get(index) {
-                let key_addr: usize = unsafe { std::mem::transmute(&vn.node.key) };
-                self.start += 1;
-                self.count += 1;
-                if self.seens.contains(&key_addr) {
-                    continue;
+
+        let mut removed = 0;
+        self.ring.retain(|vn| {
+            let retained = vn.index != index || vn.seq < quantity;
+            removed += !retained as usize;
+            retained
+        });
+        self.taken.retain(|vn| vn.index != index || vn.seq < quantity);
+
+        let mut vnodes = {
+            let key = &self.nodes[index].key;
+            (old_quantity..quantity)
+                .map(|seq| {
+                    VirtualNode {
+                        hash: self.hash.hash_vnode(key, seq),
+                        index,
+                        seq,
+                    }
+                })
+                .collect::<Vec<_>>()
+        };
+        vnodes.sort_by_key(|vn| vn.hash);
+
+        // Merges the new virtual nodes into the ring.
+        if !vnodes.is_empty() {
+            let nodes = &self.nodes;
+            let old_ring = mem::take(&mut self.ring);
+            let mut ring = Vec::with_capacity(old_ring.len() + vnodes.len());
+            let mut vnodes = vnodes.iter().cloned().peekable();
+            for vnode in old_ring {
+                let sort_key = (vnode.hash, &nodes[vnode.index].key);
+                while let Some(new) = vnodes.next_if(|new| (new.hash, key) < sort_key) {
+                    ring.push(new);
                 }
-                self.seens.insert(key_addr);
-                return Some(index);
-            } else {
-                self.start = 0;
+                ring.push(vnode);
             }
+            ring.extend(vnodes);
+            self.ring = ring;
         }
-        None
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
+        self.lookup_index = layout::LookupIndex::new(self.ring.iter().map(|vn| vn.hash).collect());
 
-    #[test]
-    fn it_works() {
-        let mut nodes = Vec::new();
-        nodes.push(Node::new("foo").quantity(5));
-        nodes.push(Node::new("bar").quantity(5));
-        nodes.push(Node::new("baz").quantity(1));
-        nodes.push(Node::new("baz").quantity(2)); // ignored (duplicate key)
+        let was_live = self.vnode_counts[index] > 0;
+        self.vnode_counts[index] = self.vnode_counts[index] + vnodes.len() - removed;
+        self.nodes[index].quantity = quantity;
+        match (was_live, self.vnode_counts[index] > 0) {
+            (false, true) => self.live_nodes += 1,
+            (true, false) => self.live_nodes -= 1,
+            _ => {}
+        }
+        self.generation += 1;
+        self.debug_assert_valid();
+        true
+    }
 
-        let ring = StaticHashRing::new(DefaultHash, nodes.into_iter());
-        assert_eq!(ring.len(), 11);
-        assert_eq!(ring.nodes().len(), 3);
+    /// Returns the distinct nodes in ring order, starting after the first virtual node of `node_key`.
+    ///
+    /// The "first virtual node" is the one which has the smallest hash code among the virtual nodes of the node.
+    /// The node itself is not contained in the resulting sequence.
+    /// This is useful for implementing protocols defined in terms of ring adjacency
+    /// (e.g., gossip repair or token handoff).
+    ///
+    /// If the ring has no virtual nodes of `node_key`, this returns `None`.
+    pub fn successors(&self, node_key: &K) -> Option<Candidates<'_, K, V>> {
+        self.ring.iter().position(|vn| self.nodes[vn.index].key == *node_key).map(|i| {
+            let mut vnodes = self.candidate_vnodes(i + 1).ignore_replica_only();
+            vnodes.mark_seen(self.ring[i].index);
+            Candidates(vnodes)
+        })
+    }
 
-        assert_eq!(ring.calc_candidates(&"aa").map(|n| &n.key).collect::<Vec<_>>(),
-                   [&"bar", &"foo", &"baz"]);
-        assert_eq!(ring.calc_candidates(&"bb").map(|n| &n.key).collect::<Vec<_>>(),
-                   [&"foo", &"bar", &"baz"]);
+    /// Returns the hash codes (tokens) of the virtual nodes of `node_key` remaining in this ring, in ascending order.
+    ///
+    /// If this ring has no such node, the iterator is empty.
+    /// Tokens can be passed to `StaticHashRing::from_tokens` for reproducing the layout of this ring
+    /// (e.g., via external configuration tooling).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+    /// let tokens = ring.nodes().iter().map(|n| (n.clone(), ring.tokens_of(&n.key).collect())).collect::<Vec<_>>();
+    ///
+    /// let other = StaticHashRing::from_tokens(DefaultHash, tokens.into_iter());
+    /// assert_eq!(other.fingerprint(), ring.fingerprint());
+    /// assert_eq!(ring.tokens_of(&3).count(), 0);
+    /// ```
+    pub fn tokens_of<'a>(&'a self, node_key: &K) -> impl Iterator<Item = u64> + 'a {
+        let index = self.node_index(node_key);
+        self.ring.iter().filter(move |vn| Some(vn.index) == index).map(|vn| vn.hash)
     }
 
-    #[test]
-    fn take_works() {
-        let mut nodes = Vec::new();
-        nodes.push(Node::new("foo").quantity(5));
-        nodes.push(Node::new("bar").quantity(5));
-        nodes.push(Node::new("baz").quantity(1));
+    /// Re-applies the removal described by `record`.
+    ///
+    /// This is intended to recover the claims recorded by a `TakeJournal`
+    /// to a ring which is built from the same nodes.
+    /// The journal is not notified of replayed removals.
+    ///
+    /// Returns `false` if there is no virtual node corresponding to `record`.
+    pub fn replay_take(&mut self, record: &TakeRecord<K>) -> bool {
+        let result = self.ring
+            .binary_search_by(|vn| (vn.hash, &self.nodes[vn.index].key).cmp(&(record.vnode_hash, record.node_key)));
+        if let Ok(index) = result {
+            self.detach_vnode(index);
+            self.generation = std::cmp::max(self.generation, record.generation);
+            true
+        } else {
+            false
+        }
+    }
 
-        let mut ring = StaticHashRing::new(DefaultHash, nodes.into_iter());
-        assert_eq!(ring.take(&"aa").map(|n| n.key).unwrap(), "bar");
-        assert_eq!(ring.take(&"aa").map(|n| n.key).unwrap(), "foo");
-        assert_eq!(ring.take(&"aa").map(|n| n.key).unwrap(), "bar");
-        assert_eq!(ring.take(&"aa").map(|n| n.key).unwrap(), "bar");
-        assert_eq!(ring.take(&"aa").map(|n| n.key).unwrap(), "foo");
-        assert_eq!(ring.take(&"aa").map(|n| n.key).unwrap(), "foo");
-        assert_eq!(ring.take(&"aa").map(|n| n.key).unwrap(), "foo");
-        assert_eq!(ring.take(&"aa").map(|n| n.key).unwrap(), "bar");
-        assert_eq!(ring.take(&"aa").map(|n| n.key).unwrap(), "baz");
+    /// Puts the virtual nodes of `node_key` removed by `take` and the like back into this ring.
+    ///
+    /// This is useful for failover scenarios (e.g., temporarily skipping a dead node, then reinstating it)
+    /// without rebuilding the ring.
+    /// If any virtual nodes are restored, the generation of this ring is incremented.
+    /// The journal is not notified of restorations.
+    ///
+    /// Returns the count of the restored virtual nodes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let mut ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(2)));
+    /// let owner = ring.calc_node(&"foo").map(|n| n.key).unwrap();
+    /// while ring.take_if(&"foo", |c| c.node.key == owner).is_some() {}
+    /// assert_ne!(ring.calc_node(&"foo").map(|n| n.key), Some(owner));
+    ///
+    /// assert_eq!(ring.restore(&owner), 2);
+    /// assert_eq!(ring.calc_node(&"foo").map(|n| n.key), Some(owner));
+    /// assert_eq!(ring.len(), 6);
+    /// ```
+    pub fn restore(&mut self, node_key: &K) -> usize {
+        let index = match self.node_index(node_key) {
+            None => return 0,
+            Some(index) => index,
+        };
+        let restored = self.taken.iter().filter(|vn| vn.index == index).cloned().collect::<Vec<_>>();
+        if restored.is_empty() {
+            return 0;
+        }
+        self.taken.retain(|vn| vn.index != index);
+        if self.vnode_counts[index] == 0 {
+            self.live_nodes += 1;
+        }
+        self.vnode_counts[index] += restored.len();
+        for vnode in &restored {
+            let key = &self.nodes[index].key;
+            let nodes = &self.nodes;
+            let i = self.ring
+                .binary_search_by(|vn| (vn.hash, &nodes[vn.index].key).cmp(&(vnode.hash, key)))
+                .unwrap_or_else(|i| i);
+            self.ring.insert(i, *vnode);
+            self.lookup_index.insert(i, vnode.hash);
+        }
+        self.generation += 1;
+        self.debug_assert_valid();
+        restored.len()
+    }
+}
+impl<K, V, H> StaticHashRing<K, V, H>
+    where H: RingHash
+{
+    /// Returns the candidate nodes for `item`.
+    ///
+    /// The higher priority node is located in front of the returned candidate sequence.
+    ///
+    /// `item` may be unsized (e.g., `str` or `[u8]`).
+    /// References are hashed as their referents, so an item is placed identically
+    /// whether it is passed as `"foo"`, `&"foo"`, or `&String::from("foo")`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+    /// assert!(ring.calc_candidates("foo").eq(ring.calc_candidates(&"foo")));
+    /// assert!(ring.calc_candidates("foo").eq(ring.calc_candidates(&String::from("foo"))));
+    /// ```
+    pub fn calc_candidates<T: Hash + ?Sized>(&self, item: &T) -> Candidates<'_, K, V> {
+        self.calc_candidates_by_hash(self.hash.hash_item(&item))
+    }
+
+    /// Returns the candidate nodes for `item`, or `Err(EmptyRing)` if this ring has no virtual nodes.
+    ///
+    /// A ring becomes empty if the total quantity of its nodes is zero,
+    /// or if all of its virtual nodes have been removed by `take` and the like.
+    /// Otherwise this is the same as `calc_candidates`
+    /// (note that the candidates may still be empty if every node is excluded).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash, EmptyRing};
+    ///
+    /// let mut ring = StaticHashRing::new(DefaultHash, vec![Node::new("foo").quantity(1)].into_iter());
+    /// assert_eq!(ring.try_calc_candidates(&"bar").map(|c| c.count()), Ok(1));
+    ///
+    /// ring.take(&"bar");
+    /// assert!(ring.is_empty());
+    /// assert_eq!(ring.try_calc_candidates(&"bar").err(), Some(EmptyRing));
+    /// ```
+    pub fn try_calc_candidates<T: Hash + ?Sized>(&self, item: &T) -> Result<Candidates<'_, K, V>, EmptyRing> {
+        if self.ring.is_empty() {
+            Err(EmptyRing)
+        } else {
+            Ok(self.calc_candidates(item))
+        }
+    }
+
+    /// Returns the candidate nodes for the item whose hash code is `item_hash`.
+    ///
+    /// This is equivalent to `calc_candidates`, except that `item_hash` is used as it is
+    /// instead of being computed by `RingHash::hash_item`
+    /// (e.g., it is cached or received from another system).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash, RingHash};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+    /// let item_hash = DefaultHash.hash_item(&"foo");
+    /// assert!(ring.calc_candidates_by_hash(item_hash).eq(ring.calc_candidates(&"foo")));
+    /// ```
+    pub fn calc_candidates_by_hash(&self, item_hash: u64) -> Candidates<'_, K, V> {
+        let started = self.observer.start();
+        let start = self.locate(item_hash);
+        if let Some(started) = started {
+            self.notify_lookup(item_hash, self.candidate_vnodes(start).next(), started);
+        }
+        Candidates(self.candidate_vnodes(start))
+    }
+
+    /// Returns the candidate nodes for the item which consists of the raw bytes `bytes`.
+    ///
+    /// The hash code of the item is computed by `RingHash::hash_bytes`,
+    /// so routing keys received as wire bytes are placed compatibly with non-Rust peers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash, RingHash};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+    /// let item_hash = DefaultHash.hash_bytes(b"foo");
+    /// assert!(ring.calc_candidates_bytes(b"foo").eq(ring.calc_candidates_by_hash(item_hash)));
+    /// ```
+    pub fn calc_candidates_bytes(&self, bytes: &[u8]) -> Candidates<'_, K, V> {
+        self.calc_candidates_by_hash(self.hash.hash_bytes(bytes))
+    }
+
+    /// Returns the highest priority node for `item` (i.e., `calc_candidates(item).next()`).
+    ///
+    /// If this ring is empty, this returns `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+    /// assert_eq!(ring.calc_node(&"foo"), ring.calc_candidates(&"foo").next());
+    ///
+    /// let empty = StaticHashRing::new(DefaultHash, (0..0).map(Node::new));
+    /// assert_eq!(empty.calc_node(&"foo"), None);
+    /// ```
+    pub fn calc_node<T: Hash + ?Sized>(&self, item: &T) -> Option<&Node<K, V>> {
+        self.calc_node_by_hash(self.hash.hash_item(&item))
+    }
+
+    /// Returns the highest priority node for the item whose hash code is `item_hash`.
+    ///
+    /// See `calc_candidates_by_hash`.
+    pub fn calc_node_by_hash(&self, item_hash: u64) -> Option<&Node<K, V>> {
+        let started = self.observer.start();
+        let vnode_index = self.candidate_vnodes(self.locate(item_hash)).next();
+        if let Some(started) = started {
+            self.notify_lookup(item_hash, vnode_index, started);
+        }
+        vnode_index.map(|i| &self.nodes[self.ring[i].index])
+    }
+
+    /// Returns the candidate nodes for the sub key which is derived from `item` by `f`.
+    ///
+    /// This is equivalent to `self.calc_candidates(&f(item))`.
+    /// Items which have the same sub key (e.g., the same tenant identifier) are co-located.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..10).map(|i| Node::new(i).quantity(10)));
+    ///
+    /// // (tenant id, item id)
+    /// let tenant = |item: &(u32, u32)| item.0;
+    /// assert_eq!(ring.calc_candidates_by(&(7, 1), tenant).next(),
+    ///            ring.calc_candidates_by(&(7, 2), tenant).next());
+    /// ```
+    pub fn calc_candidates_by<T, U, F>(&self, item: &T, f: F) -> Candidates<'_, K, V>
+        where U: Hash,
+              F: FnOnce(&T) -> U
+    {
+        self.calc_candidates(&f(item))
+    }
+
+    /// Returns the candidate nodes for `key` routed by its Redis-style hash tag.
+    ///
+    /// The bytes extracted by `hash_tag` function are hashed as a byte slice (`&[u8]`),
+    /// so keys which have the same tag (e.g., `"{user1000}.following"` and `"{user1000}.followers"`)
+    /// are guaranteed to be routed to the same nodes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..10).map(|i| Node::new(i).quantity(10)));
+    /// assert_eq!(ring.calc_candidates_by_hash_tag(&"{user1000}.following").next(),
+    ///            ring.calc_candidates_by_hash_tag(&"{user1000}.followers").next());
+    /// ```
+    pub fn calc_candidates_by_hash_tag<B>(&self, key: &B) -> Candidates<'_, K, V>
+        where B: AsRef<[u8]>
+    {
+        self.calc_candidates(&hash_tag(key.as_ref()))
+    }
+
+    /// Returns the indices of the candidate nodes for `item`.
+    ///
+    /// The index `i` corresponds to the node `self.nodes()[i]`.
+    /// The order of the resulting sequence is the same as `calc_candidates`.
+    pub fn calc_candidate_indices<T: Hash + ?Sized>(&self, item: &T) -> CandidateIndices<'_, K, V> {
+        self.calc_candidate_indices_by_hash(self.hash.hash_item(&item))
+    }
+
+    /// Returns the indices of the candidate nodes for the item whose hash code is `item_hash`.
+    ///
+    /// See `calc_candidates_by_hash`.
+    pub fn calc_candidate_indices_by_hash(&self, item_hash: u64) -> CandidateIndices<'_, K, V> {
+        let started = self.observer.start();
+        let start = self.locate(item_hash);
+        if let Some(started) = started {
+            self.notify_lookup(item_hash, self.candidate_vnodes(start).next(), started);
+        }
+        CandidateIndices(self.candidate_vnodes(start))
+    }
+
+    /// Returns the candidate nodes for `item` along with the virtual nodes which select them.
+    ///
+    /// Each element is a tuple of the ring distance (i.e., the clockwise distance from the hash code of `item`
+    /// to the hash code of the virtual node, modulo `2^64`), the hash code of the virtual node, and the node.
+    /// The order of the resulting sequence is the same as `calc_candidates`,
+    /// so the distances are ascending except where replica-only nodes are moved back.
+    ///
+    /// The distances can be used as tie-breaking or debugging signals (e.g., how close a key is to a boundary).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash, RingHash};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+    /// let item_hash = DefaultHash.hash_item(&"foo");
+    /// let scored = ring.calc_candidates_with_scores(&"foo").collect::<Vec<_>>();
+    ///
+    /// assert!(scored.iter().map(|s| s.2).eq(ring.calc_candidates(&"foo")));
+    /// assert!(scored.iter().all(|&(distance, vnode_hash, _)| item_hash.wrapping_add(distance) == vnode_hash));
+    /// assert!(scored.windows(2).all(|w| w[0].0 <= w[1].0));
+    /// ```
+    pub fn calc_candidates_with_scores<T: Hash + ?Sized>(&self, item: &T) -> ScoredCandidates<'_, K, V> {
+        let item_hash = self.hash.hash_item(&item);
+        let started = self.observer.start();
+        let start = self.locate(item_hash);
+        if let Some(started) = started {
+            self.notify_lookup(item_hash, self.candidate_vnodes(start).next(), started);
+        }
+        ScoredCandidates {
+            vnodes: self.candidate_vnodes(start),
+            item_hash,
+        }
+    }
+
+    /// Returns the bitmap of the indices of the top-`k` candidate nodes for `item`.
+    ///
+    /// The `i`-th bit of the result is set if the node `self.nodes()[i]` is one of the candidates.
+    /// This makes it cheap to combine candidates with other node sets (e.g., a mask of healthy nodes).
+    ///
+    /// # Panics
+    ///
+    /// Panics if this ring contains more than 128 real nodes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..10).map(|i| Node::new(i).quantity(5)));
+    /// let healthy = !0b1; // the node `0` is down
+    /// let available = ring.candidate_mask(&"foo", 3) & healthy;
+    /// assert!(available.count_ones() >= 2);
+    /// ```
+    pub fn candidate_mask<T: Hash + ?Sized>(&self, item: &T, k: usize) -> u128 {
+        assert!(self.nodes.len() <= 128,
+                "Too many nodes for a 128-bit mask: {}",
+                self.nodes.len());
+        self.calc_candidate_indices(item).take(k).fold(0, |mask, i| mask | (1 << i))
+    }
+
+    /// Returns the shard identifier of `item`.
+    ///
+    /// The identifier is the hash code of the virtual node of the primary node of `item`
+    /// (i.e., the node returned by `calc_node`), so it is stable until the membership
+    /// or the node states of this ring change.
+    /// Items which have the same identifier are always routed to the same nodes.
+    ///
+    /// If no node can be primary (e.g., this ring has no virtual nodes), this returns `None`.
+    pub fn shard_id<T: Hash + ?Sized>(&self, item: &T) -> Option<u64> {
+        let start = self.locate(self.hash.hash_item(&item));
+        self.candidate_vnodes(start).next().map(|i| self.ring[i].hash)
+    }
+
+    /// Returns the assignment table of `items`, which maps each item to the key of its primary node.
+    ///
+    /// The entries are in the same order as `items`.
+    /// The table consists of plain references, so it can easily be serialized
+    /// (e.g., to push precomputed routing tables to processes which cannot afford live hashing).
+    ///
+    /// This is a collected form of `assign_all`, so items which cannot be assigned are skipped
+    /// (e.g., if this ring has no virtual nodes, the resulting table is empty).
+    pub fn assignments<'k, T, I>(&self, items: I) -> Vec<(&'k T, &K)>
+        where T: 'k + Hash,
+              I: IntoIterator<Item = &'k T>
+    {
+        self.assign_all(items).map(|(item, node)| (item, &node.key)).collect()
+    }
+
+    /// Returns the first `n` candidate nodes for `item`.
+    ///
+    /// If `n` exceeds the count of the selectable nodes (i.e., the live nodes minus the disabled or
+    /// excluded ones), the resulting sequence is truncated to that count.
+    /// So the iterator knows its exact length.
+    pub fn calc_candidates_n<T: Hash + ?Sized>(&self, item: &T, n: usize) -> CandidatesN<'_, K, V> {
+        let candidates = self.calc_candidates(item);
+        CandidatesN {
+            remaining: std::cmp::min(n, candidates.len()),
+            candidates,
+        }
+    }
+
+    /// Returns the position of the node identified by `node_key` in the candidates for `item`.
+    ///
+    /// `Some(0)` means that the node is the primary node of `item`,
+    /// `Some(1)` means that it is the second candidate, and so on.
+    /// If no such node exists or the node has no virtual nodes, this returns `None`.
+    ///
+    /// Storage nodes can use this to decide which replication role to play for incoming data.
+    pub fn replica_index_of<T: Hash + ?Sized>(&self, item: &T, node_key: &K) -> Option<usize>
+        where K: PartialEq
+    {
+        self.calc_candidates(item).position(|n| n.key == *node_key)
+    }
+
+    /// Returns an iterator over the successive virtual nodes from the position of `item`.
+    ///
+    /// Unlike `calc_candidates`, nodes are not deduplicated and replica-only nodes are not treated specially,
+    /// so this can be used for building custom selection algorithms (e.g., CRUSH-like retries).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(4)));
+    ///
+    /// let walk = ring.walk_vnodes(&"foo").collect::<Vec<_>>();
+    /// assert_eq!(walk.len(), 12);
+    /// assert_eq!(walk[0].1, ring.calc_candidates(&"foo").next().unwrap());
+    /// ```
+    pub fn walk_vnodes<T: Hash + ?Sized>(&self, item: &T) -> VnodeWalk<'_, K, V> {
+        VnodeWalk::new(&self.ring, &self.nodes, self.locate(self.hash.hash_item(&item)))
+    }
+
+    /// Removes the virtual node which associated to `item` and returns the description of the removed one.
+    ///
+    /// If this ring has no virtual nodes (e.g., all of them have already been taken),
+    /// this returns `None` and leaves this ring (including its generation) untouched.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let mut ring = StaticHashRing::new(DefaultHash, vec![Node::new("foo").quantity(2)].into_iter());
+    ///
+    /// let first = ring.take(&"bar").map(|t| (t.node.key, t.seq, t.remaining)).unwrap();
+    /// assert_eq!((first.0, first.2), ("foo", 1));
+    ///
+    /// let second = ring.take(&"bar").unwrap();
+    /// assert!(second.is_last());
+    /// assert_ne!(second.seq, first.1);
+    /// assert!(ring.take(&"bar").is_none());
+    /// ```
+    pub fn take<T: Hash + ?Sized>(&mut self, item: &T) -> Option<TakenVnode<'_, K, V>> {
+        self.take_if(item, |_| true)
+    }
+
+    /// Removes the virtual node which has the highest priority for `item`
+    /// among satisfying the predicate `f`,
+    /// and returns the description of the removed one.
+    ///
+    /// `f` is called with the context of each candidate virtual node in priority order
+    /// until it returns `true`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let mut ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+    /// let candidates = ring.calc_candidates(&"foo").map(|n| n.key).collect::<Vec<_>>();
+    ///
+    /// // Skips the nodes which were already tried.
+    /// let mut tried = vec![candidates[0]];
+    /// let taken = ring.take_if(&"foo", |c| {
+    ///     if tried.contains(&c.node.key) {
+    ///         false
+    ///     } else {
+    ///         tried.push(c.node.key);
+    ///         true
+    ///     }
+    /// });
+    /// assert_eq!(taken.map(|t| t.node.key), Some(candidates[1]));
+    /// ```
+    pub fn take_if<T: Hash + ?Sized, F>(&mut self, item: &T, mut f: F) -> Option<TakenVnode<'_, K, V>>
+        where F: FnMut(&TakeContext<K, V>) -> bool
+    {
+        let item_hash = self.hash.hash_item(&item);
+        let started = self.observer.start();
+        let start = self.locate(item_hash);
+        let vnode_index = self.candidate_vnodes(start).find(|&i| {
+            f(&TakeContext {
+                node: &self.nodes[self.ring[i].index],
+                vnode_hash: self.ring[i].hash,
+                position: i,
+            })
+        });
+        vnode_index.map(move |i| self.remove_vnode(i, item_hash, started))
+    }
+
+    /// Removes the virtual node which associated to the item whose hash code is `item_hash`,
+    /// and returns the description of the removed one.
+    ///
+    /// See `calc_candidates_by_hash`.
+    pub fn take_by_hash(&mut self, item_hash: u64) -> Option<TakenVnode<'_, K, V>> {
+        let started = self.observer.start();
+        let vnode_index = self.candidate_vnodes(self.locate(item_hash)).next();
+        vnode_index.map(move |i| self.remove_vnode(i, item_hash, started))
+    }
+
+    // Removes the virtual node at `index` which was chosen for `item_hash` by the take started at `started`.
+    fn remove_vnode(&mut self, index: usize, item_hash: u64, started: Option<Instant>) -> TakenVnode<'_, K, V> {
+        if let Some(started) = started {
+            self.notify_take(item_hash, index, started);
+        }
+        let vnode = self.detach_vnode(index);
+        self.generation += 1;
+        self.journal.record(&TakeRecord {
+            vnode_hash: vnode.hash,
+            node_key: &self.nodes[vnode.index].key,
+            generation: self.generation,
+        });
+        #[cfg(feature = "http-admin")]
+        self.history.record(vnode.hash, vnode.index, self.generation);
+        TakenVnode {
+            node: &self.nodes[vnode.index],
+            hash: vnode.hash,
+            seq: vnode.seq,
+            remaining: self.vnode_counts[vnode.index],
+        }
+    }
+
+    /// Returns the virtual node which succeeds the hash code `hash` on this ring,
+    /// as a pair of its position (in the order of `vnodes`) and its owner.
+    ///
+    /// The successor is the first virtual node whose hash code is greater than or equal to `hash`
+    /// (i.e., a virtual node owns the range which ends at its own hash code inclusively).
+    /// If there is no such virtual node, the search wraps around to the first virtual node (at position `0`).
+    /// Among virtual nodes which have the same hash code, the first one in ring order is returned.
+    ///
+    /// This is the primitive which every lookup starts from, and it ignores
+    /// the replica-only and excluded states of nodes.
+    /// If this ring is empty, this returns `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+    /// let hashes = ring.vnodes().map(|(h, _, _)| h).collect::<Vec<_>>();
+    ///
+    /// assert_eq!(ring.successor_of(hashes[3]).map(|(i, _)| i), Some(3));
+    /// assert_eq!(ring.successor_of(hashes[3] + 1).map(|(i, _)| i), Some(4));
+    /// assert_eq!(ring.successor_of(hashes[29] + 1).map(|(i, _)| i), Some(0));
+    /// ```
+    pub fn successor_of(&self, hash: u64) -> Option<(usize, &Node<K, V>)> {
+        if self.ring.is_empty() {
+            return None;
+        }
+        let i = self.locate(hash) % self.ring.len();
+        Some((i, &self.nodes[self.ring[i].index]))
+    }
+
+    // Returns the index of the first virtual node which has a hash code greater than or equal to `item_hash`.
+    //
+    // Note that the result may be equal to `self.ring.len()` (i.e., wrapped around).
+    fn locate(&self, item_hash: u64) -> usize {
+        self.lookup_index.locate(item_hash)
+    }
+}
+impl<K, V, H> StaticHashRing<K, V, H> {
+    /// Returns the count of the real nodes which have one or more virtual nodes in this ring.
+    ///
+    /// This is the maximum length of candidate sequences.
+    pub fn live_nodes(&self) -> usize {
+        self.live_nodes
+    }
+
+    /// Returns the count of the virtual nodes in this ring.
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+
+    /// Returns `true` if this ring has no virtual nodes.
+    pub fn is_empty(&self) -> bool {
+        self.ring.is_empty()
+    }
+
+    /// Touches the internal arrays of this ring to pre-fault their pages.
+    ///
+    /// Calling this method after building (or deserializing) a ring
+    /// avoids latency spikes on the first lookups.
+    pub fn prefetch(&self) {
+        let mut acc = 0;
+        for vnode in &self.ring {
+            acc ^= vnode.hash ^ vnode.index as u64;
+        }
+        for state in &self.states {
+            acc ^= state.replica_only as u64;
+        }
+        hint::black_box(acc);
+    }
+
+    // Returns `true` if no node is replica-only, excluded, disabled or scheduled for removal
+    // (i.e., the owner of the virtual node at a lookup position is always the primary node).
+    fn has_plain_states(&self) -> bool {
+        self.excluded_nodes == 0 && self.disabled_nodes == 0 && self.removal_nodes == 0 &&
+        self.states.iter().all(|s| !s.replica_only)
+    }
+
+    fn candidate_vnodes(&self, start: usize) -> CandidateVnodes<'_, K, V> {
+        let mut vnodes = CandidateVnodes::new(start, self.live_nodes, &self.ring, &self.nodes, &self.states);
+        if self.excluded_nodes > 0 || self.removal_nodes > 0 {
+            vnodes.now = Some(self.clock.now());
+        }
+        if self.excluded_nodes > 0 || self.disabled_nodes > 0 || self.removal_nodes > 0 {
+            vnodes.unseen_excluded = (0..self.nodes.len())
+                .filter(|&i| self.vnode_counts[i] > 0 && vnodes.is_excluded(i))
+                .count();
+        }
+        vnodes
+    }
+
+    fn detach_vnode(&mut self, index: usize) -> VirtualNode {
+        let vnode = self.ring.remove(index);
+        self.lookup_index.remove(index);
+        self.vnode_counts[vnode.index] -= 1;
+        if self.vnode_counts[vnode.index] == 0 {
+            self.live_nodes -= 1;
+        }
+        self.taken.push(vnode);
+        vnode
+    }
+
+    /// Returns the reference to the real nodes contained in this ring.
+    ///
+    /// The nodes are sorted by their keys,
+    /// and the position of each node is its stable index (see `node_index` method).
+    pub fn nodes(&self) -> &[Node<K, V>] {
+        &self.nodes[..]
+    }
+
+    /// Returns the reference to the node located at `index`.
+    pub fn node_at(&self, index: usize) -> Option<&Node<K, V>> {
+        self.nodes.get(index)
+    }
+
+    /// Returns an iterator over the keys and the mutable values of the nodes (in the order of `nodes`).
+    ///
+    /// See also `node_value_mut`.
+    pub fn values_mut(&mut self) -> impl Iterator<Item = (&K, &mut V)> {
+        self.nodes.iter_mut().map(|n| (&n.key, &mut n.value))
+    }
+
+    /// Returns an iterator over the virtual nodes remaining in this ring (in ring order).
+    ///
+    /// Each element is a tuple of the hash code of a virtual node, the node which owns it,
+    /// and its sequence number (i.e., the `vnode_seq` passed to `RingHash::hash_vnode`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, RingHash, StaticHashRing, DefaultHash};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+    /// assert_eq!(ring.vnodes().len(), 30);
+    /// for (hash, node, seq) in ring.vnodes() {
+    ///     assert_eq!(hash, DefaultHash.hash_vnode(&node.key, seq));
+    /// }
+    /// ```
+    pub fn vnodes(&self) -> Vnodes<'_, K, V> {
+        Vnodes {
+            ring: self.ring.iter(),
+            nodes: &self.nodes,
+        }
+    }
+
+    /// Returns the format tag of this ring (e.g., `"ring-v1-siphash13"`).
+    ///
+    /// The tag consists of `RING_ALGORITHM_VERSION` and the identifier of the hasher.
+    /// Serialized ring specifications and snapshots should embed this tag,
+    /// so that a ring is never reconstructed by an incompatible algorithm.
+    pub fn format_tag(&self) -> String
+        where H: RingHash
+    {
+        format!("{}-{}", RING_ALGORITHM_VERSION, self.hash.algorithm_id())
+    }
+
+    /// Checks whether `tag` is equal to the format tag of this ring.
+    pub fn check_format_tag(&self, tag: &str) -> Result<(), FormatMismatch>
+        where H: RingHash
+    {
+        let expected = self.format_tag();
+        if expected == tag {
+            Ok(())
+        } else {
+            Err(FormatMismatch {
+                expected,
+                actual: tag.to_owned(),
+            })
+        }
+    }
+
+    /// Returns the fingerprint of the placement of this ring.
+    ///
+    /// The fingerprint is derived from the format tag and the virtual nodes (and their owners),
+    /// so rings which have the same fingerprint place items identically (with high probability).
+    /// The generation of a ring does not affect its fingerprint.
+    pub fn fingerprint(&self) -> u64
+        where K: Hash,
+              H: RingHash
+    {
+        let mut hasher = SipHasher13::new();
+        self.format_tag().hash(&mut hasher);
+        for vn in &self.ring {
+            (vn.hash, &self.nodes[vn.index].key, self.states[vn.index].replica_only).hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    /// Returns `true` if this ring and `other` are equivalent in placement, ignoring the values of nodes.
+    ///
+    /// Two rings are topologically equal if they have the same format tag (i.e., hasher identity),
+    /// the same node keys and quantities, and the same virtual nodes and replica-only flags.
+    /// Unlike `fingerprint`, this comparison is exact.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let a = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).value("a").quantity(10)));
+    /// let b = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).value("b").quantity(10)));
+    /// let c = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).value("a").quantity(11)));
+    /// assert!(a.topology_eq(&b));
+    /// assert!(!a.topology_eq(&c));
+    /// ```
+    pub fn topology_eq<W, H2>(&self, other: &StaticHashRing<K, W, H2>) -> bool
+        where K: Eq,
+              H: RingHash,
+              H2: RingHash
+    {
+        self.format_tag() == other.format_tag() && self.nodes.len() == other.nodes.len() &&
+        self.nodes
+            .iter()
+            .zip(other.nodes.iter())
+            .all(|(a, b)| a.key == b.key && a.quantity == b.quantity) &&
+        self.ring.len() == other.ring.len() &&
+        self.ring.iter().zip(other.ring.iter()).all(|(a, b)| {
+            a.hash == b.hash && a.index == b.index &&
+            self.states[a.index].replica_only == other.states[b.index].replica_only
+        })
+    }
+
+    /// Returns the generation of this ring.
+    ///
+    /// The generation starts from `0` and is incremented each time a virtual node is removed
+    /// (and each time virtual nodes are restored by `restore` or a quantity is changed by `set_quantity`).
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Sets the journal which receives a record for each virtual node removed by `take` and its variants.
+    ///
+    /// If `journal` is `None`, the current journal is unset.
+    pub fn set_journal(&mut self, journal: Option<Box<dyn TakeJournal<K> + Send>>) {
+        self.journal.set(journal);
+    }
+}
+
+/// Clones the ring, including its remaining virtual nodes, node states, generation, and clock.
+///
+/// The journal is not cloned (i.e., the clone has no journal), while the observer is shared.
+impl<K: Clone, V: Clone, H: Clone> Clone for StaticHashRing<K, V, H> {
+    fn clone(&self) -> Self {
+        StaticHashRing {
+            hash: self.hash.clone(),
+            nodes: self.nodes.clone(),
+            ring: self.ring.clone(),
+            lookup_index: self.lookup_index.clone(),
+            states: self.states.clone(),
+            taken: self.taken.clone(),
+            vnode_counts: self.vnode_counts.clone(),
+            live_nodes: self.live_nodes,
+            read_policy: self.read_policy,
+            ring_id: self.ring_id,
+            generation: self.generation,
+            journal: journal::JournalSlot::new(),
+            clock: self.clock.clone(),
+            excluded_nodes: self.excluded_nodes,
+            disabled_nodes: self.disabled_nodes,
+            removal_nodes: self.removal_nodes,
+            observer: self.observer.clone(),
+            #[cfg(feature = "http-admin")]
+            history: self.history.clone(),
+        }
+    }
+}
+
+/// Two rings are equal if they have the same hash, nodes, remaining virtual nodes, node states,
+/// and read policy.
+///
+/// Operational states such as the generation, the journal, and the clock are not compared.
+/// See also `topology_eq`.
+impl<K: PartialEq, V: PartialEq, H: PartialEq> PartialEq for StaticHashRing<K, V, H> {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash && self.nodes == other.nodes && self.ring == other.ring &&
+        self.states == other.states && self.read_policy == other.read_policy
+    }
+}
+
+/// The context of a candidate virtual node passed to the predicate of `StaticHashRing::take_if`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TakeContext<'a, K: 'a, V: 'a> {
+    /// The node which owns the virtual node.
+    pub node: &'a Node<K, V>,
+
+    /// The hash code of the virtual node.
+    pub vnode_hash: u64,
+
+    /// The position of the virtual node in ring order (i.e., the index in `StaticHashRing::vnodes`).
+    pub position: usize,
+}
+
+/// A virtual node removed by `StaticHashRing::take` and the like.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TakenVnode<'a, K: 'a, V: 'a> {
+    /// The node which owned the virtual node.
+    pub node: &'a Node<K, V>,
+
+    /// The hash code of the virtual node.
+    pub hash: u64,
+
+    /// The sequence number of the virtual node.
+    pub seq: usize,
+
+    /// The count of the virtual nodes of `node` remaining in the ring.
+    pub remaining: usize,
+}
+impl<'a, K: 'a, V: 'a> TakenVnode<'a, K, V> {
+    /// Returns `true` if the last virtual node of the node was removed, otherwise `false`.
+    pub fn is_last(&self) -> bool {
+        self.remaining == 0
+    }
+}
+
+/// An iterator which represents a sequence of the candidate nodes for an item.
+///
+/// The higher priority node is placed in front of this sequence.
+///
+/// The exact count of the remaining candidates is known in advance (i.e., this is an `ExactSizeIterator`),
+/// and iterating from the back yields the lowest priority candidates first.
+///
+/// This is created by calling `StaticHashRing::calc_candidates` method.
+pub struct Candidates<'a, K: 'a, V: 'a>(CandidateVnodes<'a, K, V>);
+impl<'a, K: 'a, V: 'a> Iterator for Candidates<'a, K, V> {
+    type Item = &'a Node<K, V>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|i| self.0.owner(i))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+impl<'a, K: 'a, V: 'a> DoubleEndedIterator for Candidates<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(|i| self.0.owner(i))
+    }
+}
+impl<'a, K: 'a, V: 'a> ExactSizeIterator for Candidates<'a, K, V> {}
+impl<'a, K: 'a, V: 'a> FusedIterator for Candidates<'a, K, V> {}
+impl<'a, K: 'a, V: 'a> Clone for Candidates<'a, K, V> {
+    fn clone(&self) -> Self {
+        Candidates(self.0.clone())
+    }
+}
+
+/// An iterator which represents a sequence of the indices of the candidate nodes for an item.
+///
+/// This is created by calling `StaticHashRing::calc_candidate_indices` method.
+pub struct CandidateIndices<'a, K: 'a, V: 'a>(CandidateVnodes<'a, K, V>);
+impl<'a, K: 'a, V: 'a> Iterator for CandidateIndices<'a, K, V> {
+    type Item = usize;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|i| self.0.ring[i].index)
+    }
+}
+
+/// An iterator which represents a sequence of the candidate nodes for an item,
+/// each of which is paired with its ring distance and the hash code of its virtual node.
+///
+/// This is created by calling `StaticHashRing::calc_candidates_with_scores` method.
+pub struct ScoredCandidates<'a, K: 'a, V: 'a> {
+    vnodes: CandidateVnodes<'a, K, V>,
+    item_hash: u64,
+}
+impl<'a, K: 'a, V: 'a> Iterator for ScoredCandidates<'a, K, V> {
+    type Item = (u64, u64, &'a Node<K, V>);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.vnodes.next().map(|i| {
+            let vnode_hash = self.vnodes.ring[i].hash;
+            (vnode_hash.wrapping_sub(self.item_hash), vnode_hash, self.vnodes.owner(i))
+        })
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.vnodes.size_hint()
+    }
+}
+impl<'a, K: 'a, V: 'a> ExactSizeIterator for ScoredCandidates<'a, K, V> {}
+
+/// An iterator which represents the first `n` candidate nodes for an item.
+///
+/// This is created by calling `StaticHashRing::calc_candidates_n` method.
+pub struct CandidatesN<'a, K: 'a, V: 'a> {
+    candidates: Candidates<'a, K, V>,
+    remaining: usize,
+}
+impl<'a, K: 'a, V: 'a> Iterator for CandidatesN<'a, K, V> {
+    type Item = &'a Node<K, V>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            None
+        } else {
+            self.remaining -= 1;
+            self.candidates.next()
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+impl<'a, K: 'a, V: 'a> ExactSizeIterator for CandidatesN<'a, K, V> {}
+
+/// An iterator over the successive virtual nodes from the position of an item (without deduplication).
+///
+/// Each element is a pair of the hash code of a virtual node and the node which owns it.
+/// This visits every virtual node exactly once (wrapping around the ring).
+///
+/// This is created by calling `StaticHashRing::walk_vnodes` method.
+pub struct VnodeWalk<'a, K: 'a, V: 'a> {
+    ring: &'a [VirtualNode],
+    nodes: &'a [Node<K, V>],
+    start: usize,
+    count: usize,
+}
+impl<'a, K: 'a, V: 'a> VnodeWalk<'a, K, V> {
+    fn new(ring: &'a [VirtualNode], nodes: &'a [Node<K, V>], start: usize) -> Self {
+        VnodeWalk {
+            ring,
+            nodes,
+            start,
+            count: 0,
+        }
+    }
+}
+impl<'a, K: 'a, V: 'a> Iterator for VnodeWalk<'a, K, V> {
+    type Item = (u64, &'a Node<K, V>);
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.count == self.ring.len() {
+            return None;
+        }
+        let vnode = &self.ring[(self.start + self.count) % self.ring.len()];
+        self.count += 1;
+        Some((vnode.hash, &self.nodes[vnode.index]))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.ring.len() - self.count;
+        (n, Some(n))
+    }
+}
+impl<'a, K: 'a, V: 'a> ExactSizeIterator for VnodeWalk<'a, K, V> {}
+
+/// An iterator over the virtual nodes of a ring (in ring order).
+///
+/// Each element is a tuple of the hash code of a virtual node, the node which owns it,
+/// and its sequence number.
+///
+/// This is created by calling `StaticHashRing::vnodes` method.
+pub struct Vnodes<'a, K: 'a, V: 'a> {
+    ring: slice::Iter<'a, VirtualNode>,
+    nodes: &'a [Node<K, V>],
+}
+impl<'a, K: 'a, V: 'a> Iterator for Vnodes<'a, K, V> {
+    type Item = (u64, &'a Node<K, V>, usize);
+    fn next(&mut self) -> Option<Self::Item> {
+        let nodes = self.nodes;
+        self.ring.next().map(|vn| (vn.hash, &nodes[vn.index], vn.seq))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.ring.size_hint()
+    }
+}
+impl<'a, K: 'a, V: 'a> ExactSizeIterator for Vnodes<'a, K, V> {}
+
+// The number of the inline words of `SeenSet`.
+const INLINE_WORDS: usize = 4;
+
+// The set of the indices of the seen nodes.
+//
+// If a ring has up to `64 * INLINE_WORDS` nodes, the inline words are used as a bitset indexed by node indices.
+// Otherwise the inline words keep the first few nodes (most lookups only consume the first few candidates,
+// e.g., `calc_candidates(item).next()`), and the rest are kept in a bitset allocated on the heap
+// (or lent by a `CandidateBuffer`).
+#[derive(Clone)]
+struct SeenSet {
+    len: usize,
+    nodes: usize,
+    inline: [u64; INLINE_WORDS],
+    heap: Vec<u64>,
+}
+impl SeenSet {
+    // Makes an empty set of the indices less than `nodes`.
+    fn new(nodes: usize) -> Self {
+        SeenSet {
+            len: 0,
+            nodes,
+            inline: [0; INLINE_WORDS],
+            heap: Vec::new(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn contains(&self, node_index: usize) -> bool {
+        let bit = 1 << (node_index % 64);
+        if self.nodes <= 64 * INLINE_WORDS {
+            self.inline[node_index / 64] & bit != 0
+        } else if self.len <= INLINE_WORDS {
+            self.inline[..self.len].contains(&(node_index as u64))
+        } else {
+            self.inline.contains(&(node_index as u64)) || self.heap[node_index / 64] & bit != 0
+        }
+    }
+
+    // Inserts `node_index`, which must not be contained in this set.
+    fn insert(&mut self, node_index: usize) {
+        let bit = 1 << (node_index % 64);
+        if self.nodes <= 64 * INLINE_WORDS {
+            self.inline[node_index / 64] |= bit;
+        } else if self.len < INLINE_WORDS {
+            self.inline[self.len] = node_index as u64;
+        } else {
+            if self.heap.is_empty() {
+                self.heap.resize(self.nodes.div_ceil(64), 0);
+            }
+            self.heap[node_index / 64] |= bit;
+        }
+        self.len += 1;
+    }
+}
+
+struct CandidateVnodes<'a, K: 'a, V: 'a> {
+    start: usize,
+    nodes: usize,
+    ring: &'a [VirtualNode],
+    owners: &'a [Node<K, V>],
+    states: &'a [NodeState],
+    count: usize,
+
+    // The maximum count of the virtual nodes to visit (usually `ring.len()`).
+    max_count: usize,
+    seens: SeenSet,
+    primary_found: bool,
+    deferred: VecDeque<usize>,
+
+    // The time for checking exclusions (`None` if there are no excluded nodes).
+    now: Option<SystemTime>,
+
+    // The count of the live nodes which are excluded (or disabled) and not seen yet.
+    unseen_excluded: usize,
+
+    // Whether excluded (or disabled) nodes are skipped.
+    skip_excluded: bool,
+
+    // The remaining candidates (materialized by the first `next_back` call).
+    tail: Option<VecDeque<usize>>,
+}
+impl<'a, K: 'a, V: 'a> CandidateVnodes<'a, K, V> {
+    fn new(start: usize,
+           nodes: usize,
+           ring: &'a [VirtualNode],
+           owners: &'a [Node<K, V>],
+           states: &'a [NodeState])
+           -> Self {
+        CandidateVnodes {
+            start,
+            nodes,
+            ring,
+            owners,
+            states,
+            count: 0,
+            max_count: ring.len(),
+            seens: SeenSet::new(owners.len()),
+            primary_found: false,
+            deferred: VecDeque::new(),
+            now: None,
+            unseen_excluded: 0,
+            skip_excluded: true,
+            tail: None,
+        }
+    }
+
+    // Returns the node which owns the virtual node at `index`.
+    fn owner(&self, index: usize) -> &'a Node<K, V> {
+        &self.owners[self.ring[index].index]
+    }
+
+    // Disables the special handling of replica-only nodes (i.e., yields nodes in pure ring order).
+    fn ignore_replica_only(mut self) -> Self {
+        self.primary_found = true;
+        self
+    }
+
+    // Makes excluded (and disabled) nodes be yielded as usual.
+    //
+    // This must be called before iterating.
+    fn include_excluded(mut self) -> Self {
+        self.skip_excluded = false;
+        self.unseen_excluded = 0;
+        self
+    }
+
+    fn is_excluded(&self, node_index: usize) -> bool {
+        if !self.skip_excluded {
+            return false;
+        }
+        let state = &self.states[node_index];
+        if state.disabled {
+            return true;
+        }
+        match self.now {
+            None => false,
+            Some(now) => {
+                state.excluded_until.is_some_and(|deadline| now < deadline) ||
+                state.removal_at.is_some_and(|deadline| deadline <= now)
+            }
+        }
+    }
+
+    // Makes the bitset of seen nodes (if needed) reuse the memory of `heap`.
+    //
+    // This must be called before iterating.
+    fn with_seen_buffer(mut self, mut heap: Vec<u64>) -> Self {
+        heap.clear();
+        self.seens.heap = heap;
+        self
+    }
+
+    // Returns the memory of the bitset of seen nodes for reuse.
+    fn into_seen_buffer(self) -> Vec<u64> {
+        self.seens.heap
+    }
+
+    // Returns `true` if the node cannot be primary (i.e., it is replica-only or scheduled for removal).
+    fn is_replica_only(&self, node_index: usize) -> bool {
+        let state = &self.states[node_index];
+        state.replica_only || (state.removal_at.is_some() && self.now.is_some())
+    }
+
+    fn is_seen(&self, node_index: usize) -> bool {
+        self.seens.contains(node_index)
+    }
+
+    fn mark_seen(&mut self, node_index: usize) {
+        self.seens.insert(node_index);
+        if self.is_excluded(node_index) {
+            self.unseen_excluded -= 1;
+        }
+    }
+}
+impl<'a, K: 'a, V: 'a> Clone for CandidateVnodes<'a, K, V> {
+    fn clone(&self) -> Self {
+        CandidateVnodes {
+            start: self.start,
+            nodes: self.nodes,
+            ring: self.ring,
+            owners: self.owners,
+            states: self.states,
+            count: self.count,
+            max_count: self.max_count,
+            seens: self.seens.clone(),
+            primary_found: self.primary_found,
+            deferred: self.deferred.clone(),
+            now: self.now,
+            unseen_excluded: self.unseen_excluded,
+            skip_excluded: self.skip_excluded,
+            tail: self.tail.clone(),
+        }
+    }
+}
+impl<'a, K: 'a, V: 'a> Iterator for CandidateVnodes<'a, K, V> {
+    type Item = usize;
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(ref mut tail) = self.tail {
+            return tail.pop_front();
+        }
+        if self.primary_found {
+            if let Some(index) = self.deferred.pop_front() {
+                return Some(index);
+            }
+        }
+        if self.seens.len() == 0 && self.nodes > 0 && self.max_count > 0 {
+            // Fast path: the first candidate is usually the vnode at `start`.
+            let index = if self.start < self.ring.len() { self.start } else { 0 };
+            let node_index = self.ring[index].index;
+            if !self.is_replica_only(node_index) && !self.is_excluded(node_index) {
+                self.start = index + 1;
+                self.count += 1;
+                self.mark_seen(node_index);
+                self.primary_found = true;
+                return Some(index);
+            }
+        }
+        while self.seens.len() < self.nodes && self.count < self.max_count {
+            let index = self.start;
+            if let Some(vn) = self.ring.get(index) {
+                let node_index = vn.index;
+                self.start += 1;
+                self.count += 1;
+                if self.is_seen(node_index) {
+                    continue;
+                }
+                self.mark_seen(node_index);
+                if self.is_excluded(node_index) {
+                    continue;
+                }
+                if !self.primary_found {
+                    if self.is_replica_only(node_index) {
+                        // Replica-only nodes are placed after the primary node.
+                        self.deferred.push_back(index);
+                        continue;
+                    }
+                    self.primary_found = true;
+                }
+                return Some(index);
+            } else {
+                self.start = 0;
+            }
+        }
+
+        // There are no nodes which can be primary.
+        self.primary_found = true;
+        self.deferred.pop_front()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // Every live node which is neither seen nor excluded is yielded exactly once.
+        let n = match self.tail {
+            Some(ref tail) => tail.len(),
+            None => self.nodes - self.seens.len() - self.unseen_excluded + self.deferred.len(),
+        };
+        (n, Some(n))
+    }
+}
+impl<'a, K: 'a, V: 'a> DoubleEndedIterator for CandidateVnodes<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.tail.is_none() {
+            let mut tail = VecDeque::with_capacity(self.len());
+            tail.extend(self.by_ref());
+            self.tail = Some(tail);
+        }
+        self.tail.as_mut().and_then(|tail| tail.pop_back())
+    }
+}
+impl<'a, K: 'a, V: 'a> ExactSizeIterator for CandidateVnodes<'a, K, V> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::vec_init_then_push)]
+    fn it_works() {
+        let mut nodes = Vec::new();
+        nodes.push(Node::new("foo").quantity(5));
+        nodes.push(Node::new("bar").quantity(5));
+        nodes.push(Node::new("baz").quantity(1));
+        nodes.push(Node::new("baz").quantity(2)); // ignored (duplicate key)
+
+        let ring = StaticHashRing::new(DefaultHash, nodes.into_iter());
+        assert_eq!(ring.len(), 11);
+        assert_eq!(ring.nodes().len(), 3);
+
+        assert_eq!(ring.calc_candidates(&"aa").map(|n| &n.key).collect::<Vec<_>>(),
+                   [&"bar", &"foo", &"baz"]);
+        assert_eq!(ring.calc_candidates(&"bb").map(|n| &n.key).collect::<Vec<_>>(),
+                   [&"foo", &"bar", &"baz"]);
+    }
+
+    #[test]
+    fn find_node_works() {
+        let nodes = (0..100).map(|i| Node::new(format!("node{:02}", i)).value(i));
+        let ring = StaticHashRing::new(DefaultHash, nodes);
+        for i in 0..100 {
+            let key = format!("node{:02}", i);
+            assert_eq!(ring.find_node(key.as_str()).map(|n| n.value), Some(i));
+            assert_eq!(ring.find_node(&key).map(|n| n.value), Some(i));
+        }
+        assert_eq!(ring.find_node("node100"), None);
+    }
+
+    #[test]
+    fn node_value_mut_works() {
+        let mut ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).value(0).quantity(10)));
+        let before = ring.calc_candidates(&"foo").map(|n| n.key).collect::<Vec<_>>();
+
+        *ring.node_value_mut(&2).unwrap() += 5;
+        for (&k, v) in ring.values_mut() {
+            *v += k;
+        }
+        assert_eq!(ring.nodes().iter().map(|n| n.value).collect::<Vec<_>>(), [0, 1, 7]);
+        assert_eq!(ring.calc_candidates(&"foo").map(|n| n.key).collect::<Vec<_>>(), before);
+        assert_eq!(ring.node_value_mut(&3), None);
+    }
+
+    #[test]
+    fn send_sync_works() {
+        use std::sync::Arc;
+        use std::thread;
+
+        fn assert_send_sync<T: Send + Sync + 'static>(_: &T) {}
+
+        let ring = Arc::new(StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10))));
+        assert_send_sync(&ring);
+        let expected = ring.calc_candidates(&"foo").map(|n| n.key).collect::<Vec<_>>();
+        let handles = (0..4)
+            .map(|_| {
+                let ring = ring.clone();
+                thread::spawn(move || ring.calc_candidates(&"foo").map(|n| n.key).collect::<Vec<_>>())
+            })
+            .collect::<Vec<_>>();
+        for h in handles {
+            assert_eq!(h.join().unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn prefetch_works() {
+        let nodes = (0..3).map(|i| Node::new(i).quantity(10));
+        let ring = StaticHashRing::new(DefaultHash, nodes);
+        let before = ring.calc_candidates(&"foo").map(|n| n.key).collect::<Vec<_>>();
+        ring.prefetch();
+        assert_eq!(ring.calc_candidates(&"foo").map(|n| n.key).collect::<Vec<_>>(), before);
+    }
+
+    #[test]
+    fn take_works() {
+        let nodes = vec![
+            Node::new("foo").quantity(5),
+            Node::new("bar").quantity(5),
+            Node::new("baz").quantity(1),
+        ];
+
+        let mut ring = StaticHashRing::new(DefaultHash, nodes.into_iter());
+        assert_eq!(ring.take(&"aa").map(|t| t.node.key).unwrap(), "bar");
+        assert_eq!(ring.take(&"aa").map(|t| t.node.key).unwrap(), "foo");
+        assert_eq!(ring.take(&"aa").map(|t| t.node.key).unwrap(), "bar");
+        assert_eq!(ring.take(&"aa").map(|t| t.node.key).unwrap(), "bar");
+        assert_eq!(ring.take(&"aa").map(|t| t.node.key).unwrap(), "foo");
+        assert_eq!(ring.take(&"aa").map(|t| t.node.key).unwrap(), "foo");
+        assert_eq!(ring.take(&"aa").map(|t| t.node.key).unwrap(), "foo");
+        assert_eq!(ring.take(&"aa").map(|t| t.node.key).unwrap(), "bar");
+        assert_eq!(ring.take(&"aa").map(|t| t.node.key).unwrap(), "baz");
+    }
+
+    #[test]
+    fn taken_vnode_works() {
+        let nodes = (0..3).map(|i| Node::new(i).quantity(i + 1));
+        let original = StaticHashRing::new(DefaultHash, nodes.clone());
+        let mut ring = StaticHashRing::new(DefaultHash, nodes);
+        let mut lasts = Vec::new();
+        for i in 0..6 {
+            let (key, hash, seq, remaining, is_last) = ring.take(&i)
+                .map(|t| (t.node.key, t.hash, t.seq, t.remaining, t.is_last()))
+                .unwrap();
+            let expected = original.vnodes().find(|v| v.0 == hash).unwrap();
+            assert_eq!((key, seq), (expected.1.key, expected.2));
+            if is_last {
+                lasts.push(key);
+            }
+            assert_eq!(remaining, ring.vnodes().filter(|v| v.1.key == key).count());
+        }
+        lasts.sort();
+        assert_eq!(lasts, [0, 1, 2]);
+        assert!(ring.take(&0).is_none());
+    }
+
+    #[test]
+    fn scored_candidates_works() {
+        let mut ring = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(10)));
+        let item_hash = DefaultHash.hash_item(&"foo");
+        let walk = ring.walk_vnodes(&"foo").collect::<Vec<_>>();
+        for (distance, vnode_hash, node) in ring.calc_candidates_with_scores(&"foo") {
+            // Each candidate is selected by its first virtual node in the walk.
+            let first = walk.iter().find(|v| v.1.key == node.key).unwrap();
+            assert_eq!((vnode_hash, distance), (first.0, first.0.wrapping_sub(item_hash)));
+        }
+        assert_eq!(ring.calc_candidates_with_scores(&"foo").len(), 4);
+
+        let primary = ring.calc_node(&"foo").unwrap().key;
+        ring.set_replica_only(&primary, true);
+        let scored = ring.calc_candidates_with_scores(&"foo").collect::<Vec<_>>();
+        assert!(scored.iter().map(|s| s.2).eq(ring.calc_candidates(&"foo")));
+        assert!(scored[0].0 > scored[1].0);
+        let empty = StaticHashRing::new(DefaultHash, (0..0).map(Node::new));
+        assert!(empty.calc_candidates_with_scores(&"foo").next().is_none());
+    }
+
+    #[test]
+    fn empty_ring_works() {
+        let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(0)));
+        assert!(ring.is_empty());
+        assert_eq!(ring.try_calc_candidates(&"foo").err(), Some(EmptyRing));
+
+        let mut ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(2)));
+        while ring.take(&"foo").is_some() {}
+        let generation = ring.generation();
+        assert!(ring.take(&"foo").is_none());
+        assert!(ring.take_weighted(&"foo", |_| 1.0).is_none());
+        assert_eq!(ring.generation(), generation);
+        assert_eq!(ring.try_calc_candidates(&"foo").err(), Some(EmptyRing));
+
+        ring.restore(&1);
+        assert_eq!(ring.try_calc_candidates(&"foo").map(|c| c.map(|n| n.key).collect::<Vec<_>>()), Ok(vec![1]));
+    }
+
+    #[test]
+    fn set_quantity_works() {
+        let quantities = [10, 20, 30];
+        let ring_of = |q: &[usize]| StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(q[i])));
+        let mut ring = ring_of(&[10, 0, 30]);
+        assert_eq!(ring.live_nodes(), 2);
+        for &q in &[1, 5, 20] {
+            assert!(ring.set_quantity(&1, q));
+            assert!(ring == ring_of(&[10, q, 30]));
+        }
+        assert_eq!(ring.live_nodes(), 3);
+        assert!(ring.set_quantity(&2, 0));
+        assert!(ring == ring_of(&[10, 20, 0]));
+        assert_eq!(ring.live_nodes(), 2);
+        assert!(!ring.set_quantity(&3, 1));
+
+        // Taken virtual nodes stay taken.
+        let mut ring = ring_of(&quantities);
+        let taken = (0..20).filter_map(|i| ring.take(&i).map(|t| t.hash)).collect::<Vec<_>>();
+        let generation = ring.generation();
+        assert!(ring.set_quantity(&0, 15));
+        assert!(ring.set_quantity(&0, 10));
+        assert_eq!(ring.generation(), generation + 2);
+        assert!(ring.vnodes().all(|v| !taken.contains(&v.0)));
+        for i in 0..3 {
+            ring.restore(&i);
+        }
+        assert!(ring == ring_of(&quantities));
+    }
+
+    #[test]
+    fn take_if_context_works() {
+        let mut ring = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(5)));
+        let vnodes = ring.vnodes().map(|v| (v.0, v.1.key)).collect::<Vec<_>>();
+        let candidates = ring.calc_candidates(&"foo").map(|n| n.key).collect::<Vec<_>>();
+
+        let mut seen = Vec::new();
+        assert!(ring.take_if(&"foo", |c| {
+                seen.push(c.node.key);
+                assert_eq!(vnodes[c.position], (c.vnode_hash, c.node.key));
+                false
+            })
+            .is_none());
+        assert_eq!(seen, candidates);
+
+        let mut calls = 0;
+        let taken = ring.take_if(&"foo", |_| {
+                calls += 1;
+                calls == 3
+            })
+            .map(|t| t.node.key);
+        assert_eq!(taken, Some(candidates[2]));
+    }
+
+    #[test]
+    fn clone_and_eq_works() {
+        let nodes = (0..3).map(|i| Node::new(i).quantity(10));
+        let mut ring = StaticHashRing::new(DefaultHash, nodes.clone());
+        ring.set_journal(Some(Box::new(|_: &TakeRecord<i32>| panic!())));
+        assert!(ring == StaticHashRing::new(DefaultHash, nodes.clone()));
+
+        let mut snapshot = ring.clone();
+        assert!(snapshot == ring);
+        let taken = snapshot.take(&"foo").map(|t| t.node.key);
+        assert!(snapshot != ring);
+        assert_eq!(ring.clone().take(&"foo").map(|t| t.node.key), taken);
+        assert_eq!(snapshot.restore(&taken.unwrap()), 1);
+        assert!(snapshot == ring);
+        assert_eq!(snapshot.generation(), 2);
+
+        snapshot.set_replica_only(&0, true);
+        assert!(snapshot != ring);
+        assert!(ring != StaticHashRing::new(DefaultHash, nodes.take(2)));
+    }
+
+    #[test]
+    fn restore_works() {
+        let nodes = (0..3).map(|i| Node::new(i).quantity(4));
+        let original = StaticHashRing::new(DefaultHash, nodes.clone());
+        let mut ring = StaticHashRing::new(DefaultHash, nodes);
+        for i in 0..8 {
+            ring.take(&i);
+        }
+        while ring.take_if(&"foo", |c| c.node.key == 1).is_some() {}
+        assert_eq!(ring.live_nodes(), 2);
+        let generation = ring.generation();
+
+        assert_eq!(ring.restore(&1), 4);
+        assert_eq!(ring.restore(&1), 0);
+        assert_eq!(ring.restore(&5), 0);
+        assert_eq!(ring.live_nodes(), 3);
+        assert_eq!(ring.generation(), generation + 1);
+        let remaining = ring.len();
+        assert_eq!(ring.restore(&0) + ring.restore(&2), 12 - remaining);
+        assert!(ring.vnodes().map(|v| (v.0, v.2)).eq(original.vnodes().map(|v| (v.0, v.2))));
+        for i in 0..100 {
+            assert_eq!(ring.calc_node(&i), original.calc_node(&i));
+        }
+    }
+
+    #[test]
+    fn candidates_traits_works() {
+        use std::time::Duration;
+
+        let mut ring = StaticHashRing::new(DefaultHash, (0..8).map(|i| Node::new(i).quantity(i % 3 * 2)));
+        ring.set_replica_only(&2, true);
+        ring.exclude_for(&4, Duration::from_secs(10));
+        ring.exclude_for(&6, Duration::from_secs(10)); // has no virtual nodes
+        for i in 0..50 {
+            let forward = ring.calc_candidates(&i).map(|n| n.key).collect::<Vec<_>>();
+            assert_eq!(ring.calc_candidates(&i).len(), forward.len());
+            assert_eq!(forward.len(), 4);
+
+            let mut backward = ring.calc_candidates(&i).rev().map(|n| n.key).collect::<Vec<_>>();
+            backward.reverse();
+            assert_eq!(backward, forward);
+
+            let mut candidates = ring.calc_candidates(&i);
+            candidates.next();
+            assert_eq!(candidates.len(), 3);
+            let last = candidates.next_back().map(|n| n.key);
+            assert_eq!(last, forward.last().cloned());
+            assert_eq!(candidates.len(), 2);
+            assert!(candidates.clone().map(|n| n.key).eq(forward[1..3].iter().cloned()));
+            assert_eq!(candidates.by_ref().count(), 2);
+            assert!(candidates.next().is_none() && candidates.next_back().is_none());
+        }
+
+        let successors = ring.successors(&4).unwrap();
+        assert_eq!(successors.len(), 4);
+        assert_eq!(successors.count(), 4);
+        let successors = ring.successors(&1).unwrap();
+        assert_eq!(successors.len(), 3);
+        assert_eq!(successors.count(), 3);
+    }
+
+    #[test]
+    fn take_weighted_works() {
+        let nodes = vec![Node::new("foo").quantity(1000), Node::new("bar").quantity(1000)];
+        let mut ring = StaticHashRing::new(DefaultHash, nodes.into_iter());
+
+        let weight = |n: &Node<&str, ()>| if n.key == "foo" { 3.0 } else { 1.0 };
+        let mut foo_count = 0;
+        for i in 0..1000 {
+            if ring.take_weighted(&i, weight).unwrap().key == "foo" {
+                foo_count += 1;
+            }
+        }
+        assert!(650 < foo_count && foo_count < 850, "foo_count={}", foo_count);
+        assert_eq!(ring.len(), 1000);
+
+        let weight = |n: &Node<&str, ()>| if n.key == "foo" { 0.0 } else { 1.0 };
+        assert_eq!(ring.take_weighted(&"aa", weight).map(|n| n.key), Some("bar"));
+    }
+
+    #[test]
+    fn journal_works() {
+        use std::sync::{Arc, Mutex};
+
+        let nodes = || {
+            vec![Node::new("foo").quantity(5), Node::new("bar").quantity(5)].into_iter()
+        };
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let mut ring = StaticHashRing::new(DefaultHash, nodes());
+        {
+            let records = records.clone();
+            ring.set_journal(Some(Box::new(move |r: &TakeRecord<&'static str>| {
+                records.lock().unwrap().push((r.vnode_hash, *r.node_key, r.generation));
+            })));
+        }
+        ring.take(&"aa");
+        ring.take(&"bb");
+        assert_eq!(ring.generation(), 2);
+        assert_eq!(records.lock().unwrap().len(), 2);
+
+        // Recovers the claims
+        let mut recovered = StaticHashRing::new(DefaultHash, nodes());
+        for &(vnode_hash, node_key, generation) in records.lock().unwrap().iter() {
+            let record = TakeRecord {
+                vnode_hash,
+                node_key: &node_key,
+                generation,
+            };
+            assert!(recovered.replay_take(&record));
+        }
+        assert_eq!(recovered.generation(), 2);
+        assert_eq!(recovered.calc_candidates(&"cc").collect::<Vec<_>>(),
+                   ring.calc_candidates(&"cc").collect::<Vec<_>>());
+        assert_eq!(recovered.take(&"aa"), ring.take(&"aa"));
+    }
+
+    #[test]
+    fn format_tag_works() {
+        let ring = StaticHashRing::new(DefaultHash, vec![Node::new("foo")].into_iter());
+        assert_eq!(ring.format_tag(), "ring-v1-siphash13");
+        assert!(ring.check_format_tag("ring-v1-siphash13").is_ok());
+        assert!(ring.check_format_tag("ring-v2-siphash13").is_err());
+    }
+
+    #[test]
+    fn unsized_items_work() {
+        let mut ring = StaticHashRing::new(DefaultHash, (0..5).map(|i| Node::new(i).quantity(10)));
+        let bytes = vec![1u8, 2, 3];
+        assert_eq!(ring.calc_node(&bytes[..]), ring.calc_node(&bytes));
+        assert_eq!(ring.calc_candidate_indices("foo").collect::<Vec<_>>(),
+                   ring.calc_candidate_indices(&&&"foo").collect::<Vec<_>>());
+        let all = ring.calc_candidates(&"foo").collect::<Vec<_>>();
+        assert_eq!(ring.calc_read_candidates("foo").collect::<Vec<_>>(), all);
+        assert_eq!(ring.calc_candidates_with_budget("foo", 50).collect::<Vec<_>>(), all);
+        assert_eq!(ring.calc_candidates_with_fallback("foo", ExclusionFallback::Empty).collect::<Vec<_>>(),
+                   all);
+        assert_eq!(ring.snapshot_candidates("foo"), ring.snapshot_candidates(&"foo"));
+        let max = std::time::Duration::from_secs(1);
+        assert_eq!(ring.stable_jitter("foo", max), ring.stable_jitter(&"foo", max));
+
+        let expected = ring.calc_node(&"foo".to_owned()).map(|n| n.key);
+        assert_eq!(ring.take("foo").map(|t| t.node.key), expected);
+        let next = ring.calc_node("foo").map(|n| n.key);
+        assert_eq!(ring.take_if("foo", |_| true).map(|t| t.node.key), next);
+    }
+
+    #[test]
+    fn calc_candidates_bytes_works() {
+        let mut hasher = SipHasher13::new();
+        hasher.write(b"foo");
+        assert_eq!(DefaultHash.hash_bytes(b"foo"), hasher.finish());
+        assert_eq!(DefaultHash::with_keys(0, 0).hash_bytes(b"foo"), hasher.finish());
+
+        let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+        assert_eq!(ring.calc_candidates_bytes(b"foo").collect::<Vec<_>>(),
+                   ring.calc_candidates_by_hash(hasher.finish()).collect::<Vec<_>>());
+        assert_eq!(ring.calc_candidates_bytes(b"").count(), 3);
+    }
+
+    #[test]
+    fn build_hasher_ring_hash_works() {
+        use std::collections::hash_map::RandomState;
+        use std::hash::BuildHasherDefault;
+
+        let hash = BuildHasherRingHash::new(BuildHasherDefault::<SipHasher13>::default());
+        assert_eq!(hash.hash_item(&(1, "foo")), DefaultHash.hash_item(&(1, "foo")));
+        assert_eq!(hash.hash_vnode(&"foo", 3), DefaultHash.hash_vnode(&"foo", 3));
+        assert_eq!(hash.algorithm_id(), "custom");
+
+        let state = RandomState::new();
+        let ring0 = StaticHashRing::new(BuildHasherRingHash::new(state.clone()).with_algorithm_id("random"),
+                                        (0..3).map(|i| Node::new(i).quantity(10)));
+        let ring1 = StaticHashRing::new(BuildHasherRingHash::new(state), (0..3).map(|i| Node::new(i).quantity(10)));
+        assert_eq!(ring0.format_tag(), "ring-v1-random");
+        assert_eq!(ring0.calc_candidates(&"foo").collect::<Vec<_>>(),
+                   ring1.calc_candidates(&"foo").collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn keyed_hash_works() {
+        let nodes = || (0..4).map(|i| Node::new(i).quantity(10));
+        let layout = |ring: &StaticHashRing<i32, (), KeyedHash>| {
+            (0..100).map(|k| ring.calc_node(&k).unwrap().key).collect::<Vec<_>>()
+        };
+        let ring0 = StaticHashRing::new(DefaultHash::with_keys(1, 2), nodes());
+        let ring1 = StaticHashRing::new(DefaultHash::with_keys(1, 2), nodes());
+        let ring2 = StaticHashRing::new(DefaultHash::with_keys(2, 1), nodes());
+        assert_eq!(layout(&ring0), layout(&ring1));
+        assert_ne!(layout(&ring0), layout(&ring2));
+        assert!(ring0.format_tag().starts_with("ring-v1-siphash13-keyed-"));
+        assert_ne!(ring0.format_tag(), ring2.format_tag());
+
+        // The zero keys are the same as the default ones.
+        assert_eq!(DefaultHash::with_keys(0, 0).hash_item(&"foo"), DefaultHash.hash_item(&"foo"));
+        assert_ne!(DefaultHash::with_keys(1, 2).hash_item(&"foo"), DefaultHash.hash_item(&"foo"));
+    }
+
+    #[test]
+    fn fingerprint_works() {
+        let ring0 = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(5)));
+        let mut ring1 = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).value("v").quantity(5)));
+        assert_eq!(ring0.fingerprint(), ring1.fingerprint());
+
+        ring1.set_replica_only(&0, true);
+        assert_ne!(ring0.fingerprint(), ring1.fingerprint());
+        ring1.set_replica_only(&0, false);
+
+        ring1.take(&"foo");
+        let mut ring2 = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(5)));
+        ring2.take(&"foo");
+        assert_ne!(ring0.fingerprint(), ring1.fingerprint());
+        assert_eq!(ring1.fingerprint(), ring2.fingerprint());
+    }
+
+    #[test]
+    fn topology_eq_works() {
+        let ring0 = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(5)));
+        let mut ring1 = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).value("v").quantity(5)));
+        assert!(ring0.topology_eq(&ring1));
+
+        ring1.set_replica_only(&0, true);
+        assert!(!ring0.topology_eq(&ring1));
+        ring1.set_replica_only(&0, false);
+
+        ring1.take(&"foo");
+        assert!(!ring0.topology_eq(&ring1));
+
+        let ring2 = StaticHashRing::new(DefaultHash, (1..4).map(|i| Node::new(i).quantity(5)));
+        assert!(!ring0.topology_eq(&ring2));
+    }
+
+    #[test]
+    fn from_tokens_works() {
+        let tokens = |i: u64| (0..5).map(|j| j * 1000 + i).collect::<Vec<_>>();
+        let ring = StaticHashRing::from_tokens(DefaultHash,
+                                               (0..3).map(|i| (Node::new(i).quantity(100), tokens(i))));
+        assert_eq!(ring.len(), 15);
+        let vnodes = ring.vnodes().map(|(h, n, seq)| (h, n.key, seq)).collect::<Vec<_>>();
+        assert_eq!(vnodes[..4], [(0, 0, 0), (1, 1, 0), (2, 2, 0), (1000, 0, 1)]);
+        assert_eq!(ring.calc_candidates_by_hash(1500).map(|n| n.key).collect::<Vec<_>>(), [0, 1, 2]);
+        assert_eq!(ring.calc_candidates_by_hash(4002).map(|n| n.key).collect::<Vec<_>>(), [2, 0, 1]);
+
+        // Duplicate keys and empty token lists.
+        let ring = StaticHashRing::from_tokens(DefaultHash,
+                                               vec![(Node::new(0), vec![5]), (Node::new(0), vec![1]), (Node::new(1), vec![])]
+                                                   .into_iter());
+        assert_eq!(ring.vnodes().map(|(h, n, _)| (h, n.key)).collect::<Vec<_>>(), [(5, 0)]);
+        assert_eq!((ring.nodes().len(), ring.live_nodes()), (2, 1));
+    }
+
+    #[test]
+    fn tokens_of_works() {
+        let mut ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+        for node in ring.nodes() {
+            let tokens = ring.tokens_of(&node.key).collect::<Vec<_>>();
+            let mut expected = (0..10).map(|seq| DefaultHash.hash_vnode(&node.key, seq)).collect::<Vec<_>>();
+            expected.sort();
+            assert_eq!(tokens, expected);
+        }
+
+        // Taken virtual nodes are excluded, and the layout can be round-tripped.
+        let taken = ring.take(&"foo").map(|t| (t.node.key, t.hash)).unwrap();
+        assert_eq!(ring.tokens_of(&taken.0).count(), 9);
+        assert!(ring.tokens_of(&taken.0).all(|h| h != taken.1));
+        let tokens = ring.nodes().iter().map(|n| (n.clone(), ring.tokens_of(&n.key).collect::<Vec<_>>()));
+        let other = StaticHashRing::from_tokens(DefaultHash, tokens.collect::<Vec<_>>().into_iter());
+        assert_eq!(other.fingerprint(), ring.fingerprint());
+        for item in 0..100 {
+            assert!(other.calc_candidates(&item).map(|n| n.key).eq(ring.calc_candidates(&item).map(|n| n.key)));
+        }
+    }
+
+    #[test]
+    fn successor_of_works() {
+        let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+        let vnodes = ring.vnodes().map(|(h, n, _)| (h, n.key)).collect::<Vec<_>>();
+        for (i, &(hash, key)) in vnodes.iter().enumerate() {
+            assert_eq!(ring.successor_of(hash).map(|(j, n)| (j, n.key)), Some((i, key)));
+            let prev = if i == 0 { 0 } else { vnodes[i - 1].0 + 1 };
+            assert_eq!(ring.successor_of(prev).map(|(j, _)| j), Some(i));
+            assert_eq!(ring.successor_of(hash), ring.calc_node_by_hash(hash).map(|n| (i, n)));
+        }
+        assert_eq!(ring.successor_of(vnodes[29].0 + 1).map(|(i, _)| i), Some(0));
+        assert_eq!(ring.successor_of(u64::MAX).map(|(i, _)| i), Some(0));
+        assert_eq!(ring.successor_of(0).map(|(i, _)| i), Some(0));
+
+        // Virtual nodes which have the same hash code.
+        struct ConstHash(u64);
+        impl RingHash for ConstHash {
+            fn hash_item<T: Hash>(&self, _item: &T) -> u64 {
+                self.0
+            }
+        }
+        let ring = StaticHashRing::new(ConstHash(7), (0..3).map(|i| Node::new(i).quantity(2)));
+        assert_eq!(ring.successor_of(7).map(|(i, n)| (i, n.key)), Some((0, 0)));
+        assert_eq!(ring.successor_of(8).map(|(i, n)| (i, n.key)), Some((0, 0)));
+
+        let empty = StaticHashRing::new(DefaultHash, (0..0).map(Node::new));
+        assert_eq!(empty.successor_of(0), None);
+    }
+
+    #[test]
+    fn successors_works() {
+        let nodes = vec![
+            Node::new("foo").quantity(5),
+            Node::new("bar").quantity(5),
+            Node::new("baz").quantity(1),
+        ];
+        let ring = StaticHashRing::new(DefaultHash, nodes.into_iter());
+        for node in ring.nodes() {
+            let successors = ring.successors(&node.key).unwrap().collect::<Vec<_>>();
+            assert_eq!(successors.len(), 2);
+            assert!(successors.iter().all(|n| n.key != node.key));
+
+            let first = ring.ring.iter().position(|vn| ring.nodes[vn.index].key == node.key).unwrap();
+            let next = (first + 1..)
+                .map(|i| &ring.nodes[ring.ring[i % ring.len()].index])
+                .find(|n| n.key != node.key)
+                .unwrap();
+            assert_eq!(successors[0], next);
+        }
+        assert!(ring.successors(&"qux").is_none());
+    }
+
+    #[test]
+    fn calc_candidates_by_works() {
+        let ring = StaticHashRing::new(DefaultHash, (0..10).map(|i| Node::new(i).quantity(10)));
+        let tenant = |item: &(u32, u32)| item.0;
+        for i in 0..100 {
+            assert!(ring.calc_candidates_by(&(7, i), tenant).eq(ring.calc_candidates(&7)));
+        }
+    }
+
+    #[test]
+    fn calc_node_works() {
+        let mut ring = StaticHashRing::new(DefaultHash, (0..5).map(|i| Node::new(i).quantity(4)));
+        ring.set_replica_only(&0, true);
+        for item in 0..100 {
+            assert_eq!(ring.calc_node(&item), ring.calc_candidates(&item).next());
+            assert_eq!(ring.calc_node_by_hash(DefaultHash.hash_item(&item)), ring.calc_node(&item));
+        }
+        while ring.take(&"foo").is_some() {}
+        assert_eq!(ring.calc_node(&"foo"), None);
+    }
+
+    #[test]
+    fn calc_candidates_by_hash_works() {
+        let mut ring = StaticHashRing::new(DefaultHash, (0..5).map(|i| Node::new(i).quantity(4)));
+        let mut other = StaticHashRing::new(DefaultHash, (0..5).map(|i| Node::new(i).quantity(4)));
+        for item in 0..20 {
+            let h = DefaultHash.hash_item(&item);
+            assert!(ring.calc_candidates_by_hash(h).eq(ring.calc_candidates(&item)));
+            assert!(ring.calc_candidate_indices_by_hash(h).eq(ring.calc_candidate_indices(&item)));
+            assert_eq!(ring.take_by_hash(h), other.take(&item));
+        }
+        assert!(ring.is_empty());
+        assert_eq!(ring.take_by_hash(0), None);
+    }
+
+    #[test]
+    fn calc_candidates_by_hash_tag_works() {
+        let ring = StaticHashRing::new(DefaultHash, (0..10).map(|i| Node::new(i).quantity(10)));
+        let tag: &[u8] = b"user1000";
+        for key in &["{user1000}.following", "{user1000}", "a{user1000}b{c}"] {
+            assert!(ring.calc_candidates_by_hash_tag(key).eq(ring.calc_candidates(&tag)));
+        }
+        let key: &[u8] = b"foo{}";
+        assert!(ring.calc_candidates_by_hash_tag(&key).eq(ring.calc_candidates(&key)));
+    }
+
+    #[test]
+    fn calc_candidates_n_works() {
+        let nodes = vec![
+            Node::new("foo").quantity(5),
+            Node::new("bar").quantity(5),
+            Node::new("baz").quantity(1),
+            Node::new("qux").quantity(0),
+        ];
+        let mut ring = StaticHashRing::new(DefaultHash, nodes.into_iter());
+        assert_eq!(ring.live_nodes(), 3);
+
+        let candidates = ring.calc_candidates_n(&"aa", 2);
+        assert_eq!(candidates.len(), 2);
+        assert_eq!(candidates.map(|n| n.key).collect::<Vec<_>>(), ["bar", "foo"]);
+
+        let candidates = ring.calc_candidates_n(&"aa", 10);
+        assert_eq!(candidates.len(), 3);
+        assert_eq!(candidates.count(), 3);
+
+        while ring.take_if(&"aa", |c| c.node.key == "baz").is_some() {}
+        assert_eq!(ring.live_nodes(), 2);
+        assert_eq!(ring.calc_candidates_n(&"aa", 10).len(), 2);
+        assert_eq!(ring.calc_candidates_n(&"aa", 10).count(), 2);
+
+        ring.disable_node(&"bar");
+        let candidates = ring.calc_candidates_n(&"aa", 10);
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates.map(|n| n.key).collect::<Vec<_>>(), ["foo"]);
+    }
+
+    #[test]
+    fn replica_index_of_works() {
+        let nodes = (0..5).map(|i| Node::new(i).quantity(if i < 4 { 3 } else { 0 }));
+        let ring = StaticHashRing::new(DefaultHash, nodes);
+        for item in 0..100 {
+            for (i, node) in ring.calc_candidates(&item).enumerate() {
+                assert_eq!(ring.replica_index_of(&item, &node.key), Some(i));
+            }
+            assert_eq!(ring.replica_index_of(&item, &4), None);
+            assert_eq!(ring.replica_index_of(&item, &5), None);
+        }
+    }
+
+    #[test]
+    fn walk_vnodes_works() {
+        let ring = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(5)));
+        let mut hashes = ring.walk_vnodes(&"foo").map(|(h, _)| h).collect::<Vec<_>>();
+        let pos = hashes.windows(2).position(|w| w[0] > w[1]).map_or(0, |p| p + 1);
+        hashes.rotate_left(pos);
+        assert!(hashes.windows(2).all(|w| w[0] <= w[1]));
+
+        let mut walk = ring.walk_vnodes(&"foo");
+        assert_eq!(walk.len(), 20);
+        walk.next();
+        assert_eq!(walk.len(), 19);
+
+        let empty = StaticHashRing::new(DefaultHash, (0..0).map(Node::new));
+        assert_eq!(empty.walk_vnodes(&"foo").count(), 0);
+    }
+
+    #[test]
+    fn vnodes_works() {
+        let mut ring = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(5)));
+        let vnodes = ring.vnodes().collect::<Vec<_>>();
+        assert_eq!(vnodes.len(), 20);
+        assert!(vnodes.windows(2).all(|w| w[0].0 <= w[1].0));
+        for &(hash, node, seq) in &vnodes {
+            assert_eq!(hash, DefaultHash.hash_vnode(&node.key, seq));
+        }
+        let mut seqs = vnodes.iter().filter(|v| v.1.key == 2).map(|v| v.2).collect::<Vec<_>>();
+        seqs.sort();
+        assert_eq!(seqs, [0, 1, 2, 3, 4]);
+
+        ring.take(&"foo");
+        assert_eq!(ring.vnodes().len(), 19);
+    }
+
+    #[test]
+    fn calc_candidate_indices_works() {
+        let ring = StaticHashRing::new(DefaultHash, (0..10).map(|i| Node::new(i).quantity(3)));
+        for item in 0..100 {
+            let nodes = ring.calc_candidate_indices(&item).map(|i| &ring.nodes()[i]);
+            assert!(nodes.eq(ring.calc_candidates(&item)));
+        }
+    }
+
+    #[test]
+    fn node_index_works() {
+        let nodes = vec![
+            Node::new("foo").quantity(5),
+            Node::new("bar").quantity(5),
+            Node::new("baz").quantity(1),
+            Node::new("bar").quantity(2), // ignored (duplicate key)
+        ];
+        let ring = StaticHashRing::new(DefaultHash, nodes.into_iter());
+        assert_eq!(ring.nodes().iter().map(|n| n.key).collect::<Vec<_>>(),
+                   ["bar", "baz", "foo"]);
+        for (i, node) in ring.nodes().iter().enumerate() {
+            assert_eq!(ring.node_index(&node.key), Some(i));
+            assert_eq!(ring.node_at(i), Some(node));
+        }
+        assert_eq!(ring.node_at(0).map(|n| n.quantity), Some(5));
+        assert_eq!(ring.node_index(&"qux"), None);
+        assert_eq!(ring.node_at(3), None);
+    }
+
+    #[test]
+    fn candidate_mask_works() {
+        let ring = StaticHashRing::new(DefaultHash, (0..128).map(|i| Node::new(i).quantity(3)));
+        for item in 0..100 {
+            let mask = ring.candidate_mask(&item, 3);
+            assert_eq!(mask.count_ones(), 3);
+            for i in ring.calc_candidate_indices(&item).take(3) {
+                assert_ne!(mask & (1 << i), 0);
+            }
+        }
+        assert_eq!(ring.candidate_mask(&0, 0), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn candidate_mask_too_many_nodes() {
+        let ring = StaticHashRing::new(DefaultHash, (0..129).map(Node::new));
+        ring.candidate_mask(&0, 1);
+    }
+
+    #[test]
+    fn shard_id_works() {
+        let ring = StaticHashRing::new(DefaultHash, (0..5).map(|i| Node::new(i).quantity(10)));
+        let mut shards = std::collections::HashMap::new();
+        for item in 0..1000 {
+            let shard = ring.shard_id(&item).unwrap();
+            let primary = ring.calc_candidates(&item).next().unwrap();
+            assert_eq!(*shards.entry(shard).or_insert(primary), primary);
+        }
+        assert!(shards.len() > 5);
+
+        let ring = StaticHashRing::new(DefaultHash, vec![Node::new(0).quantity(0)].into_iter());
+        assert_eq!(ring.shard_id(&0), None);
+    }
+
+    #[test]
+    fn assignments_works() {
+        let ring = StaticHashRing::new(DefaultHash, (0..5).map(|i| Node::new(i).quantity(10)));
+        let items = (0..100).collect::<Vec<_>>();
+        let table = ring.assignments(&items);
+        assert_eq!(table.len(), 100);
+        for (&(item, key), expected) in table.iter().zip(items.iter()) {
+            assert_eq!(item, expected);
+            assert_eq!(ring.calc_candidates(item).next().map(|n| &n.key), Some(key));
+        }
+    }
+
+    #[test]
+    fn shard_id_and_assignments_follow_node_states() {
+        let mut ring = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(10)));
+        ring.set_replica_only(&1, true);
+        ring.disable_node(&2);
+        let owners = ring.vnodes()
+            .map(|(hash, node, _)| (hash, node.key))
+            .collect::<std::collections::HashMap<_, _>>();
+        let items = (0..200).collect::<Vec<_>>();
+        for &item in &items {
+            let shard = ring.shard_id(&item).unwrap();
+            assert_eq!(Some(&owners[&shard]), ring.calc_node(&item).map(|n| &n.key));
+        }
+
+        let table = ring.assignments(&items);
+        assert_eq!(table.len(), items.len());
+        for (item, key) in table {
+            assert_eq!(Some(key), ring.calc_node(item).map(|n| &n.key));
+            assert!(*key != 1 && *key != 2);
+        }
+    }
+
+    #[test]
+    fn replica_only_works() {
+        let nodes = vec![
+            Node::new("foo").quantity(5),
+            Node::new("bar").quantity(5),
+            Node::new("baz").quantity(1),
+        ];
+        let mut ring = StaticHashRing::new(DefaultHash, nodes.into_iter());
+        fn keys(ring: &StaticHashRing<&'static str, (), DefaultHash>) -> Vec<&'static str> {
+            ring.calc_candidates(&"aa").map(|n| n.key).collect()
+        }
+        assert_eq!(keys(&ring), ["bar", "foo", "baz"]);
+
+        assert!(ring.set_replica_only(&"bar", true));
+        assert!(ring.set_replica_only(&"foo", true));
+        assert_eq!(keys(&ring), ["baz", "bar", "foo"]);
+        assert_eq!(ring.calc_candidates(&"aa").nth(1).map(|n| n.key), Some("bar"));
+        assert_eq!(ring.take(&"aa").map(|t| t.node.key), Some("baz"));
+
+        // All candidates are replica-only
+        assert_eq!(keys(&ring), ["bar", "foo"]);
+
+        assert!(ring.set_replica_only(&"bar", false));
+        assert_eq!(keys(&ring), ["bar", "foo"]);
+        assert!(!ring.set_replica_only(&"qux", true));
+    }
+
+    #[test]
+    fn nth_works() {
+        let nodes = (0..10).map(|i| Node::new(i).quantity(3));
+        let ring = StaticHashRing::new(DefaultHash, nodes);
+
+        let candidates = ring.calc_candidates(&"aa").collect::<Vec<_>>();
+        assert_eq!(candidates.len(), 10);
+        for (i, c) in candidates.iter().enumerate() {
+            assert_eq!(ring.calc_candidates(&"aa").nth(i), Some(*c));
+        }
+        assert_eq!(ring.calc_candidates(&"aa").nth(10), None);
     }
 }
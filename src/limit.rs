@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::{Duration, SystemTime};
+
+use {Clock, RingHash, StaticHashRing, TakenVnode};
+
+/// This trait allows throttling the claims (i.e., `take`s) against each node.
+///
+/// See `StaticHashRing::take_rate_limited`.
+///
+/// This is implemented for any `FnMut(&K) -> bool` closures.
+pub trait TokenBucket<K> {
+    /// Tries to consume a token of the node `key`.
+    ///
+    /// If the node has no tokens (i.e., it is overloaded), this returns `false`.
+    fn try_acquire(&mut self, key: &K) -> bool;
+}
+impl<K, F> TokenBucket<K> for F
+    where F: FnMut(&K) -> bool
+{
+    fn try_acquire(&mut self, key: &K) -> bool {
+        self(key)
+    }
+}
+
+/// A `TokenBucket` implementation which has an independent bucket for each node.
+///
+/// Each bucket holds up to `capacity` tokens, and it is refilled at `rate` tokens per second.
+/// A bucket is created (full) when the node is seen first.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::{Duration, UNIX_EPOCH};
+/// use consistent_hash::{MockClock, NodeTokenBuckets, TokenBucket};
+///
+/// let clock = MockClock::new(UNIX_EPOCH);
+/// let mut buckets = NodeTokenBuckets::new(2.0, 1.0, &clock);
+///
+/// assert!(buckets.try_acquire(&"foo"));
+/// assert!(buckets.try_acquire(&"foo"));
+/// assert!(!buckets.try_acquire(&"foo"));
+/// assert!(buckets.try_acquire(&"bar"));
+///
+/// clock.advance(Duration::from_secs(1));
+/// assert!(buckets.try_acquire(&"foo"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct NodeTokenBuckets<K, C> {
+    capacity: f64,
+    rate: f64,
+    clock: C,
+    buckets: HashMap<K, (f64, SystemTime)>,
+}
+impl<K, C> NodeTokenBuckets<K, C>
+    where K: Hash + Eq + Clone,
+          C: Clock
+{
+    /// Makes a new `NodeTokenBuckets` instance.
+    ///
+    /// # Panics
+    ///
+    /// If `capacity` or `rate` is negative (or not a number), this function will panic.
+    pub fn new(capacity: f64, rate: f64, clock: C) -> Self {
+        assert!(capacity >= 0.0, "capacity must be non-negative: {}", capacity);
+        assert!(rate >= 0.0, "rate must be non-negative: {}", rate);
+        NodeTokenBuckets {
+            capacity,
+            rate,
+            clock,
+            buckets: HashMap::new(),
+        }
+    }
+
+    /// Returns the count of the tokens which the node `key` currently has.
+    pub fn tokens(&self, key: &K) -> f64 {
+        self.buckets.get(key).map_or(self.capacity, |&(tokens, at)| self.refilled(tokens, at, self.clock.now()))
+    }
+
+    fn refilled(&self, tokens: f64, at: SystemTime, now: SystemTime) -> f64 {
+        let elapsed = now.duration_since(at).unwrap_or(Duration::from_secs(0));
+        (tokens + elapsed.as_secs_f64() * self.rate).min(self.capacity)
+    }
+}
+impl<K, C> TokenBucket<K> for NodeTokenBuckets<K, C>
+    where K: Hash + Eq + Clone,
+          C: Clock
+{
+    fn try_acquire(&mut self, key: &K) -> bool {
+        let now = self.clock.now();
+        let tokens = self.tokens(key);
+        let acquired = tokens >= 1.0;
+        let rest = if acquired { tokens - 1.0 } else { tokens };
+        self.buckets.insert(key.clone(), (rest, now));
+        acquired
+    }
+}
+
+impl<K, V, H> StaticHashRing<K, V, H>
+    where K: Hash + Eq + Ord,
+          H: RingHash
+{
+    /// Removes the virtual node which has the highest priority for `item`
+    /// among the nodes which are granted a token by `limiter`,
+    /// and returns the description of the removed one.
+    ///
+    /// Tokens are requested in priority order, and the walk stops at the first granted node,
+    /// so only the claimed node consumes a token.
+    /// If every node is throttled (or this ring is empty), this returns `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let mut ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+    /// let candidates = ring.calc_candidates(&"foo").map(|n| n.key).collect::<Vec<_>>();
+    ///
+    /// // The first candidate is overloaded.
+    /// let mut limiter = |key: &i32| *key != candidates[0];
+    /// let taken = ring.take_rate_limited(&"foo", &mut limiter).map(|t| t.node.key);
+    /// assert_eq!(taken, Some(candidates[1]));
+    /// assert!(ring.take_rate_limited(&"foo", &mut |_: &i32| false).is_none());
+    /// ```
+    pub fn take_rate_limited<T, L>(&mut self, item: &T, limiter: &mut L) -> Option<TakenVnode<'_, K, V>>
+        where T: Hash,
+              L: TokenBucket<K> + ?Sized
+    {
+        self.take_if(item, |c| limiter.try_acquire(&c.node.key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+    use {DefaultHash, MockClock, Node, StaticHashRing};
+    use super::*;
+
+    #[test]
+    fn take_rate_limited_works() {
+        let clock = MockClock::new(UNIX_EPOCH);
+        let mut buckets = NodeTokenBuckets::new(2.0, 0.5, &clock);
+        let mut ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(100)));
+
+        // Each node can be claimed twice until refilled.
+        let mut claims = vec![0; 3];
+        for i in 0..10 {
+            match ring.take_rate_limited(&i, &mut buckets) {
+                None => break,
+                Some(t) => claims[t.node.key] += 1,
+            }
+        }
+        assert_eq!(claims, [2, 2, 2]);
+        assert!(ring.take_rate_limited(&"foo", &mut buckets).is_none());
+        assert_eq!(ring.nodes().iter().map(|n| buckets.tokens(&n.key)).sum::<f64>(), 0.0);
+
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(buckets.tokens(&0), 1.0);
+        assert!(ring.take_rate_limited(&"foo", &mut buckets).is_some());
+        clock.advance(Duration::from_secs(100));
+        assert_eq!(buckets.tokens(&0), 2.0);
+    }
+}
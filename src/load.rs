@@ -0,0 +1,390 @@
+use std::cell::Cell;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use {CandidateIndices, Node, RingHash, StaticHashRing};
+
+// The upper limit of the default stripe count.
+const MAX_DEFAULT_STRIPES: usize = 16;
+
+// Counters are aligned to cache lines, so that threads updating different stripes don't contend.
+#[derive(Debug, Default)]
+#[repr(align(64))]
+struct Counter(AtomicUsize);
+
+static NEXT_STRIPE_SEED: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static STRIPE_SEED: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+fn stripe_seed() -> usize {
+    STRIPE_SEED.with(|seed| {
+        if let Some(s) = seed.get() {
+            s
+        } else {
+            let s = NEXT_STRIPE_SEED.fetch_add(1, Ordering::Relaxed);
+            seed.set(Some(s));
+            s
+        }
+    })
+}
+
+/// Per-node load counters which can be updated concurrently.
+///
+/// The counter of each node is split into multiple stripes,
+/// and each thread updates its own stripe,
+/// so tracking loads does not serialize concurrent lookups.
+///
+/// Nodes are identified by their indices (i.e., the positions in `StaticHashRing::nodes`).
+///
+/// # Examples
+///
+/// ```
+/// use consistent_hash::{Node, StaticHashRing, DefaultHash, LoadTracker};
+///
+/// let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+/// let tracker = LoadTracker::new(ring.nodes().len());
+///
+/// let primary = ring.calc_candidate_indices(&"foo").next().unwrap();
+/// {
+///     let _guard = tracker.acquire(primary);
+///     assert_eq!(tracker.load(primary), 1);
+/// }
+/// assert_eq!(tracker.load_snapshot(), [0, 0, 0]);
+/// ```
+#[derive(Debug)]
+pub struct LoadTracker {
+    nodes: usize,
+    stripes: usize,
+    counters: Vec<Counter>,
+}
+impl LoadTracker {
+    /// Makes a new `LoadTracker` instance for `nodes` nodes.
+    ///
+    /// The count of stripes is derived from the available parallelism of the system.
+    pub fn new(nodes: usize) -> Self {
+        let stripes = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+            .min(MAX_DEFAULT_STRIPES);
+        Self::with_stripes(nodes, stripes)
+    }
+
+    /// Makes a new `LoadTracker` instance for `nodes` nodes, which has `stripes` stripes per node.
+    ///
+    /// If `stripes` is `0`, it is regarded as `1`.
+    pub fn with_stripes(nodes: usize, stripes: usize) -> Self {
+        let stripes = stripes.max(1);
+        LoadTracker {
+            nodes,
+            stripes,
+            counters: (0..nodes * stripes).map(|_| Counter::default()).collect(),
+        }
+    }
+
+    /// Returns the count of the nodes tracked by this instance.
+    pub fn nodes(&self) -> usize {
+        self.nodes
+    }
+
+    /// Increments the load of the node at `node_index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `node_index` is out of range.
+    pub fn increment(&self, node_index: usize) {
+        self.counter(node_index).fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Decrements the load of the node at `node_index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `node_index` is out of range.
+    pub fn decrement(&self, node_index: usize) {
+        self.counter(node_index).fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Increments the load of the node at `node_index`,
+    /// and returns the guard object which decrements the load when dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `node_index` is out of range.
+    pub fn acquire(&self, node_index: usize) -> LoadGuard<'_> {
+        self.increment(node_index);
+        LoadGuard {
+            tracker: self,
+            node_index,
+        }
+    }
+
+    /// Returns the current load of the node at `node_index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `node_index` is out of range.
+    pub fn load(&self, node_index: usize) -> usize {
+        assert!(node_index < self.nodes, "node_index={}, nodes={}", node_index, self.nodes);
+        (0..self.stripes)
+            .map(|s| self.counters[s * self.nodes + node_index].0.load(Ordering::Relaxed))
+            .fold(0, usize::wrapping_add)
+    }
+
+    /// Returns the current loads of all nodes.
+    ///
+    /// Since the counters may be updated concurrently,
+    /// the result is not necessarily an atomic snapshot of all nodes.
+    pub fn load_snapshot(&self) -> Vec<usize> {
+        (0..self.nodes).map(|i| self.load(i)).collect()
+    }
+
+    /// Returns the sum of the current loads of all nodes.
+    pub fn total_load(&self) -> usize {
+        self.load_snapshot().into_iter().fold(0, usize::wrapping_add)
+    }
+
+    fn counter(&self, node_index: usize) -> &AtomicUsize {
+        assert!(node_index < self.nodes, "node_index={}, nodes={}", node_index, self.nodes);
+        let stripe = stripe_seed() % self.stripes;
+        &self.counters[stripe * self.nodes + node_index].0
+    }
+}
+
+/// A guard object which decrements the load of a node when dropped.
+///
+/// This is created by calling `LoadTracker::acquire` method.
+#[derive(Debug)]
+pub struct LoadGuard<'a> {
+    tracker: &'a LoadTracker,
+    node_index: usize,
+}
+impl<'a> LoadGuard<'a> {
+    /// Returns the index of the node whose load is held by this guard.
+    pub fn node_index(&self) -> usize {
+        self.node_index
+    }
+}
+impl<'a> Drop for LoadGuard<'a> {
+    fn drop(&mut self) {
+        self.tracker.decrement(self.node_index);
+    }
+}
+
+impl<K, V, H> StaticHashRing<K, V, H>
+    where H: RingHash
+{
+    /// Returns the candidate nodes for `item` whose loads are within the bound
+    /// (i.e., "Consistent Hashing with Bounded Loads").
+    ///
+    /// The bound is `ceil(c * (total_load + 1) / live_nodes)`, where `total_load` is the sum of the loads in `tracker`.
+    /// Candidates whose loads reach the bound are skipped, so hot keys overflow to the next candidates.
+    /// `c` is the balance parameter (e.g., `1.25`), and values less than `1.0` are regarded as `1.0`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the count of the nodes in `tracker` differs from the one in this ring.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash, LoadTracker};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(10)));
+    /// let tracker = LoadTracker::new(ring.nodes().len());
+    ///
+    /// // Every request for the same hot key.
+    /// let guards = (0..8)
+    ///     .map(|_| ring.acquire_bounded(&"hot", &tracker, 1.25).unwrap().1)
+    ///     .collect::<Vec<_>>();
+    /// assert!(tracker.load_snapshot().iter().all(|&load| load <= 3));
+    /// ```
+    pub fn calc_candidates_bounded<'t, T: Hash>(&self,
+                                                item: &T,
+                                                tracker: &'t LoadTracker,
+                                                c: f64)
+                                                -> BoundedCandidates<'_, 't, K, V> {
+        assert_eq!(tracker.nodes(), self.nodes.len());
+        let c = if c > 1.0 { c } else { 1.0 };
+        let live_nodes = self.live_nodes.max(1) as f64;
+        let bound = (c * (tracker.total_load() + 1) as f64 / live_nodes).ceil() as usize;
+        BoundedCandidates {
+            candidates: self.calc_candidate_indices(item),
+            nodes: &self.nodes,
+            tracker,
+            bound,
+        }
+    }
+
+    /// Chooses the first candidate of `calc_candidates_bounded` and increments its load.
+    ///
+    /// The returned guard decrements the load when dropped.
+    /// If no candidates are within the bound, this returns `None`.
+    pub fn acquire_bounded<'t, T: Hash>(&self,
+                                        item: &T,
+                                        tracker: &'t LoadTracker,
+                                        c: f64)
+                                        -> Option<(&Node<K, V>, LoadGuard<'t>)> {
+        let mut candidates = self.calc_candidates_bounded(item, tracker, c);
+        candidates.next_index().map(|i| (&self.nodes[i], tracker.acquire(i)))
+    }
+
+    /// Returns the first `k` candidate nodes for `item`, ordered by their loads (in ascending order).
+    ///
+    /// The load of each candidate is given by `load` (e.g., the count of in-flight requests),
+    /// and candidates which have the same load keep the order of `calc_candidates`.
+    /// So, choosing the first node implements the (bounded) power of `k` choices:
+    /// a hot key spreads over its first `k` candidates instead of overloading its primary node,
+    /// while the other keys mostly stay on their primary nodes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(10)));
+    /// let candidates = ring.calc_candidates(&"hot").map(|n| n.key).collect::<Vec<_>>();
+    ///
+    /// let mut loads = [0; 4];
+    /// for _ in 0..10 {
+    ///     let node = ring.calc_candidates_least_loaded(&"hot", 2, |n| loads[n.key])[0];
+    ///     loads[node.key] += 1;
+    /// }
+    /// assert_eq!((loads[candidates[0]], loads[candidates[1]]), (5, 5));
+    /// ```
+    pub fn calc_candidates_least_loaded<T, F, L>(&self, item: &T, k: usize, mut load: F) -> Vec<&Node<K, V>>
+        where T: Hash + ?Sized,
+              F: FnMut(&Node<K, V>) -> L,
+              L: Ord
+    {
+        let mut candidates = self.calc_candidates_n(item, k).map(|n| (load(n), n)).collect::<Vec<_>>();
+        candidates.sort_by(|a, b| a.0.cmp(&b.0));
+        candidates.into_iter().map(|(_, n)| n).collect()
+    }
+}
+
+/// An iterator which represents a sequence of the candidate nodes whose loads are within a bound.
+///
+/// This is created by calling `StaticHashRing::calc_candidates_bounded` method.
+pub struct BoundedCandidates<'a, 't, K: 'a, V: 'a> {
+    candidates: CandidateIndices<'a, K, V>,
+    nodes: &'a [Node<K, V>],
+    tracker: &'t LoadTracker,
+    bound: usize,
+}
+impl<'a, 't, K: 'a, V: 'a> BoundedCandidates<'a, 't, K, V> {
+    /// Returns the load bound used by this iterator.
+    pub fn bound(&self) -> usize {
+        self.bound
+    }
+
+    fn next_index(&mut self) -> Option<usize> {
+        let tracker = self.tracker;
+        let bound = self.bound;
+        self.candidates.find(|&i| tracker.load(i) < bound)
+    }
+}
+impl<'a, 't, K: 'a, V: 'a> Iterator for BoundedCandidates<'a, 't, K, V> {
+    type Item = &'a Node<K, V>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_index().map(|i| &self.nodes[i])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+    use {DefaultHash, Node, StaticHashRing};
+    use super::*;
+
+    #[test]
+    fn load_tracker_works() {
+        let tracker = Arc::new(LoadTracker::with_stripes(3, 4));
+        let handles = (0..8)
+            .map(|t| {
+                let tracker = tracker.clone();
+                thread::spawn(move || for i in 0..1000 {
+                    tracker.increment((t + i) % 3);
+                })
+            })
+            .collect::<Vec<_>>();
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(tracker.total_load(), 8000);
+        assert_eq!(tracker.load_snapshot().iter().sum::<usize>(), 8000);
+
+        // Decrements made by other threads (i.e., on other stripes) are also reflected.
+        let t = tracker.clone();
+        thread::spawn(move || for _ in 0..10 {
+                t.decrement(0);
+            })
+            .join()
+            .unwrap();
+        assert_eq!(tracker.total_load(), 7990);
+    }
+
+    #[test]
+    fn load_guard_works() {
+        let tracker = LoadTracker::new(2);
+        let guard0 = tracker.acquire(1);
+        let guard1 = tracker.acquire(1);
+        assert_eq!(guard0.node_index(), 1);
+        assert_eq!(tracker.load_snapshot(), [0, 2]);
+        drop(guard0);
+        assert_eq!(tracker.load(1), 1);
+        drop(guard1);
+        assert_eq!(tracker.load(1), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn load_out_of_range() {
+        LoadTracker::new(2).increment(2);
+    }
+
+    #[test]
+    fn least_loaded_works() {
+        let ring = StaticHashRing::new(DefaultHash, (0..5).map(|i| Node::new(i).quantity(10)));
+        let candidates = ring.calc_candidates(&"foo").collect::<Vec<_>>();
+        assert_eq!(ring.calc_candidates_least_loaded(&"foo", 3, |_| 0), candidates[..3]);
+        assert_eq!(ring.calc_candidates_least_loaded(&"foo", 10, |_| 0), candidates);
+        assert!(ring.calc_candidates_least_loaded(&"foo", 0, |_| 0).is_empty());
+
+        // Only the first `k` candidates are considered.
+        let loads = |n: &Node<usize, ()>| candidates.iter().position(|c| c.key == n.key).map(|p| 10 - p);
+        let keys = |nodes: Vec<&Node<usize, ()>>| nodes.into_iter().map(|n| n.key).collect::<Vec<_>>();
+        assert_eq!(keys(ring.calc_candidates_least_loaded(&"foo", 3, loads)),
+                   [candidates[2].key, candidates[1].key, candidates[0].key]);
+    }
+
+    #[test]
+    fn bounded_loads_works() {
+        let ring = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(10)));
+        let tracker = LoadTracker::new(4);
+
+        let candidates = ring.calc_candidates(&"hot").collect::<Vec<_>>();
+        let bounded = ring.calc_candidates_bounded(&"hot", &tracker, 1.0);
+        assert_eq!(bounded.bound(), 1);
+        assert_eq!(bounded.collect::<Vec<_>>(), candidates);
+
+        let mut guards = Vec::new();
+        for &candidate in &candidates {
+            let (node, guard) = ring.acquire_bounded(&"hot", &tracker, 1.0).unwrap();
+            assert_eq!(node, candidate);
+            guards.push(guard);
+        }
+        assert_eq!(tracker.load_snapshot(), [1, 1, 1, 1]);
+
+        // Loads are balanced even if all requests are for the same key.
+        for _ in 0..96 {
+            guards.push(ring.acquire_bounded(&"hot", &tracker, 1.0).unwrap().1);
+        }
+        assert_eq!(tracker.load_snapshot(), [25, 25, 25, 25]);
+        drop(guards);
+        assert_eq!(tracker.total_load(), 0);
+    }
+}
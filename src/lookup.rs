@@ -0,0 +1,215 @@
+use std::fmt;
+use std::hash::Hash;
+
+use {Candidates, Node, RingHash, StaticHashRing};
+
+/// A lookup result which is tagged with the generation of the ring.
+///
+/// Callers can keep (a mapped version of) this result,
+/// and can detect whether the ring has changed since the lookup by `StaticHashRing::is_stale`.
+///
+/// This is created by calling `StaticHashRing::lookup` or `StaticHashRing::lookup_candidates` method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LookupResult<N> {
+    /// The result of the lookup.
+    pub node: N,
+
+    /// The generation of the ring at the lookup.
+    pub generation: u64,
+}
+impl<N> LookupResult<N> {
+    /// Converts the result by applying `f`, keeping the generation.
+    ///
+    /// This is useful for detaching the result from the borrowed ring (e.g., by cloning the key of the node).
+    pub fn map<F, M>(self, f: F) -> LookupResult<M>
+        where F: FnOnce(N) -> M
+    {
+        LookupResult {
+            node: f(self.node),
+            generation: self.generation,
+        }
+    }
+}
+
+/// A detached record of a routing decision.
+///
+/// This holds only plain data (integers and the keys of the nodes),
+/// so it can be logged, serialized, or attached to distributed traces,
+/// and the decision can be replayed later by `StaticHashRing::replay_candidates`.
+///
+/// This is created by calling `StaticHashRing::snapshot_candidates` method.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CandidatesSnapshot<K> {
+    /// The hash code of the item.
+    pub item_hash: u64,
+
+    /// The keys of the candidate nodes (in the order of preference).
+    pub nodes: Vec<K>,
+
+    /// The generation of the ring at the lookup.
+    pub generation: u64,
+
+    /// The fingerprint of the ring at the lookup (see `StaticHashRing::fingerprint`).
+    pub fingerprint: u64,
+}
+impl<K: fmt::Display> fmt::Display for CandidatesSnapshot<K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "item_hash={:016x} generation={} fingerprint={:016x} nodes=[",
+               self.item_hash,
+               self.generation,
+               self.fingerprint)?;
+        for (i, node) in self.nodes.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}", node)?;
+        }
+        write!(f, "]")
+    }
+}
+
+impl<K, V, H> StaticHashRing<K, V, H>
+    where K: Hash + Clone,
+          H: RingHash
+{
+    /// Records the candidate nodes for `item` as a `CandidatesSnapshot`.
+    ///
+    /// Note that this calculates the fingerprint of this ring, which takes time proportional to the ring size.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let mut ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+    ///
+    /// let snapshot = ring.snapshot_candidates(&"foo");
+    /// assert_eq!(snapshot.nodes.len(), 3);
+    /// assert_eq!(ring.replay_candidates(&snapshot), snapshot);
+    ///
+    /// ring.take(&"foo");
+    /// let replayed = ring.replay_candidates(&snapshot);
+    /// assert_eq!(replayed.generation, 1);
+    /// assert_ne!(replayed.fingerprint, snapshot.fingerprint);
+    /// ```
+    pub fn snapshot_candidates<T: Hash + ?Sized>(&self, item: &T) -> CandidatesSnapshot<K> {
+        self.snapshot_candidates_by_hash(self.hash.hash_item(&item))
+    }
+
+    /// Records the candidate nodes for the item which has the hash code `item_hash` as a `CandidatesSnapshot`.
+    pub fn snapshot_candidates_by_hash(&self, item_hash: u64) -> CandidatesSnapshot<K> {
+        CandidatesSnapshot {
+            item_hash,
+            nodes: self.calc_candidates_by_hash(item_hash).map(|n| n.key.clone()).collect(),
+            generation: self.generation,
+            fingerprint: self.fingerprint(),
+        }
+    }
+
+    /// Replays the lookup recorded by `snapshot` against the current state of this ring.
+    ///
+    /// If the result equals to `snapshot`, this ring would make the same decision.
+    pub fn replay_candidates(&self, snapshot: &CandidatesSnapshot<K>) -> CandidatesSnapshot<K> {
+        self.snapshot_candidates_by_hash(snapshot.item_hash)
+    }
+}
+
+impl<K, V, H> StaticHashRing<K, V, H>
+    where H: RingHash
+{
+    /// Returns the primary node for `item`, tagged with the current generation of this ring.
+    ///
+    /// If this ring is empty, this returns `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let mut ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+    ///
+    /// let result = ring.lookup(&"foo").unwrap().map(|n| n.key);
+    /// assert_eq!(result.generation, 0);
+    /// assert!(!ring.is_stale(&result));
+    ///
+    /// ring.take(&"bar");
+    /// assert!(ring.is_stale(&result));
+    /// ```
+    pub fn lookup<T: Hash + ?Sized>(&self, item: &T) -> Option<LookupResult<&Node<K, V>>> {
+        self.lookup_candidates(item).node.next().map(|node| {
+            LookupResult {
+                node,
+                generation: self.generation,
+            }
+        })
+    }
+
+    /// Returns the candidate nodes for `item`, tagged with the current generation of this ring.
+    pub fn lookup_candidates<T: Hash + ?Sized>(&self, item: &T) -> LookupResult<Candidates<'_, K, V>> {
+        LookupResult {
+            node: self.calc_candidates(item),
+            generation: self.generation,
+        }
+    }
+
+    /// Returns `true` if the generation of this ring differs from the one of `result`, otherwise `false`.
+    ///
+    /// A stale result may not reflect the current placements.
+    pub fn is_stale<N>(&self, result: &LookupResult<N>) -> bool {
+        result.generation != self.generation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {DefaultHash, Node, StaticHashRing};
+
+    #[test]
+    fn lookup_works() {
+        let mut ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(2)));
+        let result = ring.lookup(&"foo").unwrap();
+        assert_eq!(Some(result.node), ring.calc_candidates(&"foo").next());
+        let result = result.map(|n| n.key);
+
+        let candidates = ring.lookup_candidates(&"foo").map(|c| c.map(|n| n.key).collect::<Vec<_>>());
+        assert_eq!(candidates.generation, 0);
+        assert_eq!(candidates.node[0], result.node);
+
+        ring.take(&"foo");
+        assert!(ring.is_stale(&result) && ring.is_stale(&candidates));
+        let result = ring.lookup(&"foo").unwrap();
+        assert_eq!(result.generation, 1);
+        assert!(!ring.is_stale(&result));
+
+        let empty = StaticHashRing::new(DefaultHash, (0..0).map(Node::new));
+        assert_eq!(empty.lookup(&"foo"), None);
+    }
+
+    #[test]
+    fn snapshot_candidates_works() {
+        let mut ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(5)));
+        let snapshot = ring.snapshot_candidates(&"foo");
+        assert_eq!(snapshot.nodes, ring.calc_candidates(&"foo").map(|n| n.key).collect::<Vec<_>>());
+        assert_eq!((snapshot.generation, snapshot.fingerprint), (0, ring.fingerprint()));
+        assert_eq!(snapshot.to_string(),
+                   format!("item_hash={:016x} generation=0 fingerprint={:016x} nodes=[{},{},{}]",
+                           snapshot.item_hash,
+                           snapshot.fingerprint,
+                           snapshot.nodes[0],
+                           snapshot.nodes[1],
+                           snapshot.nodes[2]));
+
+        // An identically built ring reproduces the decision.
+        let other = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(5)));
+        assert_eq!(other.replay_candidates(&snapshot), snapshot);
+
+        ring.take(&"foo");
+        let replayed = ring.replay_candidates(&snapshot);
+        assert_eq!(replayed.generation, 1);
+        assert_eq!(replayed.item_hash, snapshot.item_hash);
+
+        let empty = StaticHashRing::new(DefaultHash, (0..0).map(Node::new));
+        assert!(empty.snapshot_candidates(&"foo").nodes.is_empty());
+    }
+}
@@ -0,0 +1,281 @@
+use std::hash::Hash;
+
+use weight::allocate;
+use {Node, RingHash};
+
+/// The default size of lookup tables of `MaglevRing`.
+pub const DEFAULT_MAGLEV_TABLE_SIZE: usize = 65_537;
+
+/// A hash ring based on Maglev hashing.
+///
+/// A fixed-size lookup table is built from the permutations of the nodes,
+/// so the primary node of an item is found by a single array indexing.
+/// The quantity of a node is used as its weight (i.e., the count of the table entries assigned to it
+/// is proportional to its quantity), and nodes whose quantities are `0` are never chosen.
+///
+/// Unlike `StaticHashRing`, a change of the membership may move a small amount of extra items
+/// between the remaining nodes.
+///
+/// See [Maglev: A Fast and Reliable Software Network Load Balancer](https://research.google/pubs/pub44824/).
+///
+/// # Examples
+///
+/// ```
+/// use consistent_hash::{Node, MaglevRing, DefaultHash};
+///
+/// let nodes = vec![
+///     Node::new("foo").quantity(1),
+///     Node::new("bar").quantity(1),
+///     Node::new("baz").quantity(2),
+/// ];
+/// let ring = MaglevRing::new(DefaultHash, nodes.into_iter());
+/// assert_eq!(ring.table_size(), 65_537);
+///
+/// let candidates = ring.calc_candidates(&"aa").collect::<Vec<_>>();
+/// assert_eq!(candidates.len(), 3);
+/// assert_eq!(ring.calc_candidate(&"aa"), Some(candidates[0]));
+/// ```
+#[derive(Debug, Clone)]
+pub struct MaglevRing<K, V, H> {
+    hash: H,
+    nodes: Vec<Node<K, V>>,
+    table: Vec<u32>,
+    live_nodes: usize,
+}
+impl<K, V, H> MaglevRing<K, V, H>
+    where K: Hash + Eq + Ord,
+          H: RingHash
+{
+    /// Makes a new `MaglevRing` instance which has a lookup table of `DEFAULT_MAGLEV_TABLE_SIZE` entries.
+    ///
+    /// If multiple nodes which have the same key are contained in `nodes`,
+    /// all of those nodes but first one are ignored.
+    pub fn new<I>(hash: H, nodes: I) -> Self
+        where I: Iterator<Item = Node<K, V>>
+    {
+        Self::with_table_size(hash, nodes, DEFAULT_MAGLEV_TABLE_SIZE)
+    }
+
+    /// Makes a new `MaglevRing` instance which has a lookup table of `table_size` entries.
+    ///
+    /// The table size should be much larger than the count of nodes (e.g., 100 times or more)
+    /// to keep the table balanced.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `table_size` is not a prime number, or the count of nodes exceeds `u32::MAX`.
+    pub fn with_table_size<I>(hash: H, nodes: I, table_size: usize) -> Self
+        where I: Iterator<Item = Node<K, V>>
+    {
+        assert!(is_prime(table_size), "table_size={} is not a prime number", table_size);
+        let mut nodes = nodes.collect::<Vec<_>>();
+        nodes.sort_by(|a, b| a.key.cmp(&b.key));
+        nodes.dedup_by(|a, b| a.key == b.key);
+        assert!(nodes.len() <= u32::MAX as usize);
+
+        let mut ring = MaglevRing {
+            hash,
+            nodes,
+            table: Vec::new(),
+            live_nodes: 0,
+        };
+        ring.build_table(table_size);
+        ring
+    }
+
+    fn build_table(&mut self, table_size: usize) {
+        let size = table_size as u64;
+        let weights = self.nodes.iter().map(|n| n.quantity as f64).collect::<Vec<_>>();
+        let targets = allocate(&weights, table_size);
+        self.live_nodes = targets.iter().filter(|&&t| t > 0).count();
+        if self.live_nodes == 0 {
+            return;
+        }
+
+        let permutations = self.nodes
+            .iter()
+            .map(|n| {
+                let offset = self.hash.hash_vnode(&n.key, 0) % size;
+                let skip = self.hash.hash_vnode(&n.key, 1) % (size - 1) + 1;
+                (offset, skip)
+            })
+            .collect::<Vec<_>>();
+        let mut nexts = vec![0; self.nodes.len()];
+        let mut counts = vec![0; self.nodes.len()];
+        let mut table = vec![u32::MAX; table_size];
+        let mut filled = 0;
+        while filled < table_size {
+            for (i, &(offset, skip)) in permutations.iter().enumerate() {
+                if counts[i] == targets[i] {
+                    continue;
+                }
+                loop {
+                    let c = ((offset + nexts[i] * skip) % size) as usize;
+                    nexts[i] += 1;
+                    if table[c] == u32::MAX {
+                        table[c] = i as u32;
+                        break;
+                    }
+                }
+                counts[i] += 1;
+                filled += 1;
+            }
+        }
+        self.table = table;
+    }
+
+    /// Returns the primary node for `item`.
+    ///
+    /// If this ring has no nodes which have positive quantities, this returns `None`.
+    pub fn calc_candidate<T: Hash>(&self, item: &T) -> Option<&Node<K, V>> {
+        self.calc_candidate_index(item).map(|i| &self.nodes[i])
+    }
+
+    /// Returns the index of the primary node for `item`.
+    ///
+    /// The index is the position of the node in `nodes()`.
+    pub fn calc_candidate_index<T: Hash>(&self, item: &T) -> Option<usize> {
+        if self.table.is_empty() {
+            None
+        } else {
+            let slot = self.hash.hash_item(item) % self.table.len() as u64;
+            Some(self.table[slot as usize] as usize)
+        }
+    }
+
+    /// Returns the candidate nodes for `item`.
+    ///
+    /// The first candidate is the primary node, and the rest are the distinct nodes found
+    /// by walking the lookup table from the entry of `item`.
+    pub fn calc_candidates<T: Hash>(&self, item: &T) -> MaglevCandidates<'_, K, V> {
+        let start = if self.table.is_empty() {
+            0
+        } else {
+            (self.hash.hash_item(item) % self.table.len() as u64) as usize
+        };
+        MaglevCandidates {
+            nodes: &self.nodes,
+            table: &self.table,
+            position: start,
+            count: 0,
+            seens: Vec::new(),
+            live_nodes: self.live_nodes,
+        }
+    }
+}
+impl<K, V, H> MaglevRing<K, V, H> {
+    /// Returns the nodes of this ring (sorted in key order).
+    pub fn nodes(&self) -> &[Node<K, V>] {
+        &self.nodes
+    }
+
+    /// Returns the count of the nodes which have positive quantities.
+    pub fn live_nodes(&self) -> usize {
+        self.live_nodes
+    }
+
+    /// Returns the count of the entries of the lookup table.
+    ///
+    /// If this ring has no live nodes, this returns `0`.
+    pub fn table_size(&self) -> usize {
+        self.table.len()
+    }
+
+    /// Returns the count of the table entries assigned to each node (ordered as `nodes()`).
+    pub fn table_entries(&self) -> Vec<usize> {
+        let mut entries = vec![0; self.nodes.len()];
+        for &i in &self.table {
+            entries[i as usize] += 1;
+        }
+        entries
+    }
+}
+
+fn is_prime(n: usize) -> bool {
+    if n < 2 {
+        return false;
+    }
+    (2..).take_while(|i| i * i <= n).all(|i| !n.is_multiple_of(i))
+}
+
+/// An iterator which represents a sequence of the candidate nodes for an item in a `MaglevRing`.
+///
+/// This is created by calling `MaglevRing::calc_candidates` method.
+#[derive(Debug)]
+pub struct MaglevCandidates<'a, K: 'a, V: 'a> {
+    nodes: &'a [Node<K, V>],
+    table: &'a [u32],
+    position: usize,
+    count: usize,
+    seens: Vec<u32>,
+    live_nodes: usize,
+}
+impl<'a, K: 'a, V: 'a> Iterator for MaglevCandidates<'a, K, V> {
+    type Item = &'a Node<K, V>;
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.seens.len() < self.live_nodes && self.count < self.table.len() {
+            let i = self.table[self.position];
+            self.position = (self.position + 1) % self.table.len();
+            self.count += 1;
+            if !self.seens.contains(&i) {
+                self.seens.push(i);
+                return Some(&self.nodes[i as usize]);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use DefaultHash;
+    use super::*;
+
+    #[test]
+    fn maglev_ring_works() {
+        let nodes = (0..10).map(|i| Node::new(i).quantity(if i == 9 { 0 } else { 1 }));
+        let ring = MaglevRing::with_table_size(DefaultHash, nodes, 1009);
+        assert_eq!(ring.table_size(), 1009);
+        assert_eq!(ring.live_nodes(), 9);
+        let entries = ring.table_entries();
+        assert_eq!(entries.iter().sum::<usize>(), 1009);
+        assert!(entries[..9].iter().all(|&e| e == 112 || e == 113), "{:?}", entries);
+        assert_eq!(entries[9], 0);
+
+        for i in 0..100 {
+            let candidates = ring.calc_candidates(&i).map(|n| n.key).collect::<Vec<_>>();
+            assert_eq!(candidates.len(), 9);
+            assert!(!candidates.contains(&9));
+            assert_eq!(ring.calc_candidate(&i).map(|n| n.key), Some(candidates[0]));
+        }
+
+        // Most items stay on the same nodes when a node is removed.
+        let nodes = (0..8).map(|i| Node::new(i).quantity(1));
+        let removed = MaglevRing::with_table_size(DefaultHash, nodes, 1009);
+        let moved = (0..1000)
+            .filter(|i| {
+                let before = ring.calc_candidate(i).unwrap().key;
+                before != 8 && removed.calc_candidate(i).unwrap().key != before
+            })
+            .count();
+        assert!(moved < 100, "{}", moved);
+
+        let empty = MaglevRing::with_table_size(DefaultHash, (0..3).map(|i| Node::new(i).quantity(0)), 7);
+        assert_eq!(empty.table_size(), 0);
+        assert_eq!(empty.calc_candidate(&"foo"), None);
+        assert_eq!(empty.calc_candidates(&"foo").count(), 0);
+    }
+
+    #[test]
+    fn maglev_weights_works() {
+        let nodes = vec![Node::new(0).quantity(1), Node::new(1).quantity(3)];
+        let ring = MaglevRing::with_table_size(DefaultHash, nodes.into_iter(), 1009);
+        assert_eq!(ring.table_entries(), [252, 757]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn maglev_table_size_not_prime() {
+        MaglevRing::with_table_size(DefaultHash, (0..3).map(Node::new), 1000);
+    }
+}
@@ -0,0 +1,256 @@
+use std::collections::hash_map::{self, HashMap};
+use std::hash::Hash;
+use std::mem;
+use std::slice;
+
+use {DefaultHash, RingHash, StaticHashRing};
+
+/// A map which shards its entries across the nodes (i.e., buckets) of a ring.
+///
+/// The bucket of an entry is the primary node of its key in the ring.
+/// Exclusions (see `StaticHashRing::exclude_for` and the like) are ignored,
+/// so the bucket of a key only changes when the ring is replaced by `rebalance`.
+///
+/// # Examples
+///
+/// ```
+/// use consistent_hash::{ConsistentHashMap, DefaultHash, Node, StaticHashRing};
+///
+/// let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(50)));
+/// let mut map = ConsistentHashMap::new(ring);
+/// for i in 0..1000 {
+///     map.insert(i, i * 2);
+/// }
+/// assert_eq!(map.get(&10), Some(&20));
+/// assert_eq!(map.bucket_of(&10), map.ring().calc_node(&10).map(|n| &n.key));
+///
+/// // Adds a node: only the entries owned by the new node are moved.
+/// let ring = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(50)));
+/// let moved = map.rebalance(ring);
+/// assert_eq!(moved, map.bucket_len(&3));
+/// assert_eq!(map.len(), 1000);
+/// assert_eq!(map.get(&10), Some(&20));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConsistentHashMap<K, V, S, H = DefaultHash> {
+    ring: StaticHashRing<S, (), H>,
+
+    // `buckets[i]` holds the entries owned by the node `ring.nodes()[i]` (with the hash codes of their keys).
+    buckets: Vec<HashMap<K, (u64, V)>>,
+    len: usize,
+}
+impl<K, V, S, H> ConsistentHashMap<K, V, S, H>
+    where K: Hash + Eq,
+          S: Eq,
+          H: RingHash
+{
+    /// Makes a new empty `ConsistentHashMap` instance whose buckets are the nodes of `ring`.
+    pub fn new(ring: StaticHashRing<S, (), H>) -> Self {
+        ConsistentHashMap {
+            buckets: ring.nodes().iter().map(|_| HashMap::new()).collect(),
+            ring,
+            len: 0,
+        }
+    }
+
+    /// Inserts an entry into the bucket of `key`, and returns the old value of the key (if any).
+    ///
+    /// # Panics
+    ///
+    /// If the ring has no virtual nodes, this function will panic.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let hash = self.ring.hash.hash_item(&key);
+        let bucket = self.bucket_index(hash).expect("The ring has no virtual nodes");
+        let old = self.buckets[bucket].insert(key, (hash, value)).map(|(_, v)| v);
+        if old.is_none() {
+            self.len += 1;
+        }
+        old
+    }
+
+    /// Returns a reference to the value of `key`.
+    pub fn get(&self, key: &K) -> Option<&V> {
+        let bucket = self.bucket_index(self.ring.hash.hash_item(key))?;
+        self.buckets[bucket].get(key).map(|e| &e.1)
+    }
+
+    /// Returns a mutable reference to the value of `key`.
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let bucket = self.bucket_index(self.ring.hash.hash_item(key))?;
+        self.buckets[bucket].get_mut(key).map(|e| &mut e.1)
+    }
+
+    /// Returns `true` if this map contains `key`, otherwise `false`.
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Removes the entry of `key`, and returns its value.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let bucket = self.bucket_index(self.ring.hash.hash_item(key))?;
+        let value = self.buckets[bucket].remove(key).map(|(_, v)| v);
+        if value.is_some() {
+            self.len -= 1;
+        }
+        value
+    }
+
+    /// Returns the key of the node (i.e., the bucket) which owns `key`.
+    ///
+    /// If the ring has no virtual nodes, this returns `None`.
+    pub fn bucket_of(&self, key: &K) -> Option<&S> {
+        self.bucket_index(self.ring.hash.hash_item(key)).map(|i| &self.ring.nodes[i].key)
+    }
+
+    /// Returns the count of the entries in the bucket of the node `node_key`.
+    pub fn bucket_len(&self, node_key: &S) -> usize {
+        self.ring.nodes.iter().position(|n| n.key == *node_key).map_or(0, |i| self.buckets[i].len())
+    }
+
+    /// Replaces the ring with `ring`, and moves the entries whose buckets are changed by the replacement.
+    ///
+    /// The buckets of the nodes which exist in both rings are kept,
+    /// and only the moved entries are reinserted, so this takes time proportional to the count of the entries
+    /// (without rehashing their keys) plus the count of the moved ones.
+    /// This returns the count of the moved entries.
+    ///
+    /// # Panics
+    ///
+    /// If `ring` has no virtual nodes and this map is not empty, this function will panic.
+    pub fn rebalance(&mut self, ring: StaticHashRing<S, (), H>) -> usize {
+        assert!(self.len == 0 || !ring.is_empty(), "The new ring has no virtual nodes");
+        let old_ring = mem::replace(&mut self.ring, ring);
+        let mut old_buckets = mem::take(&mut self.buckets).into_iter().map(Some).collect::<Vec<_>>();
+
+        // Carries over the buckets of the remaining nodes.
+        self.buckets = self.ring
+            .nodes
+            .iter()
+            .map(|n| {
+                old_ring.nodes
+                    .iter()
+                    .position(|o| o.key == n.key)
+                    .and_then(|i| old_buckets[i].take())
+                    .unwrap_or_default()
+            })
+            .collect();
+
+        let mut moved = Vec::new();
+        for (i, bucket) in self.buckets.iter_mut().enumerate() {
+            let ring = &self.ring;
+            moved.extend(bucket.extract_if(|_, e| bucket_index(ring, e.0) != Some(i)));
+        }
+        moved.extend(old_buckets.into_iter().flatten().flatten());
+
+        let count = moved.len();
+        for (key, entry) in moved {
+            let bucket = self.bucket_index(entry.0).expect("Never fails");
+            self.buckets[bucket].insert(key, entry);
+        }
+        count
+    }
+
+    fn bucket_index(&self, item_hash: u64) -> Option<usize> {
+        bucket_index(&self.ring, item_hash)
+    }
+}
+impl<K, V, S, H> ConsistentHashMap<K, V, S, H> {
+    /// Returns the count of the entries in this map.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if this map has no entries, otherwise `false`.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns an iterator over the entries of this map (in arbitrary order).
+    pub fn iter(&self) -> Entries<'_, K, V> {
+        Entries {
+            buckets: self.buckets.iter(),
+            bucket: None,
+        }
+    }
+
+    /// Returns a reference to the ring.
+    pub fn ring(&self) -> &StaticHashRing<S, (), H> {
+        &self.ring
+    }
+}
+
+fn bucket_index<S, H: RingHash>(ring: &StaticHashRing<S, (), H>, item_hash: u64) -> Option<usize> {
+    if ring.ring.is_empty() {
+        return None;
+    }
+    ring.candidate_vnodes(ring.locate(item_hash)).include_excluded().next().map(|i| ring.ring[i].index)
+}
+
+/// An iterator over the entries of a `ConsistentHashMap`.
+///
+/// This is created by calling `ConsistentHashMap::iter` method.
+#[derive(Debug)]
+pub struct Entries<'a, K: 'a, V: 'a> {
+    buckets: slice::Iter<'a, HashMap<K, (u64, V)>>,
+    bucket: Option<hash_map::Iter<'a, K, (u64, V)>>,
+}
+impl<'a, K: 'a, V: 'a> Iterator for Entries<'a, K, V> {
+    type Item = (&'a K, &'a V);
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((k, e)) = self.bucket.as_mut().and_then(|b| b.next()) {
+                return Some((k, &e.1));
+            }
+            self.bucket = Some(self.buckets.next()?.iter());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use {DefaultHash, Node, StaticHashRing};
+    use super::*;
+
+    #[test]
+    fn consistent_hash_map_works() {
+        let ring = |keys: &[u32]| StaticHashRing::new(DefaultHash, keys.iter().map(|&k| Node::new(k).quantity(30)));
+        let mut map = ConsistentHashMap::new(ring(&[0, 1, 2, 3]));
+        for i in 0..2000 {
+            assert_eq!(map.insert(i, i), None);
+        }
+        assert_eq!(map.insert(7, 70), Some(7));
+        assert_eq!(map.remove(&8), Some(8));
+        assert_eq!(map.remove(&8), None);
+        *map.get_mut(&9).unwrap() = 90;
+        assert_eq!(map.len(), 1999);
+        assert_eq!(map.iter().count(), 1999);
+
+        // Removes node 1 and adds node 4.
+        let before = (0..2000).map(|i| map.bucket_of(&i).cloned()).collect::<Vec<_>>();
+        let moved = map.rebalance(ring(&[0, 2, 3, 4]));
+        let after = (0..2000).map(|i| map.bucket_of(&i).cloned()).collect::<Vec<_>>();
+        let expected = (0..2000).filter(|&i| i != 8 && before[i] != after[i]).count();
+        assert_eq!(moved, expected);
+        assert_eq!(map.bucket_len(&1), 0);
+        assert_eq!(map.len(), 1999);
+        for i in 0..2000 {
+            let expected = match i {
+                7 => Some(70),
+                8 => None,
+                9 => Some(90),
+                _ => Some(i),
+            };
+            assert_eq!(map.get(&i).cloned(), expected);
+        }
+        assert_eq!((0..5).map(|k| map.bucket_len(&k)).sum::<usize>(), 1999);
+
+        // Exclusions do not change the buckets.
+        let mut excluded = ring(&[0, 2, 3, 4]);
+        excluded.exclude_for(&0, Duration::from_secs(60));
+        assert_eq!(map.rebalance(excluded), 0);
+
+        map.rebalance(ring(&[0]));
+        assert_eq!(map.bucket_len(&0), 1999);
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::BTreeMap;
+use std::hash::Hash;
+
+use {Node, RingHash, StaticHashRing};
+
+/// A batched fetch plan for a set of keys.
+///
+/// This is created by calling `StaticHashRing::plan_multi_get` method.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultiGetPlan<'a, 'k, K: 'a, V: 'a, T: 'k> {
+    /// The batches of the keys grouped by their primary nodes.
+    ///
+    /// The batches are sorted by the indices of the nodes.
+    pub batches: Vec<MultiGetBatch<'a, 'k, K, V, T>>,
+
+    /// The keys which could not be routed because the ring has no virtual nodes.
+    pub unroutable: Vec<&'k T>,
+}
+
+/// A batch of keys which have the same primary node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultiGetBatch<'a, 'k, K: 'a, V: 'a, T: 'k> {
+    /// The primary node of the keys.
+    pub node: &'a Node<K, V>,
+
+    /// The keys to be fetched from `node`.
+    pub keys: Vec<PlannedKey<'a, 'k, K, V, T>>,
+}
+
+/// A key in a `MultiGetBatch`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedKey<'a, 'k, K: 'a, V: 'a, T: 'k> {
+    /// The key.
+    pub key: &'k T,
+
+    /// The fallback nodes of the key in priority order.
+    pub fallbacks: Vec<&'a Node<K, V>>,
+}
+
+impl<K, V, H> StaticHashRing<K, V, H>
+    where H: RingHash
+{
+    /// Makes a batched fetch plan for `keys`.
+    ///
+    /// The keys are grouped by their primary nodes,
+    /// and up to `fallbacks` fallback nodes are reported for each key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+    /// let keys = ["foo", "bar", "baz", "qux"];
+    ///
+    /// let plan = ring.plan_multi_get(&keys, 1);
+    /// assert_eq!(plan.batches.iter().map(|b| b.keys.len()).sum::<usize>(), 4);
+    /// for batch in plan.batches {
+    ///     for k in batch.keys {
+    ///         assert_eq!(ring.calc_candidates(k.key).next(), Some(batch.node));
+    ///         assert_eq!(k.fallbacks.len(), 1);
+    ///     }
+    /// }
+    /// ```
+    pub fn plan_multi_get<'k, T, I>(&self,
+                                    keys: I,
+                                    fallbacks: usize)
+                                    -> MultiGetPlan<'_, 'k, K, V, T>
+        where T: 'k + Hash,
+              I: IntoIterator<Item = &'k T>
+    {
+        let mut batches = BTreeMap::new();
+        let mut unroutable = Vec::new();
+        for key in keys {
+            let mut candidates = self.calc_candidate_indices(key);
+            if let Some(primary) = candidates.next() {
+                let fallbacks = candidates.take(fallbacks).map(|i| &self.nodes()[i]).collect();
+                batches.entry(primary)
+                    .or_insert_with(Vec::new)
+                    .push(PlannedKey { key, fallbacks });
+            } else {
+                unroutable.push(key);
+            }
+        }
+        MultiGetPlan {
+            batches: batches.into_iter()
+                .map(|(i, keys)| {
+                    MultiGetBatch {
+                        node: &self.nodes()[i],
+                        keys,
+                    }
+                })
+                .collect(),
+            unroutable,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {DefaultHash, Node, StaticHashRing};
+
+    #[test]
+    fn plan_multi_get_works() {
+        let ring = StaticHashRing::new(DefaultHash, (0..5).map(|i| Node::new(i).quantity(10)));
+        let keys = (0..100).collect::<Vec<_>>();
+        let plan = ring.plan_multi_get(&keys, 2);
+        assert!(plan.unroutable.is_empty());
+        assert_eq!(plan.batches.iter().map(|b| b.keys.len()).sum::<usize>(), 100);
+        for batch in &plan.batches {
+            for k in &batch.keys {
+                let candidates = ring.calc_candidates(k.key).take(3).collect::<Vec<_>>();
+                assert_eq!(candidates[0], batch.node);
+                assert_eq!(candidates[1..], k.fallbacks[..]);
+            }
+        }
+
+        let empty = StaticHashRing::new(DefaultHash, vec![Node::new(0).quantity(0)].into_iter());
+        let plan = empty.plan_multi_get(&keys, 2);
+        assert!(plan.batches.is_empty());
+        assert_eq!(plan.unroutable.len(), 100);
+    }
+}
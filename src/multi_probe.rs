@@ -0,0 +1,201 @@
+use std::hash::Hash;
+
+use {Node, RingHash};
+
+/// The default count of probes of `MultiProbeRing`.
+pub const DEFAULT_PROBES: usize = 21;
+
+/// A hash ring based on multi-probe consistent hashing.
+///
+/// Each node has only one point on the ring, and a lookup hashes an item `probes` times
+/// and chooses the node whose point is the closest (in ring order) to one of the probes.
+/// This achieves good balance without virtual nodes (e.g., the peak-to-mean ratio is about `1.05`
+/// with 21 probes), so the memory usage is proportional to the count of nodes.
+///
+/// The quantities of nodes are only used for excluding the nodes whose quantities are `0`.
+///
+/// See [Multi-Probe Consistent Hashing](https://arxiv.org/abs/1505.00062).
+///
+/// # Examples
+///
+/// ```
+/// use consistent_hash::{Node, MultiProbeRing, DefaultHash};
+///
+/// let ring = MultiProbeRing::new(DefaultHash, (0..100).map(Node::new));
+/// assert_eq!(ring.probes(), 21);
+///
+/// let candidates = ring.calc_candidates(&"foo").take(3).collect::<Vec<_>>();
+/// assert_eq!(ring.calc_candidate(&"foo"), Some(candidates[0]));
+/// ```
+#[derive(Debug, Clone)]
+pub struct MultiProbeRing<K, V, H> {
+    hash: H,
+    nodes: Vec<Node<K, V>>,
+    points: Vec<(u64, usize)>,
+    probes: usize,
+}
+impl<K, V, H> MultiProbeRing<K, V, H>
+    where K: Hash + Eq + Ord,
+          H: RingHash
+{
+    /// Makes a new `MultiProbeRing` instance which probes `DEFAULT_PROBES` times per lookup.
+    ///
+    /// If multiple nodes which have the same key are contained in `nodes`,
+    /// all of those nodes but first one are ignored.
+    pub fn new<I>(hash: H, nodes: I) -> Self
+        where I: Iterator<Item = Node<K, V>>
+    {
+        Self::with_probes(hash, nodes, DEFAULT_PROBES)
+    }
+
+    /// Makes a new `MultiProbeRing` instance which probes `probes` times per lookup.
+    ///
+    /// If `probes` is `0`, it is regarded as `1`.
+    pub fn with_probes<I>(hash: H, nodes: I, probes: usize) -> Self
+        where I: Iterator<Item = Node<K, V>>
+    {
+        let mut nodes = nodes.collect::<Vec<_>>();
+        nodes.sort_by(|a, b| a.key.cmp(&b.key));
+        nodes.dedup_by(|a, b| a.key == b.key);
+
+        let mut points = nodes.iter()
+            .enumerate()
+            .filter(|&(_, n)| n.quantity > 0)
+            .map(|(i, n)| (hash.hash_vnode(&n.key, 0), i))
+            .collect::<Vec<_>>();
+        points.sort_by(|a, b| (a.0, &nodes[a.1].key).cmp(&(b.0, &nodes[b.1].key)));
+        MultiProbeRing {
+            hash,
+            nodes,
+            points,
+            probes: probes.max(1),
+        }
+    }
+
+    /// Returns the primary node for `item`.
+    ///
+    /// If this ring has no nodes which have positive quantities, this returns `None`.
+    pub fn calc_candidate<T: Hash>(&self, item: &T) -> Option<&Node<K, V>> {
+        self.primary_point(item).map(|p| &self.nodes[self.points[p].1])
+    }
+
+    /// Returns the candidate nodes for `item`.
+    ///
+    /// The first candidate is the primary node,
+    /// and the rest are the nodes following it in ring order.
+    pub fn calc_candidates<T: Hash>(&self, item: &T) -> MultiProbeCandidates<'_, K, V> {
+        MultiProbeCandidates {
+            nodes: &self.nodes,
+            points: &self.points,
+            start: self.primary_point(item).unwrap_or(0),
+            count: 0,
+        }
+    }
+
+    // Returns the position of the point which is the closest to one of the probes of `item`.
+    fn primary_point<T: Hash>(&self, item: &T) -> Option<usize> {
+        if self.points.is_empty() {
+            return None;
+        }
+        let item_hash = self.hash.hash_item(item);
+        (0..self.probes)
+            .map(|i| if i == 0 {
+                item_hash
+            } else {
+                self.hash.hash_item(&(item_hash, i))
+            })
+            .map(|h| {
+                let p = match self.points.binary_search_by_key(&(h, 0), |&(hash, _)| (hash, 1)) {
+                    Ok(_) => unreachable!(),
+                    Err(p) if p == self.points.len() => 0,
+                    Err(p) => p,
+                };
+                (self.points[p].0.wrapping_sub(h), p)
+            })
+            .min_by_key(|&(distance, _)| distance)
+            .map(|(_, p)| p)
+    }
+}
+impl<K, V, H> MultiProbeRing<K, V, H> {
+    /// Returns the nodes of this ring (sorted in key order).
+    pub fn nodes(&self) -> &[Node<K, V>] {
+        &self.nodes
+    }
+
+    /// Returns the count of the nodes which have positive quantities.
+    pub fn live_nodes(&self) -> usize {
+        self.points.len()
+    }
+
+    /// Returns the count of probes per lookup.
+    pub fn probes(&self) -> usize {
+        self.probes
+    }
+}
+
+/// An iterator which represents a sequence of the candidate nodes for an item in a `MultiProbeRing`.
+///
+/// This is created by calling `MultiProbeRing::calc_candidates` method.
+#[derive(Debug)]
+pub struct MultiProbeCandidates<'a, K: 'a, V: 'a> {
+    nodes: &'a [Node<K, V>],
+    points: &'a [(u64, usize)],
+    start: usize,
+    count: usize,
+}
+impl<'a, K: 'a, V: 'a> Iterator for MultiProbeCandidates<'a, K, V> {
+    type Item = &'a Node<K, V>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.count == self.points.len() {
+            return None;
+        }
+        let (_, i) = self.points[(self.start + self.count) % self.points.len()];
+        self.count += 1;
+        Some(&self.nodes[i])
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.points.len() - self.count;
+        (n, Some(n))
+    }
+}
+impl<'a, K: 'a, V: 'a> ExactSizeIterator for MultiProbeCandidates<'a, K, V> {}
+
+#[cfg(test)]
+mod tests {
+    use DefaultHash;
+    use super::*;
+
+    #[test]
+    fn multi_probe_ring_works() {
+        let nodes = (0..11).map(|i| Node::new(i).quantity(if i == 10 { 0 } else { 1 }));
+        let ring = MultiProbeRing::new(DefaultHash, nodes);
+        assert_eq!(ring.live_nodes(), 10);
+
+        let mut counts = [0; 11];
+        for i in 0..10000 {
+            let candidates = ring.calc_candidates(&i).map(|n| n.key).collect::<Vec<_>>();
+            assert_eq!(candidates.len(), 10);
+            assert!(!candidates.contains(&10));
+            assert_eq!(ring.calc_candidate(&i).map(|n| n.key), Some(candidates[0]));
+            counts[candidates[0]] += 1;
+        }
+        // The peak-to-mean ratio is low.
+        assert!(counts.iter().all(|&c| c < 1300), "{:?}", counts);
+
+        // A single probe is the same as the classic consistent hashing without virtual nodes.
+        let single = MultiProbeRing::with_probes(DefaultHash, (0..10).map(Node::new), 0);
+        assert_eq!(single.probes(), 1);
+        let balanced = |ring: &MultiProbeRing<_, _, _>| {
+            let mut counts = [0; 10];
+            for i in 0..10000 {
+                counts[ring.calc_candidate(&i).unwrap().key] += 1;
+            }
+            counts.iter().cloned().max().unwrap()
+        };
+        assert!(balanced(&single) > balanced(&ring));
+
+        let empty = MultiProbeRing::new(DefaultHash, (0..0).map(Node::new));
+        assert_eq!(empty.calc_candidate(&"foo"), None);
+        assert_eq!(empty.calc_candidates(&"foo").count(), 0);
+    }
+}
@@ -0,0 +1,120 @@
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+use {rendezvous_score, Candidates, Node, RingHash, StaticHashRing};
+
+impl<K, V, H> StaticHashRing<K, V, H>
+    where K: Hash,
+          H: RingHash
+{
+    /// Returns the two-level candidates for `item`: a node (e.g., host) and one of its sub-resources (e.g., disk).
+    ///
+    /// Nodes are chosen in the same order as `calc_candidates`,
+    /// and a sub-resource is chosen from `sub_resources(node)` by weighted rendezvous hashing
+    /// (each element of the slice is a pair of a sub-resource and its weight).
+    /// So, the choice of a sub-resource is also consistent
+    /// (e.g., adding a disk to a host only moves items to the new disk),
+    /// and replicas are never placed on the sub-resources of the same node.
+    ///
+    /// Nodes which have no sub-resources with positive weights are skipped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// // The values of nodes are their (disk, weight) pairs.
+    /// let nodes = vec![
+    ///     Node::new("host0").value(vec![("sda", 1.0), ("sdb", 2.0)]).quantity(10),
+    ///     Node::new("host1").value(vec![("sda", 1.0)]).quantity(10),
+    ///     Node::new("host2").value(vec![]).quantity(10),
+    /// ];
+    /// let ring = StaticHashRing::new(DefaultHash, nodes.into_iter());
+    ///
+    /// let candidates = ring.calc_candidates_nested(&"foo", |n| &n.value[..])
+    ///     .map(|(n, &disk)| (n.key, disk))
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(candidates.len(), 2);
+    /// assert_ne!(candidates[0].0, candidates[1].0);
+    /// ```
+    pub fn calc_candidates_nested<'s, T, D, F>(&'s self,
+                                               item: &T,
+                                               sub_resources: F)
+                                               -> NestedCandidates<'s, K, V, H, D, F>
+        where T: Hash,
+              D: 's,
+              F: Fn(&'s Node<K, V>) -> &'s [(D, f64)]
+    {
+        let item_hash = self.hash.hash_item(item);
+        NestedCandidates {
+            candidates: Candidates(self.candidate_vnodes(self.locate(item_hash))),
+            hash: &self.hash,
+            item_hash,
+            sub_resources,
+            _sub_resource: PhantomData,
+        }
+    }
+}
+
+/// An iterator which represents a sequence of the two-level candidates for an item.
+///
+/// This is created by calling `StaticHashRing::calc_candidates_nested` method.
+pub struct NestedCandidates<'a, K: 'a, V: 'a, H: 'a, D: 'a, F> {
+    candidates: Candidates<'a, K, V>,
+    hash: &'a H,
+    item_hash: u64,
+    sub_resources: F,
+    _sub_resource: PhantomData<&'a D>,
+}
+impl<'a, K: 'a, V: 'a, H: 'a, D: 'a, F> Iterator for NestedCandidates<'a, K, V, H, D, F>
+    where K: Hash,
+          H: RingHash,
+          F: Fn(&'a Node<K, V>) -> &'a [(D, f64)]
+{
+    type Item = (&'a Node<K, V>, &'a D);
+    fn next(&mut self) -> Option<Self::Item> {
+        for node in self.candidates.by_ref() {
+            let mut chosen = None;
+            let mut min_score = f64::INFINITY;
+            for (i, &(ref d, w)) in (self.sub_resources)(node).iter().enumerate() {
+                if w.is_nan() || w <= 0.0 {
+                    continue;
+                }
+                let h = self.hash.hash_item(&(self.item_hash, &node.key, i));
+                let score = rendezvous_score(h, w);
+                if score < min_score {
+                    min_score = score;
+                    chosen = Some(d);
+                }
+            }
+            if let Some(d) = chosen {
+                return Some((node, d));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {DefaultHash, Node, StaticHashRing};
+
+    #[test]
+    fn calc_candidates_nested_works() {
+        let disks = vec![(0, 1.0), (1, 3.0), (2, 0.0)];
+        let nodes = (0..4).map(|i| Node::new(i).value(disks.clone()).quantity(10));
+        let ring = StaticHashRing::new(DefaultHash, nodes);
+
+        let mut counts = [0; 3];
+        for item in 0..1000 {
+            let candidates = ring.calc_candidates_nested(&item, |n| &n.value[..]).collect::<Vec<_>>();
+            let hosts = candidates.iter().map(|&(n, _)| n).collect::<Vec<_>>();
+            assert_eq!(hosts, ring.calc_candidates(&item).collect::<Vec<_>>());
+            for &(_, &disk) in &candidates {
+                counts[disk] += 1;
+            }
+        }
+        assert_eq!(counts[2], 0);
+        assert!(2700 < counts[1] && counts[1] < 3300, "{:?}", counts);
+    }
+}
@@ -0,0 +1,147 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::hash::Hash;
+
+use {CandidateIndices, Node, RingHash, StaticHashRing};
+
+/// A compact identifier of a node in a ring.
+///
+/// This is the index of the node in `StaticHashRing::nodes` as a `u32`,
+/// so it can be copied, compared, and serialized cheaply instead of (potentially large) node keys.
+/// Identifiers are only meaningful for the ring which issued them
+/// (and rings which have the same nodes, since nodes are sorted by their keys).
+///
+/// # Examples
+///
+/// ```
+/// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+///
+/// let ring = StaticHashRing::new(DefaultHash, vec![Node::new("foo"), Node::new("bar")].into_iter());
+/// let id = ring.node_id(&"foo").unwrap();
+/// assert_eq!(id.index(), 1);
+/// assert_eq!(ring.key_of(id), Some(&"foo"));
+///
+/// let ids = ring.calc_candidate_ids(&"item").collect::<Vec<_>>();
+/// assert_eq!(ring.node_by_id(ids[0]), ring.calc_node(&"item"));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct NodeId(u32);
+impl NodeId {
+    /// Makes a new `NodeId` instance from the raw value.
+    pub fn new(id: u32) -> Self {
+        NodeId(id)
+    }
+
+    /// Makes a new `NodeId` instance from the index of a node.
+    ///
+    /// # Panics
+    ///
+    /// If `index` exceeds `u32::MAX`, this function will panic.
+    pub fn from_index(index: usize) -> Self {
+        NodeId(u32::try_from(index).expect("Too large node index"))
+    }
+
+    /// Returns the raw value of this identifier.
+    pub fn as_u32(self) -> u32 {
+        self.0
+    }
+
+    /// Returns the index of the node (i.e., the position in `StaticHashRing::nodes`).
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+impl From<u32> for NodeId {
+    fn from(f: u32) -> Self {
+        NodeId(f)
+    }
+}
+impl From<NodeId> for u32 {
+    fn from(f: NodeId) -> Self {
+        f.0
+    }
+}
+impl From<NodeId> for usize {
+    fn from(f: NodeId) -> Self {
+        f.index()
+    }
+}
+impl fmt::Display for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+impl<K, V, H> StaticHashRing<K, V, H>
+    where K: Hash + Eq + Ord,
+          H: RingHash
+{
+    /// Returns the identifier of the node `key`.
+    ///
+    /// If this ring does not contain the node, this returns `None`.
+    pub fn node_id(&self, key: &K) -> Option<NodeId> {
+        self.node_index(key).map(NodeId::from_index)
+    }
+}
+impl<K, V, H> StaticHashRing<K, V, H> {
+    /// Returns the node identified by `id`.
+    pub fn node_by_id(&self, id: NodeId) -> Option<&Node<K, V>> {
+        self.nodes.get(id.index())
+    }
+
+    /// Returns the key of the node identified by `id`.
+    pub fn key_of(&self, id: NodeId) -> Option<&K> {
+        self.node_by_id(id).map(|n| &n.key)
+    }
+}
+impl<K, V, H> StaticHashRing<K, V, H>
+    where H: RingHash
+{
+    /// Returns the identifiers of the candidate nodes for `item`.
+    ///
+    /// This is the same as `calc_candidate_indices` except for the element type.
+    pub fn calc_candidate_ids<T: Hash + ?Sized>(&self, item: &T) -> CandidateIds<'_, K, V> {
+        CandidateIds(self.calc_candidate_indices(item))
+    }
+
+    /// Returns the identifiers of the candidate nodes for the item whose hash code is `item_hash`.
+    pub fn calc_candidate_ids_by_hash(&self, item_hash: u64) -> CandidateIds<'_, K, V> {
+        CandidateIds(self.calc_candidate_indices_by_hash(item_hash))
+    }
+}
+
+/// An iterator which represents a sequence of the identifiers of the candidate nodes for an item.
+///
+/// This is created by calling `StaticHashRing::calc_candidate_ids` method.
+pub struct CandidateIds<'a, K: 'a, V: 'a>(CandidateIndices<'a, K, V>);
+impl<'a, K: 'a, V: 'a> Iterator for CandidateIds<'a, K, V> {
+    type Item = NodeId;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(NodeId::from_index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {DefaultHash, Node, StaticHashRing};
+    use super::*;
+
+    #[test]
+    fn node_id_works() {
+        let ring = StaticHashRing::new(DefaultHash, (0..5).map(|i| Node::new(i * 10).quantity(10)));
+        for (i, node) in ring.nodes().iter().enumerate() {
+            let id = ring.node_id(&node.key).unwrap();
+            assert_eq!(id, NodeId::from_index(i));
+            assert_eq!(usize::from(id), i);
+            assert_eq!(ring.key_of(id), Some(&node.key));
+        }
+        assert_eq!(ring.node_id(&1), None);
+        assert_eq!(ring.node_by_id(NodeId::new(5)), None);
+        assert_eq!(NodeId::from(3).to_string(), "#3");
+
+        for item in 0..100 {
+            let ids = ring.calc_candidate_ids(&item).map(usize::from).collect::<Vec<_>>();
+            assert_eq!(ids, ring.calc_candidate_indices(&item).collect::<Vec<_>>());
+        }
+    }
+}
@@ -0,0 +1,279 @@
+#[cfg(feature = "observer")]
+use std::fmt;
+#[cfg(feature = "observer")]
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "observer")]
+use std::time::Duration;
+use std::time::Instant;
+
+#[cfg(feature = "observer")]
+use NodeId;
+use StaticHashRing;
+
+/// An observer of the lookups and takes of a ring.
+///
+/// An observer is notified of every lookup (`calc_candidates` and its variants, `calc_node`,
+/// and `calc_candidate_indices`) and every take (`take` and its variants) which chooses a node,
+/// so per-node selection counters and latency histograms can be maintained
+/// without wrapping every call site (see `StaticHashRing::set_observer`).
+///
+/// Both methods do nothing by default.
+/// Observers are called synchronously on the lookup path, so they should be cheap.
+///
+/// This is available if the `observer` feature is enabled.
+/// Otherwise the notifications are compiled out entirely.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use consistent_hash::{Node, StaticHashRing, DefaultHash, SelectionCounters};
+///
+/// let mut ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+/// let counters = Arc::new(SelectionCounters::new());
+/// ring.set_observer(Some(counters.clone()));
+///
+/// let primary = ring.calc_node(&"foo").unwrap().key;
+/// ring.calc_candidates(&"foo").count();
+/// ring.take(&"foo");
+///
+/// let id = ring.node_id(&primary).unwrap();
+/// assert_eq!(counters.lookups(id), 2);
+/// assert_eq!(counters.takes(id), 1);
+/// ```
+#[cfg(feature = "observer")]
+pub trait RingObserver {
+    /// Called when a lookup chooses a node (i.e., the highest priority candidate).
+    ///
+    /// This is called even if the caller does not consume the candidates.
+    fn on_lookup(&self, event: &LookupEvent) {
+        let _ = event;
+    }
+
+    /// Called when a take removes a virtual node.
+    fn on_take(&self, event: &LookupEvent) {
+        let _ = event;
+    }
+}
+
+/// A lookup or take notified to a `RingObserver`.
+///
+/// This is available if the `observer` feature is enabled.
+#[cfg(feature = "observer")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LookupEvent {
+    /// The hash code of the item.
+    pub item_hash: u64,
+
+    /// The identifier of the chosen node.
+    pub node: NodeId,
+
+    /// The hash code of the chosen virtual node.
+    pub vnode_hash: u64,
+
+    /// The time spent on locating the node (excluding the hashing of the item).
+    pub elapsed: Duration,
+}
+
+/// A `RingObserver` which counts the lookups and takes of each node.
+///
+/// This is available if the `observer` feature is enabled.
+#[cfg(feature = "observer")]
+#[derive(Debug, Default)]
+pub struct SelectionCounters {
+    lookups: Mutex<Vec<u64>>,
+    takes: Mutex<Vec<u64>>,
+}
+#[cfg(feature = "observer")]
+impl SelectionCounters {
+    /// Makes a new `SelectionCounters` instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the count of the lookups which chose the node `node`.
+    pub fn lookups(&self, node: NodeId) -> u64 {
+        get(&self.lookups, node)
+    }
+
+    /// Returns the count of the takes which removed virtual nodes of the node `node`.
+    pub fn takes(&self, node: NodeId) -> u64 {
+        get(&self.takes, node)
+    }
+
+    /// Resets the counters.
+    pub fn reset(&self) {
+        lock(&self.lookups).clear();
+        lock(&self.takes).clear();
+    }
+}
+#[cfg(feature = "observer")]
+impl RingObserver for SelectionCounters {
+    fn on_lookup(&self, event: &LookupEvent) {
+        increment(&self.lookups, event.node);
+    }
+    fn on_take(&self, event: &LookupEvent) {
+        increment(&self.takes, event.node);
+    }
+}
+
+#[cfg(feature = "observer")]
+fn lock(counts: &Mutex<Vec<u64>>) -> ::std::sync::MutexGuard<'_, Vec<u64>> {
+    counts.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(feature = "observer")]
+fn get(counts: &Mutex<Vec<u64>>, node: NodeId) -> u64 {
+    lock(counts).get(node.index()).cloned().unwrap_or(0)
+}
+
+#[cfg(feature = "observer")]
+fn increment(counts: &Mutex<Vec<u64>>, node: NodeId) {
+    let mut counts = lock(counts);
+    if counts.len() <= node.index() {
+        counts.resize(node.index() + 1, 0);
+    }
+    counts[node.index()] += 1;
+}
+
+// The observer of a ring.
+//
+// If the `observer` feature is disabled, this is a zero-sized type whose methods do nothing,
+// so the notifications in the lookup paths are optimized out.
+#[cfg(feature = "observer")]
+#[derive(Clone)]
+pub struct ObserverSlot(Option<Arc<dyn RingObserver + Send + Sync>>);
+#[cfg(not(feature = "observer"))]
+#[derive(Debug, Clone)]
+pub struct ObserverSlot;
+impl ObserverSlot {
+    pub fn new() -> Self {
+        #[cfg(feature = "observer")]
+        {
+            ObserverSlot(None)
+        }
+        #[cfg(not(feature = "observer"))]
+        {
+            ObserverSlot
+        }
+    }
+
+    // Returns the start time of an operation if an observer is set.
+    #[inline]
+    pub fn start(&self) -> Option<Instant> {
+        #[cfg(feature = "observer")]
+        {
+            self.0.as_ref().map(|_| Instant::now())
+        }
+        #[cfg(not(feature = "observer"))]
+        {
+            None
+        }
+    }
+}
+#[cfg(feature = "observer")]
+impl fmt::Debug for ObserverSlot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0.is_some() {
+            write!(f, "ObserverSlot(Some(..))")
+        } else {
+            write!(f, "ObserverSlot(None)")
+        }
+    }
+}
+
+impl<K, V, H> StaticHashRing<K, V, H> {
+    /// Sets the observer which is notified of the lookups and takes of this ring.
+    ///
+    /// If `observer` is `None`, the current observer is unset.
+    /// Clones of this ring share the observer.
+    ///
+    /// This is available if the `observer` feature is enabled.
+    #[cfg(feature = "observer")]
+    pub fn set_observer(&mut self, observer: Option<Arc<dyn RingObserver + Send + Sync>>) {
+        self.observer = ObserverSlot(observer);
+    }
+
+    // Notifies the observer that the virtual node at `position` (if any) is chosen by a lookup for `item_hash`.
+    #[cfg_attr(not(feature = "observer"), allow(unused_variables))]
+    pub(crate) fn notify_lookup(&self, item_hash: u64, position: Option<usize>, started: Instant) {
+        #[cfg(feature = "observer")]
+        {
+            let event = self.lookup_event(item_hash, position, started);
+            if let (Some(observer), Some(event)) = (&self.observer.0, event) {
+                observer.on_lookup(&event);
+            }
+        }
+    }
+
+    // Notifies the observer that the virtual node at `position` is about to be removed by a take for `item_hash`.
+    #[cfg_attr(not(feature = "observer"), allow(unused_variables))]
+    pub(crate) fn notify_take(&self, item_hash: u64, position: usize, started: Instant) {
+        #[cfg(feature = "observer")]
+        {
+            let event = self.lookup_event(item_hash, Some(position), started);
+            if let (Some(observer), Some(event)) = (&self.observer.0, event) {
+                observer.on_take(&event);
+            }
+        }
+    }
+
+    #[cfg(feature = "observer")]
+    fn lookup_event(&self, item_hash: u64, position: Option<usize>, started: Instant) -> Option<LookupEvent> {
+        let vnode = self.ring[position?];
+        Some(LookupEvent {
+            item_hash,
+            node: NodeId::from_index(vnode.index),
+            vnode_hash: vnode.hash,
+            elapsed: started.elapsed(),
+        })
+    }
+}
+
+#[cfg(all(test, feature = "observer"))]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use {DefaultHash, Node, RingHash, StaticHashRing};
+    use super::*;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<(&'static str, LookupEvent)>>);
+    impl RingObserver for Recorder {
+        fn on_lookup(&self, event: &LookupEvent) {
+            self.0.lock().unwrap().push(("lookup", *event));
+        }
+        fn on_take(&self, event: &LookupEvent) {
+            self.0.lock().unwrap().push(("take", *event));
+        }
+    }
+
+    #[test]
+    fn observer_works() {
+        let mut ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+        let recorder = Arc::new(Recorder::default());
+        ring.set_observer(Some(recorder.clone()));
+
+        let item_hash = DefaultHash.hash_item(&"foo");
+        let primary = ring.calc_node(&"foo").map(|n| n.key).unwrap();
+        ring.calc_candidate_indices(&"foo").next();
+        let taken = ring.take(&"foo").map(|t| (t.node.key, t.hash)).unwrap();
+        ring.take_by_hash(item_hash);
+        ring.take_spread(vec!["foo"], 10);
+        ring.take_weighted(&"foo", |_| 1.0);
+
+        let events = recorder.0.lock().unwrap().clone();
+        let kinds = events.iter().map(|e| e.0).collect::<Vec<_>>();
+        assert_eq!(kinds, ["lookup", "lookup", "take", "take", "take", "take"]);
+        assert!(events.iter().all(|e| e.1.item_hash == item_hash));
+        assert_eq!(events[0].1.node, ring.node_id(&primary).unwrap());
+        assert_eq!((ring.key_of(events[2].1.node).cloned(), events[2].1.vnode_hash), (Some(taken.0), taken.1));
+
+        // Lookups which choose no node are not notified.
+        let mut empty = StaticHashRing::new(DefaultHash, (0..0).map(Node::new));
+        empty.set_observer(Some(recorder.clone()));
+        assert_eq!(empty.calc_node(&"foo"), None);
+        ring.set_observer(None);
+        ring.calc_node(&"foo");
+        assert_eq!(recorder.0.lock().unwrap().len(), 6);
+    }
+}
@@ -0,0 +1,963 @@
+use std::collections::BTreeMap;
+use std::hash::Hash;
+
+use range::HashRange;
+use {CandidateVnodes, Node, RingHash, StaticHashRing, VirtualNode};
+
+// A contiguous range of hash codes whose primary node is `node`.
+//
+// Both of `start` and `end` are inclusive.
+// The lookups for the hash codes in this range start from the virtual node at `vnode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OwnedRange {
+    pub start: u64,
+    pub end: u64,
+    pub vnode: usize,
+    pub node: usize,
+}
+impl OwnedRange {
+    pub fn len(&self) -> u128 {
+        (self.end - self.start) as u128 + 1
+    }
+}
+
+// Returns the fraction of the hash space which `len` hash codes occupy.
+pub(crate) fn space_fraction(len: u128) -> f64 {
+    len as f64 / 2f64.powi(64)
+}
+
+/// The estimated impact of a topology change on a ring.
+///
+/// This is created by calling `StaticHashRing::simulate_add` or `StaticHashRing::simulate_remove` method.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TopologyImpact<'a, K: 'a, V: 'a> {
+    /// The fraction of the hash space which would be reassigned to other (primary) nodes (`0.0..=1.0`).
+    pub moved_fraction: f64,
+
+    /// The ranges of hash codes which would be reassigned, ordered by their hash codes.
+    pub moved_ranges: Vec<HashRange>,
+
+    /// The changes of the ownership (as primary) of each affected node.
+    ///
+    /// Gains are positive and losses are negative.
+    /// The list is ordered as `StaticHashRing::nodes` (an added node comes last).
+    pub deltas: Vec<(&'a Node<K, V>, f64)>,
+}
+impl<'a, K: 'a, V: 'a> TopologyImpact<'a, K, V> {
+    /// Returns `true` if the hash code `item_hash` would be reassigned, otherwise `false`.
+    pub fn is_moved(&self, item_hash: u64) -> bool {
+        let i = self.moved_ranges.partition_point(|r| r.end < item_hash);
+        self.moved_ranges.get(i).is_some_and(|r| r.contains(item_hash))
+    }
+}
+
+/// A range of hash codes which would be moved from a node to another.
+///
+/// This is returned by `StaticHashRing::addition_preview` method.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RangeTransfer<'a, K: 'a, V: 'a> {
+    /// The first hash code of the range (inclusive).
+    pub start: u64,
+
+    /// The last hash code of the range (inclusive).
+    pub end: u64,
+
+    /// The node which currently owns the range as primary.
+    ///
+    /// If the ring is empty, this is `None`.
+    pub from: Option<&'a Node<K, V>>,
+}
+
+// A point of a ring (i.e., a virtual node) which is used for calculating owned ranges.
+#[derive(Debug, Clone, Copy)]
+struct RingPoint {
+    hash: u64,
+    node: usize,
+    replica_only: bool,
+    excluded: bool,
+}
+
+// Calculates the owned ranges of the ring which consists of `points` (sorted in ring order).
+//
+// The point at `i` owns the hash codes in `(points[i - 1].hash, points[i].hash]`,
+// and the first point also owns the codes after the last point (i.e., wrapped around).
+// As with lookups, the primary node of a range is the first node found by walking the ring
+// from the owning point which is neither excluded nor replica-only
+// (or the first non excluded node if every such node is replica-only).
+// If every node is excluded, no range has a primary node and this returns an empty list.
+fn primary_ranges(points: &[RingPoint]) -> Vec<OwnedRange> {
+    let nearest = |accepts: &dyn Fn(&RingPoint) -> bool| {
+        let mut nearests = vec![None; points.len()];
+        let mut next = None;
+        for _ in 0..2 {
+            for (i, p) in points.iter().enumerate().rev() {
+                if accepts(p) {
+                    next = Some(p.node);
+                }
+                nearests[i] = next;
+            }
+        }
+        nearests
+    };
+    let primaries = nearest(&|p| !p.excluded && !p.replica_only);
+    let fallbacks = nearest(&|p| !p.excluded);
+    if fallbacks.iter().all(Option::is_none) {
+        return Vec::new();
+    }
+
+    let mut ranges = Vec::with_capacity(points.len() + 1);
+    let mut start = 0;
+    for (i, p) in points.iter().enumerate() {
+        if i > 0 && points[i - 1].hash == p.hash {
+            continue;
+        }
+        ranges.push(OwnedRange {
+            start,
+            end: p.hash,
+            vnode: i,
+            node: primaries[i].or(fallbacks[i]).expect("Never fails"),
+        });
+        if p.hash == u64::MAX {
+            return ranges;
+        }
+        start = p.hash + 1;
+    }
+    if let Some(first) = ranges.first().cloned() {
+        ranges.push(OwnedRange {
+            start,
+            end: u64::MAX,
+            vnode: first.vnode,
+            node: first.node,
+        });
+    }
+    ranges
+}
+
+impl<K, V, H> StaticHashRing<K, V, H> {
+    // Returns the point of the virtual node `vn`, annotated with the states of its node
+    // (checked in the same way as lookups).
+    fn ring_point(&self, states: &CandidateVnodes<'_, K, V>, vn: &VirtualNode) -> RingPoint {
+        RingPoint {
+            hash: vn.hash,
+            node: vn.index,
+            replica_only: states.is_replica_only(vn.index),
+            excluded: states.is_excluded(vn.index),
+        }
+    }
+
+    fn ring_points(&self) -> Vec<RingPoint> {
+        let states = self.candidate_vnodes(0);
+        self.ring.iter().map(|vn| self.ring_point(&states, vn)).collect()
+    }
+
+    // Returns the ranges which cover the whole hash space in ascending order,
+    // each of which is annotated with its primary node.
+    pub(crate) fn primary_ranges(&self) -> Vec<OwnedRange> {
+        primary_ranges(&self.ring_points())
+    }
+
+    /// Returns how much of each hash-range bucket is owned by each node (as primary).
+    ///
+    /// The hash space is divided into `buckets` equal-sized buckets (in ascending order),
+    /// and `histogram[bucket][node_index]` is the fraction of the bucket owned by the node.
+    /// So, the fractions of each bucket sum to `1.0` unless this ring is empty (or every node is excluded).
+    /// Node indices are the positions in `nodes()`.
+    ///
+    /// This is useful for heatmap dashboards which show where in the ring each node's load comes from.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+    /// let histogram = ring.ownership_histogram(8);
+    /// assert_eq!(histogram.len(), 8);
+    /// for bucket in &histogram {
+    ///     assert_eq!(bucket.len(), 3);
+    ///     assert!((bucket.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+    /// }
+    /// ```
+    pub fn ownership_histogram(&self, buckets: usize) -> Vec<Vec<f64>> {
+        let mut histogram = vec![vec![0.0; self.nodes.len()]; buckets];
+        if buckets == 0 {
+            return histogram;
+        }
+        let bucket_start = |i: usize| ((i as u128) << 64) / buckets as u128;
+        let mut bucket = 0;
+        for r in self.primary_ranges() {
+            let mut start = r.start as u128;
+            let end = r.end as u128 + 1;
+            while start < end {
+                let bucket_end = bucket_start(bucket + 1);
+                let covered = end.min(bucket_end);
+                let bucket_len = bucket_end - bucket_start(bucket);
+                histogram[bucket][r.node] += (covered - start) as f64 / bucket_len as f64;
+                start = covered;
+                if covered == bucket_end {
+                    bucket += 1;
+                }
+            }
+        }
+        histogram
+    }
+
+    /// Returns the fraction of the hash space owned (as primary) by each group of nodes.
+    ///
+    /// The group of a node is determined by `group_of` (e.g., host, zone, or hardware class),
+    /// so imbalance can be assessed at the level operators care about.
+    /// Every group which has one or more nodes is contained in the result,
+    /// and the fractions sum to `1.0` unless this ring is empty (or every node is excluded).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// // The values of nodes are their hosts.
+    /// let nodes = (0..6).map(|i| Node::new(i).value(i / 2).quantity(50));
+    /// let ring = StaticHashRing::new(DefaultHash, nodes);
+    ///
+    /// let ownership = ring.ownership_by(|n| n.value);
+    /// assert_eq!(ownership.keys().cloned().collect::<Vec<_>>(), [0, 1, 2]);
+    /// assert!(ownership.values().all(|&f| 0.2 < f && f < 0.5));
+    /// ```
+    pub fn ownership_by<G, F>(&self, group_of: F) -> BTreeMap<G, f64>
+        where G: Ord,
+              F: Fn(&Node<K, V>) -> G
+    {
+        let groups = self.nodes.iter().map(&group_of).collect::<Vec<_>>();
+        let mut owned = vec![0; self.nodes.len()];
+        for r in self.primary_ranges() {
+            owned[r.node] += r.len();
+        }
+
+        let mut ownership = BTreeMap::new();
+        for (group, owned) in groups.into_iter().zip(owned) {
+            *ownership.entry(group).or_insert(0.0) += owned as f64 / 2f64.powi(64);
+        }
+        ownership
+    }
+}
+impl<K, V, H> StaticHashRing<K, V, H>
+    where K: Hash + Eq + Ord,
+          H: RingHash
+{
+    /// Returns the ranges of hash codes for which the node identified by `node_key` is primary.
+    ///
+    /// The ranges are ordered by their hash codes, and adjacent ranges are merged
+    /// (the last range wraps around if the node owns both ends of the hash space).
+    /// This is useful for data-migration tooling which scans only the keys owned by a node.
+    ///
+    /// As with lookups, excluded nodes are not primary for any hashes.
+    /// If no such node exists or the node is not primary for any hashes, this yields nothing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+    ///
+    /// for range in ring.owned_ranges(&1) {
+    ///     assert_eq!(ring.calc_node_by_hash(range.start).map(|n| n.key), Some(1));
+    ///     assert_eq!(ring.calc_node_by_hash(range.end).map(|n| n.key), Some(1));
+    /// }
+    /// assert_eq!(ring.owned_ranges(&10).count(), 0);
+    /// ```
+    pub fn owned_ranges(&self, node_key: &K) -> impl Iterator<Item = HashRange> {
+        let mut ranges: Vec<HashRange> = Vec::new();
+        if let Some(node) = self.node_index(node_key) {
+            for r in self.primary_ranges().into_iter().filter(|r| r.node == node) {
+                match ranges.last_mut() {
+                    Some(last) if last.end.wrapping_add(1) == r.start => last.end = r.end,
+                    _ => ranges.push(HashRange::new(r.start, r.end)),
+                }
+            }
+        }
+        if ranges.len() > 1 && ranges[0].start == 0 && ranges[ranges.len() - 1].end == u64::MAX {
+            let first = ranges.remove(0);
+            let last = ranges.len() - 1;
+            ranges[last].end = first.end;
+        }
+        ranges.into_iter()
+    }
+
+    /// Generates `n` synthetic item hashes whose primary node is the node identified by `node_key`.
+    ///
+    /// The hashes are distributed uniformly within the ranges owned by the node,
+    /// so they can be used for load-testing a single node with realistic placement.
+    /// `rng` is a source of uniformly distributed random numbers,
+    /// and the result is deterministic for the same sequence of the numbers.
+    ///
+    /// If no such node exists or the node is not primary for any hashes, this returns an empty vector.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+    ///
+    /// let mut seed = 1u64;
+    /// let mut rng = || {
+    ///     seed ^= seed << 13;
+    ///     seed ^= seed >> 7;
+    ///     seed ^= seed << 17;
+    ///     seed
+    /// };
+    /// assert_eq!(ring.sample_keys_for(&1, 100, &mut rng).len(), 100);
+    /// assert!(ring.sample_keys_for(&10, 100, &mut rng).is_empty());
+    /// ```
+    pub fn sample_keys_for<R>(&self, node_key: &K, n: usize, rng: &mut R) -> Vec<u64>
+        where R: FnMut() -> u64
+    {
+        let node = match self.node_index(node_key) {
+            None => return Vec::new(),
+            Some(node) => node,
+        };
+        let ranges = self.primary_ranges().into_iter().filter(|r| r.node == node).collect::<Vec<_>>();
+        let total = ranges.iter().map(|r| r.len()).sum::<u128>();
+        if total == 0 {
+            return Vec::new();
+        }
+        (0..n)
+            .map(|_| {
+                let mut offset = (rng() as u128 * total) >> 64;
+                for r in &ranges {
+                    if offset < r.len() {
+                        return r.start + offset as u64;
+                    }
+                    offset -= r.len();
+                }
+                unreachable!()
+            })
+            .collect()
+    }
+
+    /// Generates a synthetic item hash whose primary node is the node identified by `node_key`.
+    ///
+    /// The hash is sampled uniformly from the whole space owned by the node
+    /// (i.e., each range is chosen with the probability proportional to its length).
+    /// The resulting hash can be routed by `calc_candidates_by_hash`.
+    ///
+    /// This is the same as `sample_keys_for(node_key, 1, rng).pop()`.
+    pub fn random_key_hash_for<R>(&self, node_key: &K, rng: &mut R) -> Option<u64>
+        where R: FnMut() -> u64
+    {
+        self.sample_keys_for(node_key, 1, rng).pop()
+    }
+
+    /// Searches for a human-readable key whose primary node is the node identified by `node_key`.
+    ///
+    /// The keys `"{base}-0"`, `"{base}-1"`, ... are tried in order, and the first matching one is returned.
+    /// This is useful for writing tests targeting a specific node or reproducing incidents.
+    ///
+    /// If no matching key is found within `max_attempts` tries, this returns `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+    ///
+    /// let key = ring.find_key_for(&2, "user", 1000).unwrap();
+    /// assert!(key.starts_with("user-"));
+    /// assert_eq!(ring.calc_node(&key).map(|n| n.key), Some(2));
+    /// ```
+    pub fn find_key_for(&self, node_key: &K, base: &str, max_attempts: usize) -> Option<String> {
+        self.node_index(node_key)?;
+        (0..max_attempts)
+            .map(|i| format!("{}-{}", base, i))
+            .find(|key| self.calc_node(key).is_some_and(|n| n.key == *node_key))
+    }
+
+    /// Estimates the impact of removing the node identified by `node_key` from this ring.
+    ///
+    /// The result is the list of the nodes which would absorb the ranges owned (as primary)
+    /// by the removed node, paired with the fraction of the ranges each of them would take.
+    /// The fractions sum to `1.0`, and the list is ordered as `nodes()`.
+    ///
+    /// If no such node exists, this returns `None`.
+    /// If the node owns no ranges, this returns an empty list.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(50)));
+    ///
+    /// let impact = ring.removal_impact(&0).unwrap();
+    /// assert_eq!(impact.iter().map(|&(n, _)| n.key).collect::<Vec<_>>(), [1, 2, 3]);
+    /// assert!((impact.iter().map(|&(_, f)| f).sum::<f64>() - 1.0).abs() < 1e-9);
+    /// ```
+    pub fn removal_impact(&self, node_key: &K) -> Option<Vec<(&Node<K, V>, f64)>> {
+        let removed = self.node_index(node_key)?;
+        let mut absorbed = vec![0; self.nodes.len()];
+        for (r, successor) in self.removal_transfers(removed) {
+            if let Some(i) = successor {
+                absorbed[i] += r.len();
+            }
+        }
+        let total = absorbed.iter().sum::<u128>();
+        Some(absorbed.into_iter()
+            .enumerate()
+            .filter(|&(_, n)| n > 0)
+            .map(|(i, n)| (&self.nodes[i], n as f64 / total as f64))
+            .collect())
+    }
+
+    /// Returns the ranges owned (as primary) by the node identified by `node_key`,
+    /// paired with the nodes which would inherit them if the node were removed.
+    ///
+    /// This is useful for pre-warming caches or pre-copying data to the inheriting nodes before decommissioning.
+    /// The ranges are ordered by their hash codes, and adjacent ranges inherited by the same node are merged.
+    ///
+    /// If no such node exists, or no other node could inherit the ranges, this yields nothing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(50)));
+    /// let remaining = StaticHashRing::new(DefaultHash, (1..4).map(|i| Node::new(i).quantity(50)));
+    ///
+    /// for (range, heir) in ring.impact_of_removal(&0) {
+    ///     assert_eq!(ring.calc_node_by_hash(range.start).map(|n| n.key), Some(0));
+    ///     assert_eq!(remaining.calc_node_by_hash(range.start), Some(heir));
+    ///     assert_eq!(remaining.calc_node_by_hash(range.end), Some(heir));
+    /// }
+    /// assert_eq!(ring.impact_of_removal(&10).count(), 0);
+    /// ```
+    pub fn impact_of_removal(&self, node_key: &K) -> impl Iterator<Item = (HashRange, &Node<K, V>)> {
+        let mut ranges: Vec<(HashRange, usize)> = Vec::new();
+        if let Some(removed) = self.node_index(node_key) {
+            for (r, heir) in self.removal_transfers(removed) {
+                let heir = match heir {
+                    None => continue,
+                    Some(heir) => heir,
+                };
+                match ranges.last_mut() {
+                    Some(&mut (ref mut last, h)) if h == heir && last.end.wrapping_add(1) == r.start => {
+                        last.end = r.end
+                    }
+                    _ => ranges.push((HashRange::new(r.start, r.end), heir)),
+                }
+            }
+        }
+        let nodes = &self.nodes;
+        ranges.into_iter().map(move |(r, heir)| (r, &nodes[heir]))
+    }
+
+    /// Estimates the impact of adding `node` to this ring, without building another ring.
+    ///
+    /// The added node gains the ranges reported by `addition_preview`, and their current owners lose them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(50)));
+    /// let node = Node::new(3).quantity(50);
+    ///
+    /// let impact = ring.simulate_add(&node);
+    /// assert!(0.15 < impact.moved_fraction && impact.moved_fraction < 0.35);
+    /// assert_eq!(impact.deltas.last(), Some(&(&node, impact.moved_fraction)));
+    /// assert!(impact.deltas[..3].iter().all(|&(_, d)| d < 0.0));
+    ///
+    /// // Estimates the fraction of the moved keys by samples.
+    /// let moved = ring.moved_key_fraction(&impact, 0..1000);
+    /// assert!((moved - impact.moved_fraction).abs() < 0.1);
+    /// ```
+    pub fn simulate_add<'a>(&'a self, node: &'a Node<K, V>) -> TopologyImpact<'a, K, V> {
+        let transfers = self.addition_preview(node);
+        let mut lost = vec![0; self.nodes.len()];
+        let mut moved_ranges = Vec::with_capacity(transfers.len());
+        for t in &transfers {
+            let range = HashRange::new(t.start, t.end);
+            if let Some(i) = t.from.and_then(|n| self.node_index(&n.key)) {
+                lost[i] += range.len();
+            }
+            moved_ranges.push(range);
+        }
+        let moved = moved_ranges.iter().map(|r| r.len()).sum::<u128>();
+        let mut deltas = self.nodes
+            .iter()
+            .zip(lost)
+            .filter(|&(_, n)| n > 0)
+            .map(|(node, n)| (node, -space_fraction(n)))
+            .collect::<Vec<_>>();
+        if moved > 0 {
+            deltas.push((node, space_fraction(moved)));
+        }
+        TopologyImpact {
+            moved_fraction: space_fraction(moved),
+            moved_ranges,
+            deltas,
+        }
+    }
+
+    /// Estimates the impact of removing the node identified by `node_key` from this ring,
+    /// without building another ring.
+    ///
+    /// The ranges owned (as primary) by the removed node are absorbed by the nodes reported by `removal_impact`
+    /// (if no nodes remain, the ranges are lost but regarded as moved).
+    /// If no such node exists, this returns `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(50)));
+    ///
+    /// let impact = ring.simulate_remove(&0).unwrap();
+    /// assert!(0.15 < impact.moved_fraction && impact.moved_fraction < 0.35);
+    /// assert_eq!(impact.deltas[0], (&ring.nodes()[0], -impact.moved_fraction));
+    /// assert!((impact.deltas.iter().map(|&(_, d)| d).sum::<f64>()).abs() < 1e-9);
+    /// ```
+    pub fn simulate_remove(&self, node_key: &K) -> Option<TopologyImpact<'_, K, V>> {
+        let removed = self.node_index(node_key)?;
+        let mut changes = vec![0i128; self.nodes.len()];
+        let mut moved_ranges = Vec::new();
+        for (r, successor) in self.removal_transfers(removed) {
+            changes[removed] -= r.len() as i128;
+            if let Some(i) = successor {
+                changes[i] += r.len() as i128;
+            }
+            match moved_ranges.last_mut() {
+                Some(&mut HashRange { ref mut end, .. }) if end.wrapping_add(1) == r.start => *end = r.end,
+                _ => moved_ranges.push(HashRange::new(r.start, r.end)),
+            }
+        }
+        let moved = moved_ranges.iter().map(|r| r.len()).sum::<u128>();
+        let deltas = self.nodes
+            .iter()
+            .zip(changes)
+            .filter(|&(_, n)| n != 0)
+            .map(|(node, n)| (node, n.signum() as f64 * space_fraction(n.unsigned_abs())))
+            .collect();
+        Some(TopologyImpact {
+            moved_fraction: space_fraction(moved),
+            moved_ranges,
+            deltas,
+        })
+    }
+
+    /// Returns the fraction of `keys` which would be reassigned according to `impact` (`0.0` if `keys` is empty).
+    ///
+    /// `impact` is expected to be created from this ring (see `simulate_add` and `simulate_remove`).
+    pub fn moved_key_fraction<T, I>(&self, impact: &TopologyImpact<K, V>, keys: I) -> f64
+        where T: Hash,
+              I: IntoIterator<Item = T>
+    {
+        let (mut moved, mut total) = (0, 0);
+        for key in keys {
+            total += 1;
+            if impact.is_moved(self.hash.hash_item(&key)) {
+                moved += 1;
+            }
+        }
+        if total == 0 { 0.0 } else { moved as f64 / total as f64 }
+    }
+
+    // Returns the ranges owned (as primary) by the node at `removed`,
+    // paired with the nodes which would absorb them if the node were removed.
+    fn removal_transfers(&self, removed: usize) -> Vec<(OwnedRange, Option<usize>)> {
+        let states = self.candidate_vnodes(0);
+        self.primary_ranges()
+            .into_iter()
+            .filter(|r| r.node == removed)
+            .map(|r| {
+                let successors = self.candidate_vnodes(r.vnode)
+                    .map(|i| self.ring[i].index)
+                    .filter(|&i| i != removed)
+                    .collect::<Vec<_>>();
+                let successor = successors.iter()
+                    .find(|&&i| !states.is_replica_only(i))
+                    .or_else(|| successors.first());
+                (r, successor.cloned())
+            })
+            .collect()
+    }
+
+    /// Returns the ranges which `node` would take (as primary) if it were added to this ring,
+    /// paired with the nodes which currently own them.
+    ///
+    /// This does not modify the ring, so migration tooling can schedule data movement
+    /// before the membership change goes live.
+    /// The ranges are ordered by their hash codes, and adjacent ranges taken from the same node are merged.
+    ///
+    /// If this ring already contains a node which has the same key as `node`,
+    /// `node` would be ignored and this returns an empty list.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(50)));
+    ///
+    /// let transfers = ring.addition_preview(&Node::new(3).quantity(50));
+    /// let total = transfers.iter().map(|t| (t.end - t.start) as f64 + 1.0).sum::<f64>();
+    /// assert!(0.15 < total / 2f64.powi(64) && total / 2f64.powi(64) < 0.35);
+    /// assert!(transfers.iter().all(|t| t.from.is_some()));
+    ///
+    /// assert!(ring.addition_preview(&Node::new(0).quantity(50)).is_empty());
+    /// ```
+    pub fn addition_preview(&self, node: &Node<K, V>) -> Vec<RangeTransfer<'_, K, V>> {
+        if self.node_index(&node.key).is_some() {
+            return Vec::new();
+        }
+
+        let added = self.nodes.len();
+        let mut vnodes = (0..node.quantity)
+            .map(|i| self.hash.hash_vnode(&node.key, i))
+            .collect::<Vec<_>>();
+        vnodes.sort();
+        let mut vnodes = vnodes.into_iter().peekable();
+        let mut points = Vec::with_capacity(self.ring.len() + node.quantity);
+        let states = self.candidate_vnodes(0);
+        for vn in &self.ring {
+            while let Some(hash) = vnodes.next_if(|&h| (h, &node.key) < (vn.hash, &self.nodes[vn.index].key)) {
+                points.push(RingPoint {
+                    hash,
+                    node: added,
+                    replica_only: false,
+                    excluded: false,
+                });
+            }
+            points.push(self.ring_point(&states, vn));
+        }
+        points.extend(vnodes.map(|hash| {
+            RingPoint {
+                hash,
+                node: added,
+                replica_only: false,
+                excluded: false,
+            }
+        }));
+
+        let old = self.primary_ranges();
+        let mut old = old.iter().peekable();
+        let mut transfers: Vec<RangeTransfer<K, V>> = Vec::new();
+        for r in primary_ranges(&points).into_iter().filter(|r| r.node == added) {
+            let mut start = r.start;
+            loop {
+                while old.peek().is_some_and(|o| o.end < start) {
+                    old.next();
+                }
+                let (end, from) = match old.peek() {
+                    None => (r.end, None),
+                    Some(o) => (o.end.min(r.end), Some(&self.nodes[o.node])),
+                };
+                match transfers.last_mut() {
+                    Some(ref mut t) if t.end.wrapping_add(1) == start &&
+                                       t.from.map(|n| &n.key) == from.map(|n| &n.key) => t.end = end,
+                    _ => transfers.push(RangeTransfer { start, end, from }),
+                }
+                if end == r.end {
+                    break;
+                }
+                start = end + 1;
+            }
+        }
+        transfers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {DefaultHash, Node, RingHash, StaticHashRing};
+    use super::*;
+
+    #[test]
+    fn impact_of_removal_works() {
+        let mut ring = StaticHashRing::new(DefaultHash, (0..5).map(|i| Node::new(i).quantity(20)));
+        ring.set_replica_only(&4, true);
+        let mut remaining = StaticHashRing::new(DefaultHash, (1..5).map(|i| Node::new(i).quantity(20)));
+        remaining.set_replica_only(&4, true);
+
+        let impact = ring.impact_of_removal(&0).collect::<Vec<_>>();
+        let owned = ring.owned_ranges(&0).map(|r| r.len()).sum::<u128>();
+        assert_eq!(impact.iter().map(|&(r, _)| r.len()).sum::<u128>(), owned);
+        assert!(impact.iter().all(|&(_, heir)| heir.key != 0 && heir.key != 4));
+        assert!(impact.windows(2).all(|w| w[0].0.end < w[1].0.start));
+        for (range, heir) in impact {
+            for &hash in &[range.start, range.end, range.start + (range.end - range.start) / 2] {
+                assert_eq!(ring.calc_node_by_hash(hash).map(|n| n.key), Some(0));
+                assert_eq!(remaining.calc_node_by_hash(hash), Some(heir));
+            }
+        }
+
+        let single = StaticHashRing::new(DefaultHash, (0..1).map(|i| Node::new(i).quantity(20)));
+        assert_eq!(single.impact_of_removal(&0).count(), 0);
+    }
+
+    #[test]
+    fn primary_ranges_works() {
+        let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+        let ranges = ring.primary_ranges();
+        assert_eq!(ranges.first().map(|r| r.start), Some(0));
+        assert_eq!(ranges.last().map(|r| r.end), Some(u64::MAX));
+        assert!(ranges.windows(2).all(|w| w[0].end + 1 == w[1].start));
+        assert_eq!(ranges.iter().map(|r| r.len()).sum::<u128>(), 1 << 64);
+
+        let empty = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(0)));
+        assert!(empty.primary_ranges().is_empty());
+    }
+
+    #[test]
+    fn owned_ranges_works() {
+        let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+        let mut total = 0;
+        for key in 0..3 {
+            let ranges = ring.owned_ranges(&key).collect::<Vec<_>>();
+            assert!(ranges.windows(2).all(|w| w[0].end.wrapping_add(1) < w[1].start));
+            for r in &ranges {
+                assert_eq!(ring.calc_node_by_hash(r.start).map(|n| n.key), Some(key));
+                assert_eq!(ring.calc_node_by_hash(r.end).map(|n| n.key), Some(key));
+                assert_ne!(ring.calc_node_by_hash(r.end.wrapping_add(1)).map(|n| n.key), Some(key));
+                total += r.len();
+            }
+        }
+        assert_eq!(total, 1 << 64);
+
+        let single = StaticHashRing::new(DefaultHash, (0..1).map(|i| Node::new(i).quantity(10)));
+        assert_eq!(single.owned_ranges(&0).collect::<Vec<_>>(), [HashRange::full()]);
+        assert_eq!(single.owned_ranges(&1).count(), 0);
+    }
+
+    #[test]
+    fn owned_ranges_skips_excluded_nodes() {
+        use std::time::Duration;
+
+        let mut ring = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(10)));
+        ring.set_replica_only(&3, true);
+        ring.exclude_for(&2, Duration::from_secs(60));
+        assert_eq!(ring.owned_ranges(&2).count(), 0);
+        assert_eq!(ring.ownership_by(|n| n.key).get(&2), Some(&0.0));
+        assert!(ring.sample_keys_for(&2, 10, &mut || 1).is_empty());
+        for r in ring.primary_ranges() {
+            assert_eq!(ring.calc_node_by_hash(r.start).map(|n| n.key), Some(r.node));
+            assert_eq!(ring.calc_node_by_hash(r.end).map(|n| n.key), Some(r.node));
+        }
+
+        // If every non excluded node is replica-only, the replica-only node is primary.
+        ring.exclude_for(&0, Duration::from_secs(60));
+        ring.exclude_for(&1, Duration::from_secs(60));
+        assert_eq!(ring.owned_ranges(&3).collect::<Vec<_>>(), [HashRange::full()]);
+
+        ring.exclude_for(&3, Duration::from_secs(60));
+        assert!(ring.primary_ranges().is_empty());
+        assert_eq!(ring.owned_ranges(&3).count(), 0);
+    }
+
+    #[test]
+    fn ownership_histogram_works() {
+        let ring = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(50)));
+        let histogram = ring.ownership_histogram(16);
+        assert_eq!(histogram.len(), 16);
+
+        let mut totals = [0.0; 4];
+        for bucket in &histogram {
+            assert!((bucket.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+            for (total, fraction) in totals.iter_mut().zip(bucket) {
+                *total += fraction / 16.0;
+            }
+        }
+        let ranges = ring.primary_ranges();
+        for (node, total) in totals.iter().enumerate() {
+            let owned = ranges.iter().filter(|r| r.node == node).map(|r| r.len() as f64).sum::<f64>();
+            assert!((owned / 2f64.powi(64) - total).abs() < 1e-9);
+        }
+
+        assert!(ring.ownership_histogram(0).is_empty());
+        let empty = StaticHashRing::new(DefaultHash, (0..2).map(|i| Node::new(i).quantity(0)));
+        assert_eq!(empty.ownership_histogram(2), vec![vec![0.0; 2]; 2]);
+    }
+
+    #[test]
+    fn removal_transfers_skip_retiring_nodes() {
+        use std::time::{Duration, UNIX_EPOCH};
+        use MockClock;
+
+        let clock = MockClock::new(UNIX_EPOCH);
+        let mut ring = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(20)));
+        ring.set_clock(clock.clone());
+        ring.schedule_removal(&3, UNIX_EPOCH + Duration::from_secs(60));
+
+        let mut removed = ring.clone();
+        removed.disable_node(&0);
+        let transfers = ring.removal_transfers(0);
+        assert!(!transfers.is_empty());
+        for (r, heir) in transfers {
+            let expected = removed.calc_candidates_by_hash(r.start).next().map(|n| n.key);
+            assert_eq!(heir.map(|i| ring.nodes[i].key), expected);
+            assert_ne!(expected, Some(3));
+        }
+
+        let impact = ring.removal_impact(&0).unwrap();
+        assert_eq!(impact.iter().map(|&(n, _)| n.key).collect::<Vec<_>>(), [1, 2]);
+        assert!((impact.iter().map(|&(_, f)| f).sum::<f64>() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn removal_impact_works() {
+        let mut ring = StaticHashRing::new(DefaultHash, (0..5).map(|i| Node::new(i).quantity(20)));
+        ring.set_replica_only(&4, true);
+        assert_eq!(ring.removal_impact(&10), None);
+        assert_eq!(ring.removal_impact(&4), Some(Vec::new()));
+
+        let impact = ring.removal_impact(&0).unwrap();
+        let mut new = StaticHashRing::new(DefaultHash, (1..5).map(|i| Node::new(i).quantity(20)));
+        new.set_replica_only(&4, true);
+        let owned = |ring: &StaticHashRing<i32, (), DefaultHash>, key: i32| {
+            let node = ring.node_index(&key).unwrap();
+            ring.primary_ranges()
+                .iter()
+                .filter(|r| r.node == node)
+                .map(|r| r.len() as f64)
+                .sum::<f64>()
+        };
+        let removed = owned(&ring, 0);
+        assert_eq!(impact.len(), 3);
+        for &(node, fraction) in &impact {
+            let gained = owned(&new, node.key) - owned(&ring, node.key);
+            assert!((gained / removed - fraction).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn addition_preview_works() {
+        let mut ring = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(20)));
+        ring.set_replica_only(&3, true);
+        let transfers = ring.addition_preview(&Node::new(4).quantity(20));
+
+        let mut new = StaticHashRing::new(DefaultHash, (0..5).map(|i| Node::new(i).quantity(20)));
+        new.set_replica_only(&3, true);
+        let added = new.node_index(&4).unwrap();
+        let taken = new.primary_ranges().iter().filter(|r| r.node == added).map(|r| r.len()).sum::<u128>();
+        let transferred = transfers.iter().map(|t| (t.end - t.start) as u128 + 1).sum::<u128>();
+        assert_eq!(transferred, taken);
+        assert!(transfers.windows(2).all(|w| w[0].end < w[1].start));
+        for t in &transfers {
+            let from = t.from.unwrap();
+            assert_ne!(from.key, 3);
+            for &h in &[t.start, t.end] {
+                let primary = ring.candidate_vnodes(ring.locate(h)).next().unwrap();
+                assert_eq!(ring.nodes[ring.ring[primary].index].key, from.key);
+                let primary = new.candidate_vnodes(new.locate(h)).next().unwrap();
+                assert_eq!(new.nodes[new.ring[primary].index].key, 4);
+            }
+        }
+
+        let empty = StaticHashRing::new(DefaultHash, (0..1).map(|i| Node::new(i).quantity(0)));
+        let transfers = empty.addition_preview(&Node::new(1).quantity(3));
+        assert_eq!(transfers, [RangeTransfer { start: 0, end: u64::MAX, from: None }]);
+    }
+
+    #[test]
+    fn simulate_works() {
+        let ring = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(20)));
+        let grown = StaticHashRing::new(DefaultHash, (0..5).map(|i| Node::new(i).quantity(20)));
+        let shrunk = StaticHashRing::new(DefaultHash, (1..4).map(|i| Node::new(i).quantity(20)));
+
+        let node = Node::new(4).quantity(20);
+        let impacts = [(ring.simulate_add(&node), ring.diff(&grown)),
+                       (ring.simulate_remove(&0).unwrap(), ring.diff(&shrunk))];
+        for (impact, diff) in &impacts {
+            assert!((impact.moved_fraction - diff.moved_fraction()).abs() < 1e-12);
+            assert_eq!(impact.moved_ranges, diff.moves.iter().map(|m| m.range).collect::<Vec<_>>());
+            assert!(impact.deltas.iter().map(|&(_, d)| d).sum::<f64>().abs() < 1e-12);
+            for &(node, delta) in &impact.deltas {
+                let gained = diff.moves.iter().filter(|m| m.after == Some(node)).map(|m| m.range.len()).sum::<u128>();
+                let lost = diff.moves.iter().filter(|m| m.before == Some(node)).map(|m| m.range.len()).sum::<u128>();
+                assert!((delta - (gained as f64 - lost as f64) / 2f64.powi(64)).abs() < 1e-12);
+            }
+            for i in 0..100 {
+                let moved = diff.moves.iter().any(|m| m.range.contains(DefaultHash.hash_item(&i)));
+                assert_eq!(ring.moved_key_fraction(impact, Some(i)), if moved { 1.0 } else { 0.0 });
+            }
+        }
+        assert!(ring.simulate_remove(&4).is_none());
+        assert_eq!(ring.moved_key_fraction(&impacts[0].0, 0..0), 0.0);
+
+        let single = StaticHashRing::new(DefaultHash, (0..1).map(|i| Node::new(i).quantity(20)));
+        let impact = single.simulate_remove(&0).unwrap();
+        assert_eq!(impact.moved_fraction, 1.0);
+        assert_eq!(impact.deltas, [(&single.nodes()[0], -1.0)]);
+    }
+
+    #[test]
+    fn ownership_by_works() {
+        let nodes = (0..6).map(|i| Node::new(i).value(i % 3).quantity(if i < 3 { 30 } else { 10 }));
+        let ring = StaticHashRing::new(DefaultHash, nodes);
+        let ownership = ring.ownership_by(|n| n.value);
+        assert_eq!(ownership.len(), 3);
+        assert!((ownership.values().sum::<f64>() - 1.0).abs() < 1e-9);
+
+        let histogram = ring.ownership_histogram(1);
+        for (group, &fraction) in &ownership {
+            let expected = ring.nodes()
+                .iter()
+                .zip(&histogram[0])
+                .filter(|&(n, _)| n.value == *group)
+                .map(|(_, f)| f)
+                .sum::<f64>();
+            assert!((fraction - expected).abs() < 1e-9);
+        }
+
+        let empty = StaticHashRing::new(DefaultHash, (0..2).map(|i| Node::new(i).quantity(0)));
+        assert_eq!(empty.ownership_by(|_| ()).get(&()), Some(&0.0));
+    }
+
+    #[test]
+    fn sample_keys_for_works() {
+        let mut ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+        ring.set_replica_only(&2, true);
+
+        let mut seed = 0u64;
+        let mut rng = || {
+            seed = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            seed
+        };
+        for key in 0..2 {
+            let hashes = ring.sample_keys_for(&key, 100, &mut rng);
+            assert_eq!(hashes.len(), 100);
+            for h in hashes {
+                let primary = ring.candidate_vnodes(ring.locate(h)).next().unwrap();
+                assert_eq!(ring.nodes[ring.ring[primary].index].key, key);
+            }
+        }
+        assert!(ring.sample_keys_for(&2, 100, &mut rng).is_empty());
+
+        for _ in 0..100 {
+            let h = ring.random_key_hash_for(&1, &mut rng).unwrap();
+            assert_eq!(ring.calc_node_by_hash(h).map(|n| n.key), Some(1));
+        }
+        assert_eq!(ring.random_key_hash_for(&2, &mut rng), None);
+        assert_eq!(ring.random_key_hash_for(&3, &mut rng), None);
+    }
+
+    #[test]
+    fn find_key_for_works() {
+        let mut ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+        ring.set_replica_only(&2, true);
+
+        let key = ring.find_key_for(&1, "foo", 1000).unwrap();
+        assert_eq!(ring.calc_node(&key).map(|n| n.key), Some(1));
+        let index = key["foo-".len()..].parse::<usize>().unwrap();
+        for i in 0..index {
+            assert_ne!(ring.calc_node(&format!("foo-{}", i)).map(|n| n.key), Some(1));
+        }
+
+        assert_eq!(ring.find_key_for(&2, "foo", 1000), None);
+        assert_eq!(ring.find_key_for(&3, "foo", 1000), None);
+        assert_eq!(ring.find_key_for(&0, "foo", 0), None);
+    }
+}
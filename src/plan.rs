@@ -0,0 +1,165 @@
+use std::hash::Hash;
+
+use {Node, RingHash, StaticHashRing};
+
+/// A change of the membership of a ring.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MembershipChange<K, V> {
+    /// Adds the node (or replaces the node which has the same key).
+    Add(Node<K, V>),
+
+    /// Removes the node which has the key (if it exists).
+    Remove(K),
+}
+
+/// The key movement caused by a step of a membership change plan.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlanStep {
+    /// The count of the sample keys whose primary nodes were changed by this step.
+    pub moved_keys: usize,
+
+    /// The fraction of the sample keys whose primary nodes were changed by this step.
+    pub moved_fraction: f64,
+
+    /// The fraction of the sample keys whose primary nodes differ from the initial ones after this step.
+    pub net_moved_fraction: f64,
+}
+
+/// The result of `StaticHashRing::simulate_plan`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanReport {
+    /// The count of the sample keys.
+    pub sample_keys: usize,
+
+    /// The key movement of each step (in the order of the changes).
+    pub steps: Vec<PlanStep>,
+}
+impl PlanReport {
+    /// Returns the sum of the fractions of the keys moved by each step.
+    ///
+    /// A key which is moved by multiple steps is counted multiple times,
+    /// so this approximates the total amount of data transfer required by the plan.
+    pub fn total_moved_fraction(&self) -> f64 {
+        self.steps.iter().map(|s| s.moved_fraction).sum()
+    }
+
+    /// Returns the fraction of the sample keys whose primary nodes differ from the initial ones after the whole plan.
+    pub fn net_moved_fraction(&self) -> f64 {
+        self.steps.last().map_or(0.0, |s| s.net_moved_fraction)
+    }
+}
+
+impl<K, V, H> StaticHashRing<K, V, H>
+    where K: Hash + Eq + Ord + Clone,
+          V: Clone,
+          H: RingHash + Clone
+{
+    /// Applies `changes` to (a copy of) this ring virtually one by one,
+    /// and reports the movement of the primary nodes of `sample_keys` at each step.
+    ///
+    /// This helps comparing plans before executing them
+    /// (e.g., adding three nodes at once versus adding them one at a time).
+    ///
+    /// The ring of each step is rebuilt from its nodes, with the replica-only states of this ring.
+    /// Virtual nodes removed by `take` (and the like) are not reflected.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash, MembershipChange};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(50)));
+    ///
+    /// // Adds a node, then removes it.
+    /// let report = ring.simulate_plan(&[MembershipChange::Add(Node::new(3).quantity(50)),
+    ///                                   MembershipChange::Remove(3)],
+    ///                                 0..1000);
+    /// assert_eq!(report.steps.len(), 2);
+    /// assert_eq!(report.steps[0].moved_keys, report.steps[1].moved_keys);
+    /// assert!(0.15 < report.steps[0].moved_fraction && report.steps[0].moved_fraction < 0.35);
+    /// assert_eq!(report.net_moved_fraction(), 0.0);
+    /// ```
+    pub fn simulate_plan<T, I>(&self, changes: &[MembershipChange<K, V>], sample_keys: I) -> PlanReport
+        where T: Hash,
+              I: IntoIterator<Item = T>
+    {
+        let hashes = sample_keys.into_iter().map(|k| self.hash.hash_item(&k)).collect::<Vec<_>>();
+        let owners_of = |ring: &Self| {
+            hashes.iter().map(|&h| ring.calc_node_by_hash(h).map(|n| n.key.clone())).collect::<Vec<_>>()
+        };
+        let replica_only = self.nodes
+            .iter()
+            .zip(&self.states)
+            .filter(|&(_, s)| s.replica_only)
+            .map(|(n, _)| n.key.clone())
+            .collect::<Vec<_>>();
+
+        let initial = owners_of(self);
+        let mut owners = initial.clone();
+        let mut nodes = self.nodes.clone();
+        let mut steps = Vec::with_capacity(changes.len());
+        for change in changes {
+            match *change {
+                MembershipChange::Add(ref node) => {
+                    nodes.retain(|n| n.key != node.key);
+                    nodes.push(node.clone());
+                }
+                MembershipChange::Remove(ref key) => nodes.retain(|n| n.key != *key),
+            }
+            let mut ring = StaticHashRing::new(self.hash.clone(), nodes.iter().cloned());
+            for key in &replica_only {
+                ring.set_replica_only(key, true);
+            }
+
+            let next = owners_of(&ring);
+            let moved_keys = owners.iter().zip(&next).filter(|&(a, b)| a != b).count();
+            let net_moved = initial.iter().zip(&next).filter(|&(a, b)| a != b).count();
+            steps.push(PlanStep {
+                moved_keys,
+                moved_fraction: fraction(moved_keys, hashes.len()),
+                net_moved_fraction: fraction(net_moved, hashes.len()),
+            });
+            owners = next;
+        }
+        PlanReport {
+            sample_keys: hashes.len(),
+            steps,
+        }
+    }
+}
+
+fn fraction(n: usize, total: usize) -> f64 {
+    if total == 0 { 0.0 } else { n as f64 / total as f64 }
+}
+
+#[cfg(test)]
+mod tests {
+    use {DefaultHash, Node, StaticHashRing};
+    use super::*;
+
+    #[test]
+    fn simulate_plan_works() {
+        let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(50)));
+        let adds = (3..6).map(|i| MembershipChange::Add(Node::new(i).quantity(50))).collect::<Vec<_>>();
+
+        // A key may be moved by multiple steps, so the total can exceed the net movement.
+        let gradual = ring.simulate_plan(&adds, 0..1000);
+        assert_eq!(gradual.sample_keys, 1000);
+        assert_eq!(gradual.steps.len(), 3);
+        assert!(gradual.total_moved_fraction() >= gradual.net_moved_fraction());
+        assert!(gradual.steps.windows(2).all(|w| w[0].net_moved_fraction <= w[1].net_moved_fraction));
+
+        let all_at_once = StaticHashRing::new(DefaultHash, (0..6).map(|i| Node::new(i).quantity(50)));
+        let expected = (0..1000)
+            .filter(|k| ring.calc_node(k).map(|n| n.key) != all_at_once.calc_node(k).map(|n| n.key))
+            .count();
+        assert_eq!(gradual.net_moved_fraction(), expected as f64 / 1000.0);
+
+        // Replacing and removing unknown nodes.
+        let report = ring.simulate_plan(&[MembershipChange::Add(Node::new(0).quantity(50)), MembershipChange::Remove(9)],
+                                        0..100);
+        assert!(report.steps.iter().all(|s| s.moved_keys == 0));
+        assert_eq!(ring.simulate_plan(&[], 0..100).net_moved_fraction(), 0.0);
+        assert_eq!(ring.simulate_plan(&adds, 0..0).total_moved_fraction(), 0.0);
+    }
+}
@@ -0,0 +1,167 @@
+use std::hash::Hash;
+use std::slice;
+
+use {Node, RingHash, StaticHashRing};
+
+/// A ring which stores the first candidate nodes of every virtual node in advance.
+///
+/// A lookup is a binary search plus an array read (i.e., no walks over the ring),
+/// at the cost of the memory for `ring.len() * candidates` node indices.
+/// This is worthwhile for latency sensitive lookups of the top few replicas on moderately sized rings.
+///
+/// The table is calculated when this is built, and it reflects the replica-only states of the nodes.
+/// Exclusions (see `StaticHashRing::exclude_for` and the like) are not reflected,
+/// so rings which rely on them should use `StaticHashRing::calc_candidates` instead.
+///
+/// # Examples
+///
+/// ```
+/// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+///
+/// let ring = StaticHashRing::new(DefaultHash, (0..5).map(|i| Node::new(i).quantity(10)));
+/// let expected = ring.calc_candidates(&"foo").take(3).map(|n| n.key).collect::<Vec<_>>();
+///
+/// let ring = ring.with_precomputed_candidates(3);
+/// assert_eq!(ring.calc_candidates(&"foo").map(|n| n.key).collect::<Vec<_>>(), expected);
+/// assert_eq!(ring.calc_candidate_indices(&"foo").len(), 3);
+/// ```
+#[derive(Debug, Clone)]
+pub struct PrecomputedRing<K, V, H> {
+    ring: StaticHashRing<K, V, H>,
+    width: usize,
+
+    // The indices of the candidate nodes of the virtual node at `i` are `table[i * width..(i + 1) * width]`.
+    table: Vec<usize>,
+}
+impl<K, V, H> PrecomputedRing<K, V, H>
+    where H: RingHash
+{
+    /// Makes a new `PrecomputedRing` instance which stores the first `candidates` candidates of each virtual node.
+    ///
+    /// If `ring` has fewer live nodes than `candidates`, all of the live nodes are stored.
+    pub fn new(ring: StaticHashRing<K, V, H>, candidates: usize) -> Self {
+        let width = candidates.min(ring.live_nodes);
+        let mut table = Vec::with_capacity(ring.ring.len() * width);
+        for start in 0..ring.ring.len() {
+            table.extend(ring.candidate_vnodes(start)
+                .include_excluded()
+                .take(width)
+                .map(|i| ring.ring[i].index));
+        }
+        PrecomputedRing { ring, width, table }
+    }
+
+    /// Returns the first candidate nodes for `item`.
+    ///
+    /// The higher priority node is located in front of the returned candidate sequence.
+    pub fn calc_candidates<T: Hash>(&self, item: &T) -> PrecomputedCandidates<'_, K, V> {
+        self.calc_candidates_by_hash(self.ring.hash.hash_item(item))
+    }
+
+    /// Returns the first candidate nodes for the item whose hash code is `item_hash`.
+    pub fn calc_candidates_by_hash(&self, item_hash: u64) -> PrecomputedCandidates<'_, K, V> {
+        PrecomputedCandidates {
+            indices: self.candidate_indices_by_hash(item_hash).iter(),
+            nodes: &self.ring.nodes,
+        }
+    }
+
+    /// Returns the indices (i.e., the positions in `StaticHashRing::nodes`) of the first candidate nodes for `item`.
+    pub fn calc_candidate_indices<T: Hash>(&self, item: &T) -> &[usize] {
+        self.candidate_indices_by_hash(self.ring.hash.hash_item(item))
+    }
+
+    fn candidate_indices_by_hash(&self, item_hash: u64) -> &[usize] {
+        if self.ring.ring.is_empty() {
+            return &[];
+        }
+        let start = self.ring.locate(item_hash) % self.ring.ring.len();
+        &self.table[start * self.width..(start + 1) * self.width]
+    }
+}
+impl<K, V, H> PrecomputedRing<K, V, H> {
+    /// Returns the count of the candidates stored for each virtual node.
+    pub fn candidates(&self) -> usize {
+        self.width
+    }
+
+    /// Returns a reference to the inner ring.
+    pub fn ring(&self) -> &StaticHashRing<K, V, H> {
+        &self.ring
+    }
+
+    /// Takes the inner ring out of this instance.
+    pub fn into_ring(self) -> StaticHashRing<K, V, H> {
+        self.ring
+    }
+}
+
+impl<K, V, H> StaticHashRing<K, V, H>
+    where H: RingHash
+{
+    /// Converts this ring into a `PrecomputedRing` which stores the first `candidates` candidates of each virtual node.
+    ///
+    /// See `PrecomputedRing` for the details.
+    pub fn with_precomputed_candidates(self, candidates: usize) -> PrecomputedRing<K, V, H> {
+        PrecomputedRing::new(self, candidates)
+    }
+}
+
+/// An iterator which represents a sequence of the precomputed candidate nodes for an item.
+///
+/// This is created by calling `PrecomputedRing::calc_candidates` method.
+#[derive(Debug, Clone)]
+pub struct PrecomputedCandidates<'a, K: 'a, V: 'a> {
+    indices: slice::Iter<'a, usize>,
+    nodes: &'a [Node<K, V>],
+}
+impl<'a, K: 'a, V: 'a> Iterator for PrecomputedCandidates<'a, K, V> {
+    type Item = &'a Node<K, V>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.indices.next().map(|&i| &self.nodes[i])
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.indices.size_hint()
+    }
+}
+impl<'a, K: 'a, V: 'a> DoubleEndedIterator for PrecomputedCandidates<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.indices.next_back().map(|&i| &self.nodes[i])
+    }
+}
+impl<'a, K: 'a, V: 'a> ExactSizeIterator for PrecomputedCandidates<'a, K, V> {}
+
+#[cfg(test)]
+mod tests {
+    use {DefaultHash, Node, StaticHashRing};
+
+    #[test]
+    fn precomputed_ring_works() {
+        let mut ring = StaticHashRing::new(DefaultHash, (0..6).map(|i| Node::new(i).quantity(20)));
+        ring.set_replica_only(&2, true);
+        ring.take(&"foo");
+        let expected = ring.clone();
+
+        for &n in &[0, 1, 3, 6, 10] {
+            let precomputed = ring.clone().with_precomputed_candidates(n);
+            assert_eq!(precomputed.candidates(), n.min(6));
+            for item in 0..200 {
+                let actual = precomputed.calc_candidates(&item).collect::<Vec<_>>();
+                let expected = expected.calc_candidates(&item).take(n).collect::<Vec<_>>();
+                assert_eq!(actual, expected);
+                let last = precomputed.calc_candidates(&item).next_back();
+                assert_eq!(last, expected.last().cloned());
+            }
+        }
+        let precomputed = ring.with_precomputed_candidates(3);
+        for hash in &[0, u64::MAX] {
+            let expected = expected.calc_candidates_by_hash(*hash).take(3).collect::<Vec<_>>();
+            assert_eq!(precomputed.calc_candidates_by_hash(*hash).collect::<Vec<_>>(), expected);
+        }
+        assert!(precomputed.ring().topology_eq(&expected));
+
+        let empty = StaticHashRing::new(DefaultHash, (0..0).map(Node::new)).with_precomputed_candidates(3);
+        assert_eq!(empty.calc_candidates(&"foo").len(), 0);
+        assert_eq!(empty.candidates(), 0);
+    }
+}
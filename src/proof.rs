@@ -0,0 +1,394 @@
+use std::error::Error;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use siphasher::sip::SipHasher13;
+
+use range::HashRange;
+use {RingHash, StaticHashRing};
+
+/// A compact proof of the ownership of an item.
+///
+/// This is created by calling `StaticHashRing::ownership_proof` method,
+/// and can be verified by another process holding the same ring
+/// (see `StaticHashRing::verify_ownership_proof`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OwnershipProof<K> {
+    /// The hash code of the item.
+    pub item_hash: u64,
+
+    /// The hash code of the virtual node which owns the item.
+    pub vnode_hash: u64,
+
+    /// The key of the node which owns the item.
+    pub node_key: K,
+
+    /// The fingerprint of the ring which issued this proof.
+    pub fingerprint: u64,
+
+    /// The identifier of the ring which issued this proof (see `StaticHashRing::ring_id`).
+    pub ring_id: u64,
+
+    /// The generation of the ring which issued this proof.
+    ///
+    /// This is informational and is not verified.
+    pub generation: u64,
+}
+
+/// The error which is returned when an ownership proof is not consistent with the local ring.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ProofMismatch<K> {
+    /// The proof was issued by another logical ring.
+    RingId {
+        /// The identifier of the local ring.
+        ring_id: u64,
+    },
+
+    /// The local ring has no nodes which can own the item.
+    Unroutable,
+
+    /// The item is owned by another virtual node in the local ring.
+    Owner {
+        /// The hash code of the owning virtual node in the local ring.
+        vnode_hash: u64,
+
+        /// The key of the owning node in the local ring.
+        node_key: K,
+    },
+
+    /// The owner is the same, but the fingerprint differs (i.e., the rings differ elsewhere).
+    Fingerprint {
+        /// The fingerprint of the local ring.
+        fingerprint: u64,
+    },
+}
+impl<K: fmt::Debug> fmt::Display for ProofMismatch<K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ProofMismatch::RingId { ring_id } => {
+                write!(f, "Ownership proof mismatch: ring_id is {:016x}", ring_id)
+            }
+            ProofMismatch::Unroutable => write!(f, "Ownership proof mismatch: unroutable"),
+            ProofMismatch::Owner { vnode_hash, ref node_key } => {
+                write!(f,
+                       "Ownership proof mismatch: owner is {:?} (vnode_hash={:016x})",
+                       node_key,
+                       vnode_hash)
+            }
+            ProofMismatch::Fingerprint { fingerprint } => {
+                write!(f, "Ownership proof mismatch: fingerprint is {:016x}", fingerprint)
+            }
+        }
+    }
+}
+impl<K: fmt::Debug> Error for ProofMismatch<K> {}
+
+/// A token which formalizes the handover of a hash range from a node to another.
+///
+/// This is created by calling `StaticHashRing::handover` method on the ring after the handover,
+/// and can be validated by the receiving side against its own ring
+/// (see `StaticHashRing::validate_handover`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HandoverToken<K> {
+    /// The range of hash codes to be handed over.
+    pub range: HashRange,
+
+    /// The key of the node which hands the range over.
+    pub from: K,
+
+    /// The key of the node which receives the range.
+    pub to: K,
+
+    /// The generation of the ring which issued this token.
+    pub generation: u64,
+
+    /// The fingerprint of the ring which issued this token.
+    pub fingerprint: u64,
+
+    /// The identifier of the ring which issued this token (see `StaticHashRing::ring_id`).
+    pub ring_id: u64,
+
+    /// The checksum of the other fields.
+    ///
+    /// This detects corrupted or altered tokens, but it is not a cryptographic signature.
+    pub checksum: u64,
+}
+impl<K: Hash> HandoverToken<K> {
+    fn calc_checksum(&self) -> u64 {
+        let mut hasher = SipHasher13::new();
+        (self.range.start, self.range.end, &self.from, &self.to, self.generation, self.fingerprint)
+            .hash(&mut hasher);
+        self.ring_id.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// The error which is returned when a handover token is not consistent with the local ring.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum HandoverMismatch<K> {
+    /// The checksum of the token is broken.
+    Checksum,
+
+    /// The token was issued by another logical ring.
+    RingId {
+        /// The identifier of the local ring.
+        ring_id: u64,
+    },
+
+    /// The fingerprint of the local ring differs from the one of the token.
+    Fingerprint {
+        /// The fingerprint of the local ring.
+        fingerprint: u64,
+    },
+
+    /// The generation of the local ring differs from the one of the token.
+    Generation {
+        /// The generation of the local ring.
+        generation: u64,
+    },
+
+    /// The receiving node does not own (a part of) the range in the local ring.
+    Owner {
+        /// The key of the owning node in the local ring (`None` if the ring is empty).
+        node_key: Option<K>,
+    },
+}
+impl<K: fmt::Debug> fmt::Display for HandoverMismatch<K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            HandoverMismatch::Checksum => write!(f, "Handover token mismatch: broken checksum"),
+            HandoverMismatch::RingId { ring_id } => {
+                write!(f, "Handover token mismatch: ring_id is {:016x}", ring_id)
+            }
+            HandoverMismatch::Fingerprint { fingerprint } => {
+                write!(f, "Handover token mismatch: fingerprint is {:016x}", fingerprint)
+            }
+            HandoverMismatch::Generation { generation } => {
+                write!(f, "Handover token mismatch: generation is {}", generation)
+            }
+            HandoverMismatch::Owner { ref node_key } => {
+                write!(f, "Handover token mismatch: owner is {:?}", node_key)
+            }
+        }
+    }
+}
+impl<K: fmt::Debug> Error for HandoverMismatch<K> {}
+
+impl<K, V, H> StaticHashRing<K, V, H>
+    where K: Hash + Clone,
+          H: RingHash
+{
+    /// Returns the proof of the ownership of `item` in this ring.
+    ///
+    /// If this ring is empty, this returns `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash, ProofMismatch};
+    ///
+    /// let ring0 = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+    /// let proof = ring0.ownership_proof(&"foo").unwrap();
+    /// assert_eq!(Some(&proof.node_key), ring0.calc_candidates(&"foo").next().map(|n| &n.key));
+    ///
+    /// let ring1 = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+    /// assert_eq!(ring1.verify_ownership_proof(&proof), Ok(()));
+    ///
+    /// let ring2 = StaticHashRing::new(DefaultHash, (0..0).map(|i| Node::new(i).quantity(10)));
+    /// assert_eq!(ring2.verify_ownership_proof(&proof), Err(ProofMismatch::Unroutable));
+    /// ```
+    pub fn ownership_proof<T: Hash + ?Sized>(&self, item: &T) -> Option<OwnershipProof<K>> {
+        let item_hash = self.hash.hash_item(&item);
+        self.owner_vnode(item_hash).map(|i| {
+            let vnode = &self.ring[i];
+            OwnershipProof {
+                item_hash,
+                vnode_hash: vnode.hash,
+                node_key: self.nodes[vnode.index].key.clone(),
+                fingerprint: self.fingerprint(),
+                ring_id: self.ring_id(),
+                generation: self.generation,
+            }
+        })
+    }
+
+    /// Verifies that `proof` is consistent with this ring.
+    ///
+    /// The identifier of the ring is checked first, then the owner of the item, and then the fingerprint of the ring.
+    pub fn verify_ownership_proof(&self, proof: &OwnershipProof<K>) -> Result<(), ProofMismatch<K>>
+        where K: Eq
+    {
+        let ring_id = self.ring_id();
+        if ring_id != proof.ring_id {
+            return Err(ProofMismatch::RingId { ring_id });
+        }
+        let vnode = match self.owner_vnode(proof.item_hash) {
+            None => return Err(ProofMismatch::Unroutable),
+            Some(i) => &self.ring[i],
+        };
+        if vnode.hash != proof.vnode_hash || self.nodes[vnode.index].key != proof.node_key {
+            return Err(ProofMismatch::Owner {
+                vnode_hash: vnode.hash,
+                node_key: self.nodes[vnode.index].key.clone(),
+            });
+        }
+        let fingerprint = self.fingerprint();
+        if fingerprint != proof.fingerprint {
+            return Err(ProofMismatch::Fingerprint { fingerprint });
+        }
+        Ok(())
+    }
+
+    /// Issues the token of the handover of `range` from `from` to `to`.
+    ///
+    /// This ring is expected to be the ring after the handover,
+    /// so if `to` is not the primary node of the whole range in this ring, this returns `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let old = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+    /// let new = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(10)));
+    ///
+    /// let diff = old.diff(&new);
+    /// let m = &diff.moves[0];
+    /// let token = new.handover(&m.range, m.before.unwrap().key, m.after.unwrap().key).unwrap();
+    ///
+    /// // The receiving side holds the same ring.
+    /// let receiver = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(10)));
+    /// assert_eq!(receiver.validate_handover(&token), Ok(()));
+    /// assert!(old.validate_handover(&token).is_err());
+    /// ```
+    pub fn handover(&self, range: &HashRange, from: K, to: K) -> Option<HandoverToken<K>>
+        where K: Eq
+    {
+        if self.range_owner_mismatch(range, &to).is_some() {
+            return None;
+        }
+        let mut token = HandoverToken {
+            range: *range,
+            from,
+            to,
+            generation: self.generation,
+            fingerprint: self.fingerprint(),
+            ring_id: self.ring_id(),
+            checksum: 0,
+        };
+        token.checksum = token.calc_checksum();
+        Some(token)
+    }
+
+    /// Validates that `token` is consistent with this ring.
+    ///
+    /// The checksum, the ring identifier, the fingerprint, the generation, and the owner of the range
+    /// are checked in order.
+    pub fn validate_handover(&self, token: &HandoverToken<K>) -> Result<(), HandoverMismatch<K>>
+        where K: Eq
+    {
+        if token.calc_checksum() != token.checksum {
+            return Err(HandoverMismatch::Checksum);
+        }
+        let ring_id = self.ring_id();
+        if ring_id != token.ring_id {
+            return Err(HandoverMismatch::RingId { ring_id });
+        }
+        let fingerprint = self.fingerprint();
+        if fingerprint != token.fingerprint {
+            return Err(HandoverMismatch::Fingerprint { fingerprint });
+        }
+        if self.generation != token.generation {
+            return Err(HandoverMismatch::Generation { generation: self.generation });
+        }
+        if let Some(node_key) = self.range_owner_mismatch(&token.range, &token.to) {
+            return Err(HandoverMismatch::Owner { node_key });
+        }
+        Ok(())
+    }
+
+    // Returns the owner of a part of `range` if it is not `to` (`Some(None)` if this ring is empty).
+    fn range_owner_mismatch(&self, range: &HashRange, to: &K) -> Option<Option<K>>
+        where K: Eq
+    {
+        let primary_ranges = self.primary_ranges();
+        if primary_ranges.is_empty() {
+            return Some(None);
+        }
+        primary_ranges
+            .into_iter()
+            .map(|r| (HashRange::new(r.start, r.end), &self.nodes[r.node].key))
+            .find(|&(ref r, key)| r.intersects(range) && key != to)
+            .map(|(_, key)| Some(key.clone()))
+    }
+
+    fn owner_vnode(&self, item_hash: u64) -> Option<usize> {
+        self.candidate_vnodes(self.locate(item_hash)).next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {DefaultHash, Node, StaticHashRing};
+    use super::*;
+
+    #[test]
+    fn ownership_proof_works() {
+        let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+        let mut other = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+        for item in 0..100 {
+            let proof = ring.ownership_proof(&item).unwrap();
+            assert_eq!(proof.generation, 0);
+            assert_eq!(other.verify_ownership_proof(&proof), Ok(()));
+        }
+
+        other.set_replica_only(&0, true);
+        let mut owners = 0;
+        let mut fingerprints = 0;
+        for item in 0..100 {
+            let proof = ring.ownership_proof(&item).unwrap();
+            match other.verify_ownership_proof(&proof) {
+                Err(ProofMismatch::Owner { node_key, .. }) => {
+                    assert_eq!(proof.node_key, 0);
+                    assert_ne!(node_key, 0);
+                    owners += 1;
+                }
+                Err(ProofMismatch::Fingerprint { fingerprint }) => {
+                    assert_eq!(fingerprint, other.fingerprint());
+                    fingerprints += 1;
+                }
+                other => panic!("{:?}", other),
+            }
+        }
+        assert!(owners > 0 && fingerprints > 0);
+    }
+
+    #[test]
+    fn handover_works() {
+        let old = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+        let mut new = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(10)));
+        let moves = old.diff(&new).moves;
+        assert!(!moves.is_empty());
+        for m in &moves {
+            let (from, to) = (m.before.unwrap().key, m.after.unwrap().key);
+            let token = new.handover(&m.range, from, to).unwrap();
+            assert_eq!(new.validate_handover(&token), Ok(()));
+            assert!(new.handover(&m.range, to, from).is_none());
+
+            let mut broken = token.clone();
+            broken.to = from;
+            assert_eq!(new.validate_handover(&broken), Err(HandoverMismatch::Checksum));
+            assert_eq!(old.validate_handover(&token),
+                       Err(HandoverMismatch::Fingerprint { fingerprint: old.fingerprint() }));
+        }
+
+        let token = new.handover(&moves[0].range, 0, moves[0].after.unwrap().key).unwrap();
+        new.take(&"foo");
+        match new.validate_handover(&token) {
+            Err(HandoverMismatch::Fingerprint { .. }) => {}
+            other => panic!("{:?}", other),
+        }
+
+        let empty = StaticHashRing::new(DefaultHash, (0..0).map(Node::new));
+        assert!(empty.handover(&HashRange::full(), 0, 1).is_none());
+    }
+}
@@ -0,0 +1,228 @@
+//! Arithmetic on ranges of a hash ring.
+//!
+//! The hash space of a ring is the circle of `u64` values, in which `u64::MAX` is followed by `0`.
+//! A `HashRange` is a contiguous arc of the circle which may wrap around.
+//!
+//! # Examples
+//!
+//! ```
+//! use consistent_hash::range::{self, HashRange};
+//!
+//! let a = HashRange::new(u64::MAX - 9, 9);
+//! assert!(a.wraps());
+//! assert_eq!(a.len(), 20);
+//! assert!(a.contains(0));
+//!
+//! let b = HashRange::new(5, 100);
+//! assert_eq!(range::union(&[a, b]), [HashRange::new(u64::MAX - 9, 100)]);
+//! assert_eq!(a.intersection(&b), [HashRange::new(5, 9)]);
+//! ```
+
+/// A contiguous range of hash codes on the ring.
+///
+/// Both of `start` and `end` are inclusive, and the range wraps around if `start > end`.
+/// So, a range is never empty, and it covers the whole circle if `end` is `start - 1` (modulo `2^64`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HashRange {
+    /// The first hash code of the range (inclusive).
+    pub start: u64,
+
+    /// The last hash code of the range (inclusive).
+    pub end: u64,
+}
+impl HashRange {
+    /// Makes a new `HashRange` instance.
+    pub fn new(start: u64, end: u64) -> Self {
+        HashRange { start, end }
+    }
+
+    /// Makes a new `HashRange` instance which covers the whole circle.
+    pub fn full() -> Self {
+        HashRange::new(0, u64::MAX)
+    }
+
+    /// Returns the count of the hash codes in this range (`1..=2^64`).
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> u128 {
+        self.end.wrapping_sub(self.start) as u128 + 1
+    }
+
+    /// Returns `true` if this range covers the whole circle, otherwise `false`.
+    pub fn is_full(&self) -> bool {
+        self.end.wrapping_add(1) == self.start
+    }
+
+    /// Returns `true` if this range wraps around (i.e., `start > end`), otherwise `false`.
+    pub fn wraps(&self) -> bool {
+        self.start > self.end
+    }
+
+    /// Returns `true` if this range contains `hash`, otherwise `false`.
+    pub fn contains(&self, hash: u64) -> bool {
+        hash.wrapping_sub(self.start) <= self.end.wrapping_sub(self.start)
+    }
+
+    /// Returns `true` if this range contains the whole of `other`, otherwise `false`.
+    pub fn contains_range(&self, other: &HashRange) -> bool {
+        if self.is_full() {
+            return true;
+        }
+        let offset = other.start.wrapping_sub(self.start) as u128;
+        offset + other.len() <= self.len()
+    }
+
+    /// Returns `true` if this range and `other` share one or more hash codes, otherwise `false`.
+    pub fn intersects(&self, other: &HashRange) -> bool {
+        self.contains(other.start) || other.contains(self.start)
+    }
+
+    /// Returns the hash codes shared by this range and `other`.
+    ///
+    /// The result is normalized as `union`, and consists of at most two ranges
+    /// (e.g., when two wrapping ranges overlap at both ends).
+    pub fn intersection(&self, other: &HashRange) -> Vec<HashRange> {
+        let mut pieces = Vec::new();
+        for &(s0, e0) in &self.linear_pieces() {
+            for &(s1, e1) in &other.linear_pieces() {
+                let (s, e) = (s0.max(s1), e0.min(e1));
+                if s <= e {
+                    pieces.push(HashRange::new(s, e));
+                }
+            }
+        }
+        union(&pieces)
+    }
+
+    /// Splits this range at `hash` into `start..=hash - 1` and `hash..=end`.
+    ///
+    /// If this range does not contain `hash` or `hash` is `start`, this returns `None`.
+    pub fn split_at(&self, hash: u64) -> Option<(HashRange, HashRange)> {
+        if hash == self.start || !self.contains(hash) {
+            return None;
+        }
+        Some((HashRange::new(self.start, hash.wrapping_sub(1)), HashRange::new(hash, self.end)))
+    }
+
+    /// Splits this range into `n` contiguous ranges whose lengths differ by at most one.
+    ///
+    /// If `n` exceeds the length of this range, the result consists of single-code ranges.
+    /// If `n` is `0`, this returns an empty vector.
+    pub fn split(&self, n: usize) -> Vec<HashRange> {
+        let len = self.len();
+        let n = (n as u128).min(len);
+        let offset = |i: u128| self.start.wrapping_add((i * len / n) as u64);
+        (0..n)
+            .map(|i| HashRange::new(offset(i), offset(i + 1).wrapping_sub(1)))
+            .collect()
+    }
+
+    // Returns the non-wrapping pieces of this range.
+    fn linear_pieces(&self) -> Vec<(u64, u64)> {
+        if self.is_full() {
+            vec![(0, u64::MAX)]
+        } else if self.wraps() {
+            vec![(0, self.end), (self.start, u64::MAX)]
+        } else {
+            vec![(self.start, self.end)]
+        }
+    }
+}
+
+/// Returns the union of `ranges`.
+///
+/// Overlapping and adjacent ranges are merged, and the result is ordered by the start positions.
+/// At most one range in the result wraps around, and it comes last.
+pub fn union(ranges: &[HashRange]) -> Vec<HashRange> {
+    let mut pieces = ranges.iter().flat_map(|r| r.linear_pieces()).collect::<Vec<_>>();
+    pieces.sort();
+
+    let mut merged: Vec<(u64, u64)> = Vec::with_capacity(pieces.len());
+    for (s, e) in pieces {
+        if let Some(last) = merged.last_mut() {
+            if last.1 == u64::MAX || s <= last.1 + 1 {
+                last.1 = last.1.max(e);
+                continue;
+            }
+        }
+        merged.push((s, e));
+    }
+
+    let mut result = merged.iter().map(|&(s, e)| HashRange::new(s, e)).collect::<Vec<_>>();
+    if result.len() > 1 && result[0].start == 0 && result[result.len() - 1].end == u64::MAX {
+        let first = result.remove(0);
+        let last = result.last_mut().unwrap();
+        last.end = first.end;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+        use super::*;
+
+    #[test]
+    fn hash_range_works() {
+        let r = HashRange::new(10, 19);
+        assert_eq!(r.len(), 10);
+        assert!(!r.wraps() && !r.is_full());
+        assert!(r.contains(10) && r.contains(19) && !r.contains(9) && !r.contains(20));
+
+        let w = HashRange::new(u64::MAX - 4, 4);
+        assert_eq!(w.len(), 10);
+        assert!(w.wraps());
+        assert!(w.contains(u64::MAX) && w.contains(0) && !w.contains(5));
+        assert!(w.contains_range(&HashRange::new(u64::MAX, 2)));
+        assert!(!w.contains_range(&HashRange::new(u64::MAX, 5)));
+        assert!(!r.contains_range(&w));
+
+        let full = HashRange::full();
+        assert_eq!(full.len(), 1 << 64);
+        assert!(full.is_full() && HashRange::new(5, 4).is_full());
+        assert!(HashRange::new(5, 4).contains_range(&full));
+        assert!(full.contains(u64::MAX) && full.intersects(&r));
+
+        assert!(r.intersects(&HashRange::new(19, 30)));
+        assert!(!r.intersects(&w));
+        assert!(w.intersects(&HashRange::new(3, 8)));
+    }
+
+    #[test]
+    fn split_works() {
+        let w = HashRange::new(u64::MAX - 4, 4);
+        assert_eq!(w.split_at(0),
+                   Some((HashRange::new(u64::MAX - 4, u64::MAX), HashRange::new(0, 4))));
+        assert_eq!(w.split_at(u64::MAX - 4), None);
+        assert_eq!(w.split_at(5), None);
+
+        assert_eq!(w.split(3),
+                   [HashRange::new(u64::MAX - 4, u64::MAX - 2),
+                    HashRange::new(u64::MAX - 1, 0),
+                    HashRange::new(1, 4)]);
+        assert_eq!(HashRange::new(0, 1).split(3),
+                   [HashRange::new(0, 0), HashRange::new(1, 1)]);
+        assert!(w.split(0).is_empty());
+
+        let quarters = HashRange::full().split(4);
+        assert_eq!(quarters.len(), 4);
+        assert!(quarters.iter().all(|q| q.len() == 1 << 62));
+        assert_eq!(quarters[3].end, u64::MAX);
+    }
+
+    #[test]
+    fn union_and_intersection_works() {
+        assert_eq!(union(&[HashRange::new(10, 20), HashRange::new(21, 30), HashRange::new(0, 5)]),
+                   [HashRange::new(0, 5), HashRange::new(10, 30)]);
+        assert_eq!(union(&[HashRange::new(u64::MAX - 4, 4), HashRange::new(0, 10)]),
+                   [HashRange::new(u64::MAX - 4, 10)]);
+        assert_eq!(union(&[HashRange::new(10, 5), HashRange::new(6, 9)]), [HashRange::full()]);
+        assert_eq!(union(&[HashRange::new(5, u64::MAX), HashRange::new(0, 3)]),
+                   [HashRange::new(5, 3)]);
+        assert!(union(&[]).is_empty());
+
+        let a = HashRange::new(100, 10);
+        let b = HashRange::new(5, 200);
+        assert_eq!(a.intersection(&b), [HashRange::new(5, 10), HashRange::new(100, 200)]);
+        assert_eq!(a.intersection(&HashRange::new(20, 30)), []);
+        assert_eq!(a.intersection(&HashRange::full()), [a]);
+    }
+}
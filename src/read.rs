@@ -0,0 +1,375 @@
+use std::collections::VecDeque;
+use std::hash::Hash;
+use std::time::Duration;
+use std::vec;
+
+use {Candidates, Node, RingHash, StaticHashRing};
+
+/// The policy which determines the order of the candidates for reads.
+///
+/// See `StaticHashRing::calc_read_candidates`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReadPolicy {
+    /// Reads use the same order as writes (i.e., ring order).
+    #[default]
+    Primary,
+
+    /// Reads are spread across the first `replicas` candidates.
+    ///
+    /// The first `replicas` candidates are rotated by an offset derived from the item,
+    /// so each replica serves a roughly equal share of reads.
+    /// The remaining candidates follow in ring order.
+    Spread {
+        /// The number of candidates among which reads are spread.
+        replicas: usize,
+    },
+}
+
+impl<K, V, H> StaticHashRing<K, V, H>
+    where H: RingHash
+{
+    /// Returns the candidate nodes for writing `item`.
+    ///
+    /// Writes always follow ring order, so this is the same as `calc_candidates`.
+    pub fn calc_write_candidates<T: Hash + ?Sized>(&self, item: &T) -> Candidates<'_, K, V> {
+        self.calc_candidates(item)
+    }
+
+    /// Returns the candidate nodes for reading `item`.
+    ///
+    /// The order of the candidates is determined by the read policy of this ring
+    /// (see `set_read_policy` method).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash, ReadPolicy};
+    ///
+    /// let mut ring = StaticHashRing::new(DefaultHash, (0..5).map(|i| Node::new(i).quantity(10)));
+    /// ring.set_read_policy(ReadPolicy::Spread { replicas: 3 });
+    ///
+    /// let mut reads = ring.calc_read_candidates(&"foo").take(3).collect::<Vec<_>>();
+    /// let mut writes = ring.calc_write_candidates(&"foo").take(3).collect::<Vec<_>>();
+    /// reads.sort();
+    /// writes.sort();
+    /// assert_eq!(reads, writes);
+    /// ```
+    pub fn calc_read_candidates<T: Hash + ?Sized>(&self, item: &T) -> ReadCandidates<'_, K, V> {
+        let item_hash = self.hash.hash_item(&item);
+        let mut rest = Candidates(self.candidate_vnodes(self.locate(item_hash)));
+        let head = match self.read_policy {
+            ReadPolicy::Primary => Vec::new(),
+            ReadPolicy::Spread { replicas } => {
+                let mut head = rest.by_ref().take(replicas).collect::<Vec<_>>();
+                if !head.is_empty() {
+                    let offset = (item_hash % head.len() as u64) as usize;
+                    head.rotate_left(offset);
+                }
+                head
+            }
+        };
+        ReadCandidates {
+            head: head.into_iter(),
+            rest,
+        }
+    }
+
+    /// Returns the candidate nodes for reading `item` with the replicas ranked by their latencies.
+    ///
+    /// The primary node always comes first (so reads stay consistent with writes),
+    /// and the following `replicas - 1` candidates are stable-sorted in ascending order
+    /// of the latencies reported by `tracker` (e.g., recent p99s).
+    /// Replicas whose latencies are unknown are placed after the others.
+    /// The remaining candidates follow in ring order.
+    ///
+    /// This ignores the read policy of this ring.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..5).map(|i| Node::new(i).quantity(10)));
+    /// let writes = ring.calc_write_candidates(&"foo").map(|n| n.key).collect::<Vec<_>>();
+    ///
+    /// // The first replica is slow.
+    /// let slow = writes[1];
+    /// let p99 = |key: &u8| Some(Duration::from_millis(if *key == slow { 500 } else { 5 }));
+    /// let reads = ring.calc_read_candidates_by_latency(&"foo", 3, &p99)
+    ///     .map(|n| n.key)
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(reads[..3], [writes[0], writes[2], slow]);
+    /// assert_eq!(reads[3..], writes[3..]);
+    /// ```
+    pub fn calc_read_candidates_by_latency<T, L>(&self,
+                                                 item: &T,
+                                                 replicas: usize,
+                                                 tracker: &L)
+                                                 -> ReadCandidates<'_, K, V>
+        where T: Hash + ?Sized,
+              L: LatencyTracker<K> + ?Sized
+    {
+        let mut rest = self.calc_candidates(item);
+        let mut head = rest.by_ref().take(replicas).collect::<Vec<_>>();
+        if head.len() > 2 {
+            head[1..].sort_by_key(|n| match tracker.latency(&n.key) {
+                Some(latency) => (false, latency),
+                None => (true, Duration::from_secs(0)),
+            });
+        }
+        ReadCandidates {
+            head: head.into_iter(),
+            rest,
+        }
+    }
+
+    /// Chooses one of the first `weights.len()` candidates for `item` with the probability
+    /// proportional to the corresponding weight (e.g., `[90.0, 9.0, 1.0]`).
+    ///
+    /// `r` is a uniformly distributed random number which determines the choice
+    /// (e.g., generated by a caller-provided RNG, or derived from a request identifier),
+    /// so the same `r` always chooses the same candidate.
+    /// This can be used for implementing shadow traffic or gradual shifting at the routing layer.
+    ///
+    /// Non-positive weights are regarded as `0.0`.
+    /// If there are fewer candidates than weights, the remaining weights are ignored.
+    /// If no candidates have positive weights, this returns `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..5).map(|i| Node::new(i).quantity(10)));
+    /// let candidates = ring.calc_candidates(&"foo").take(3).collect::<Vec<_>>();
+    ///
+    /// let weights = [90.0, 9.0, 1.0];
+    /// assert_eq!(ring.choose_candidate(&"foo", &weights, 0), Some(candidates[0]));
+    /// assert_eq!(ring.choose_candidate(&"foo", &weights, u64::MAX), Some(candidates[2]));
+    /// ```
+    pub fn choose_candidate<T: Hash + ?Sized>(&self,
+                                              item: &T,
+                                              weights: &[f64],
+                                              r: u64)
+                                              -> Option<&Node<K, V>> {
+        let candidates = self.calc_candidates(item)
+            .zip(weights.iter().map(|&w| if w > 0.0 { w } else { 0.0 }))
+            .collect::<Vec<_>>();
+        let total = candidates.iter().map(|&(_, w)| w).sum::<f64>();
+        if total <= 0.0 {
+            return None;
+        }
+        let mut point = (r as f64 / 2f64.powi(64)) * total;
+        for &(node, w) in &candidates {
+            if point < w {
+                return Some(node);
+            }
+            point -= w;
+        }
+        candidates.iter().rev().find(|&&(_, w)| w > 0.0).map(|&(n, _)| n)
+    }
+
+    /// Returns the candidate nodes for `item` with local nodes preferred within each tier.
+    ///
+    /// The candidate sequence is split into tiers of `tier` nodes
+    /// (if `tier` is `0`, the whole sequence is regarded as a single tier),
+    /// and the nodes in each tier are stable-partitioned so that the nodes satisfying `is_local`
+    /// (e.g., the nodes in the same zone as the caller) come first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// // The values of nodes are their zones.
+    /// let nodes = (0..6).map(|i| Node::new(i).value(i % 2).quantity(10));
+    /// let ring = StaticHashRing::new(DefaultHash, nodes);
+    /// let my_zone = 1;
+    ///
+    /// let candidates = ring.calc_candidates_local_first(&"foo", 3, |n| n.value == my_zone)
+    ///     .collect::<Vec<_>>();
+    /// let locals = candidates[..3].iter().filter(|n| n.value == my_zone).count();
+    /// assert!(candidates[..locals].iter().all(|n| n.value == my_zone));
+    /// ```
+    pub fn calc_candidates_local_first<T, F>(&self,
+                                             item: &T,
+                                             tier: usize,
+                                             is_local: F)
+                                             -> LocalFirstCandidates<'_, K, V, F>
+        where T: Hash + ?Sized,
+              F: Fn(&Node<K, V>) -> bool
+    {
+        LocalFirstCandidates {
+            candidates: self.calc_candidates(item),
+            tier,
+            is_local,
+            buffer: VecDeque::new(),
+        }
+    }
+}
+impl<K, V, H> StaticHashRing<K, V, H> {
+    /// Returns the read policy of this ring.
+    ///
+    /// The default value is `ReadPolicy::Primary`.
+    pub fn read_policy(&self) -> ReadPolicy {
+        self.read_policy
+    }
+
+    /// Sets the read policy of this ring.
+    pub fn set_read_policy(&mut self, policy: ReadPolicy) {
+        self.read_policy = policy;
+    }
+}
+
+/// This trait allows an external latency tracker to rank replicas for reads.
+///
+/// See `StaticHashRing::calc_read_candidates_by_latency`.
+///
+/// This is implemented for any `Fn(&K) -> Option<Duration>` closures.
+pub trait LatencyTracker<K> {
+    /// Returns the recent latency (e.g., p99) of the node `key`, or `None` if it is unknown.
+    fn latency(&self, key: &K) -> Option<Duration>;
+}
+impl<K, F> LatencyTracker<K> for F
+    where F: Fn(&K) -> Option<Duration>
+{
+    fn latency(&self, key: &K) -> Option<Duration> {
+        self(key)
+    }
+}
+
+/// An iterator which represents a sequence of the candidate nodes for reading an item.
+///
+/// This is created by calling `StaticHashRing::calc_read_candidates` method.
+pub struct ReadCandidates<'a, K: 'a, V: 'a> {
+    head: vec::IntoIter<&'a Node<K, V>>,
+    rest: Candidates<'a, K, V>,
+}
+impl<'a, K: 'a, V: 'a> Iterator for ReadCandidates<'a, K, V> {
+    type Item = &'a Node<K, V>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.head.next().or_else(|| self.rest.next())
+    }
+}
+
+/// An iterator which represents a sequence of the candidate nodes preferring local nodes.
+///
+/// This is created by calling `StaticHashRing::calc_candidates_local_first` method.
+pub struct LocalFirstCandidates<'a, K: 'a, V: 'a, F> {
+    candidates: Candidates<'a, K, V>,
+    tier: usize,
+    is_local: F,
+    buffer: VecDeque<&'a Node<K, V>>,
+}
+impl<'a, K: 'a, V: 'a, F> Iterator for LocalFirstCandidates<'a, K, V, F>
+    where F: Fn(&Node<K, V>) -> bool
+{
+    type Item = &'a Node<K, V>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buffer.is_empty() {
+            let tier = if self.tier == 0 { usize::MAX } else { self.tier };
+            let mut remotes = Vec::new();
+            for node in self.candidates.by_ref().take(tier) {
+                if (self.is_local)(node) {
+                    self.buffer.push_back(node);
+                } else {
+                    remotes.push(node);
+                }
+            }
+            self.buffer.extend(remotes);
+        }
+        self.buffer.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {DefaultHash, Node, StaticHashRing};
+    use super::*;
+
+    #[test]
+    fn read_candidates_works() {
+        let mut ring = StaticHashRing::new(DefaultHash, (0..5).map(|i| Node::new(i).quantity(10)));
+        assert_eq!(ring.read_policy(), ReadPolicy::Primary);
+        assert!(ring.calc_read_candidates(&0).eq(ring.calc_write_candidates(&0)));
+
+        ring.set_read_policy(ReadPolicy::Spread { replicas: 3 });
+        let mut firsts = [0; 3];
+        for item in 0..1000 {
+            let reads = ring.calc_read_candidates(&item).collect::<Vec<_>>();
+            let writes = ring.calc_write_candidates(&item).collect::<Vec<_>>();
+            assert_eq!(reads[3..], writes[3..]);
+            let i = writes.iter().position(|n| *n == reads[0]).unwrap();
+            firsts[i] += 1;
+        }
+        assert!(firsts.iter().all(|&c| c > 250), "{:?}", firsts);
+    }
+
+    #[test]
+    fn read_candidates_by_latency_works() {
+        use std::collections::HashMap;
+
+        let ring = StaticHashRing::new(DefaultHash, (0..6).map(|i| Node::new(i).quantity(10)));
+        let latencies = (0..5).map(|i| (i, Duration::from_millis(100 - i * 10))).collect::<HashMap<_, _>>();
+        let tracker = |key: &u64| latencies.get(key).cloned();
+        for item in 0..100 {
+            let writes = ring.calc_write_candidates(&item).map(|n| n.key).collect::<Vec<_>>();
+            let reads = ring.calc_read_candidates_by_latency(&item, 4, &tracker)
+                .map(|n| n.key)
+                .collect::<Vec<_>>();
+            assert_eq!(reads[0], writes[0]);
+            assert_eq!(reads[4..], writes[4..]);
+
+            // Node `5` has no latency and the others are faster as their keys grow.
+            let mut expected = writes[1..4].to_vec();
+            expected.sort_by_key(|&k| if k == 5 { (1, 0) } else { (0, 5 - k) });
+            assert_eq!(reads[1..4], expected[..]);
+        }
+
+        let reads = ring.calc_read_candidates_by_latency(&0, 0, &tracker).collect::<Vec<_>>();
+        assert!(reads.into_iter().eq(ring.calc_write_candidates(&0)));
+    }
+
+    #[test]
+    fn choose_candidate_works() {
+        let ring = StaticHashRing::new(DefaultHash, (0..5).map(|i| Node::new(i).quantity(10)));
+        let candidates = ring.calc_candidates(&"foo").collect::<Vec<_>>();
+
+        let mut counts = [0; 5];
+        let weights = [90.0, 9.0, 0.0, 1.0];
+        for i in 0..10000u64 {
+            let r = i.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+            let node = ring.choose_candidate(&"foo", &weights, r).unwrap();
+            counts[candidates.iter().position(|&n| n == node).unwrap()] += 1;
+        }
+        assert!(8800 < counts[0] && counts[0] < 9200, "{:?}", counts);
+        assert!(800 < counts[1] && counts[1] < 1000, "{:?}", counts);
+        assert_eq!(counts[2], 0);
+        assert!(50 < counts[3] && counts[3] < 150, "{:?}", counts);
+        assert_eq!(counts[4], 0);
+
+        assert_eq!(ring.choose_candidate(&"foo", &[0.0, -1.0], 0), None);
+        assert_eq!(ring.choose_candidate(&"foo", &[], 0), None);
+        assert_eq!(ring.choose_candidate(&"foo", &[1.0; 10], u64::MAX), Some(candidates[4]));
+    }
+
+    #[test]
+    fn local_first_works() {
+        let nodes = (0..6).map(|i| Node::new(i).value(i % 3).quantity(10));
+        let ring = StaticHashRing::new(DefaultHash, nodes);
+        for item in 0..100 {
+            let plain = ring.calc_candidates(&item).collect::<Vec<_>>();
+            for &tier in &[0, 1, 2, 4] {
+                let candidates = ring.calc_candidates_local_first(&item, tier, |n| n.value == 0)
+                    .collect::<Vec<_>>();
+                let chunk = if tier == 0 { plain.len() } else { tier };
+                for (a, b) in candidates.chunks(chunk).zip(plain.chunks(chunk)) {
+                    let mut expected = b.iter().filter(|n| n.value == 0).collect::<Vec<_>>();
+                    expected.extend(b.iter().filter(|n| n.value != 0));
+                    assert_eq!(a.iter().collect::<Vec<_>>(), expected);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use std::cmp::Ordering;
+use std::hash::Hash;
+use std::mem;
+use std::vec;
+
+use {rendezvous_score, Node, RingHash};
+
+/// A hash "ring" based on rendezvous hashing (a.k.a. highest random weight hashing).
+///
+/// Every lookup scores all of the nodes, so it takes `O(n)` time,
+/// but only the items owned by the added (or removed) node move when the membership changes.
+/// This is suitable for small clusters.
+///
+/// The quantity of a node is used as its weight (i.e., a node takes items proportionally to its quantity),
+/// and nodes whose quantities are `0` are never chosen.
+///
+/// # Examples
+///
+/// ```
+/// use consistent_hash::{Node, RendezvousRing, DefaultHash};
+///
+/// let nodes = vec![
+///     Node::new("foo").quantity(1),
+///     Node::new("bar").quantity(1),
+///     Node::new("baz").quantity(0),
+/// ];
+/// let mut ring = RendezvousRing::new(DefaultHash, nodes.into_iter());
+/// assert_eq!(ring.len(), 3);
+/// assert_eq!(ring.calc_candidates(&"aa").count(), 2);
+///
+/// let owner = ring.calc_candidate(&"aa").unwrap().key;
+/// let other = if owner == "foo" { "bar" } else { "foo" };
+/// ring.remove_node(&other);
+/// assert_eq!(ring.calc_candidate(&"aa").map(|n| n.key), Some(owner));
+/// ```
+#[derive(Debug, Clone)]
+pub struct RendezvousRing<K, V, H> {
+    hash: H,
+    nodes: Vec<Node<K, V>>,
+}
+impl<K, V, H> RendezvousRing<K, V, H>
+    where K: Hash + Eq + Ord,
+          H: RingHash
+{
+    /// Makes a new `RendezvousRing` instance.
+    ///
+    /// If multiple nodes which have the same key are contained in `nodes`,
+    /// all of those nodes but first one are ignored.
+    pub fn new<I>(hash: H, nodes: I) -> Self
+        where I: Iterator<Item = Node<K, V>>
+    {
+        let mut nodes = nodes.collect::<Vec<_>>();
+        nodes.sort_by(|a, b| a.key.cmp(&b.key));
+        nodes.dedup_by(|a, b| a.key == b.key);
+        RendezvousRing { hash, nodes }
+    }
+
+    /// Inserts `node` into this ring.
+    ///
+    /// If this ring already contains a node which has the same key,
+    /// the node is replaced by `node` and the old one is returned.
+    pub fn insert_node(&mut self, node: Node<K, V>) -> Option<Node<K, V>> {
+        match self.nodes.binary_search_by(|n| n.key.cmp(&node.key)) {
+            Ok(i) => Some(mem::replace(&mut self.nodes[i], node)),
+            Err(i) => {
+                self.nodes.insert(i, node);
+                None
+            }
+        }
+    }
+
+    /// Removes the node identified by `key` from this ring, and returns it.
+    pub fn remove_node(&mut self, key: &K) -> Option<Node<K, V>> {
+        self.nodes
+            .binary_search_by(|n| n.key.cmp(key))
+            .ok()
+            .map(|i| self.nodes.remove(i))
+    }
+
+    /// Returns a reference to the node identified by `key`.
+    pub fn get_node(&self, key: &K) -> Option<&Node<K, V>> {
+        self.nodes.binary_search_by(|n| n.key.cmp(key)).ok().map(|i| &self.nodes[i])
+    }
+
+    /// Returns the node which has the best score for `item`.
+    ///
+    /// This is the same as `calc_candidates(item).next()`, but does not rank the rest of nodes.
+    pub fn calc_candidate<T: Hash>(&self, item: &T) -> Option<&Node<K, V>> {
+        let item_hash = self.hash.hash_item(item);
+        self.scores(item_hash)
+            .fold(None, |best: Option<(f64, &Node<K, V>)>, (score, node)| match best {
+                Some((s, _)) if s <= score => best,
+                _ => Some((score, node)),
+            })
+            .map(|(_, node)| node)
+    }
+
+    /// Returns the candidate nodes for `item`, ranked by their scores.
+    ///
+    /// The elements of the result iterator are ordered by the priority of `item`.
+    /// Nodes whose quantities are `0` are not contained.
+    pub fn calc_candidates<T: Hash>(&self, item: &T) -> RendezvousCandidates<'_, K, V> {
+        let item_hash = self.hash.hash_item(item);
+        let mut scores = self.scores(item_hash).collect::<Vec<_>>();
+        scores.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+        RendezvousCandidates(scores.into_iter())
+    }
+
+    // Returns the scores of the nodes which have positive quantities (the lowest score wins).
+    fn scores(&self, item_hash: u64) -> impl Iterator<Item = (f64, &Node<K, V>)> {
+        self.nodes.iter().filter(|n| n.quantity > 0).map(move |n| {
+            let score = rendezvous_score(self.hash.hash_item(&(item_hash, &n.key)), n.quantity as f64);
+            (score, n)
+        })
+    }
+}
+impl<K, V, H> RendezvousRing<K, V, H> {
+    /// Returns the nodes of this ring (sorted in key order).
+    pub fn nodes(&self) -> &[Node<K, V>] {
+        &self.nodes
+    }
+
+    /// Returns the count of the nodes in this ring.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns `true` if this ring has no nodes, otherwise `false`.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+/// An iterator which represents a sequence of the candidate nodes for an item ranked by rendezvous hashing.
+///
+/// This is created by calling `RendezvousRing::calc_candidates` method.
+#[derive(Debug)]
+pub struct RendezvousCandidates<'a, K: 'a, V: 'a>(vec::IntoIter<(f64, &'a Node<K, V>)>);
+impl<'a, K: 'a, V: 'a> Iterator for RendezvousCandidates<'a, K, V> {
+    type Item = &'a Node<K, V>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(_, n)| n)
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+impl<'a, K: 'a, V: 'a> ExactSizeIterator for RendezvousCandidates<'a, K, V> {}
+
+#[cfg(test)]
+mod tests {
+    use DefaultHash;
+    use super::*;
+
+    #[test]
+    fn rendezvous_ring_works() {
+        let mut ring = RendezvousRing::new(DefaultHash, (0..5).map(|i| Node::new(i).quantity(1)));
+        let before = (0..1000)
+            .map(|i| ring.calc_candidates(&i).map(|n| n.key).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        for (i, candidates) in (0..1000).zip(&before) {
+            assert_eq!(candidates.len(), 5);
+            assert_eq!(ring.calc_candidate(&i).map(|n| n.key), Some(candidates[0]));
+        }
+
+        // Only the items owned by the removed node move.
+        assert_eq!(ring.remove_node(&2).map(|n| n.key), Some(2));
+        assert_eq!(ring.remove_node(&2), None);
+        for (i, candidates) in (0..1000).zip(&before) {
+            let expected = candidates.iter().cloned().filter(|&k| k != 2).collect::<Vec<_>>();
+            assert_eq!(ring.calc_candidates(&i).map(|n| n.key).collect::<Vec<_>>(), expected);
+        }
+
+        assert_eq!(ring.insert_node(Node::new(2).quantity(1)), None);
+        assert_eq!(ring.insert_node(Node::new(0).quantity(0)), Some(Node::new(0).quantity(1)));
+        assert_eq!(ring.get_node(&0), Some(&Node::new(0).quantity(0)));
+        for (i, candidates) in (0..1000).zip(&before) {
+            let expected = candidates.iter().cloned().filter(|&k| k != 0).collect::<Vec<_>>();
+            assert_eq!(ring.calc_candidates(&i).map(|n| n.key).collect::<Vec<_>>(), expected);
+        }
+    }
+
+    #[test]
+    fn rendezvous_weights_works() {
+        let nodes = vec![Node::new(0).quantity(1), Node::new(1).quantity(3)];
+        let ring = RendezvousRing::new(DefaultHash, nodes.into_iter());
+        let heavy = (0..10000).filter(|i| ring.calc_candidate(i).unwrap().key == 1).count();
+        assert!(7200 < heavy && heavy < 7800, "{}", heavy);
+
+        let empty = RendezvousRing::new(DefaultHash, (0..0).map(Node::new));
+        assert_eq!(empty.calc_candidate(&"foo"), None);
+        assert_eq!(empty.calc_candidates(&"foo").count(), 0);
+    }
+}
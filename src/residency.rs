@@ -0,0 +1,134 @@
+use std::hash::Hash;
+
+use {Node, RingHash, StaticHashRing};
+
+/// A key which is not stored on its expected node.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Misplacement<T, K> {
+    /// The misplaced key.
+    pub key: T,
+
+    /// The key of the node on which the key was observed (`None` if the key was not found).
+    pub observed: Option<K>,
+}
+
+/// The misplaced keys of a node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeMisplacements<'a, T, K: 'a, V: 'a> {
+    /// The expected (primary) node of the keys.
+    pub node: &'a Node<K, V>,
+
+    /// The misplaced keys (in the order of the given keys).
+    pub keys: Vec<Misplacement<T, K>>,
+}
+
+/// The result of `StaticHashRing::verify_residency`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResidencyReport<'a, T, K: 'a, V: 'a> {
+    /// The count of the checked keys.
+    pub checked: usize,
+
+    /// The misplaced keys grouped by their expected (primary) nodes.
+    ///
+    /// Only the nodes which have misplaced keys are contained (ordered as `StaticHashRing::nodes`).
+    pub misplaced: Vec<NodeMisplacements<'a, T, K, V>>,
+}
+impl<'a, T, K: 'a, V: 'a> ResidencyReport<'a, T, K, V> {
+    /// Returns `true` if no keys are misplaced, otherwise `false`.
+    pub fn is_clean(&self) -> bool {
+        self.misplaced.is_empty()
+    }
+
+    /// Returns the count of the misplaced keys.
+    pub fn misplaced_count(&self) -> usize {
+        self.misplaced.iter().map(|m| m.keys.len()).sum()
+    }
+}
+
+impl<K, V, H> StaticHashRing<K, V, H>
+    where K: Eq,
+          H: RingHash
+{
+    /// Compares the placements observed by a storage scan against the expectations of this ring.
+    ///
+    /// `observed_node` returns the key of the node on which each of `keys` is actually stored
+    /// (or `None` if the key is not found), and the keys whose observed nodes differ from
+    /// their primary nodes are reported as misplacements.
+    /// This is the core of anti-entropy audits.
+    ///
+    /// If this ring is empty, no keys are regarded as misplaced (since no keys have expected nodes).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+    ///
+    /// // Every key is stored on node `0` (and the key `5` is lost).
+    /// let report = ring.verify_residency(0..10, |&key| if key == 5 { None } else { Some(0) });
+    /// assert_eq!(report.checked, 10);
+    /// assert!(report.misplaced.iter().all(|m| m.node.key != 0));
+    /// let expected = (0..10).filter(|&k| k == 5 || ring.calc_node(&k).unwrap().key != 0).count();
+    /// assert_eq!(report.misplaced_count(), expected);
+    /// ```
+    pub fn verify_residency<T, I, F>(&self, keys: I, mut observed_node: F) -> ResidencyReport<'_, T, K, V>
+        where T: Hash,
+              I: IntoIterator<Item = T>,
+              F: FnMut(&T) -> Option<K>
+    {
+        let mut checked = 0;
+        let mut groups = (0..self.nodes.len()).map(|_| Vec::new()).collect::<Vec<_>>();
+        for key in keys {
+            checked += 1;
+            let start = self.locate(self.hash.hash_item(&key));
+            let expected = match self.candidate_vnodes(start).next() {
+                None => continue,
+                Some(i) => self.ring[i].index,
+            };
+            let observed = observed_node(&key);
+            if observed.as_ref() != Some(&self.nodes[expected].key) {
+                groups[expected].push(Misplacement { key, observed });
+            }
+        }
+        ResidencyReport {
+            checked,
+            misplaced: self.nodes
+                .iter()
+                .zip(groups)
+                .filter(|(_, keys)| !keys.is_empty())
+                .map(|(node, keys)| NodeMisplacements { node, keys })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {DefaultHash, Node, StaticHashRing};
+
+    #[test]
+    fn verify_residency_works() {
+        let ring = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(10)));
+        let report = ring.verify_residency(0..100, |k| ring.calc_node(k).map(|n| n.key));
+        assert_eq!(report.checked, 100);
+        assert!(report.is_clean());
+
+        // Node `3` was replaced with an empty node, and node `1` holds the keys of node `2`.
+        let report = ring.verify_residency(0..100, |k| match ring.calc_node(k).unwrap().key {
+            3 => None,
+            2 => Some(1),
+            key => Some(key),
+        });
+        assert_eq!(report.misplaced.iter().map(|m| m.node.key).collect::<Vec<_>>(), [2, 3]);
+        for m in &report.misplaced {
+            let expected = (0..100).filter(|k| ring.calc_node(k) == Some(m.node)).collect::<Vec<_>>();
+            assert_eq!(m.keys.iter().map(|k| k.key).collect::<Vec<_>>(), expected);
+            let observed = if m.node.key == 2 { Some(1) } else { None };
+            assert!(m.keys.iter().all(|k| k.observed == observed));
+        }
+
+        let empty = StaticHashRing::new(DefaultHash, (0..0).map(Node::new));
+        assert!(empty.verify_residency(0..10, |_| Some(0)).is_clean());
+    }
+}
@@ -0,0 +1,90 @@
+use std::hash::Hash;
+
+use {Candidates, Node, RingHash, StaticHashRing};
+
+impl<K, V, H> StaticHashRing<K, V, H>
+    where H: RingHash
+{
+    /// Returns the nodes to which `attempts` successive attempts (e.g., retries) for `item` are sent.
+    ///
+    /// The sequence goes through the distinct candidates of `calc_candidates` first,
+    /// and then repeats them in the same order if `attempts` exceeds the count of the candidates.
+    /// If this ring is empty, the sequence is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+    /// let candidates = ring.calc_candidates(&"foo").collect::<Vec<_>>();
+    ///
+    /// let sequence = ring.retry_sequence(&"foo", 5).collect::<Vec<_>>();
+    /// assert_eq!(sequence, [candidates[0], candidates[1], candidates[2], candidates[0], candidates[1]]);
+    /// ```
+    pub fn retry_sequence<T: Hash + ?Sized>(&self, item: &T, attempts: usize) -> RetrySequence<'_, K, V> {
+        RetrySequence {
+            candidates: Some(self.calc_candidates(item)),
+            yielded: Vec::new(),
+            position: 0,
+            remaining: attempts,
+        }
+    }
+}
+
+/// An iterator which represents the nodes for successive attempts.
+///
+/// This is created by calling `StaticHashRing::retry_sequence` method.
+pub struct RetrySequence<'a, K: 'a, V: 'a> {
+    // `None` once all of the candidates are yielded.
+    candidates: Option<Candidates<'a, K, V>>,
+    yielded: Vec<&'a Node<K, V>>,
+    position: usize,
+    remaining: usize,
+}
+impl<'a, K: 'a, V: 'a> Iterator for RetrySequence<'a, K, V> {
+    type Item = &'a Node<K, V>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        if let Some(node) = self.candidates.as_mut().and_then(|c| c.next()) {
+            self.remaining -= 1;
+            self.yielded.push(node);
+            return Some(node);
+        }
+        self.candidates = None;
+        if self.yielded.is_empty() {
+            self.remaining = 0;
+            return None;
+        }
+        let node = self.yielded[self.position % self.yielded.len()];
+        self.position += 1;
+        self.remaining -= 1;
+        Some(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {DefaultHash, Node, StaticHashRing};
+
+    #[test]
+    fn retry_sequence_works() {
+        let mut ring = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(10)));
+        ring.set_replica_only(&0, true);
+        for item in 0..100 {
+            let candidates = ring.calc_candidates(&item).collect::<Vec<_>>();
+            let sequence = ring.retry_sequence(&item, 10).collect::<Vec<_>>();
+            assert_eq!(sequence.len(), 10);
+            for (i, node) in sequence.into_iter().enumerate() {
+                assert_eq!(node, candidates[i % 4]);
+            }
+            assert_eq!(ring.retry_sequence(&item, 2).collect::<Vec<_>>(), &candidates[..2]);
+            assert_eq!(ring.retry_sequence(&item, 0).count(), 0);
+        }
+
+        let empty = StaticHashRing::new(DefaultHash, (0..0).map(Node::new));
+        assert_eq!(empty.retry_sequence(&"foo", 3).count(), 0);
+    }
+}
@@ -0,0 +1,69 @@
+// The finalizer of SplitMix64.
+pub fn mix(mut h: u64) -> u64 {
+    h = (h ^ (h >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    h ^ (h >> 31)
+}
+
+/// A tiny deterministic pseudo random number generator.
+///
+/// The algorithm is SplitMix64 (Steele, Lea, and Flood, 2014),
+/// and it is fixed in this crate, so the same seed always produces the same sequence
+/// regardless of the version of this crate or the build environment.
+/// This is intended for the strategies which take random numbers
+/// (e.g., `StaticHashRing::sample_keys_for` and `StaticHashRing::choose_candidate`),
+/// so that placements derived from them are reproducible.
+///
+/// This is not cryptographically secure.
+///
+/// # Examples
+///
+/// ```
+/// use consistent_hash::{Node, StaticHashRing, DefaultHash, SplitMix64};
+///
+/// let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+///
+/// let mut rng = SplitMix64::new(1234);
+/// let keys = ring.sample_keys_for(&1, 10, &mut || rng.next_u64());
+///
+/// let mut rng = SplitMix64::new(1234);
+/// assert_eq!(ring.sample_keys_for(&1, 10, &mut || rng.next_u64()), keys);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SplitMix64 {
+    state: u64,
+}
+impl SplitMix64 {
+    /// Makes a new `SplitMix64` instance from `seed`.
+    pub fn new(seed: u64) -> Self {
+        SplitMix64 { state: seed }
+    }
+
+    /// Returns the next uniformly distributed `u64` number.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        mix(self.state)
+    }
+
+    /// Returns the next uniformly distributed number in the range `[0.0, 1.0)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_mix64_works() {
+        let mut rng = SplitMix64::new(0);
+        assert_eq!(rng.next_u64(), 0xe220_a839_7b1d_cdaf);
+        assert_eq!(rng.next_u64(), 0x6e78_9e6a_a1b9_65f4);
+        assert_eq!(rng.next_u64(), 0x06c4_5d18_8009_454f);
+        assert_eq!(SplitMix64::new(1_234_567).next_u64(), 6_457_827_717_110_365_317);
+
+        let mut rng = SplitMix64::new(42);
+        assert!((0..1000).map(|_| rng.next_f64()).all(|r| (0.0..1.0).contains(&r)));
+    }
+}
@@ -0,0 +1,112 @@
+use std::hash::Hash;
+
+use {DefaultHash, Node, StaticHashRing};
+
+/// The default number of virtual nodes of each node of `ConsistentRouter` (the same as ketama).
+pub const DEFAULT_ROUTER_VNODES: usize = 160;
+
+/// A minimal router which maps items to node keys.
+///
+/// This is a thin wrapper of `StaticHashRing` which has no node values,
+/// uses `DefaultHash`, and gives the same number of virtual nodes to every node.
+/// Use `StaticHashRing` directly if values, weights (i.e., quantities), or candidates are needed.
+///
+/// # Examples
+///
+/// ```
+/// use consistent_hash::ConsistentRouter;
+///
+/// let router = ConsistentRouter::new(vec!["foo", "bar", "baz"]);
+/// let node = router.route(&"item");
+/// assert!(["foo", "bar", "baz"].contains(node));
+/// assert_eq!(router.route(&"item"), node);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConsistentRouter<K> {
+    ring: StaticHashRing<K, (), DefaultHash>,
+}
+impl<K> ConsistentRouter<K>
+    where K: Hash + Eq + Ord
+{
+    /// Makes a new `ConsistentRouter` instance which gives `DEFAULT_ROUTER_VNODES` virtual nodes to each node.
+    ///
+    /// Duplicate keys are ignored.
+    pub fn new<I>(keys: I) -> Self
+        where I: IntoIterator<Item = K>
+    {
+        Self::with_vnodes(keys, DEFAULT_ROUTER_VNODES)
+    }
+
+    /// Makes a new `ConsistentRouter` instance which gives `vnodes` virtual nodes to each node.
+    ///
+    /// The placement is the same as the `StaticHashRing` which has the nodes of the quantity `vnodes`.
+    pub fn with_vnodes<I>(keys: I, vnodes: usize) -> Self
+        where I: IntoIterator<Item = K>
+    {
+        let nodes = keys.into_iter().map(|k| Node::new(k).quantity(vnodes));
+        ConsistentRouter { ring: StaticHashRing::new(DefaultHash, nodes) }
+    }
+
+    /// Returns the key of the node to which `item` is routed.
+    ///
+    /// # Panics
+    ///
+    /// If this router has no nodes (or `vnodes` is zero), this function will panic.
+    pub fn route<T: Hash>(&self, item: &T) -> &K {
+        self.try_route(item).expect("ConsistentRouter has no nodes")
+    }
+
+    /// Returns the key of the node to which `item` is routed, or `None` if this router has no nodes.
+    pub fn try_route<T: Hash>(&self, item: &T) -> Option<&K> {
+        self.ring.calc_node(item).map(|n| &n.key)
+    }
+}
+impl<K> ConsistentRouter<K> {
+    /// Returns the keys of the nodes contained in this router (in ascending order).
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.ring.nodes().iter().map(|n| &n.key)
+    }
+
+    /// Returns the count of the nodes contained in this router.
+    pub fn len(&self) -> usize {
+        self.ring.nodes().len()
+    }
+
+    /// Returns `true` if this router has no nodes, otherwise `false`.
+    pub fn is_empty(&self) -> bool {
+        self.ring.nodes().is_empty()
+    }
+
+    /// Returns a reference to the inner ring.
+    pub fn ring(&self) -> &StaticHashRing<K, (), DefaultHash> {
+        &self.ring
+    }
+
+    /// Takes the inner ring out of this router.
+    pub fn into_ring(self) -> StaticHashRing<K, (), DefaultHash> {
+        self.ring
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {DefaultHash, Node, StaticHashRing};
+    use super::*;
+
+    #[test]
+    fn consistent_router_works() {
+        let router = ConsistentRouter::new(vec![3, 1, 2, 1]);
+        assert_eq!(router.len(), 3);
+        assert_eq!(router.keys().cloned().collect::<Vec<_>>(), [1, 2, 3]);
+
+        let ring = StaticHashRing::new(DefaultHash, (1..4).map(|i| Node::new(i).quantity(DEFAULT_ROUTER_VNODES)));
+        for item in 0..100 {
+            assert_eq!(Some(router.route(&item)), ring.calc_node(&item).map(|n| &n.key));
+        }
+        assert!(router.ring().topology_eq(&ring));
+
+        let empty = ConsistentRouter::<u32>::new(None);
+        assert!(empty.is_empty());
+        assert_eq!(empty.try_route(&"foo"), None);
+    }
+}
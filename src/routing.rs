@@ -0,0 +1,237 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use ownership::space_fraction;
+use range::HashRange;
+use {Node, NodeId, StaticHashRing};
+
+/// An entry of a `RoutingTable`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoutingEntry<'a, K: 'a, V: 'a> {
+    /// The range of hash codes which is routed to `node` (never wraps around).
+    pub range: HashRange,
+
+    /// The (primary) node of the range.
+    pub node: &'a Node<K, V>,
+
+    /// The identifier of `node` (compact for serialized tables).
+    pub node_id: NodeId,
+}
+
+/// A compact approximation of a ring, which maps contiguous ranges of hash codes to nodes.
+///
+/// This is created by calling `StaticHashRing::export_routing_table` method.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoutingTable<'a, K: 'a, V: 'a> {
+    /// The entries ordered by their hash codes.
+    ///
+    /// The ranges of the entries cover the whole hash space without gaps (unless the ring is empty).
+    pub entries: Vec<RoutingEntry<'a, K, V>>,
+
+    /// The fraction of the hash space which is routed to nodes other than the primary ones (`0.0..=1.0`).
+    pub error: f64,
+
+    /// The maximum difference between the ownership (as primary) of a node in this table and the exact one.
+    ///
+    /// This never exceeds `error`.
+    pub ownership_error: f64,
+}
+impl<'a, K: 'a, V: 'a> RoutingTable<'a, K, V> {
+    /// Returns the node to which the hash code `item_hash` is routed.
+    ///
+    /// If the table is empty, this returns `None`.
+    pub fn lookup(&self, item_hash: u64) -> Option<&'a Node<K, V>> {
+        let i = self.entries.partition_point(|e| e.range.end < item_hash);
+        self.entries.get(i).map(|e| e.node)
+    }
+}
+
+// A segment of the routing table under construction (a node of a doubly linked list).
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    start: u64,
+    end: u64,
+    node: usize,
+    prev: Option<usize>,
+    next: Option<usize>,
+    alive: bool,
+}
+impl Segment {
+    fn len(&self) -> u128 {
+        (self.end - self.start) as u128 + 1
+    }
+}
+
+impl<K, V, H> StaticHashRing<K, V, H> {
+    /// Approximates this ring by a routing table which has at most `max_entries` entries
+    /// (at least one entry is kept unless this ring is empty).
+    ///
+    /// The exact table has an entry for each range owned by a primary node
+    /// (adjacent ranges of the same node are merged).
+    /// While the table has too many entries, the smallest range is absorbed into its neighbor,
+    /// so the error (see `RoutingTable::error`) stays small.
+    /// This is intended for pushing routing tables to constrained clients which cannot hold all virtual nodes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash, RingHash};
+    ///
+    /// let ring = StaticHashRing::new(DefaultHash, (0..10).map(|i| Node::new(i).quantity(10)));
+    ///
+    /// let exact = ring.export_routing_table(usize::MAX);
+    /// assert_eq!(exact.error, 0.0);
+    /// let item_hash = DefaultHash.hash_item(&"foo");
+    /// assert_eq!(exact.lookup(item_hash), ring.calc_node_by_hash(item_hash));
+    ///
+    /// let table = ring.export_routing_table(50);
+    /// assert!(table.entries.len() <= 50);
+    /// assert!(0.0 < table.error && table.error < 0.2);
+    /// assert!(table.ownership_error <= table.error);
+    /// ```
+    pub fn export_routing_table(&self, max_entries: usize) -> RoutingTable<'_, K, V> {
+        let mut segments: Vec<Segment> = Vec::new();
+        for r in self.primary_ranges() {
+            match segments.last_mut() {
+                Some(s) if s.node == r.node => s.end = r.end,
+                _ => {
+                    let i = segments.len();
+                    segments.push(Segment {
+                        start: r.start,
+                        end: r.end,
+                        node: r.node,
+                        prev: i.checked_sub(1),
+                        next: None,
+                        alive: true,
+                    });
+                    if i > 0 {
+                        segments[i - 1].next = Some(i);
+                    }
+                }
+            }
+        }
+
+        let exact = segments.clone();
+        let mut heap = segments.iter().enumerate().map(|(i, s)| Reverse((s.len(), i))).collect::<BinaryHeap<_>>();
+        let mut count = segments.len();
+        while count > max_entries.max(1) {
+            let Reverse((len, i)) = heap.pop().expect("Never fails");
+            if !segments[i].alive || segments[i].len() != len {
+                continue;
+            }
+
+            // Absorbs the segment into its neighbor (the previous one, if any).
+            let s = segments[i];
+            segments[i].alive = false;
+            count -= 1;
+            let absorber = match (s.prev, s.next) {
+                (Some(p), next) => {
+                    segments[p].end = s.end;
+                    segments[p].next = next;
+                    if let Some(n) = next {
+                        segments[n].prev = Some(p);
+                    }
+                    p
+                }
+                (None, Some(n)) => {
+                    segments[n].start = s.start;
+                    segments[n].prev = None;
+                    n
+                }
+                (None, None) => unreachable!(),
+            };
+
+            // Merges the absorber and the following segment if they have the same node.
+            if let Some(n) = segments[absorber].next {
+                if segments[n].node == segments[absorber].node {
+                    segments[absorber].end = segments[n].end;
+                    segments[absorber].next = segments[n].next;
+                    if let Some(nn) = segments[n].next {
+                        segments[nn].prev = Some(absorber);
+                    }
+                    segments[n].alive = false;
+                    count -= 1;
+                }
+            }
+            heap.push(Reverse((segments[absorber].len(), absorber)));
+        }
+
+        // Sums up the hash codes routed to the other nodes than the exact ones.
+        let segments = segments.into_iter().filter(|s| s.alive).collect::<Vec<_>>();
+        let mut error = 0;
+        let mut owned = vec![(0, 0); self.nodes.len()];
+        for e in &exact {
+            owned[e.node].0 += e.len();
+        }
+        for s in &segments {
+            owned[s.node].1 += s.len();
+        }
+        let mut exact = exact.iter().peekable();
+        for s in &segments {
+            while let Some(e) = exact.next_if(|e| e.end <= s.end) {
+                if e.node != s.node {
+                    error += (e.end - e.start.max(s.start)) as u128 + 1;
+                }
+            }
+            if let Some(e) = exact.peek() {
+                if e.start <= s.end && e.node != s.node {
+                    error += (s.end - e.start.max(s.start)) as u128 + 1;
+                }
+            }
+        }
+
+        RoutingTable {
+            entries: segments.iter()
+                .map(|s| {
+                    RoutingEntry {
+                        range: HashRange::new(s.start, s.end),
+                        node: &self.nodes[s.node],
+                        node_id: NodeId::from_index(s.node),
+                    }
+                })
+                .collect(),
+            error: space_fraction(error),
+            ownership_error: owned.into_iter().map(|(a, b)| space_fraction(a.abs_diff(b))).fold(0.0, f64::max),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {DefaultHash, Node, StaticHashRing};
+
+    #[test]
+    fn export_routing_table_works() {
+        let mut ring = StaticHashRing::new(DefaultHash, (0..5).map(|i| Node::new(i).quantity(50)));
+        ring.set_replica_only(&0, true);
+
+        let exact = ring.export_routing_table(usize::MAX);
+        assert_eq!(exact.error, 0.0);
+        assert!(exact.entries.windows(2).all(|w| w[0].node != w[1].node));
+        assert!(exact.entries.iter().all(|e| e.node.key != 0));
+        for item_hash in (0..1000u64).map(|i| i.wrapping_mul(0x9e37_79b9_7f4a_7c15)) {
+            assert_eq!(exact.lookup(item_hash), ring.calc_node_by_hash(item_hash));
+        }
+
+        for &max in &[100, 10, 1] {
+            let table = ring.export_routing_table(max);
+            assert!(table.entries.len() <= max);
+            assert_eq!(table.entries.first().map(|e| e.range.start), Some(0));
+            assert_eq!(table.entries.last().map(|e| e.range.end), Some(u64::MAX));
+            assert!(table.entries.windows(2).all(|w| w[0].range.end + 1 == w[1].range.start));
+            assert!(table.entries.iter().all(|e| ring.node_by_id(e.node_id) == Some(e.node)));
+
+            // The error is the fraction of the misrouted hash space.
+            let samples = (0..10_000u64).map(|i| i.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+            let misrouted = samples.filter(|&h| table.lookup(h) != ring.calc_node_by_hash(h)).count();
+            assert!((misrouted as f64 / 10_000.0 - table.error).abs() < 0.05);
+            assert!(table.ownership_error <= table.error);
+        }
+        assert_eq!(ring.export_routing_table(0).entries.len(), 1);
+
+        let empty = StaticHashRing::new(DefaultHash, (0..0).map(Node::new));
+        let table = empty.export_routing_table(10);
+        assert!(table.entries.is_empty());
+        assert_eq!(table.lookup(0), None);
+    }
+}
@@ -0,0 +1,162 @@
+use std::hash::Hash;
+use std::mem;
+use std::time::SystemTime;
+
+use {Candidates, Clock, RingHash, StaticHashRing};
+
+/// A set of ring versions, each of which becomes active at its activation time.
+///
+/// Lookups are answered "as of" a time,
+/// so a coordinated cluster can agree to switch placement at a future instant
+/// without racing the configuration push.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::{Duration, UNIX_EPOCH};
+/// use consistent_hash::{Node, StaticHashRing, DefaultHash, ScheduledRing};
+///
+/// let t0 = UNIX_EPOCH;
+/// let t1 = t0 + Duration::from_secs(60);
+///
+/// let mut rings = ScheduledRing::new();
+/// rings.schedule(t0, StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10))));
+/// rings.schedule(t1, StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(10))));
+///
+/// assert_eq!(rings.ring_at(t0).map(|r| r.nodes().len()), Some(3));
+/// assert_eq!(rings.ring_at(t1 - Duration::from_secs(1)).map(|r| r.nodes().len()), Some(3));
+/// assert_eq!(rings.ring_at(t1).map(|r| r.nodes().len()), Some(4));
+/// assert!(rings.ring_at(t0 - Duration::from_secs(1)).is_none());
+/// ```
+#[derive(Debug)]
+pub struct ScheduledRing<K, V, H> {
+    versions: Vec<(SystemTime, StaticHashRing<K, V, H>)>,
+}
+impl<K, V, H> ScheduledRing<K, V, H> {
+    /// Makes a new `ScheduledRing` instance which has no versions.
+    pub fn new() -> Self {
+        ScheduledRing { versions: Vec::new() }
+    }
+
+    /// Schedules `ring` to become active at `activation`.
+    ///
+    /// If there is already a version which has the same activation time,
+    /// it is replaced with `ring` and returned.
+    pub fn schedule(&mut self,
+                    activation: SystemTime,
+                    ring: StaticHashRing<K, V, H>)
+                    -> Option<StaticHashRing<K, V, H>> {
+        match self.versions.binary_search_by_key(&activation, |v| v.0) {
+            Ok(i) => Some(mem::replace(&mut self.versions[i].1, ring)),
+            Err(i) => {
+                self.versions.insert(i, (activation, ring));
+                None
+            }
+        }
+    }
+
+    /// Returns the ring which is active at `time`.
+    ///
+    /// It is the version which has the latest activation time not later than `time`.
+    /// If no versions are active at `time`, this returns `None`.
+    pub fn ring_at(&self, time: SystemTime) -> Option<&StaticHashRing<K, V, H>> {
+        self.active_index(time).map(|i| &self.versions[i].1)
+    }
+
+    /// Returns the activation time of the version which is active at `time`.
+    pub fn activation_at(&self, time: SystemTime) -> Option<SystemTime> {
+        self.active_index(time).map(|i| self.versions[i].0)
+    }
+
+    /// Removes the versions which were superseded before `time`.
+    ///
+    /// The version active at `time` and the subsequent versions are retained.
+    /// Returns the count of the removed versions.
+    pub fn prune(&mut self, time: SystemTime) -> usize {
+        let active = self.active_index(time).unwrap_or(0);
+        self.versions.drain(..active).count()
+    }
+
+    /// Returns an iterator over the versions ordered by their activation times.
+    pub fn versions(&self) -> impl Iterator<Item = (SystemTime, &StaticHashRing<K, V, H>)> {
+        self.versions.iter().map(|v| (v.0, &v.1))
+    }
+
+    /// Returns the count of the versions.
+    pub fn len(&self) -> usize {
+        self.versions.len()
+    }
+
+    /// Returns `true` if this has no versions.
+    pub fn is_empty(&self) -> bool {
+        self.versions.is_empty()
+    }
+
+    /// Returns the ring which is active at the current time of `clock`.
+    pub fn ring_now<C: Clock>(&self, clock: &C) -> Option<&StaticHashRing<K, V, H>> {
+        self.ring_at(clock.now())
+    }
+
+    fn active_index(&self, time: SystemTime) -> Option<usize> {
+        let n = self.versions.partition_point(|v| v.0 <= time);
+        n.checked_sub(1)
+    }
+}
+impl<K, V, H> ScheduledRing<K, V, H>
+    where H: RingHash
+{
+    /// Returns the candidate nodes for `item` in the ring which is active at `time`.
+    ///
+    /// If no versions are active at `time`, this returns `None`.
+    pub fn calc_candidates_at<T: Hash>(&self, item: &T, time: SystemTime) -> Option<Candidates<'_, K, V>> {
+        self.ring_at(time).map(|r| r.calc_candidates(item))
+    }
+
+    /// Returns the candidate nodes for `item` in the ring which is active at the current time of `clock`.
+    pub fn calc_candidates_now<T: Hash, C: Clock>(&self, item: &T, clock: &C) -> Option<Candidates<'_, K, V>> {
+        self.calc_candidates_at(item, clock.now())
+    }
+}
+impl<K, V, H> Default for ScheduledRing<K, V, H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+    use {DefaultHash, MockClock, Node, StaticHashRing};
+    use super::*;
+
+    fn ring(nodes: usize) -> StaticHashRing<usize, (), DefaultHash> {
+        StaticHashRing::new(DefaultHash, (0..nodes).map(|i| Node::new(i).quantity(10)))
+    }
+
+    #[test]
+    fn scheduled_ring_works() {
+        let t = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+        let mut rings = ScheduledRing::new();
+        assert!(rings.is_empty());
+        assert!(rings.schedule(t(20), ring(2)).is_none());
+        assert!(rings.schedule(t(10), ring(1)).is_none());
+        assert!(rings.schedule(t(30), ring(3)).is_none());
+        assert_eq!(rings.schedule(t(30), ring(4)).map(|r| r.nodes().len()), Some(3));
+        assert_eq!(rings.versions().map(|(a, _)| a).collect::<Vec<_>>(), [t(10), t(20), t(30)]);
+
+        assert_eq!(rings.activation_at(t(9)), None);
+        assert_eq!(rings.activation_at(t(25)), Some(t(20)));
+        assert_eq!(rings.ring_at(t(100)).map(|r| r.nodes().len()), Some(4));
+        assert!(rings.calc_candidates_at(&"foo", t(15)).unwrap().eq(ring(1).calc_candidates(&"foo")));
+
+        let clock = MockClock::new(t(15));
+        assert_eq!(rings.ring_now(&clock).map(|r| r.nodes().len()), Some(1));
+        clock.advance(Duration::from_secs(5));
+        assert!(rings.calc_candidates_now(&"foo", &clock).unwrap().eq(ring(2).calc_candidates(&"foo")));
+
+        assert_eq!(rings.prune(t(5)), 0);
+        assert_eq!(rings.prune(t(25)), 1);
+        assert_eq!(rings.len(), 2);
+        assert_eq!(rings.activation_at(t(15)), None);
+    }
+}
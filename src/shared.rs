@@ -0,0 +1,267 @@
+use std::error::Error;
+use std::fmt;
+use std::hash::Hash;
+use std::mem;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use {RingHash, StaticHashRing};
+
+/// A ring which can be replaced while other threads are looking it up.
+///
+/// A writer installs a newly built ring by `store` (e.g., on a topology change),
+/// and readers look up the current ring via `SharedRingReader` handles.
+/// A reader keeps the ring it loaded last, and it only touches the lock
+/// when a newer ring has been installed, so lookups on an unchanged ring are lock-free.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use std::thread;
+/// use consistent_hash::{Node, StaticHashRing, DefaultHash, SharedRing};
+///
+/// let shared = Arc::new(SharedRing::new(StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)))));
+///
+/// let s = shared.clone();
+/// let handle = thread::spawn(move || {
+///     let mut reader = s.reader();
+///     reader.get().calc_node(&"foo").map(|n| n.key)
+/// });
+/// assert!(handle.join().unwrap().is_some());
+///
+/// shared.store(StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(10))));
+/// assert_eq!(shared.reader().get().nodes().len(), 4);
+/// assert_eq!(shared.version(), 1);
+/// ```
+#[derive(Debug)]
+pub struct SharedRing<K, V, H> {
+    current: RwLock<Arc<StaticHashRing<K, V, H>>>,
+    version: AtomicU64,
+}
+impl<K, V, H> SharedRing<K, V, H> {
+    /// Makes a new `SharedRing` instance which initially holds `ring`.
+    pub fn new(ring: StaticHashRing<K, V, H>) -> Self {
+        SharedRing {
+            current: RwLock::new(Arc::new(ring)),
+            version: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the current ring.
+    pub fn load(&self) -> Arc<StaticHashRing<K, V, H>> {
+        self.read().clone()
+    }
+
+    /// Installs `ring` as the current ring, and returns the previous one.
+    ///
+    /// Readers which are using the previous ring keep it until they look up the ring again.
+    pub fn store(&self, ring: StaticHashRing<K, V, H>) -> Arc<StaticHashRing<K, V, H>> {
+        let mut current = self.write();
+        let previous = mem::replace(&mut *current, Arc::new(ring));
+        self.version.fetch_add(1, Ordering::Release);
+        previous
+    }
+
+    /// Returns the number of the rings installed by `store` so far.
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::Acquire)
+    }
+
+    /// Makes a new reader handle of this ring.
+    ///
+    /// A handle is intended to be owned by a thread (e.g., a worker of a server).
+    pub fn reader(&self) -> SharedRingReader<'_, K, V, H> {
+        let current = self.read();
+        SharedRingReader {
+            shared: self,
+            version: self.version.load(Ordering::Acquire),
+            ring: current.clone(),
+        }
+    }
+
+    /// Replaces the value of the node `key` with `value` if no rings have been installed since `expected_version`.
+    ///
+    /// This is a minimal optimistic-concurrency primitive for the metadata of nodes:
+    /// a controller reads the version, decides the new value, and then tries to set it,
+    /// so concurrent controllers never clobber each other's updates.
+    /// On success, a copy of the current ring which has the new value is installed (see `store`),
+    /// and the new version is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash, SharedRing, SetValueError};
+    ///
+    /// let shared = SharedRing::new(StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).value("up"))));
+    /// let version = shared.version();
+    ///
+    /// assert_eq!(shared.compare_and_set_value(&1, version, "down"), Ok(version + 1));
+    /// assert_eq!(shared.load().nodes()[1].value, "down");
+    ///
+    /// // Another controller which read the old version fails.
+    /// assert_eq!(shared.compare_and_set_value(&1, version, "up"),
+    ///            Err(SetValueError::Conflict { version: version + 1 }));
+    /// assert_eq!(shared.compare_and_set_value(&3, version + 1, "up"), Err(SetValueError::NotFound));
+    /// ```
+    pub fn compare_and_set_value(&self, key: &K, expected_version: u64, value: V) -> Result<u64, SetValueError>
+        where K: Hash + Eq + Ord + Clone,
+              V: Clone,
+              H: RingHash + Clone
+    {
+        let mut current = self.write();
+        let version = self.version.load(Ordering::Acquire);
+        if version != expected_version {
+            return Err(SetValueError::Conflict { version });
+        }
+        let mut ring = (**current).clone();
+        match ring.node_value_mut(key) {
+            None => return Err(SetValueError::NotFound),
+            Some(v) => *v = value,
+        }
+        *current = Arc::new(ring);
+        self.version.fetch_add(1, Ordering::Release);
+        Ok(version + 1)
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Arc<StaticHashRing<K, V, H>>> {
+        self.current.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Arc<StaticHashRing<K, V, H>>> {
+        self.current.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// The error which is returned by `SharedRing::compare_and_set_value`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SetValueError {
+    /// Another ring has been installed since the expected version.
+    Conflict {
+        /// The current version.
+        version: u64,
+    },
+
+    /// The ring does not contain the node.
+    NotFound,
+}
+impl fmt::Display for SetValueError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SetValueError::Conflict { version } => write!(f, "Ring version conflict: current version is {}", version),
+            SetValueError::NotFound => write!(f, "No such node"),
+        }
+    }
+}
+impl Error for SetValueError {}
+
+/// A reader handle of `SharedRing`.
+///
+/// This is created by calling `SharedRing::reader` method.
+#[derive(Debug)]
+pub struct SharedRingReader<'a, K: 'a, V: 'a, H: 'a> {
+    shared: &'a SharedRing<K, V, H>,
+    version: u64,
+    ring: Arc<StaticHashRing<K, V, H>>,
+}
+impl<'a, K: 'a, V: 'a, H: 'a> SharedRingReader<'a, K, V, H> {
+    /// Returns the current ring.
+    ///
+    /// If no new ring has been installed since the last call, this does not acquire any locks.
+    pub fn get(&mut self) -> &StaticHashRing<K, V, H> {
+        if self.shared.version.load(Ordering::Acquire) != self.version {
+            // The version is only changed while the write lock is held.
+            let current = self.shared.read();
+            self.version = self.shared.version.load(Ordering::Acquire);
+            self.ring = current.clone();
+        }
+        &self.ring
+    }
+
+    /// Returns the version of the ring returned by the last `get` call.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+    use {DefaultHash, Node, StaticHashRing};
+    use super::*;
+
+    #[test]
+    fn shared_ring_works() {
+        let ring = |n| StaticHashRing::new(DefaultHash, (0..n).map(|i| Node::new(i).quantity(10)));
+        let shared = Arc::new(SharedRing::new(ring(1)));
+
+        let mut reader = shared.reader();
+        assert_eq!((reader.get().nodes().len(), reader.version()), (1, 0));
+        let previous = shared.store(ring(2));
+        assert_eq!(previous.nodes().len(), 1);
+        assert_eq!((reader.get().nodes().len(), reader.version()), (2, 1));
+        assert_eq!(shared.load().nodes().len(), 2);
+
+        let handles = (0..4)
+            .map(|_| {
+                let shared = shared.clone();
+                thread::spawn(move || {
+                    let mut reader = shared.reader();
+                    let mut last = 0;
+                    for _ in 0..1000 {
+                        let len = reader.get().nodes().len();
+                        assert!(last <= len);
+                        assert!(reader.get().calc_node(&"foo").is_some());
+                        last = len;
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for n in 3..10 {
+            shared.store(ring(n));
+        }
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(shared.version(), 8);
+        assert_eq!(shared.reader().get().nodes().len(), 9);
+    }
+
+    #[test]
+    fn compare_and_set_value_works() {
+        let mut ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).value(0).quantity(10)));
+        ring.take(&"foo");
+        let shared = Arc::new(SharedRing::new(ring));
+        let mut reader = shared.reader();
+        let before = reader.get().clone();
+
+        // Controllers increment the values concurrently.
+        let handles = (0..4)
+            .map(|_| {
+                let shared = shared.clone();
+                thread::spawn(move || {
+                    for _ in 0..100 {
+                        loop {
+                            let version = shared.version();
+                            let value = shared.load().nodes()[1].value;
+                            match shared.compare_and_set_value(&1, version, value + 1) {
+                                Ok(_) => break,
+                                Err(SetValueError::Conflict { .. }) => {}
+                                Err(e) => panic!("{}", e),
+                            }
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(shared.version(), 400);
+        let ring = reader.get();
+        assert_eq!(ring.nodes().iter().map(|n| n.value).collect::<Vec<_>>(), [0, 400, 0]);
+        assert!(ring.topology_eq(&before));
+        assert_eq!(ring.generation(), before.generation());
+    }
+}
@@ -0,0 +1,185 @@
+use std::hash::Hash;
+use std::ops::Range;
+
+use {Node, RingHash, StaticHashRing};
+
+/// The default number of slots of `SlotRing` (the same as Redis Cluster).
+pub const DEFAULT_SLOTS: usize = 16_384;
+
+/// A ring which maps a fixed number of slots (i.e., partitions) to nodes.
+///
+/// An item belongs to a slot derived from its hash code, and each slot is assigned to
+/// the primary node of the slot in the inner ring.
+/// So, rebalancing moves whole slots rather than individual items,
+/// and the slot table can be exported to systems which route by partitions (e.g., Redis Cluster style clients).
+///
+/// # Examples
+///
+/// ```
+/// use consistent_hash::{Node, StaticHashRing, DefaultHash, SlotRing};
+///
+/// let ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(50)));
+/// let slots = SlotRing::new(ring);
+/// assert_eq!(slots.slots(), 16_384);
+///
+/// let slot = slots.slot_of(&"foo");
+/// assert!(slot < 16_384);
+/// assert_eq!(slots.calc_node(&"foo"), slots.node_of_slot(slot));
+///
+/// let ranges = slots.slot_ranges();
+/// assert_eq!(ranges.first().map(|r| r.0.start), Some(0));
+/// assert_eq!(ranges.last().map(|r| r.0.end), Some(16_384));
+/// ```
+#[derive(Debug, Clone)]
+pub struct SlotRing<K, V, H> {
+    ring: StaticHashRing<K, V, H>,
+
+    // The indices of the nodes assigned to the slots.
+    table: Vec<Option<usize>>,
+}
+impl<K, V, H> SlotRing<K, V, H>
+    where K: Hash + Eq + Ord,
+          H: RingHash
+{
+    /// Makes a new `SlotRing` instance which has `DEFAULT_SLOTS` slots.
+    pub fn new(ring: StaticHashRing<K, V, H>) -> Self {
+        Self::with_slots(ring, DEFAULT_SLOTS)
+    }
+
+    /// Makes a new `SlotRing` instance which has `slots` slots.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `slots` is `0`.
+    pub fn with_slots(ring: StaticHashRing<K, V, H>, slots: usize) -> Self {
+        assert!(slots > 0, "The number of slots must be positive");
+        let table = (0..slots)
+            .map(|slot| ring.calc_candidate_indices(&(slot as u64)).next())
+            .collect();
+        SlotRing { ring, table }
+    }
+
+    /// Returns the slot which `item` belongs to.
+    pub fn slot_of<T: Hash>(&self, item: &T) -> usize {
+        (self.ring.hash.hash_item(item) % self.table.len() as u64) as usize
+    }
+
+    /// Returns the node which `item` is assigned to (via its slot).
+    ///
+    /// If the inner ring is empty, this returns `None`.
+    pub fn calc_node<T: Hash>(&self, item: &T) -> Option<&Node<K, V>> {
+        self.node_of_slot(self.slot_of(item))
+    }
+
+    /// Returns the slots assigned to the node `key` (in ascending order).
+    pub fn slots_of(&self, key: &K) -> Vec<usize> {
+        match self.ring.node_index(key) {
+            None => Vec::new(),
+            Some(index) => (0..self.table.len()).filter(|&slot| self.table[slot] == Some(index)).collect(),
+        }
+    }
+
+    /// Returns the slots whose nodes differ between this and `other` (in ascending order).
+    ///
+    /// Nodes are compared by their keys.
+    /// If the numbers of the slots differ, only the common slots are compared.
+    pub fn moved_slots<H2>(&self, other: &SlotRing<K, V, H2>) -> Vec<usize> {
+        (0..self.table.len().min(other.table.len()))
+            .filter(|&slot| self.node_of_slot(slot).map(|n| &n.key) != other.node_of_slot(slot).map(|n| &n.key))
+            .collect()
+    }
+}
+impl<K, V, H> SlotRing<K, V, H> {
+    /// Returns the number of the slots.
+    pub fn slots(&self) -> usize {
+        self.table.len()
+    }
+
+    /// Returns the node which the slot `slot` is assigned to.
+    ///
+    /// If `slot` is out of range or the inner ring is empty, this returns `None`.
+    pub fn node_of_slot(&self, slot: usize) -> Option<&Node<K, V>> {
+        self.table.get(slot).and_then(|&i| i).map(|i| &self.ring.nodes[i])
+    }
+
+    /// Returns the slot table (i.e., the node of each slot, indexed by slots).
+    pub fn slot_table(&self) -> Vec<Option<&Node<K, V>>> {
+        self.table.iter().map(|&i| i.map(|i| &self.ring.nodes[i])).collect()
+    }
+
+    /// Returns the ranges of the consecutive slots which are assigned to the same nodes (in ascending order).
+    ///
+    /// If the inner ring is empty, this returns an empty list.
+    pub fn slot_ranges(&self) -> Vec<(Range<usize>, &Node<K, V>)> {
+        let mut ranges: Vec<(Range<usize>, usize)> = Vec::new();
+        for (slot, &index) in self.table.iter().enumerate() {
+            let index = match index {
+                None => continue,
+                Some(index) => index,
+            };
+            match ranges.last_mut() {
+                Some(&mut (ref mut r, i)) if i == index && r.end == slot => r.end += 1,
+                _ => ranges.push((slot..slot + 1, index)),
+            }
+        }
+        ranges.into_iter().map(|(r, i)| (r, &self.ring.nodes[i])).collect()
+    }
+
+    /// Returns a reference to the inner ring.
+    pub fn ring(&self) -> &StaticHashRing<K, V, H> {
+        &self.ring
+    }
+
+    /// Unwraps this instance, returning the inner ring.
+    pub fn into_ring(self) -> StaticHashRing<K, V, H> {
+        self.ring
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {DefaultHash, Node, StaticHashRing};
+    use super::*;
+
+    #[test]
+    fn slot_ring_works() {
+        let ring = |n| StaticHashRing::new(DefaultHash, (0..n).map(|i| Node::new(i).quantity(50)));
+        let slots = SlotRing::with_slots(ring(4), 1024);
+        assert_eq!(slots.slots(), 1024);
+        assert_eq!(slots.node_of_slot(1024), None);
+
+        let table = slots.slot_table();
+        let mut counts = [0; 4];
+        for node in &table {
+            counts[node.unwrap().key] += 1;
+        }
+        assert!(counts.iter().all(|&c| c > 128), "{:?}", counts);
+        for (key, &count) in counts.iter().enumerate() {
+            let owned = slots.slots_of(&key);
+            assert_eq!(owned.len(), count);
+            assert!(owned.iter().all(|&s| table[s].map(|n| n.key) == Some(key)));
+        }
+        assert!(slots.slots_of(&4).is_empty());
+
+        let ranges = slots.slot_ranges();
+        assert_eq!(ranges.iter().map(|r| r.0.len()).sum::<usize>(), 1024);
+        for w in ranges.windows(2) {
+            assert_eq!(w[0].0.end, w[1].0.start);
+            assert_ne!(w[0].1, w[1].1);
+        }
+        for item in 0..100 {
+            let slot = slots.slot_of(&item);
+            assert_eq!(slots.calc_node(&item), table[slot]);
+        }
+
+        // Adding a node only moves slots to it.
+        let grown = SlotRing::with_slots(ring(5), 1024);
+        let moved = slots.moved_slots(&grown);
+        assert!(!moved.is_empty());
+        assert_eq!(moved, grown.slots_of(&4));
+
+        let empty = SlotRing::with_slots(ring(0), 16);
+        assert_eq!(empty.calc_node(&"foo"), None);
+        assert!(empty.slot_ranges().is_empty());
+    }
+}
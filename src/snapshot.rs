@@ -0,0 +1,371 @@
+use std::error::Error;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use siphasher::sip::SipHasher13;
+
+use layout::LookupIndex;
+use {FormatMismatch, Node, ReadPolicy, RingHash, StaticHashRing, VirtualNode};
+
+// The first bytes of snapshots.
+const SNAPSHOT_MAGIC: &[u8; 4] = b"CHRS";
+
+/// The version of the binary snapshot format (see `StaticHashRing::to_bytes`).
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// The error which is returned when a binary snapshot cannot be loaded.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SnapshotError {
+    /// The bytes are truncated or malformed.
+    Malformed,
+
+    /// The version of the snapshot format is not supported.
+    Version {
+        /// The version of the snapshot.
+        version: u32,
+    },
+
+    /// The checksum of the snapshot is broken.
+    Checksum,
+
+    /// The snapshot was built with another hasher (or ring algorithm).
+    Format(FormatMismatch),
+
+    /// The key and value of the node at `index` cannot be decoded.
+    Node {
+        /// The index of the node.
+        index: usize,
+    },
+}
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SnapshotError::Malformed => write!(f, "Malformed ring snapshot"),
+            SnapshotError::Version { version } => write!(f, "Unsupported ring snapshot version: {}", version),
+            SnapshotError::Checksum => write!(f, "Ring snapshot checksum mismatch"),
+            SnapshotError::Format(ref e) => write!(f, "{}", e),
+            SnapshotError::Node { index } => write!(f, "Cannot decode the node at {} in ring snapshot", index),
+        }
+    }
+}
+impl Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            SnapshotError::Format(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl<K, V, H> StaticHashRing<K, V, H>
+    where K: Hash + Eq + Ord,
+          H: RingHash
+{
+    /// Serializes the fully built ring into a compact binary snapshot.
+    ///
+    /// A snapshot holds the format version, the format tag (i.e., the hasher identifier),
+    /// the identifier and the generation of the ring, the read policy, the nodes and their replica-only flags,
+    /// and the virtual nodes (including the ones removed by `take` and the like, so `restore` keeps working).
+    /// The key and value of each node are encoded by `encode_node`.
+    /// Exclusions, disabled nodes, scheduled removals, the journal, and the clock are not contained.
+    ///
+    /// Loading a snapshot by `from_bytes` does not hash any virtual nodes,
+    /// so worker processes can load a ring built once by a coordinator cheaply.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let mut ring = StaticHashRing::new(DefaultHash, (0..3u32).map(|i| Node::new(i).value(i * 10).quantity(100)));
+    /// ring.take(&"foo");
+    ///
+    /// let bytes = ring.to_bytes(|key, value| [key.to_le_bytes(), value.to_le_bytes()].concat());
+    /// let loaded = StaticHashRing::from_bytes(DefaultHash, &bytes, |b| {
+    ///     let word = |i: usize| b.get(i * 4..i * 4 + 4).map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]));
+    ///     Some((word(0)?, word(1)?))
+    /// }).unwrap();
+    /// assert!(loaded == ring);
+    /// assert_eq!(loaded.generation(), 1);
+    /// ```
+    pub fn to_bytes<F>(&self, mut encode_node: F) -> Vec<u8>
+        where F: FnMut(&K, &V) -> Vec<u8>
+    {
+        let mut out = Vec::with_capacity(64 + self.ring.len() * 16);
+        out.extend_from_slice(SNAPSHOT_MAGIC);
+        put_u32(&mut out, SNAPSHOT_VERSION);
+        put_bytes(&mut out, self.format_tag().as_bytes());
+        match self.ring_id {
+            None => out.push(0),
+            Some(id) => {
+                out.push(1);
+                put_u64(&mut out, id);
+            }
+        }
+        put_u64(&mut out, self.generation);
+        match self.read_policy {
+            ReadPolicy::Primary => out.push(0),
+            ReadPolicy::Spread { replicas } => {
+                out.push(1);
+                put_u64(&mut out, replicas as u64);
+            }
+        }
+        put_u64(&mut out, self.nodes.len() as u64);
+        for (node, state) in self.nodes.iter().zip(&self.states) {
+            put_u64(&mut out, node.quantity as u64);
+            out.push(state.replica_only as u8);
+            put_bytes(&mut out, &encode_node(&node.key, &node.value));
+        }
+        for vnodes in &[&self.ring, &self.taken] {
+            put_u64(&mut out, vnodes.len() as u64);
+            for vn in vnodes.iter() {
+                put_u64(&mut out, vn.hash);
+                put_u64(&mut out, vn.index as u64);
+                put_u64(&mut out, vn.seq as u64);
+            }
+        }
+        let checksum = checksum(&out);
+        put_u64(&mut out, checksum);
+        out
+    }
+
+    /// Loads a ring from a binary snapshot created by `to_bytes`.
+    ///
+    /// The key and value of each node are decoded by `decode_node`
+    /// (if it returns `None`, this returns `SnapshotError::Node`).
+    /// If the format tag of the snapshot differs from the one of the ring built with `hash`,
+    /// this returns `SnapshotError::Format`.
+    ///
+    /// The checksum only detects accidental corruption, so the virtual nodes are also validated
+    /// (e.g., the sequence numbers are less than the quantities and no virtual node is lost or duplicated).
+    /// If the validation fails, this returns `SnapshotError::Malformed`.
+    pub fn from_bytes<F>(hash: H, bytes: &[u8], mut decode_node: F) -> Result<Self, SnapshotError>
+        where F: FnMut(&[u8]) -> Option<(K, V)>
+    {
+        if bytes.len() < SNAPSHOT_MAGIC.len() + 4 + 8 || &bytes[..SNAPSHOT_MAGIC.len()] != SNAPSHOT_MAGIC {
+            return Err(SnapshotError::Malformed);
+        }
+        let mut reader = Reader(&bytes[SNAPSHOT_MAGIC.len()..bytes.len() - 8]);
+        let version = reader.u32()?;
+        if version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::Version { version });
+        }
+        let (body, tail) = bytes.split_at(bytes.len() - 8);
+        if checksum(body) != Reader(tail).u64()? {
+            return Err(SnapshotError::Checksum);
+        }
+
+        let tag = ::std::str::from_utf8(reader.bytes()?).map_err(|_| SnapshotError::Malformed)?;
+        let mut ring = StaticHashRing::unbuilt(hash, Vec::new());
+        ring.check_format_tag(tag).map_err(SnapshotError::Format)?;
+        ring.ring_id = match reader.u8()? {
+            0 => None,
+            1 => Some(reader.u64()?),
+            _ => return Err(SnapshotError::Malformed),
+        };
+        ring.generation = reader.u64()?;
+        ring.read_policy = match reader.u8()? {
+            0 => ReadPolicy::Primary,
+            1 => ReadPolicy::Spread { replicas: reader.usize()? },
+            _ => return Err(SnapshotError::Malformed),
+        };
+
+        let node_count = reader.usize()?;
+        for index in 0..node_count {
+            let quantity = reader.usize()?;
+            let replica_only = match reader.u8()? {
+                0 => false,
+                1 => true,
+                _ => return Err(SnapshotError::Malformed),
+            };
+            let (key, value) = decode_node(reader.bytes()?).ok_or(SnapshotError::Node { index })?;
+            if ring.nodes.last().is_some_and(|n| n.key >= key) {
+                return Err(SnapshotError::Malformed);
+            }
+            ring.nodes.push(Node::new(key).value(value).quantity(quantity));
+            ring.states.push(Default::default());
+            ring.states[index].replica_only = replica_only;
+        }
+
+        ring.vnode_counts = vec![0; node_count];
+        for taken in &[false, true] {
+            let count = reader.usize()?;
+            let mut vnodes = Vec::with_capacity(count.min(reader.0.len() / 24));
+            for _ in 0..count {
+                let hash = reader.u64()?;
+                let index = reader.usize()?;
+                let seq = reader.usize()?;
+                if index >= node_count {
+                    return Err(SnapshotError::Malformed);
+                }
+                vnodes.push(VirtualNode { hash, index, seq });
+            }
+            if *taken {
+                ring.taken = vnodes;
+            } else {
+                for vn in &vnodes {
+                    ring.vnode_counts[vn.index] += 1;
+                }
+                ring.lookup_index = LookupIndex::new(vnodes.iter().map(|vn| vn.hash).collect());
+                ring.ring = vnodes;
+            }
+        }
+        if !reader.0.is_empty() || ring.check_vnodes().is_err() {
+            return Err(SnapshotError::Malformed);
+        }
+        ring.live_nodes = ring.vnode_counts.iter().filter(|&&c| c > 0).count();
+        Ok(ring)
+    }
+}
+
+fn checksum(bytes: &[u8]) -> u64 {
+    let mut hasher = SipHasher13::new();
+    bytes.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn put_u32(out: &mut Vec<u8>, n: u32) {
+    out.extend_from_slice(&n.to_le_bytes());
+}
+
+fn put_u64(out: &mut Vec<u8>, n: u64) {
+    out.extend_from_slice(&n.to_le_bytes());
+}
+
+fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    put_u64(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+// A cursor over the body of a snapshot.
+struct Reader<'a>(&'a [u8]);
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], SnapshotError> {
+        if self.0.len() < n {
+            return Err(SnapshotError::Malformed);
+        }
+        let (head, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, SnapshotError> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, SnapshotError> {
+        let mut buf = [0; 4];
+        buf.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    fn u64(&mut self) -> Result<u64, SnapshotError> {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    fn usize(&mut self) -> Result<usize, SnapshotError> {
+        let n = self.u64()?;
+        if n > usize::MAX as u64 {
+            return Err(SnapshotError::Malformed);
+        }
+        Ok(n as usize)
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], SnapshotError> {
+        let len = self.usize()?;
+        self.take(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {DefaultHash, KetamaHash, Node, ReadPolicy, StaticHashRing};
+    use super::*;
+
+    fn encode(key: &String, _: &()) -> Vec<u8> {
+        key.as_bytes().to_vec()
+    }
+
+    fn decode(bytes: &[u8]) -> Option<(String, ())> {
+        String::from_utf8(bytes.to_vec()).ok().map(|k| (k, ()))
+    }
+
+    #[test]
+    fn snapshot_works() {
+        let nodes = (0..10).map(|i| Node::new(format!("node{}", i)).quantity(i * 10));
+        let mut ring = StaticHashRing::new(DefaultHash, nodes);
+        ring.set_replica_only(&"node3".to_owned(), true);
+        ring.set_read_policy(ReadPolicy::Spread { replicas: 2 });
+        ring.set_ring_id(42);
+        while ring.take_if(&"foo", |c| c.node.key == "node1").is_some() {}
+        ring.take(&"bar");
+
+        let bytes = ring.to_bytes(encode);
+        let mut loaded = StaticHashRing::from_bytes(DefaultHash, &bytes, decode).unwrap();
+        assert!(loaded == ring);
+        assert_eq!((loaded.ring_id(), loaded.generation()), (42, ring.generation()));
+        assert_eq!((loaded.len(), loaded.live_nodes()), (ring.len(), ring.live_nodes()));
+        assert_eq!(loaded.fingerprint(), ring.fingerprint());
+        assert_eq!(loaded.clone().take(&"baz").map(|t| (t.hash, t.remaining)),
+                   ring.clone().take(&"baz").map(|t| (t.hash, t.remaining)));
+        assert_eq!(loaded.restore(&"node1".to_owned()), 10);
+
+        for i in 0..bytes.len() {
+            let mut broken = bytes.clone();
+            broken[i] ^= 1;
+            assert!(StaticHashRing::from_bytes(DefaultHash, &broken, decode).is_err());
+        }
+        assert_eq!(StaticHashRing::from_bytes(DefaultHash, &bytes[..bytes.len() - 1], decode).err(),
+                   Some(SnapshotError::Checksum));
+        assert_eq!(StaticHashRing::from_bytes(DefaultHash, &bytes[..8], decode).err(),
+                   Some(SnapshotError::Malformed));
+        assert_eq!(StaticHashRing::from_bytes(DefaultHash, &bytes, |_| None::<(String, ())>).err(),
+                   Some(SnapshotError::Node { index: 0 }));
+        match StaticHashRing::from_bytes(KetamaHash, &bytes, decode) {
+            Err(SnapshotError::Format(e)) => assert_eq!(e.actual, "ring-v1-siphash13"),
+            other => panic!("{:?}", other.map(|r| r.len())),
+        }
+    }
+
+    #[test]
+    fn snapshot_with_recomputed_checksum_is_validated() {
+        let nodes = (0..3).map(|i| Node::new(format!("node{}", i)).quantity(4));
+        let mut ring = StaticHashRing::new(DefaultHash, nodes);
+        let taken_hash = ring.take(&"foo").unwrap().hash;
+        let bytes = ring.to_bytes(encode);
+
+        // The last virtual node in the snapshot is the taken one, and the one before it is the last of the ring.
+        let end = bytes.len() - 8;
+        let (taken_at, last_at) = (end - 24, end - 24 - 8 - 24);
+        let load = |mut body: Vec<u8>| {
+            let checksum = checksum(&body);
+            put_u64(&mut body, checksum);
+            StaticHashRing::from_bytes(DefaultHash, &body, decode).err()
+        };
+        let with_u64 = |at: usize, n: u64| {
+            let mut body = bytes[..end].to_vec();
+            body[at..at + 8].copy_from_slice(&n.to_le_bytes());
+            body
+        };
+        assert_eq!(Reader(&bytes[taken_at..]).u64(), Ok(taken_hash));
+        assert_eq!(load(bytes[..end].to_vec()), None);
+
+        // A sequence number which is out of the quantity.
+        assert_eq!(load(with_u64(taken_at + 16, 4)), Some(SnapshotError::Malformed));
+
+        // A virtual node which appears twice.
+        let last = ring.vnodes().last().unwrap();
+        let mut body = with_u64(taken_at + 8, ring.node_index(&last.1.key).unwrap() as u64);
+        body[taken_at + 16..taken_at + 24].copy_from_slice(&(last.2 as u64).to_le_bytes());
+        assert_eq!(load(body), Some(SnapshotError::Malformed));
+
+        // A virtual node which is lost.
+        let mut body = with_u64(taken_at - 8, 0);
+        body.truncate(taken_at);
+        assert_eq!(load(body), Some(SnapshotError::Malformed));
+
+        // Virtual nodes which are not sorted.
+        assert_eq!(load(with_u64(last_at, 0)), Some(SnapshotError::Malformed));
+    }
+}
@@ -0,0 +1,110 @@
+use std::hash::Hash;
+
+use {FormatMismatch, Node, RingHash, StaticHashRing};
+
+/// A plain-data representation of the topology of a `StaticHashRing`.
+///
+/// A spec consists of the format tag and the identifier of the ring, the nodes, and the keys of the replica-only nodes,
+/// so a ring can be shipped to other processes (encoded by any serialization framework)
+/// and reconstructed identically on the other side by `StaticHashRing::from_spec`.
+///
+/// Virtual nodes removed by `take` (and the like) are not contained
+/// (use `TakeJournal` for recovering them).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RingSpec<K, V> {
+    /// The format tag of the ring (see `StaticHashRing::format_tag`).
+    pub format_tag: String,
+
+    /// The identifier of the ring (see `StaticHashRing::ring_id`).
+    pub ring_id: u64,
+
+    /// The nodes of the ring (sorted by their keys).
+    pub nodes: Vec<Node<K, V>>,
+
+    /// The keys of the replica-only nodes (sorted).
+    pub replica_only: Vec<K>,
+}
+
+impl<K, V, H> StaticHashRing<K, V, H>
+    where K: Hash + Eq + Ord + Clone,
+          V: Clone,
+          H: RingHash
+{
+    /// Returns the spec of this ring.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let mut ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+    /// ring.set_replica_only(&2, true);
+    ///
+    /// let spec = ring.spec();
+    /// let other = StaticHashRing::from_spec(DefaultHash, spec).unwrap();
+    /// assert_eq!(other.fingerprint(), ring.fingerprint());
+    /// ```
+    pub fn spec(&self) -> RingSpec<K, V> {
+        RingSpec {
+            format_tag: self.format_tag(),
+            ring_id: self.ring_id(),
+            nodes: self.nodes.clone(),
+            replica_only: self.nodes
+                .iter()
+                .zip(&self.states)
+                .filter(|&(_, s)| s.replica_only)
+                .map(|(n, _)| n.key.clone())
+                .collect(),
+        }
+    }
+}
+impl<K, V, H> StaticHashRing<K, V, H>
+    where K: Hash + Eq + Ord,
+          H: RingHash
+{
+    /// Makes a new `StaticHashRing` instance from `spec`.
+    ///
+    /// If the format tag of `spec` differs from the one of the ring built with `hash`, this returns an error.
+    pub fn from_spec(hash: H, spec: RingSpec<K, V>) -> Result<Self, FormatMismatch> {
+        let mut ring = StaticHashRing::new(hash, spec.nodes.into_iter());
+        ring.check_format_tag(&spec.format_tag)?;
+        ring.set_ring_id(spec.ring_id);
+        for key in &spec.replica_only {
+            ring.set_replica_only(key, true);
+        }
+        Ok(ring)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hash::Hash;
+    use {DefaultHash, Node, RingHash, StaticHashRing};
+
+    #[test]
+    fn ring_spec_works() {
+        let mut ring = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).value(i * 10).quantity(5)));
+        ring.set_replica_only(&1, true);
+        ring.set_replica_only(&3, true);
+        let spec = ring.spec();
+        assert_eq!(spec.format_tag, "ring-v1-siphash13");
+        assert_eq!(spec.nodes, ring.nodes());
+        assert_eq!(spec.replica_only, [1, 3]);
+
+        let other = StaticHashRing::from_spec(DefaultHash, spec.clone()).unwrap();
+        assert_eq!(other.fingerprint(), ring.fingerprint());
+        for i in 0..100 {
+            assert_eq!(other.calc_candidates(&i).collect::<Vec<_>>(),
+                       ring.calc_candidates(&i).collect::<Vec<_>>());
+        }
+
+        struct OtherHash;
+        impl RingHash for OtherHash {
+            fn hash_item<T: Hash>(&self, item: &T) -> u64 {
+                DefaultHash.hash_item(item)
+            }
+        }
+        let error = StaticHashRing::from_spec(OtherHash, spec).err().unwrap();
+        assert_eq!(error.actual, "ring-v1-siphash13");
+    }
+}
@@ -0,0 +1,277 @@
+use std::hash::Hash;
+
+use {Candidates, Node, RingHash, StaticHashRing};
+
+/// The result of `StaticHashRing::take_spread` method.
+#[derive(Debug)]
+pub struct SpreadTake<'a, K: 'a, V: 'a, T> {
+    /// The items which claimed virtual nodes, paired with the owners of the claimed nodes
+    /// (in the order of the given items).
+    pub assigned: Vec<(T, &'a Node<K, V>)>,
+
+    /// The items which could not claim any virtual nodes (in the order of the given items).
+    pub unclaimed: Vec<T>,
+}
+
+impl<K, V, H> StaticHashRing<K, V, H>
+    where H: RingHash
+{
+    /// Claims virtual nodes for a batch of items, so that no node is assigned more than `per_node_cap` items.
+    ///
+    /// The items are processed in order, and each of them is assigned as `take` does,
+    /// except that nodes which already reached the cap in this batch are skipped.
+    /// Items for which all of the candidates reached the cap (or no candidates remain) are left unclaimed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let mut ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+    ///
+    /// let result = ring.take_spread(0..8, 2);
+    /// assert_eq!(result.assigned.len(), 6);
+    /// assert_eq!(result.unclaimed, [6, 7]);
+    /// for key in 0..3 {
+    ///     assert_eq!(result.assigned.iter().filter(|&&(_, n)| n.key == key).count(), 2);
+    /// }
+    /// ```
+    pub fn take_spread<T, I>(&mut self, items: I, per_node_cap: usize) -> SpreadTake<'_, K, V, T>
+        where T: Hash,
+              I: IntoIterator<Item = T>
+    {
+        let mut counts = vec![0; self.nodes.len()];
+        let mut assigned = Vec::new();
+        let mut unclaimed = Vec::new();
+        for item in items {
+            let item_hash = self.hash.hash_item(&item);
+            let started = self.observer.start();
+            let start = self.locate(item_hash);
+            let vnode_index = self.candidate_vnodes(start)
+                .find(|&i| counts[self.ring[i].index] < per_node_cap);
+            if let Some(i) = vnode_index {
+                let node_index = self.ring[i].index;
+                counts[node_index] += 1;
+                self.remove_vnode(i, item_hash, started);
+                assigned.push((item, node_index));
+            } else {
+                unclaimed.push(item);
+            }
+        }
+        let nodes = &self.nodes;
+        SpreadTake {
+            assigned: assigned.into_iter().map(|(item, i)| (item, &nodes[i])).collect(),
+            unclaimed,
+        }
+    }
+}
+
+impl<K, V, H> StaticHashRing<K, V, H>
+    where H: RingHash
+{
+    /// Returns the candidate nodes for `item`, spread across failure domains (e.g., zones or racks).
+    ///
+    /// The failure domain of a node is projected from its value by `zone_of`.
+    /// Candidates are yielded in rounds: each round takes the first remaining candidate (in the order of
+    /// `calc_candidates`) of each zone, so consecutive candidates come from distinct zones when possible.
+    /// Every candidate of `calc_candidates` appears exactly once.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// // The values of nodes are their zones.
+    /// let nodes = (0..6).map(|i| Node::new(i).value(i % 3).quantity(10));
+    /// let ring = StaticHashRing::new(DefaultHash, nodes);
+    ///
+    /// let zones = ring.calc_candidates_spread(&"foo", |zone: &i32| *zone)
+    ///     .map(|n| n.value)
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(zones.len(), 6);
+    /// assert_ne!(zones[0], zones[1]);
+    /// assert_ne!(zones[1], zones[2]);
+    /// assert_ne!(zones[0], zones[2]);
+    /// ```
+    pub fn calc_candidates_spread<T, Z, F>(&self, item: &T, zone_of: F) -> SpreadCandidates<'_, K, V, Z, F>
+        where T: Hash,
+              Z: PartialEq + Clone,
+              F: Fn(&V) -> Z
+    {
+        SpreadCandidates {
+            candidates: self.calc_candidates(item),
+            zone_of,
+            pending: Vec::new(),
+            used: Vec::new(),
+            last: None,
+        }
+    }
+
+    /// Returns the primary node for `item` and the secondary node to which a hedge request is sent.
+    ///
+    /// The secondary is the first candidate whose zone (projected by `zone_of`) differs from the primary's one.
+    /// If there is no such candidate, the second candidate is used instead,
+    /// and if there is only one candidate, the secondary is `None`.
+    /// This is the same as the first two elements of `calc_candidates_spread`.
+    ///
+    /// If this ring is empty, this returns `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// // The values of nodes are their zones.
+    /// let nodes = (0..4).map(|i| Node::new(i).value(if i == 0 { "a" } else { "b" }).quantity(10));
+    /// let ring = StaticHashRing::new(DefaultHash, nodes);
+    ///
+    /// for item in 0..10 {
+    ///     let (primary, secondary) = ring.hedged_pair(&item, |zone: &&str| *zone).unwrap();
+    ///     assert_eq!(primary, ring.calc_node(&item).unwrap());
+    ///     if primary.value == "a" {
+    ///         assert_eq!(secondary.unwrap().value, "b");
+    ///     }
+    /// }
+    /// ```
+    #[allow(clippy::type_complexity)]
+    pub fn hedged_pair<T, Z, F>(&self, item: &T, zone_of: F) -> Option<(&Node<K, V>, Option<&Node<K, V>>)>
+        where T: Hash,
+              Z: PartialEq + Clone,
+              F: Fn(&V) -> Z
+    {
+        let mut candidates = self.calc_candidates_spread(item, zone_of);
+        candidates.next().map(|primary| (primary, candidates.next()))
+    }
+}
+
+/// An iterator which represents a sequence of the candidate nodes spread across zones.
+///
+/// This is created by calling `StaticHashRing::calc_candidates_spread` method.
+pub struct SpreadCandidates<'a, K: 'a, V: 'a, Z, F> {
+    candidates: Candidates<'a, K, V>,
+    zone_of: F,
+
+    // The candidates deferred because their zones were already used in the current round.
+    pending: Vec<(&'a Node<K, V>, Z)>,
+
+    // The zones used in the current round.
+    used: Vec<Z>,
+    last: Option<Z>,
+}
+impl<'a, K: 'a, V: 'a, Z, F> SpreadCandidates<'a, K, V, Z, F>
+    where Z: PartialEq
+{
+    fn yield_node(&mut self, node: &'a Node<K, V>, zone: Z) -> Option<&'a Node<K, V>>
+        where Z: Clone
+    {
+        self.used.push(zone.clone());
+        self.last = Some(zone);
+        Some(node)
+    }
+
+    fn take_pending(&mut self) -> Option<(&'a Node<K, V>, Z)> {
+        let used = &self.used;
+        self.pending
+            .iter()
+            .position(|(_, z)| !used.contains(z))
+            .map(|i| self.pending.remove(i))
+    }
+}
+impl<'a, K: 'a, V: 'a, Z, F> Iterator for SpreadCandidates<'a, K, V, Z, F>
+    where Z: PartialEq + Clone,
+          F: Fn(&V) -> Z
+{
+    type Item = &'a Node<K, V>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some((node, zone)) = self.take_pending() {
+            return self.yield_node(node, zone);
+        }
+        while let Some(node) = self.candidates.next() {
+            let zone = (self.zone_of)(&node.value);
+            if self.used.contains(&zone) {
+                self.pending.push((node, zone));
+            } else {
+                return self.yield_node(node, zone);
+            }
+        }
+        if self.pending.is_empty() {
+            return None;
+        }
+
+        // Starts a new round (avoiding the zone of the last candidate if possible).
+        self.used.clear();
+        self.used.extend(self.last.take());
+        if let Some((node, zone)) = self.take_pending() {
+            self.used.clear();
+            return self.yield_node(node, zone);
+        }
+        self.used.clear();
+        let (node, zone) = self.pending.remove(0);
+        self.yield_node(node, zone)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {DefaultHash, Node, StaticHashRing};
+
+    #[test]
+    fn take_spread_works() {
+        let nodes = (0..4).map(|i| Node::new(i).quantity(3));
+        let mut ring = StaticHashRing::new(DefaultHash, nodes.clone());
+        let mut expected = StaticHashRing::new(DefaultHash, nodes);
+
+        // Without the cap, this is the same as sequential takes.
+        let result = ring.take_spread(0..5, usize::MAX);
+        for &(item, node) in &result.assigned {
+            assert_eq!(expected.take(&item).map(|t| t.node), Some(node));
+        }
+        assert!(result.unclaimed.is_empty());
+        assert_eq!(ring.len(), 7);
+
+        let result = ring.take_spread(5..20, 1);
+        let mut keys = result.assigned.iter().map(|&(_, n)| n.key).collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(keys, [0, 1, 2, 3]);
+        assert_eq!(result.unclaimed.len(), 11);
+        assert_eq!(ring.len(), 3);
+        assert_eq!(ring.generation(), 9);
+    }
+
+    #[test]
+    fn calc_candidates_spread_works() {
+        // Zone 0 has four nodes, and zones 1 and 2 have a node each.
+        let zone = |i: i32| if i < 4 { 0 } else { i - 3 };
+        let ring = StaticHashRing::new(DefaultHash, (0..6).map(|i| Node::new(i).value(zone(i)).quantity(10)));
+        for item in 0..100 {
+            let candidates = ring.calc_candidates(&item).collect::<Vec<_>>();
+            let spread = ring.calc_candidates_spread(&item, |zone: &i32| *zone).collect::<Vec<_>>();
+
+            let mut sorted = spread.iter().map(|n| n.key).collect::<Vec<_>>();
+            sorted.sort();
+            assert_eq!(sorted, [0, 1, 2, 3, 4, 5]);
+
+            let zones = spread.iter().map(|n| n.value).collect::<Vec<_>>();
+            let mut first_round = zones[..3].to_vec();
+            first_round.sort();
+            assert_eq!(first_round, [0, 1, 2]);
+            assert_eq!(zones[3..], [0, 0, 0]);
+
+            // Each zone keeps the order of `calc_candidates`.
+            for z in 0..3 {
+                let expected = candidates.iter().filter(|n| n.value == z).collect::<Vec<_>>();
+                assert!(spread.iter().filter(|n| n.value == z).eq(expected.into_iter()));
+            }
+
+            let (primary, secondary) = ring.hedged_pair(&item, |zone: &i32| *zone).unwrap();
+            assert_eq!((primary, secondary), (spread[0], Some(spread[1])));
+            assert_ne!(primary.value, secondary.unwrap().value);
+        }
+
+        let single = StaticHashRing::new(DefaultHash, (0..1).map(|i| Node::new(i).quantity(10)));
+        assert_eq!(single.hedged_pair(&"foo", |_| ()).map(|(n, s)| (n.key, s)), Some((0, None)));
+        let same_zone = StaticHashRing::new(DefaultHash, (0..2).map(|i| Node::new(i).quantity(10)));
+        let candidates = same_zone.calc_candidates(&"foo").collect::<Vec<_>>();
+        assert_eq!(same_zone.hedged_pair(&"foo", |_| ()), Some((candidates[0], Some(candidates[1]))));
+    }
+}
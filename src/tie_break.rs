@@ -0,0 +1,129 @@
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::hash::Hash;
+use std::iter::Peekable;
+
+use {Candidates, Node, RingHash, StaticHashRing};
+
+impl<K, V, H> StaticHashRing<K, V, H>
+    where H: RingHash
+{
+    /// Returns the candidate nodes for `item`, reordering equally acceptable nodes by `compare`.
+    ///
+    /// `class_of` returns the class of a node (e.g., its zone or priority tier),
+    /// and each run of consecutive candidates (in the order of `calc_candidates`) which have the same class
+    /// is regarded as equally acceptable. Each run is sorted by `compare` (e.g., by load or latency EWMA),
+    /// and the nodes which `compare` regards as equal keep their order.
+    /// Runs are materialized lazily, so only the candidates up to the end of the current run are visited.
+    ///
+    /// `compare` must be a total order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// // Every node is in the same zone, and node `3` is the fastest.
+    /// let ring = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(10).value("zone-a")));
+    /// let latency = |n: &Node<i32, &str>| if n.key == 3 { 1 } else { 10 };
+    ///
+    /// let candidates = ring.calc_candidates_tie_broken(&"foo", |n| n.value, |a, b| latency(a).cmp(&latency(b)))
+    ///     .map(|n| n.key)
+    ///     .collect::<Vec<_>>();
+    /// let others = ring.calc_candidates(&"foo").map(|n| n.key).filter(|&k| k != 3).collect::<Vec<_>>();
+    /// assert_eq!(candidates, [3, others[0], others[1], others[2]]);
+    /// ```
+    pub fn calc_candidates_tie_broken<T, G, F, C>(&self,
+                                                  item: &T,
+                                                  class_of: F,
+                                                  compare: C)
+                                                  -> TieBrokenCandidates<'_, K, V, F, C>
+        where T: Hash + ?Sized,
+              G: PartialEq,
+              F: Fn(&Node<K, V>) -> G,
+              C: FnMut(&Node<K, V>, &Node<K, V>) -> Ordering
+    {
+        TieBrokenCandidates {
+            candidates: self.calc_candidates(item).peekable(),
+            class_of,
+            compare,
+            run: VecDeque::new(),
+        }
+    }
+}
+
+/// An iterator which represents a sequence of the candidate nodes whose equally acceptable nodes are reordered.
+///
+/// This is created by calling `StaticHashRing::calc_candidates_tie_broken` method.
+pub struct TieBrokenCandidates<'a, K: 'a, V: 'a, F, C> {
+    candidates: Peekable<Candidates<'a, K, V>>,
+    class_of: F,
+    compare: C,
+    run: VecDeque<&'a Node<K, V>>,
+}
+impl<'a, K: 'a, V: 'a, G, F, C> Iterator for TieBrokenCandidates<'a, K, V, F, C>
+    where G: PartialEq,
+          F: Fn(&Node<K, V>) -> G,
+          C: FnMut(&Node<K, V>, &Node<K, V>) -> Ordering
+{
+    type Item = &'a Node<K, V>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(node) = self.run.pop_front() {
+            return Some(node);
+        }
+        let first = self.candidates.next()?;
+        let class_of = &self.class_of;
+        let class = class_of(first);
+        self.run.push_back(first);
+        while let Some(node) = self.candidates.next_if(|n| class_of(n) == class) {
+            self.run.push_back(node);
+        }
+        let compare = &mut self.compare;
+        self.run.make_contiguous().sort_by(|a, b| compare(a, b));
+        self.run.pop_front()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.run.len() + self.candidates.len();
+        (n, Some(n))
+    }
+}
+impl<'a, K: 'a, V: 'a, G, F, C> ExactSizeIterator for TieBrokenCandidates<'a, K, V, F, C>
+    where G: PartialEq,
+          F: Fn(&Node<K, V>) -> G,
+          C: FnMut(&Node<K, V>, &Node<K, V>) -> Ordering
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use {DefaultHash, Node, StaticHashRing};
+
+    #[test]
+    fn tie_broken_candidates_works() {
+        let ring = StaticHashRing::new(DefaultHash, (0..12).map(|i| Node::new(i).quantity(10)));
+        let tier = |n: &Node<usize, ()>| n.key % 3;
+        let load = |n: &Node<usize, ()>| n.key / 4;
+        for item in 0..50 {
+            let candidates = ring.calc_candidates(&item).collect::<Vec<_>>();
+            let tie_broken = ring.calc_candidates_tie_broken(&item, tier, |a, b| load(a).cmp(&load(b)))
+                .collect::<Vec<_>>();
+            assert_eq!(tie_broken.len(), candidates.len());
+
+            // Each run of the same tier is sorted by load (keeping the ring order within the same load).
+            let mut i = 0;
+            while i < candidates.len() {
+                let j = (i..candidates.len()).find(|&j| tier(candidates[j]) != tier(candidates[i]))
+                    .unwrap_or(candidates.len());
+                let mut run = candidates[i..j].to_vec();
+                run.sort_by_key(|n| load(n));
+                assert_eq!(tie_broken[i..j], run[..]);
+                i = j;
+            }
+        }
+
+        // An order which regards every node as equal keeps the candidates as they are.
+        let tie_broken = ring.calc_candidates_tie_broken(&"foo", |_| (), |_, _| ::std::cmp::Ordering::Equal);
+        assert_eq!(tie_broken.len(), 12);
+        assert!(tie_broken.eq(ring.calc_candidates(&"foo")));
+    }
+}
@@ -0,0 +1,196 @@
+use std::hash::Hash;
+
+use diff::moved_ranges;
+use range::HashRange;
+use {Node, NodeState, RingHash, StaticHashRing};
+
+/// A change of the topology of a ring, which is applied by `StaticHashRing::apply`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TopologyOp<K, V> {
+    /// Adds the node.
+    ///
+    /// If the ring already contains a node which has the same key,
+    /// the value and the quantity of the node are replaced (its states such as replica-only are kept).
+    Add(Node<K, V>),
+
+    /// Removes the node which has the key.
+    Remove(K),
+
+    /// Changes the quantity of the node which has the key (see `StaticHashRing::set_quantity`).
+    Reweight(K, usize),
+}
+
+/// A range of hash codes whose primary node is changed by `StaticHashRing::apply`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Relocation<K> {
+    /// The range of hash codes (never wraps around).
+    pub range: HashRange,
+
+    /// The key of the node which owned the range (`None` if the ring was empty).
+    pub from: Option<K>,
+
+    /// The key of the node which owns the range (`None` if the ring has become empty).
+    pub to: Option<K>,
+}
+
+impl<K, V, H> StaticHashRing<K, V, H>
+    where K: Hash + Eq + Ord + Clone,
+          V: Clone,
+          H: RingHash
+{
+    /// Applies the topology changes `ops` in order, and returns the ranges whose primary nodes are changed.
+    ///
+    /// Each change only adds (or removes) the virtual nodes of the affected node as `set_quantity` does,
+    /// and the resulting placements are the same as those of a ring built from the resulting nodes.
+    /// The states of the remaining nodes (e.g., exclusions) and the virtual nodes removed by `take`
+    /// and the like are kept. `Remove` and `Reweight` of unknown nodes are ignored.
+    ///
+    /// The relocations cover the net change of the whole batch, ordered by their hash codes
+    /// (adjacent ranges which have the same pair of the old and new owners are merged),
+    /// so they can drive the data movement of a membership change (i.e., the data in each range
+    /// should be moved from `from` to `to`).
+    ///
+    /// Note that adding or removing nodes shifts the indices of the nodes which follow them (see `node_index`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash, TopologyOp};
+    ///
+    /// let mut ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(50)));
+    /// let relocations = ring.apply(&[TopologyOp::Add(Node::new(3).quantity(50)), TopologyOp::Remove(0)]);
+    ///
+    /// assert!(relocations.iter().all(|r| r.from == Some(0) || r.to == Some(3)));
+    /// assert!(ring == StaticHashRing::new(DefaultHash, (1..4).map(|i| Node::new(i).quantity(50))));
+    /// assert!(ring.apply(&[TopologyOp::Reweight(4, 10)]).is_empty());
+    /// ```
+    pub fn apply(&mut self, ops: &[TopologyOp<K, V>]) -> Vec<Relocation<K>> {
+        let befores = self.primary_ranges();
+        let old_keys = self.nodes.iter().map(|n| n.key.clone()).collect::<Vec<_>>();
+        for op in ops {
+            match *op {
+                TopologyOp::Add(ref node) => self.add_node(node.clone()),
+                TopologyOp::Remove(ref key) => self.remove_node(key),
+                TopologyOp::Reweight(ref key, quantity) => {
+                    self.set_quantity(key, quantity);
+                }
+            }
+        }
+
+        let same = |a: usize, b: usize| old_keys[a] == self.nodes[b].key;
+        moved_ranges(&befores, &self.primary_ranges(), same)
+            .into_iter()
+            .map(|(range, from, to)| {
+                Relocation {
+                    range,
+                    from: from.map(|i| old_keys[i].clone()),
+                    to: to.map(|i| self.nodes[i].key.clone()),
+                }
+            })
+            .collect()
+    }
+
+    fn add_node(&mut self, node: Node<K, V>) {
+        let index = match self.nodes.binary_search_by(|n| n.key.cmp(&node.key)) {
+            Ok(index) => {
+                self.nodes[index].value = node.value;
+                self.set_quantity(&node.key, node.quantity);
+                return;
+            }
+            Err(index) => index,
+        };
+        self.shift_indices(index, true);
+        let quantity = node.quantity;
+        self.nodes.insert(index, node.quantity(0));
+        self.states.insert(index, NodeState::default());
+        self.vnode_counts.insert(index, 0);
+        let key = self.nodes[index].key.clone();
+        self.set_quantity(&key, quantity);
+    }
+
+    fn remove_node(&mut self, key: &K) {
+        let index = match self.node_index(key) {
+            None => return,
+            Some(index) => index,
+        };
+        self.set_quantity(key, 0);
+        let state = self.states.remove(index);
+        self.excluded_nodes -= state.excluded_until.is_some() as usize;
+        self.disabled_nodes -= state.disabled as usize;
+        self.removal_nodes -= state.removal_at.is_some() as usize;
+        self.nodes.remove(index);
+        self.vnode_counts.remove(index);
+        self.shift_indices(index, false);
+        self.debug_assert_valid();
+    }
+
+    // Shifts the indices of the nodes at or after `index`, for inserting (or removing) a node at `index`.
+    //
+    // The removed node must have no virtual nodes.
+    fn shift_indices(&mut self, index: usize, inserted: bool) {
+        let shift = |i: &mut usize| if *i >= index {
+            if inserted {
+                *i += 1;
+            } else {
+                *i -= 1;
+            }
+        };
+        for vn in self.ring.iter_mut().chain(&mut self.taken) {
+            shift(&mut vn.index);
+        }
+        #[cfg(feature = "http-admin")]
+        self.history.shift(index, inserted);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use {DefaultHash, Node, StaticHashRing};
+    use super::*;
+
+    #[test]
+    fn apply_works() {
+        let ring_of = |keys: &[usize]| {
+            StaticHashRing::new(DefaultHash, keys.iter().map(|&i| Node::new(i).quantity(20)))
+        };
+        let mut ring = ring_of(&[1, 3, 5]);
+        ring.exclude_for(&5, Duration::from_secs(60));
+        ring.disable_node(&3);
+        let taken = ring.take_if(&"foo", |c| c.node.key == 1).map(|t| t.hash).unwrap();
+
+        let ops = [TopologyOp::Add(Node::new(0).quantity(20)),
+                   TopologyOp::Remove(3),
+                   TopologyOp::Add(Node::new(4).quantity(5)),
+                   TopologyOp::Reweight(4, 20),
+                   TopologyOp::Remove(6)];
+        let old = ring.clone();
+        let relocations = ring.apply(&ops);
+
+        // The relocations are the same as the diff of the rings.
+        let moves = old.diff(&ring)
+            .moves
+            .into_iter()
+            .map(|m| {
+                Relocation {
+                    range: m.range,
+                    from: m.before.map(|n| n.key),
+                    to: m.after.map(|n| n.key),
+                }
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(relocations, moves);
+        assert!(relocations.iter().all(|r| r.from == Some(3) || r.to == Some(0) || r.to == Some(4)));
+
+        assert!(ring.is_excluded(&5));
+        assert!(ring.vnodes().all(|v| v.0 != taken));
+        ring.restore(&1);
+        ring.cancel_exclusion(&5);
+        assert!(ring == ring_of(&[0, 1, 4, 5]));
+
+        let relocations = ring.apply(&[0, 1, 4, 5].iter().map(|&i| TopologyOp::Remove(i)).collect::<Vec<_>>());
+        assert!(ring.is_empty() && ring.nodes().is_empty());
+        assert_eq!(relocations.iter().map(|r| r.range.len()).sum::<u128>(), 1 << 64);
+        assert!(relocations.iter().all(|r| r.to.is_none()));
+    }
+}
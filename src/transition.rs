@@ -0,0 +1,191 @@
+use std::array;
+use std::hash::Hash;
+use std::iter;
+
+use {Candidates, Node, RingHash, StaticHashRing};
+
+/// A pair of rings which is used during a migration window.
+///
+/// During a migration, readers should check both of the old and new owners of a key,
+/// and writers should write to the new owner (or to both of them, see `calc_candidates`).
+///
+/// # Examples
+///
+/// ```
+/// use consistent_hash::{Node, StaticHashRing, DefaultHash, TransitionRing};
+///
+/// let old = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+/// let new = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(10)));
+/// let ring = TransitionRing::new(old, new);
+///
+/// for item in 0..100 {
+///     let owners = ring.owners(&item);
+///     let reads = ring.read_nodes(&item);
+///     assert_eq!(reads[0], owners.new.unwrap());
+///     assert_eq!(reads.len(), if owners.is_moving() { 2 } else { 1 });
+/// }
+/// ```
+#[derive(Debug)]
+pub struct TransitionRing<K, V, H> {
+    old: StaticHashRing<K, V, H>,
+    new: StaticHashRing<K, V, H>,
+}
+impl<K, V, H> TransitionRing<K, V, H> {
+    /// Makes a new `TransitionRing` instance which migrates from `old` to `new`.
+    pub fn new(old: StaticHashRing<K, V, H>, new: StaticHashRing<K, V, H>) -> Self {
+        TransitionRing { old, new }
+    }
+
+    /// Returns the reference to the old ring.
+    pub fn old_ring(&self) -> &StaticHashRing<K, V, H> {
+        &self.old
+    }
+
+    /// Returns the reference to the new ring.
+    pub fn new_ring(&self) -> &StaticHashRing<K, V, H> {
+        &self.new
+    }
+
+    /// Finishes the migration and returns the new ring.
+    pub fn finish(self) -> StaticHashRing<K, V, H> {
+        self.new
+    }
+}
+impl<K, V, H> TransitionRing<K, V, H>
+    where K: Eq,
+          H: RingHash
+{
+    /// Returns the old and new owners (i.e., primary nodes) of `item`.
+    pub fn owners<T: Hash>(&self, item: &T) -> TransitionOwners<'_, K, V> {
+        TransitionOwners {
+            old: self.old.calc_candidates(item).next(),
+            new: self.new.calc_candidates(item).next(),
+        }
+    }
+
+    /// Returns the nodes which readers of `item` should check, in the order of preference.
+    ///
+    /// The new owner comes first, and the old owner follows if it differs from the new one.
+    pub fn read_nodes<T: Hash>(&self, item: &T) -> Vec<&Node<K, V>> {
+        let owners = self.owners(item);
+        let mut nodes = Vec::with_capacity(2);
+        nodes.extend(owners.new);
+        if owners.is_moving() {
+            nodes.extend(owners.old);
+        }
+        nodes
+    }
+
+    /// Returns the candidate nodes for writing `item` (i.e., the candidates in the new ring).
+    pub fn calc_write_candidates<T: Hash>(&self, item: &T) -> Candidates<'_, K, V> {
+        self.new.calc_candidates(item)
+    }
+
+    /// Returns the current (old) owner of `item` followed by its future (new) owner.
+    ///
+    /// The future owner is omitted if it is the same node as the current one,
+    /// so a migrating writer can read from the first node and double-write to all of them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash, MigratingRing};
+    ///
+    /// let old = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+    /// let new = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(10)));
+    /// let ring = MigratingRing::new(old, new);
+    ///
+    /// for item in 0..100 {
+    ///     let nodes = ring.calc_candidates(&item).collect::<Vec<_>>();
+    ///     assert_eq!(nodes[0], ring.old_ring().calc_node(&item).unwrap());
+    ///     assert_eq!(nodes.len(), if ring.moved(&item) { 2 } else { 1 });
+    /// }
+    /// ```
+    pub fn calc_candidates<T: Hash>(&self, item: &T) -> MigrationCandidates<'_, K, V> {
+        let owners = self.owners(item);
+        let new = if owners.is_moving() { owners.new } else { None };
+        MigrationCandidates(IntoIterator::into_iter([owners.old, new]).flatten())
+    }
+
+    /// Returns `true` if the owner of `item` differs between the old and new rings, otherwise `false`.
+    ///
+    /// This is the same as `self.owners(item).is_moving()`.
+    pub fn moved<T: Hash>(&self, item: &T) -> bool {
+        self.owners(item).is_moving()
+    }
+}
+
+/// An alias of `TransitionRing`, which holds the old and new rings of a data migration.
+pub type MigratingRing<K, V, H> = TransitionRing<K, V, H>;
+
+/// An iterator over the current and future owners of an item.
+///
+/// This is created by calling `TransitionRing::calc_candidates` method.
+#[derive(Debug, Clone)]
+pub struct MigrationCandidates<'a, K: 'a, V: 'a>(iter::Flatten<array::IntoIter<Option<&'a Node<K, V>>, 2>>);
+impl<'a, K: 'a, V: 'a> Iterator for MigrationCandidates<'a, K, V> {
+    type Item = &'a Node<K, V>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+/// The old and new owners of an item.
+///
+/// This is returned by `TransitionRing::owners` method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransitionOwners<'a, K: 'a, V: 'a> {
+    /// The owner in the old ring (`None` if the old ring is empty).
+    pub old: Option<&'a Node<K, V>>,
+
+    /// The owner in the new ring (`None` if the new ring is empty).
+    pub new: Option<&'a Node<K, V>>,
+}
+impl<'a, K: 'a, V: 'a> TransitionOwners<'a, K, V>
+    where K: Eq
+{
+    /// Returns `true` if the owner of the item differs between the old and new rings.
+    pub fn is_moving(&self) -> bool {
+        self.old.map(|n| &n.key) != self.new.map(|n| &n.key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {DefaultHash, Node, StaticHashRing};
+    use super::*;
+
+    #[test]
+    fn transition_ring_works() {
+        let old = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+        let new = StaticHashRing::new(DefaultHash, (1..4).map(|i| Node::new(i).quantity(10)));
+        let ring = TransitionRing::new(old, new);
+
+        let mut moving = 0;
+        for item in 0..1000 {
+            let owners = ring.owners(&item);
+            assert_eq!(owners.old, ring.old_ring().calc_candidates(&item).next());
+            assert_eq!(owners.new, ring.new_ring().calc_candidates(&item).next());
+            if owners.old.unwrap().key == 0 {
+                assert!(owners.is_moving());
+            }
+            if owners.is_moving() {
+                moving += 1;
+                assert_eq!(ring.read_nodes(&item), [owners.new.unwrap(), owners.old.unwrap()]);
+            } else {
+                assert_eq!(ring.read_nodes(&item), [owners.new.unwrap()]);
+            }
+            assert!(ring.calc_write_candidates(&item).eq(ring.new_ring().calc_candidates(&item)));
+            assert_eq!(ring.moved(&item), owners.is_moving());
+            assert_eq!(ring.calc_candidates(&item).collect::<Vec<_>>(),
+                       owners.old.into_iter().chain(owners.new.filter(|_| owners.is_moving())).collect::<Vec<_>>());
+        }
+        assert!(0 < moving && moving < 1000);
+
+        let empty = StaticHashRing::new(DefaultHash, (0..0).map(Node::new));
+        let ring = TransitionRing::new(empty, ring.finish());
+        assert!(ring.owners(&"foo").is_moving());
+        assert_eq!(ring.read_nodes(&"foo").len(), 1);
+        assert_eq!(ring.calc_candidates(&"foo").count(), 1);
+    }
+}
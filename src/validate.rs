@@ -0,0 +1,151 @@
+use std::collections::HashSet;
+
+use {StaticHashRing, VirtualNode};
+
+impl<K: Ord, V, H> StaticHashRing<K, V, H> {
+    /// Checks the internal invariants of this ring, and panics if any of them is violated.
+    ///
+    /// The checked invariants are the following:
+    ///
+    /// - The nodes are sorted by their keys (without duplicates).
+    /// - The virtual nodes are sorted by their hash codes (and the keys of their nodes).
+    /// - Every virtual node refers to an existing node and has a sequence number less than its quantity,
+    ///   and no virtual node appears twice (including the ones removed by `take` and the like).
+    /// - The counts of the remaining virtual nodes of each node are equal to the quantities minus the removed ones.
+    /// - The candidates of an item are a permutation of the live nodes.
+    /// - The counters of the live, excluded, disabled, and scheduled-for-removal nodes are up to date.
+    ///
+    /// This takes time linear to the count of the virtual nodes (plus a sort),
+    /// and this does nothing unless debug assertions are enabled.
+    /// So, this is intended for tests (including downstream ones) which exercise mutations of rings.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let mut ring = StaticHashRing::new(DefaultHash, (0..3).map(|i| Node::new(i).quantity(10)));
+    /// ring.take(&"foo");
+    /// ring.debug_assert_valid();
+    /// ```
+    pub fn debug_assert_valid(&self) {
+        if cfg!(debug_assertions) {
+            self.assert_valid();
+        }
+    }
+
+    // Checks the invariants of the virtual nodes (including the ones removed by `take` and the like)
+    // which external inputs such as snapshots can break.
+    //
+    // If any of them is violated, this returns the description of the violation.
+    pub(crate) fn check_vnodes(&self) -> Result<(), String> {
+        let nodes = &self.nodes;
+        for vn in self.ring.iter().chain(&self.taken) {
+            if vn.index >= nodes.len() {
+                return Err(format!("virtual node refers to an unknown node: {}", vn.index));
+            }
+        }
+        let sort_key = |vn: &VirtualNode| (vn.hash, &nodes[vn.index].key);
+        if self.ring.windows(2).any(|w| sort_key(&w[0]) > sort_key(&w[1])) {
+            return Err("virtual nodes are not sorted".to_owned());
+        }
+
+        let mut counts = vec![0; nodes.len()];
+        let mut seen = HashSet::new();
+        for vn in self.ring.iter().chain(&self.taken) {
+            if vn.seq >= nodes[vn.index].quantity {
+                return Err(format!("virtual node has an out of range sequence number: {}", vn.seq));
+            }
+            if !seen.insert((vn.index, vn.seq)) {
+                return Err(format!("virtual node appears twice: {:?}", (vn.index, vn.seq)));
+            }
+            counts[vn.index] += 1;
+        }
+        for (i, node) in nodes.iter().enumerate() {
+            if counts[i] != node.quantity {
+                return Err(format!("virtual nodes of node {} are lost", i));
+            }
+        }
+        Ok(())
+    }
+
+    fn assert_valid(&self) {
+        let nodes = &self.nodes;
+        assert!(nodes.windows(2).all(|w| w[0].key < w[1].key), "nodes are not sorted by keys");
+        assert_eq!(self.states.len(), nodes.len(), "states do not match nodes");
+        assert_eq!(self.vnode_counts.len(), nodes.len(), "vnode counts do not match nodes");
+
+        if let Err(e) = self.check_vnodes() {
+            panic!("{}", e);
+        }
+        let hashes = self.lookup_index.hashes();
+        assert!(hashes.len() == self.ring.len() && hashes.iter().zip(&self.ring).all(|(&h, vn)| h == vn.hash),
+                "lookup index does not match virtual nodes");
+        for (i, vn) in self.ring.iter().enumerate() {
+            if i == 0 || self.ring[i - 1].hash != vn.hash {
+                assert_eq!(self.lookup_index.locate(vn.hash), i, "lookup index is broken");
+            }
+        }
+
+        let mut counts = vec![0; nodes.len()];
+        for vn in &self.ring {
+            counts[vn.index] += 1;
+        }
+        assert_eq!(counts, self.vnode_counts, "vnode counts are out of date");
+        assert_eq!(self.live_nodes, counts.iter().filter(|&&c| c > 0).count(), "live node count is out of date");
+
+        if !self.ring.is_empty() {
+            let mut candidates = self.candidate_vnodes(0)
+                .include_excluded()
+                .map(|i| self.ring[i].index)
+                .collect::<Vec<_>>();
+            candidates.sort();
+            let live = (0..nodes.len()).filter(|&i| counts[i] > 0).collect::<Vec<_>>();
+            assert_eq!(candidates, live, "candidates are not a permutation of the live nodes");
+        }
+
+        let states = &self.states;
+        assert_eq!(self.excluded_nodes,
+                   states.iter().filter(|s| s.excluded_until.is_some()).count(),
+                   "excluded node count is out of date");
+        assert_eq!(self.disabled_nodes,
+                   states.iter().filter(|s| s.disabled).count(),
+                   "disabled node count is out of date");
+        assert_eq!(self.removal_nodes,
+                   states.iter().filter(|s| s.removal_at.is_some()).count(),
+                   "removal node count is out of date");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use {DefaultHash, Node, StaticHashRing};
+
+    #[test]
+    fn debug_assert_valid_works() {
+        let mut ring = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(10)));
+        ring.assert_valid();
+
+        for item in 0..15 {
+            ring.take(&item);
+        }
+        ring.set_replica_only(&1, true);
+        ring.exclude_for(&2, Duration::from_secs(60));
+        ring.disable_node(&3);
+        ring.assert_valid();
+
+        ring.restore(&0);
+        ring.enable_node(&3);
+        ring.clone().assert_valid();
+        ring.debug_assert_valid();
+    }
+
+    #[test]
+    #[should_panic(expected = "vnode counts are out of date")]
+    fn debug_assert_valid_detects_corruption() {
+        let mut ring = StaticHashRing::new(DefaultHash, (0..4).map(|i| Node::new(i).quantity(10)));
+        ring.vnode_counts[0] += 1;
+        ring.assert_valid();
+    }
+}
@@ -0,0 +1,361 @@
+use std::collections::BTreeMap;
+use std::hash::Hash;
+
+use ownership::space_fraction;
+use {Node, RingHash, StaticHashRing};
+
+/// The upper bound of the quantities chosen by `StaticHashRing::balanced`.
+pub const MAX_BALANCED_QUANTITY: usize = 1 << 16;
+
+// Distributes `total` units among `weights` proportionally (by the largest remainder method).
+//
+// Non-positive (or NaN) weights receive nothing.
+// If all of the weights are non-positive, every element receives nothing.
+pub fn allocate(weights: &[f64], total: usize) -> Vec<usize> {
+    let weights = weights.iter().map(|&w| if w > 0.0 { w } else { 0.0 }).collect::<Vec<_>>();
+    let sum = weights.iter().sum::<f64>();
+    if sum <= 0.0 {
+        return vec![0; weights.len()];
+    }
+
+    let exacts = weights.iter().map(|w| w / sum * total as f64).collect::<Vec<_>>();
+    let mut units = exacts.iter().map(|&e| e.floor() as usize).collect::<Vec<_>>();
+    let allocated = units.iter().sum::<usize>();
+
+    let mut order = (0..weights.len()).filter(|&i| weights[i] > 0.0).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| {
+        let ra = exacts[a] - exacts[a].floor();
+        let rb = exacts[b] - exacts[b].floor();
+        rb.partial_cmp(&ra).unwrap().then(a.cmp(&b))
+    });
+    for &i in order.iter().cycle().take(total.saturating_sub(allocated)) {
+        units[i] += 1;
+    }
+    units
+}
+
+/// The capacity of a node, carried by its value.
+///
+/// See `StaticHashRing::with_weights`.
+pub trait NodeWeight {
+    /// Returns the weight of this node.
+    ///
+    /// Non-positive (or NaN) weights are regarded as zero.
+    fn weight(&self) -> f64;
+}
+impl NodeWeight for f64 {
+    fn weight(&self) -> f64 {
+        *self
+    }
+}
+impl NodeWeight for f32 {
+    fn weight(&self) -> f64 {
+        f64::from(*self)
+    }
+}
+impl NodeWeight for u32 {
+    fn weight(&self) -> f64 {
+        f64::from(*self)
+    }
+}
+impl NodeWeight for u64 {
+    fn weight(&self) -> f64 {
+        *self as f64
+    }
+}
+impl NodeWeight for usize {
+    fn weight(&self) -> f64 {
+        *self as f64
+    }
+}
+impl<T: NodeWeight + ?Sized> NodeWeight for &T {
+    fn weight(&self) -> f64 {
+        (**self).weight()
+    }
+}
+
+impl<K, V, H> StaticHashRing<K, V, H>
+    where K: Hash + Eq + Ord,
+          H: RingHash
+{
+    /// Makes a new `StaticHashRing` instance in which the quantities of the nodes
+    /// are derived from the weights of their values.
+    ///
+    /// `total_vnodes` virtual nodes are distributed among `nodes` proportionally to `node.value.weight()`,
+    /// and the original quantities of `nodes` are ignored.
+    /// As with `new`, nodes which have duplicate keys are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash, NodeWeight};
+    ///
+    /// struct Server {
+    ///     cores: u32,
+    /// }
+    /// impl NodeWeight for Server {
+    ///     fn weight(&self) -> f64 {
+    ///         f64::from(self.cores)
+    ///     }
+    /// }
+    ///
+    /// let nodes = vec![
+    ///     Node::new("foo").value(Server { cores: 4 }),
+    ///     Node::new("bar").value(Server { cores: 8 }),
+    ///     Node::new("baz").value(Server { cores: 4 }),
+    /// ];
+    /// let ring = StaticHashRing::with_weights(DefaultHash, nodes.into_iter(), 100);
+    /// assert_eq!(ring.len(), 100);
+    /// assert_eq!(ring.nodes().iter().map(|n| (n.key, n.quantity)).collect::<Vec<_>>(),
+    ///            [("bar", 50), ("baz", 25), ("foo", 25)]);
+    /// ```
+    pub fn with_weights<I>(hash: H, nodes: I, total_vnodes: usize) -> Self
+        where I: Iterator<Item = Node<K, V>>,
+              V: NodeWeight
+    {
+        Self::with_weights_by(hash, nodes, total_vnodes, |n| n.value.weight())
+    }
+
+    /// Makes a new `StaticHashRing` instance in which the quantities of the nodes
+    /// are derived from the weights given by `weight_of`.
+    ///
+    /// This is the same as `with_weights` except that the weights are not necessarily carried by the values
+    /// (e.g., relative weights `1.0`, `2.0`, and `4.0` for heterogeneous machines).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// let nodes = vec![Node::new("small"), Node::new("medium"), Node::new("large")];
+    /// let weight = |n: &Node<&str, ()>| match n.key {
+    ///     "small" => 1.0,
+    ///     "medium" => 2.0,
+    ///     _ => 4.0,
+    /// };
+    /// let ring = StaticHashRing::with_weights_by(DefaultHash, nodes.into_iter(), 700, weight);
+    /// assert_eq!(ring.nodes().iter().map(|n| (n.key, n.quantity)).collect::<Vec<_>>(),
+    ///            [("large", 400), ("medium", 200), ("small", 100)]);
+    /// ```
+    pub fn with_weights_by<I, F>(hash: H, nodes: I, total_vnodes: usize, weight_of: F) -> Self
+        where I: Iterator<Item = Node<K, V>>,
+              F: Fn(&Node<K, V>) -> f64
+    {
+        let mut nodes = nodes.collect::<Vec<_>>();
+        nodes.sort_by(|a, b| a.key.cmp(&b.key));
+        nodes.dedup_by(|a, b| a.key == b.key);
+
+        let weights = nodes.iter().map(weight_of).collect::<Vec<_>>();
+        for (node, quantity) in nodes.iter_mut().zip(allocate(&weights, total_vnodes)) {
+            node.quantity = quantity;
+        }
+        Self::new(hash, nodes.into_iter())
+    }
+
+    /// Makes a new `StaticHashRing` instance in which each zone receives
+    /// the share of virtual nodes proportional to its weight.
+    ///
+    /// The zone of a node is determined by `zone_of`, and the weight of a zone by `zone_weight`.
+    /// The total count of virtual nodes (i.e., the sum of the quantities of `nodes`) is kept,
+    /// and the share of each zone is divided among its nodes proportionally to their original quantities.
+    /// So, an under-provisioned zone can receive fewer primaries
+    /// regardless of how many nodes it contains.
+    ///
+    /// Zones which have non-positive weights receive no virtual nodes.
+    /// As with `new`, nodes which have duplicate keys are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    ///
+    /// // The values of nodes are their zones.
+    /// let nodes = vec![
+    ///     Node::new("a0").value("a").quantity(10),
+    ///     Node::new("a1").value("a").quantity(10),
+    ///     Node::new("a2").value("a").quantity(10),
+    ///     Node::new("b0").value("b").quantity(10),
+    /// ];
+    /// let ring = StaticHashRing::with_zone_weights(DefaultHash,
+    ///                                              nodes.into_iter(),
+    ///                                              |n| n.value,
+    ///                                              |_| 1.0);
+    /// assert_eq!(ring.len(), 40);
+    /// assert_eq!(ring.nodes().iter().map(|n| n.quantity).collect::<Vec<_>>(),
+    ///            [7, 7, 6, 20]);
+    /// ```
+    pub fn with_zone_weights<I, Z, F, W>(hash: H, nodes: I, zone_of: F, zone_weight: W) -> Self
+        where I: Iterator<Item = Node<K, V>>,
+              Z: Ord,
+              F: Fn(&Node<K, V>) -> Z,
+              W: Fn(&Z) -> f64
+    {
+        let mut nodes = nodes.collect::<Vec<_>>();
+        nodes.sort_by(|a, b| a.key.cmp(&b.key));
+        nodes.dedup_by(|a, b| a.key == b.key);
+
+        let mut zones = BTreeMap::new();
+        for (i, node) in nodes.iter().enumerate() {
+            zones.entry(zone_of(node)).or_insert_with(Vec::new).push(i);
+        }
+
+        let total = nodes.iter().map(|n| n.quantity).sum();
+        let zone_weights = zones.iter()
+            .map(|(zone, members)| if members.iter().any(|&i| nodes[i].quantity > 0) {
+                zone_weight(zone)
+            } else {
+                0.0
+            })
+            .collect::<Vec<_>>();
+        let zone_totals = allocate(&zone_weights, total);
+        for (members, zone_total) in zones.values().zip(zone_totals) {
+            let weights = members.iter().map(|&i| nodes[i].quantity as f64).collect::<Vec<_>>();
+            for (&i, quantity) in members.iter().zip(allocate(&weights, zone_total)) {
+                nodes[i].quantity = quantity;
+            }
+        }
+        Self::new(hash, nodes.into_iter())
+    }
+
+    /// Makes a new `StaticHashRing` instance whose quantities are chosen
+    /// so that the (analytic) ownership of each node deviates from its share by at most `max_imbalance`.
+    ///
+    /// The original quantities of `nodes` are regarded as relative weights (e.g., `1` for every node),
+    /// and the share of a node is its weight divided by the total weight.
+    /// The imbalance of a node is `|ownership / share - 1|` (e.g., `0.1` means ±10%).
+    ///
+    /// The quantities start from the weights, and they are scaled up by 25% at a time
+    /// until the largest imbalance is within `max_imbalance`,
+    /// or the quantity of a node reaches `MAX_BALANCED_QUANTITY`.
+    /// So, the result uses as few virtual nodes as possible for the cluster size,
+    /// and the hash codes of the virtual nodes are computed only once.
+    /// As with `new`, nodes which have duplicate keys are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use consistent_hash::{Node, StaticHashRing, DefaultHash};
+    /// use consistent_hash::analysis::stats;
+    ///
+    /// let ring = StaticHashRing::balanced(DefaultHash, (0..4).map(Node::new), 0.1);
+    /// let stats = stats(&ring);
+    /// assert!(0.25 * 0.9 <= stats.min && stats.max <= 0.25 * 1.1);
+    /// assert!(ring.nodes().iter().all(|n| n.quantity == ring.nodes()[0].quantity));
+    /// ```
+    pub fn balanced<I>(hash: H, nodes: I, max_imbalance: f64) -> Self
+        where I: Iterator<Item = Node<K, V>>
+    {
+        let mut nodes = nodes.collect::<Vec<_>>();
+        nodes.sort_by(|a, b| a.key.cmp(&b.key));
+        nodes.dedup_by(|a, b| a.key == b.key);
+
+        let weights = nodes.iter().map(|n| n.quantity).collect::<Vec<_>>();
+        let mut ring = Self::from_sorted_nodes(hash, nodes, &[]);
+        let mut scale = 1.0;
+        while ring.imbalance(&weights) > max_imbalance &&
+              ring.nodes.iter().all(|n| n.quantity < MAX_BALANCED_QUANTITY) {
+            scale *= 1.25;
+            let mut cached = ring.nodes.iter().map(|n| vec![0; n.quantity]).collect::<Vec<_>>();
+            for vn in &ring.ring {
+                cached[vn.index][vn.seq] = vn.hash;
+            }
+            let StaticHashRing { hash, mut nodes, .. } = ring;
+            for (node, &w) in nodes.iter_mut().zip(&weights) {
+                if w > 0 {
+                    let quantity = (w as f64 * scale).round() as usize;
+                    node.quantity = quantity.max(node.quantity + 1).min(MAX_BALANCED_QUANTITY);
+                }
+            }
+            ring = Self::from_sorted_nodes(hash, nodes, &cached.into_iter().map(Some).collect::<Vec<_>>());
+        }
+        ring
+    }
+
+    // Returns the largest deviation of the ownership of a node from its share given by `weights`.
+    fn imbalance(&self, weights: &[usize]) -> f64 {
+        let total = weights.iter().sum::<usize>();
+        let mut owned = vec![0; self.nodes.len()];
+        for r in self.primary_ranges() {
+            owned[r.node] += r.len();
+        }
+        owned.into_iter()
+            .zip(weights)
+            .filter(|&(_, &w)| w > 0)
+            .map(|(owned, &w)| (space_fraction(owned) / (w as f64 / total as f64) - 1.0).abs())
+            .fold(0.0, f64::max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {DefaultHash, Node, StaticHashRing};
+    use super::*;
+
+    #[test]
+    fn balanced_works() {
+        let nodes = (0..6).map(|i| Node::new(i).quantity(if i == 0 { 2 } else { 1 }));
+        let ring = StaticHashRing::balanced(DefaultHash, nodes, 0.05);
+        let weights = [2, 1, 1, 1, 1, 1];
+        assert!(ring.imbalance(&weights) <= 0.05);
+        assert!(ring.nodes().iter().all(|n| n.quantity < MAX_BALANCED_QUANTITY));
+
+        // The first quantities which satisfy the bound are chosen.
+        let q = ring.nodes()[1].quantity;
+        assert!((ring.nodes()[0].quantity as f64 / q as f64 - 2.0).abs() < 0.1);
+        let smaller = StaticHashRing::new(DefaultHash,
+                                          (0..6).map(|i| Node::new(i).quantity(weights[i] * q * 4 / 5)));
+        assert!(smaller.imbalance(&weights) > 0.05);
+
+        // The hash codes of the virtual nodes are the same as the ones of a ring built from scratch.
+        let rebuilt = StaticHashRing::new(DefaultHash, ring.nodes().iter().cloned());
+        assert!(rebuilt == ring);
+
+        let ring = StaticHashRing::balanced(DefaultHash, (0..3).map(|i| Node::new(i).quantity(0)), 0.0);
+        assert!(ring.is_empty());
+    }
+
+    #[test]
+    fn allocate_works() {
+        assert_eq!(allocate(&[1.0, 1.0, 1.0], 10), [4, 3, 3]);
+        assert_eq!(allocate(&[3.0, 1.0], 10), [8, 2]);
+        assert_eq!(allocate(&[1.0, 0.0, -1.0], 5), [5, 0, 0]);
+        assert_eq!(allocate(&[0.0, 0.0], 5), [0, 0]);
+        assert_eq!(allocate(&[], 5), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn with_zone_weights_works() {
+        let nodes = (0..6).map(|i| Node::new(i).value(if i < 4 { 0 } else { 1 }).quantity(20));
+        let ring = StaticHashRing::with_zone_weights(DefaultHash,
+                                                     nodes,
+                                                     |n| n.value,
+                                                     |&z| if z == 0 { 1.0 } else { 3.0 });
+        assert_eq!(ring.len(), 120);
+        assert_eq!(ring.nodes().iter().map(|n| n.quantity).collect::<Vec<_>>(),
+                   [8, 8, 7, 7, 45, 45]);
+
+        let nodes = (0..4).map(|i| Node::new(i).value(i % 2).quantity(10));
+        let ring = StaticHashRing::with_zone_weights(DefaultHash, nodes, |n| n.value, |&z| z as f64);
+        assert_eq!(ring.nodes().iter().map(|n| n.quantity).collect::<Vec<_>>(),
+                   [0, 20, 0, 20]);
+        assert_eq!(ring.live_nodes(), 2);
+    }
+
+    #[test]
+    fn with_weights_works() {
+        let nodes = (0..4).map(|i| Node::new(i).value(i as f64).quantity(100));
+        let ring = StaticHashRing::with_weights(DefaultHash, nodes, 60);
+        assert_eq!(ring.len(), 60);
+        assert_eq!(ring.nodes().iter().map(|n| n.quantity).collect::<Vec<_>>(),
+                   [0, 10, 20, 30]);
+        assert_eq!(ring.live_nodes(), 3);
+
+        let nodes = vec![Node::new(0).value(&2u32), Node::new(0).value(&1u32), Node::new(1).value(&1u32)];
+        let ring = StaticHashRing::with_weights(DefaultHash, nodes.into_iter(), 30);
+        assert_eq!(ring.nodes().iter().map(|n| n.quantity).collect::<Vec<_>>(), [20, 10]);
+
+        let nodes = (0..3).map(|i| Node::new(i).quantity(1));
+        let ring = StaticHashRing::with_weights_by(DefaultHash, nodes, 71, |n| 0.5 * (1 << n.key) as f64);
+        assert_eq!(ring.nodes().iter().map(|n| n.quantity).collect::<Vec<_>>(), [10, 20, 41]);
+    }
+}
@@ -0,0 +1,232 @@
+use std::hash::{Hash, Hasher};
+
+use {DefaultHash, RingHash};
+
+const PRIME1: u64 = 11_400_714_785_074_694_791;
+const PRIME2: u64 = 14_029_467_366_897_019_727;
+const PRIME3: u64 = 1_609_587_929_392_839_161;
+const PRIME4: u64 = 9_650_029_242_287_828_579;
+const PRIME5: u64 = 2_870_177_450_012_600_261;
+
+/// A `RingHash` implementation which uses `xxHash64` (with seed `0`).
+///
+/// This is much faster than `DefaultHash` for long items, but it is not resistant to hash flooding.
+///
+/// This is available if the `xxhash` feature is enabled.
+#[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub struct XxHash;
+impl RingHash for XxHash {
+    fn hash_item<T: Hash>(&self, item: &T) -> u64 {
+        let mut hasher = XxHasher::new(0);
+        item.hash(&mut hasher);
+        hasher.finish()
+    }
+    fn algorithm_id(&self) -> &str {
+        "xxh64"
+    }
+}
+impl XxHash {
+    /// Makes a `xxHash64` hasher which uses `seed`.
+    ///
+    /// Rings built with different seeds have independent layouts.
+    pub fn with_seed(seed: u64) -> SeededXxHash {
+        SeededXxHash {
+            seed,
+            algorithm_id: format!("xxh64-seeded-{:016x}", DefaultHash.hash_item(&seed)),
+        }
+    }
+}
+
+/// A `RingHash` implementation which uses `xxHash64` with a non-zero seed.
+///
+/// This is created by calling `XxHash::with_seed` function.
+///
+/// Note that a secret seed does not make `xxHash64` resistant to hash flooding
+/// (use `DefaultHash::with_keys` for that purpose).
+///
+/// This is available if the `xxhash` feature is enabled.
+#[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub struct SeededXxHash {
+    seed: u64,
+    algorithm_id: String,
+}
+impl RingHash for SeededXxHash {
+    fn hash_item<T: Hash>(&self, item: &T) -> u64 {
+        let mut hasher = XxHasher::new(self.seed);
+        item.hash(&mut hasher);
+        hasher.finish()
+    }
+    fn algorithm_id(&self) -> &str {
+        &self.algorithm_id
+    }
+}
+
+// The streaming state of `xxHash64`.
+//
+// The written bytes are digested in 32-byte stripes, and the incomplete stripe is kept in `buf`.
+struct XxHasher {
+    seed: u64,
+    acc: [u64; 4],
+    buf: [u8; 32],
+    buf_len: usize,
+    total_len: u64,
+}
+impl XxHasher {
+    fn new(seed: u64) -> Self {
+        XxHasher {
+            seed,
+            acc: [seed.wrapping_add(PRIME1).wrapping_add(PRIME2),
+                  seed.wrapping_add(PRIME2),
+                  seed,
+                  seed.wrapping_sub(PRIME1)],
+            buf: [0; 32],
+            buf_len: 0,
+            total_len: 0,
+        }
+    }
+
+    fn stripe(&mut self, stripe: &[u8]) {
+        for (i, v) in self.acc.iter_mut().enumerate() {
+            *v = round(*v, read_u64(&stripe[i * 8..]));
+        }
+    }
+}
+impl Hasher for XxHasher {
+    fn write(&mut self, mut bytes: &[u8]) {
+        self.total_len += bytes.len() as u64;
+        if self.buf_len > 0 {
+            let n = (32 - self.buf_len).min(bytes.len());
+            self.buf[self.buf_len..self.buf_len + n].copy_from_slice(&bytes[..n]);
+            self.buf_len += n;
+            bytes = &bytes[n..];
+            if self.buf_len < 32 {
+                return;
+            }
+            let buf = self.buf;
+            self.stripe(&buf);
+            self.buf_len = 0;
+        }
+        while bytes.len() >= 32 {
+            self.stripe(&bytes[..32]);
+            bytes = &bytes[32..];
+        }
+        self.buf[..bytes.len()].copy_from_slice(bytes);
+        self.buf_len = bytes.len();
+    }
+    fn finish(&self) -> u64 {
+        let mut h = if self.total_len >= 32 {
+            let v = &self.acc;
+            let mut h = v[0]
+                .rotate_left(1)
+                .wrapping_add(v[1].rotate_left(7))
+                .wrapping_add(v[2].rotate_left(12))
+                .wrapping_add(v[3].rotate_left(18));
+            for &v in v {
+                h = merge_round(h, v);
+            }
+            h
+        } else {
+            self.seed.wrapping_add(PRIME5)
+        };
+        h = h.wrapping_add(self.total_len);
+
+        let mut rest = &self.buf[..self.buf_len];
+        while rest.len() >= 8 {
+            h ^= round(0, read_u64(rest));
+            h = h.rotate_left(27).wrapping_mul(PRIME1).wrapping_add(PRIME4);
+            rest = &rest[8..];
+        }
+        if rest.len() >= 4 {
+            h ^= u64::from(read_u32(rest)).wrapping_mul(PRIME1);
+            h = h.rotate_left(23).wrapping_mul(PRIME2).wrapping_add(PRIME3);
+            rest = &rest[4..];
+        }
+        for &b in rest {
+            h ^= u64::from(b).wrapping_mul(PRIME5);
+            h = h.rotate_left(11).wrapping_mul(PRIME1);
+        }
+
+        h ^= h >> 33;
+        h = h.wrapping_mul(PRIME2);
+        h ^= h >> 29;
+        h = h.wrapping_mul(PRIME3);
+        h ^ (h >> 32)
+    }
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    let mut buf = [0; 8];
+    buf.copy_from_slice(&bytes[..8]);
+    u64::from_le_bytes(buf)
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    let mut buf = [0; 4];
+    buf.copy_from_slice(&bytes[..4]);
+    u32::from_le_bytes(buf)
+}
+
+fn round(acc: u64, input: u64) -> u64 {
+    acc.wrapping_add(input.wrapping_mul(PRIME2))
+        .rotate_left(31)
+        .wrapping_mul(PRIME1)
+}
+
+fn merge_round(acc: u64, value: u64) -> u64 {
+    (acc ^ round(0, value)).wrapping_mul(PRIME1).wrapping_add(PRIME4)
+}
+
+#[cfg(test)]
+mod tests {
+    use {Node, RingHash, StaticHashRing};
+    use super::*;
+
+    fn xxh64(input: &[u8], seed: u64) -> u64 {
+        let mut hasher = XxHasher::new(seed);
+        hasher.write(input);
+        hasher.finish()
+    }
+
+    #[test]
+    fn xxh64_works() {
+        assert_eq!(xxh64(b"", 0), 0xef46_db37_51d8_e999);
+        assert_eq!(xxh64(b"abc", 0), 0x44bc_2cf5_ad77_0999);
+
+        assert_eq!(XxHash.hash_item(&"foo"), XxHash.hash_item(&"foo"));
+        assert_eq!(XxHash.hash_bytes(b"abc"), 0x44bc_2cf5_ad77_0999);
+        let ring = StaticHashRing::new(XxHash, (0..3).map(|i| Node::new(i).quantity(10)));
+        assert_eq!(ring.format_tag(), "ring-v1-xxh64");
+        assert_eq!(ring.calc_candidates(&"foo").count(), 3);
+    }
+
+    #[test]
+    fn xxh64_streaming_works() {
+        let input = (0..1000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        assert_eq!(xxh64(&input[..32], 0), 0xcbf5_9c51_16ff_32b4);
+        assert_eq!(xxh64(&input[..33], 0), 0x0c53_5d1a_cafb_8ead);
+        assert_eq!(xxh64(&input[..64], 0), 0xf7c6_7301_db67_13f0);
+        assert_eq!(xxh64(&input, 0), 0xf306_f04a_a88b_54d3);
+
+        for &len in &[0, 7, 31, 32, 33, 63, 64, 100, 1000] {
+            for &split in &[1, 3, 8, 31, 32, 33] {
+                let mut hasher = XxHasher::new(7);
+                for piece in input[..len].chunks(split) {
+                    hasher.write(piece);
+                }
+                assert_eq!(hasher.finish(), xxh64(&input[..len], 7));
+            }
+        }
+    }
+
+    #[test]
+    fn seeded_xxhash_works() {
+        assert_eq!(xxh64(b"", 1), 0xd5af_ba13_36a3_be4b);
+        assert_eq!(XxHash::with_seed(0).hash_item(&"foo"), XxHash.hash_item(&"foo"));
+        assert_ne!(XxHash::with_seed(1).hash_item(&"foo"), XxHash.hash_item(&"foo"));
+        assert_eq!(XxHash::with_seed(1), XxHash::with_seed(1));
+
+        let ring = StaticHashRing::new(XxHash::with_seed(1), (0..3).map(|i| Node::new(i).quantity(10)));
+        assert!(ring.format_tag().starts_with("ring-v1-xxh64-seeded-"));
+        assert_ne!(ring.format_tag(), StaticHashRing::new(XxHash::with_seed(2), (0..0).map(Node::new)).format_tag());
+    }
+}